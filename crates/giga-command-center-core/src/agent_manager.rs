@@ -1,31 +1,347 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
 use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
-use tokio::io::{AsyncBufReadExt, BufReader};
-use tokio::process::{Child, Command};
-use tokio::sync::RwLock;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStderr, ChildStdin, ChildStdout, Command};
+use tokio::sync::{watch, Mutex, RwLock};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
 use uuid::Uuid;
 
-use crate::cli_builders::*;
+use crate::backends::{CliBackend, CliOutput, CliRegistry, CliResult, OutputParser};
+use crate::history::{unix_millis, HistoryStore, RunSummary};
+use crate::permissions::{PendingPermission, PermissionBroker, PermissionDecision};
+use crate::process;
+use crate::retry::{AttemptFailure, FailureClass};
+use crate::sandbox::{self, ApiProxy, NetworkAccess, SandboxProfile};
+use crate::skills::{self, SkillDetail, SkillsRepository};
 use crate::types::*;
+use crate::usage::UsageMeter;
+use crate::vault::{self, SecretVault};
+use crate::workflow::{self, WorkflowDefinition, WorkflowId, WorkflowInfo, WorkflowRun};
+use crate::workspaces::WorkspaceStore;
+use crate::worktree::{self, Isolation, WorktreeCleanup, WorktreeInfo, WorktreeStatus};
+
+/// Default cap on agents running at once across all workspaces.
+pub const DEFAULT_MAX_CONCURRENT: usize = 8;
+
+/// Default time a stopping CLI gets to exit on its own before its process group is killed.
+pub const DEFAULT_STOP_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// Finished workflows kept for `workflow` and `list_workflows`; older ones are
+/// dropped as new workflows start.
+const FINISHED_WORKFLOWS_KEPT: usize = 100;
+
+/// How long a stopped agent's last output may take to drain before `Stopped` is emitted.
+const OUTPUT_DRAIN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Prompt for a retry that resumes the failed attempt's session, whose history
+/// already holds the original prompt.
+const RESUME_PROMPT: &str = "Continue where you left off.";
+
+/// Type-erased event emitter kept alongside a running agent so the manager can
+/// report events that aren't triggered by the CLI's own output (e.g. cancellation).
+pub type EventEmitter = Arc<dyn Fn(AgentEvent) + Send + Sync>;
 
 pub struct AgentHandle {
     pub id: AgentId,
     pub workspace_id: WorkspaceId,
-    pub child: Child,
+    pub backend: Arc<dyn CliBackend>,
+    /// `None` while `stop_agent` is shutting the process down.
+    pub child: Option<Child>,
+    /// Process group id (the CLI's pid), used to clean up its subprocesses.
+    pub pid: Option<u32>,
+    /// Open only for interactive runs, until `close_input` is called.
+    pub stdin: Option<Arc<Mutex<ChildStdin>>>,
+    /// Becomes `true` once everything the CLI printed on stdout has been emitted.
+    pub output_drained: watch::Receiver<bool>,
+    /// When the first attempt spawned; retries don't reset it.
+    pub spawned_at: Instant,
+    /// Number of the current attempt, from 1 (see `AgentConfig::retry`).
+    pub attempt: u32,
+    /// Set by `stop_agent` and `stop_all` so a run that is between attempts isn't retried.
+    pub cancelled: watch::Sender<bool>,
+    pub emit_event: EventEmitter,
+    /// Set once the run's `Result` is out, so a stopped run reports exactly one.
+    pub result_sent: bool,
+    /// The current attempt's model API proxy, under `NetworkAccess::ModelApi`.
+    pub(crate) proxy: Option<ApiProxy>,
+}
+
+/// What's needed to continue a run: the config it was started with and the
+/// session id its CLI reported.
+#[derive(Debug, Clone)]
+struct SessionRecord {
+    config: AgentConfig,
+    session_id: Option<String>,
+    started_at: u64,
+}
+
+/// A run waiting for its workspace (or a global slot) to free up.
+struct QueuedRun {
+    agent_id: AgentId,
+    config: AgentConfig,
+    emit_event: EventEmitter,
+    queued_at: u64,
+}
+
+/// A run that has left the queue, or skipped it, and is being isolated and
+/// launched. It holds a slot and its workspace as if it were running.
+struct Launching {
+    workspace_id: WorkspaceId,
+    /// Set by `stop_agent` and `stop_all`; the run is stopped once it is attached.
+    cancelled: bool,
+}
+
+/// A spawned CLI process, before it is registered with the manager.
+struct Launched {
+    child: Child,
+    backend: Arc<dyn CliBackend>,
+    stdin: Option<Arc<Mutex<ChildStdin>>>,
+    spawned_at: Instant,
+    /// The config it was spawned with, for the backend it ended up on.
+    config: AgentConfig,
+    /// Position of that backend in the run's chain; 0 is `AgentConfig::cli`.
+    backend_index: usize,
+    /// `FallingBack` events for earlier backends that couldn't be spawned.
+    fell_back: Vec<AgentEvent>,
+    /// The effective sandbox, if the run has one.
+    sandbox: Option<SandboxProfile>,
+    proxy: Option<ApiProxy>,
+}
+
+/// The config for backend `index` of a run's fallback chain, where 0 is the
+/// run's own `cli` and `model`.
+fn fallback_config(config: &AgentConfig, index: usize) -> Option<AgentConfig> {
+    if index == 0 {
+        return Some(config.clone());
+    }
+    let choice = config.fallbacks.get(index - 1)?;
+    let mut next = config.clone();
+    next.cli = Some(choice.cli.clone());
+    next.model = choice.model.clone();
+    // A session belongs to the CLI that started it.
+    next.session_id = None;
+    Some(next)
+}
+
+/// Commit message for what an isolated run changed: its prompt's first line.
+fn commit_message(agent_id: &AgentId, prompt: &str) -> String {
+    let summary: String = prompt.lines().next().unwrap_or_default().chars().take(72).collect();
+    if summary.trim().is_empty() {
+        format!("Agent run {}", agent_id)
+    } else {
+        format!("{}\n\nAgent run {}", summary.trim(), agent_id)
+    }
+}
+
+/// Folds the CLI's per-turn `result` reports (one per turn in interactive runs)
+/// into a run total. Usage and cost are tracked separately by `UsageMeter`.
+fn merge_results(earlier: CliResult, next: CliResult) -> CliResult {
+    fn sum(a: Option<u64>, b: Option<u64>) -> Option<u64> {
+        match (a, b) {
+            (Some(a), Some(b)) => Some(a + b),
+            (a, b) => a.or(b),
+        }
+    }
+    CliResult {
+        success: next.success,
+        duration_ms: sum(earlier.duration_ms, next.duration_ms),
+        api_duration_ms: sum(earlier.api_duration_ms, next.api_duration_ms),
+        ..next
+    }
+}
+
+/// Why the stdout reader stopped, and what the CLI reported before it did.
+struct OutputEnd {
+    limit: Option<StopReason>,
+    reported: Option<CliResult>,
+}
+
+/// What `complete` decided should follow a failed attempt.
+enum NextStep {
+    /// Try the same backend again after the delay.
+    Retry(Duration),
+    /// Move on to the next backend in the fallback chain.
+    Fallback,
+}
+
+/// What carries over from one attempt of a run to the next.
+struct RunProgress {
+    /// The current backend's config and position in the fallback chain.
+    config: AgentConfig,
+    backend_index: usize,
+    /// Attempt number on the current backend, from 1.
+    attempt: u32,
+    /// Usage and cost add up across attempts.
+    meter: UsageMeter,
+    /// The CLI's reports from every attempt so far, merged.
+    reported: Option<CliResult>,
+    /// Failures reported by the current attempt's events.
+    failure: Arc<std::sync::Mutex<AttemptFailure>>,
+    /// The current attempt's stderr reader.
+    stderr_reader: Option<JoinHandle<()>>,
+    last_exit: Option<ProcessExit>,
+}
+
+/// The worktree a run started with `Isolation::Worktree` works in.
+struct RunWorktree {
+    info: WorktreeInfo,
+    /// Created for this run, rather than carried over from the run it continues.
+    created: bool,
+}
+
+/// Output of a freshly launched retry, for the run's completion task to read.
+struct NextAttempt {
+    config: AgentConfig,
+    stdout: Option<ChildStdout>,
+    parser: Box<dyn OutputParser>,
+    drained: watch::Sender<bool>,
+}
+
+struct Inner {
+    agents: RwLock<HashMap<AgentId, AgentHandle>>,
+    sessions: RwLock<HashMap<AgentId, SessionRecord>>,
+    worktrees: RwLock<HashMap<AgentId, RunWorktree>>,
+    /// The skills each run was started with, resolved once so every attempt gets the same.
+    run_skills: std::sync::Mutex<HashMap<AgentId, Arc<Vec<SkillDetail>>>>,
+    workflows: RwLock<HashMap<WorkflowId, Arc<WorkflowRun>>>,
+    /// Also serializes every spawn-or-queue decision. Always locked before `agents`,
+    /// and never held while a run is isolated or launched.
+    queue: Mutex<VecDeque<QueuedRun>>,
+    launching: std::sync::Mutex<HashMap<AgentId, Launching>>,
+    history: Option<Arc<HistoryStore>>,
+    vault: std::sync::RwLock<Option<Arc<SecretVault>>>,
+    workspaces: std::sync::RwLock<Option<Arc<WorkspaceStore>>>,
+    skills: std::sync::RwLock<Option<Arc<SkillsRepository>>>,
+    permissions: PermissionBroker,
+    registry: CliRegistry,
+    max_concurrent: AtomicUsize,
+    stop_grace_ms: AtomicU64,
 }
 
 pub struct AgentManager {
-    agents: Arc<RwLock<HashMap<AgentId, AgentHandle>>>,
+    inner: Arc<Inner>,
 }
 
 impl AgentManager {
     pub fn new() -> Self {
+        Self::build(None)
+    }
+
+    /// Like [`AgentManager::new`], but every run is persisted to `history`.
+    pub fn with_history(history: HistoryStore) -> Self {
+        Self::build(Some(Arc::new(history)))
+    }
+
+    fn build(history: Option<Arc<HistoryStore>>) -> Self {
         Self {
-            agents: Arc::new(RwLock::new(HashMap::new())),
+            inner: Arc::new(Inner {
+                agents: RwLock::new(HashMap::new()),
+                sessions: RwLock::new(HashMap::new()),
+                worktrees: RwLock::new(HashMap::new()),
+                run_skills: std::sync::Mutex::new(HashMap::new()),
+                workflows: RwLock::new(HashMap::new()),
+                queue: Mutex::new(VecDeque::new()),
+                launching: std::sync::Mutex::new(HashMap::new()),
+                history,
+                vault: std::sync::RwLock::new(None),
+                workspaces: std::sync::RwLock::new(None),
+                skills: std::sync::RwLock::new(None),
+                permissions: PermissionBroker::new(),
+                registry: CliRegistry::new(),
+                max_concurrent: AtomicUsize::new(DEFAULT_MAX_CONCURRENT),
+                stop_grace_ms: AtomicU64::new(DEFAULT_STOP_GRACE_PERIOD.as_millis() as u64),
+            }),
         }
     }
 
+    /// Sets the cap on agents running at once (at least 1). Extra runs are queued.
+    pub fn with_max_concurrent(self, max_concurrent: usize) -> Self {
+        self.inner
+            .max_concurrent
+            .store(max_concurrent.max(1), Ordering::SeqCst);
+        self
+    }
+
+    pub fn max_concurrent(&self) -> usize {
+        self.inner.max_concurrent.load(Ordering::SeqCst)
+    }
+
+    /// Changes the concurrency cap at runtime, starting queued runs if it was raised.
+    pub async fn set_max_concurrent(&self, max_concurrent: usize) {
+        self.inner
+            .max_concurrent
+            .store(max_concurrent.max(1), Ordering::SeqCst);
+        self.inner.clone().pump_queue().await;
+    }
+
+    /// Sets how long a stopping CLI may take to exit before its process group is killed.
+    pub fn with_stop_grace_period(self, grace: Duration) -> Self {
+        self.inner
+            .stop_grace_ms
+            .store(grace.as_millis() as u64, Ordering::SeqCst);
+        self
+    }
+
+    pub fn stop_grace_period(&self) -> Duration {
+        self.inner.stop_grace()
+    }
+
+    pub fn history(&self) -> Option<&HistoryStore> {
+        self.inner.history.as_deref()
+    }
+
+    /// Lets runs reference `vault` secrets in `AgentConfig::env`. Secret values are
+    /// scrubbed from every event (and so from history) before it is emitted.
+    pub fn with_vault(self, vault: SecretVault) -> Self {
+        *self.inner.vault.write().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(vault));
+        self
+    }
+
+    pub fn vault(&self) -> Option<Arc<SecretVault>> {
+        self.inner.vault()
+    }
+
+    /// Keeps the canvas's workspaces in `store`, shared by every client.
+    pub fn with_workspaces(self, store: WorkspaceStore) -> Self {
+        *self.inner.workspaces.write().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(store));
+        self
+    }
+
+    pub fn workspaces(&self) -> Option<Arc<WorkspaceStore>> {
+        self.inner.workspaces.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Serves and edits the skills in `repository`.
+    pub fn with_skills(self, repository: SkillsRepository) -> Self {
+        *self.inner.skills.write().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(repository));
+        self
+    }
+
+    pub fn skills(&self) -> Option<Arc<SkillsRepository>> {
+        self.inner.skills.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Registers a custom backend (or replaces a built-in one with the same id).
+    pub fn with_backend(self, backend: impl CliBackend + 'static) -> Self {
+        self.inner.registry.register(Arc::new(backend));
+        self
+    }
+
+    /// The backends runs can use; more can be registered at any time.
+    pub fn registry(&self) -> &CliRegistry {
+        &self.inner.registry
+    }
+
+    /// Starts an agent, or queues it when its workspace already has an active run
+    /// or the global cap is reached. Either way the returned id identifies the run;
+    /// queued runs emit `Queued`, then `Dequeued` and `Started` once they spawn.
     pub async fn start_agent<F>(
         &self,
         config: AgentConfig,
@@ -34,244 +350,1727 @@ impl AgentManager {
     where
         F: Fn(AgentEvent) + Send + Sync + Clone + 'static,
     {
+        self.inner.validate(&config)?;
+        let skills = self.inner.resolve_skills(&config).await?;
+
         let agent_id = Uuid::new_v4().to_string();
-        let workspace_id = config.workspace_id.clone();
-        let cli = config.cli.as_ref().unwrap_or(&CliType::Claude);
-
-        let (binary, args) = match cli {
-            CliType::Claude => build_claude_args(&config),
-            CliType::Cursor => build_cursor_args(&config),
-            CliType::Kilo => build_kilo_args(&config),
-            CliType::Gemini => build_gemini_args(&config),
-            CliType::Grok => build_grok_args(&config),
-            CliType::DeepSeek => build_deepseek_args(&config),
+        let emit_event = self.inner.recording_emitter(agent_id.clone(), emit_event);
+        self.inner
+            .permissions
+            .open(&agent_id, &config, emit_event.clone());
+        if !skills.is_empty() {
+            self.inner
+                .lock_run_skills()
+                .insert(agent_id.clone(), Arc::new(skills));
+        }
+
+        let mut queue = self.inner.queue.lock().await;
+        if self.inner.has_capacity(&queue, &config.workspace_id).await {
+            self.inner.reserve_launch(&agent_id, &config.workspace_id);
+            drop(queue);
+            let launched = match self.inner.isolate(&agent_id, &config).await {
+                Ok(launch_config) => self
+                    .inner
+                    .launch_chain(&agent_id, &launch_config, 0)
+                    .await
+                    .map(|launched| (launch_config, launched)),
+                Err(e) => Err(e),
+            };
+            let result = match launched {
+                Ok((launch_config, launched)) => {
+                    self.inner.record_start(&agent_id, &config);
+                    self.inner
+                        .attach(agent_id.clone(), launch_config, launched, emit_event)
+                        .await;
+                    Ok(())
+                }
+                Err(e) => {
+                    self.inner.abandon_worktree(&agent_id).await;
+                    self.inner.close_run(&agent_id);
+                    Err(e)
+                }
+            };
+            self.inner.end_launch(&agent_id).await;
+            // A run that finished before the slot was released couldn't start the next.
+            tokio::spawn(self.inner.clone().pump_queue());
+            result?;
+        } else {
+            self.inner.record_start(&agent_id, &config);
+            emit_event(AgentEvent::Queued {
+                agent_id: agent_id.clone(),
+                workspace_id: config.workspace_id.clone(),
+                position: queue.len() + 1,
+            });
+            queue.push_back(QueuedRun {
+                agent_id: agent_id.clone(),
+                config,
+                emit_event,
+                queued_at: unix_millis(),
+            });
+        }
+
+        Ok(agent_id)
+    }
+
+    /// Reads the CLI's stdout until it closes or a limit in `config` trips.
+    /// Returns the limit's stop reason (`Timeout` or `Stalled`) if one did.
+    async fn process_output<R>(
+        agent_id: AgentId,
+        reader: R,
+        mut parser: Box<dyn OutputParser>,
+        config: &AgentConfig,
+        meter: &mut UsageMeter,
+        emit_event: &EventEmitter,
+        sessions: &RwLock<HashMap<AgentId, SessionRecord>>,
+    ) -> OutputEnd
+    where
+        R: tokio::io::AsyncRead + Unpin,
+    {
+        let reader = BufReader::new(reader);
+        let mut lines = reader.lines();
+        let mut reported: Option<CliResult> = None;
+
+        let deadline = config
+            .timeout_secs
+            .map(|secs| Instant::now() + Duration::from_secs(secs));
+        let idle = config.idle_timeout_secs.map(Duration::from_secs);
+
+        let limit = loop {
+            let wait = match (deadline, idle) {
+                (Some(deadline), Some(idle)) => {
+                    Some(deadline.saturating_duration_since(Instant::now()).min(idle))
+                }
+                (Some(deadline), None) => Some(deadline.saturating_duration_since(Instant::now())),
+                (None, idle) => idle,
+            };
+            let next = match wait {
+                // `next_line` is cancel-safe, so timing out loses no buffered output.
+                Some(wait) => match tokio::time::timeout(wait, lines.next_line()).await {
+                    Ok(next) => next,
+                    Err(_) if deadline.is_some_and(|d| Instant::now() >= d) => {
+                        break Some(StopReason::Timeout)
+                    }
+                    Err(_) => break Some(StopReason::Stalled),
+                },
+                None => lines.next_line().await,
+            };
+            let Ok(Some(line)) = next else {
+                break None;
+            };
+
+            let outputs = parser.parse_line(&agent_id, &line);
+            Self::dispatch(&agent_id, outputs, &mut reported, meter, emit_event, sessions).await;
         };
 
-        let mut cmd = Command::new(binary);
-        cmd.args(&args)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .stdin(Stdio::null());
+        let outputs = parser.finish(&agent_id);
+        Self::dispatch(&agent_id, outputs, &mut reported, meter, emit_event, sessions).await;
+        OutputEnd { limit, reported }
+    }
 
-        if let Some(dir) = &config.working_directory {
-            cmd.current_dir(dir);
+    /// Emits parsed output, keeping the run's usage totals and session id current.
+    async fn dispatch(
+        agent_id: &AgentId,
+        outputs: Vec<CliOutput>,
+        reported: &mut Option<CliResult>,
+        meter: &mut UsageMeter,
+        emit_event: &EventEmitter,
+        sessions: &RwLock<HashMap<AgentId, SessionRecord>>,
+    ) {
+        for output in outputs {
+            match output {
+                CliOutput::Event(event) => {
+                    if let AgentEvent::Init {
+                        session_id, model, ..
+                    } = &event
+                    {
+                        meter.set_model(model);
+                        if let Some(record) = sessions.write().await.get_mut(agent_id) {
+                            record.session_id = Some(session_id.clone());
+                        }
+                    }
+                    emit_event(event);
+                }
+                CliOutput::Usage { message_id, usage } => {
+                    if let Some(event) = meter.message(agent_id, message_id.as_deref(), usage) {
+                        emit_event(event);
+                    }
+                }
+                // Held back so the run ends with one Result that also carries measured time.
+                CliOutput::Result(result) => {
+                    if let Some(event) = meter.result(agent_id, result.usage, result.total_cost_usd) {
+                        emit_event(event);
+                    }
+                    *reported = Some(match reported.take() {
+                        Some(earlier) => merge_results(earlier, result),
+                        None => result,
+                    });
+                }
+            }
         }
+    }
 
-        let mut child = cmd.spawn().map_err(|e| AgentError::SpawnFailed(e.to_string()))?;
+    /// Sends a follow-up `prompt` to an earlier conversation by relaunching its CLI
+    /// in resume mode. `id` may be the agent id of the earlier run or the CLI
+    /// session id it reported; runs from before a restart are found via history.
+    /// The new run gets its own agent id and records the earlier one as its parent.
+    pub async fn continue_session<F>(
+        &self,
+        id: &str,
+        prompt: String,
+        emit_event: F,
+    ) -> Result<AgentId, AgentError>
+    where
+        F: Fn(AgentEvent) + Send + Sync + Clone + 'static,
+    {
+        let (parent_agent_id, record) = self.resolve_session(id).await?;
 
-        emit_event(AgentEvent::Started {
+        if self.is_running(&parent_agent_id).await {
+            return Err(AgentError::AlreadyRunning);
+        }
+        let session_id = record.session_id.ok_or(AgentError::NotFound)?;
+
+        let mut config = record.config;
+        config.prompt = prompt;
+        config.session_id = Some(session_id);
+        config.parent_agent_id = Some(parent_agent_id);
+
+        self.start_agent(config, emit_event).await
+    }
+
+    async fn resolve_session(&self, id: &str) -> Result<(AgentId, SessionRecord), AgentError> {
+        {
+            let sessions = self.inner.sessions.read().await;
+            if let Some(record) = sessions.get(id) {
+                return Ok((id.to_string(), record.clone()));
+            }
+            // Several runs can share a session id once it has been resumed; take the latest.
+            let latest = sessions
+                .iter()
+                .filter(|(_, record)| record.session_id.as_deref() == Some(id))
+                .max_by_key(|(_, record)| record.started_at);
+            if let Some((agent_id, record)) = latest {
+                return Ok((agent_id.clone(), record.clone()));
+            }
+        }
+
+        let history = self.history().ok_or(AgentError::NotFound)?;
+        let summary: RunSummary = match history.get(&id.to_string()).await {
+            Ok(run) => run.summary,
+            Err(AgentError::NotFound) => history
+                .list()
+                .await?
+                .into_iter()
+                .find(|run| run.session_id.as_deref() == Some(id))
+                .ok_or(AgentError::NotFound)?,
+            Err(e) => return Err(e),
+        };
+
+        // The session belongs to the backend the run ended up on.
+        let mut config = summary.config;
+        if let Some(cli) = summary.cli {
+            if config.cli.as_ref() != Some(&cli) {
+                config.cli = Some(cli);
+                config.model = summary.model;
+            }
+        }
+
+        Ok((
+            summary.agent_id,
+            SessionRecord {
+                config,
+                session_id: summary.session_id,
+                started_at: summary.started_at,
+            },
+        ))
+    }
+
+    /// Sends a user message to a running interactive agent over its stdin.
+    pub async fn send_input(&self, agent_id: &AgentId, text: String) -> Result<(), AgentError> {
+        let (stdin, backend, emit_event) = {
+            let agents = self.inner.agents.read().await;
+            let handle = agents.get(agent_id).ok_or(AgentError::NotFound)?;
+            let stdin = match &handle.stdin {
+                Some(stdin) => stdin.clone(),
+                None if !handle.backend.capabilities().live_input => {
+                    return Err(AgentError::NotSupported(format!(
+                        "{} does not accept live input",
+                        handle.backend.id()
+                    )))
+                }
+                None => {
+                    return Err(AgentError::NotSupported(
+                        "Agent input is closed or the agent is not interactive".to_string(),
+                    ))
+                }
+            };
+            (stdin, handle.backend.clone(), handle.emit_event.clone())
+        };
+
+        Self::write_input(&stdin, backend.as_ref(), &text).await?;
+        emit_event(AgentEvent::Input {
             agent_id: agent_id.clone(),
-            workspace_id: workspace_id.clone(),
+            content: text,
         });
+        Ok(())
+    }
 
-        let stdout = child
-            .stdout
-            .take()
-            .ok_or_else(|| AgentError::ProcessError("Failed to capture stdout".to_string()))?;
+    /// Closes an interactive agent's stdin; the CLI finishes its current turn and exits.
+    pub async fn close_input(&self, agent_id: &AgentId) -> Result<(), AgentError> {
+        let mut agents = self.inner.agents.write().await;
+        let handle = agents.get_mut(agent_id).ok_or(AgentError::NotFound)?;
+        if let Some(stdin) = handle.stdin.take() {
+            let _ = stdin.lock().await.shutdown().await;
+        }
+        Ok(())
+    }
 
-        let stderr = child
-            .stderr
-            .take()
-            .ok_or_else(|| AgentError::ProcessError("Failed to capture stderr".to_string()))?;
+    async fn write_input(
+        stdin: &Mutex<ChildStdin>,
+        backend: &dyn CliBackend,
+        text: &str,
+    ) -> Result<(), AgentError> {
+        let line = backend.input_line(text).ok_or_else(|| {
+            AgentError::NotSupported(format!("{} does not accept live input", backend.id()))
+        })?;
+        let mut stdin = stdin.lock().await;
+        stdin
+            .write_all(line.as_bytes())
+            .await
+            .map_err(|e| AgentError::ProcessError(format!("Failed to write agent input: {}", e)))?;
+        stdin
+            .flush()
+            .await
+            .map_err(|e| AgentError::ProcessError(format!("Failed to flush agent input: {}", e)))
+    }
 
-        let agent_id_clone = agent_id.clone();
-        let emit_clone = emit_event.clone();
-        let agents_clone = self.agents.clone();
+    /// Stops a running agent gracefully (see [`AgentManager::with_stop_grace_period`]),
+    /// or removes it from the queue if it hasn't spawned yet. The agent keeps its
+    /// workspace slot until its process has actually exited. A run waiting to
+    /// retry is not retried.
+    pub async fn stop_agent(&self, agent_id: &AgentId) -> Result<(), AgentError> {
+        if let Some(launching) = self.inner.lock_launching().get_mut(agent_id) {
+            launching.cancelled = true;
+            return Ok(());
+        }
+        match self.inner.shut_down(agent_id).await {
+            Some(true) => {
+                self.inner.clone().pump_queue().await;
+                Ok(())
+            }
+            // Already being stopped or reaped, or between attempts.
+            Some(false) => Ok(()),
+            None => self.cancel_queued(agent_id).await,
+        }
+    }
 
-        // Spawn stderr reader to capture errors
-        let agent_id_stderr = agent_id.clone();
-        let emit_stderr = emit_event.clone();
-        tokio::spawn(async move {
-            let reader = BufReader::new(stderr);
-            let mut lines = reader.lines();
-            while let Ok(Some(line)) = lines.next_line().await {
-                if !line.trim().is_empty() {
-                    eprintln!("[CLI stderr] {}", line);
-                    emit_stderr(AgentEvent::Error {
-                        agent_id: agent_id_stderr.clone(),
-                        message: format!("CLI: {}", line),
-                    });
-                }
+    pub async fn stop_all(&self) {
+        // Empty the queue first so nothing spawns into the slots freed below.
+        for run in self.inner.queue.lock().await.drain(..) {
+            self.inner.cancelled_queued(run);
+        }
+        for launching in self.inner.lock_launching().values_mut() {
+            launching.cancelled = true;
+        }
+
+        let children: Vec<(AgentId, Child)> = {
+            let mut agents = self.inner.agents.write().await;
+            agents
+                .iter_mut()
+                .filter_map(|(id, handle)| {
+                    handle.cancelled.send_replace(true);
+                    handle.child.take().map(|child| (id.clone(), child))
+                })
+                .collect()
+        };
+
+        // Shut everything down in parallel so the grace periods overlap.
+        let grace = self.inner.stop_grace();
+        let shutdowns: Vec<_> = children
+            .into_iter()
+            .map(|(agent_id, mut child)| {
+                tokio::spawn(async move {
+                    let exit = process::shutdown(&mut child, grace).await;
+                    (agent_id, exit)
+                })
+            })
+            .collect();
+        for shutdown in shutdowns {
+            if let Ok((agent_id, exit)) = shutdown.await {
+                self.inner.stopped(&agent_id, exit).await;
             }
-        });
+        }
+    }
 
-        tokio::spawn(async move {
-            Self::process_output(agent_id_clone.clone(), stdout, emit_clone.clone()).await;
-
-            let mut agents = agents_clone.write().await;
-            if let Some(handle) = agents.remove(&agent_id_clone) {
-                let status = handle.child.wait_with_output().await;
-                let (reason, success) = match status {
-                    Ok(output) if output.status.success() => (StopReason::Completed, true),
-                    Ok(output) => {
-                        eprintln!("[CLI] Process exited with status: {:?}", output.status);
-                        (StopReason::Error, false)
-                    }
-                    Err(e) => {
-                        eprintln!("[CLI] Process error: {}", e);
-                        (StopReason::Error, false)
-                    }
-                };
+    /// Removes a run from the queue before it spawns.
+    pub async fn cancel_queued(&self, agent_id: &AgentId) -> Result<(), AgentError> {
+        let mut queue = self.inner.queue.lock().await;
+        let index = queue
+            .iter()
+            .position(|run| &run.agent_id == agent_id)
+            .ok_or(AgentError::NotFound)?;
+        if let Some(run) = queue.remove(index) {
+            self.inner.cancelled_queued(run);
+        }
+        Inner::announce_positions(&queue, index);
+        Ok(())
+    }
 
-                // Emit result event before stopped
-                emit_clone(AgentEvent::Result {
-                    agent_id: agent_id_clone.clone(),
-                    success,
-                    duration_ms: 0,
-                });
+    /// Runs waiting to spawn, in FIFO order.
+    pub async fn list_queue(&self) -> Vec<QueueEntry> {
+        self.inner
+            .queue
+            .lock()
+            .await
+            .iter()
+            .enumerate()
+            .map(|(i, run)| QueueEntry {
+                agent_id: run.agent_id.clone(),
+                workspace_id: run.config.workspace_id.clone(),
+                position: i + 1,
+                queued_at: run.queued_at,
+            })
+            .collect()
+    }
 
-                emit_clone(AgentEvent::Stopped {
-                    agent_id: agent_id_clone,
-                    reason,
-                });
+    pub async fn list_agents(&self) -> Vec<AgentId> {
+        self.inner.agents.read().await.keys().cloned().collect()
+    }
+
+    pub async fn is_running(&self, agent_id: &AgentId) -> bool {
+        self.inner.agents.read().await.contains_key(agent_id)
+    }
+
+    /// Deletes `agent_id`'s run from history. A run that is still queued,
+    /// launching or running is refused with `AlreadyRunning`: its record is
+    /// still being written.
+    pub async fn delete_run(&self, agent_id: &AgentId) -> Result<(), AgentError> {
+        let history = self.inner.history.as_deref().ok_or_else(|| {
+            AgentError::NotSupported("Run history is disabled".to_string())
+        })?;
+        {
+            // Held so the run can't move between the queue, launch and the running set.
+            let queue = self.inner.queue.lock().await;
+            let launching = self.inner.lock_launching().contains_key(agent_id);
+            if launching
+                || queue.iter().any(|run| &run.agent_id == agent_id)
+                || self.inner.agents.read().await.contains_key(agent_id)
+            {
+                return Err(AgentError::AlreadyRunning);
             }
-        });
+        }
+        history.delete(agent_id).await
+    }
 
-        let handle = AgentHandle {
-            id: agent_id.clone(),
-            workspace_id,
-            child,
+    /// Starts a workflow: every node runs as an agent once the nodes it takes input
+    /// from have succeeded, with their output in its prompt. Node runs report their
+    /// events through `emit_event` as usual, alongside `NodeStarted`, `NodeFinished`
+    /// and a final `WorkflowFinished`.
+    pub async fn start_workflow<F>(
+        &self,
+        definition: WorkflowDefinition,
+        emit_event: F,
+    ) -> Result<WorkflowId, AgentError>
+    where
+        F: Fn(AgentEvent) + Send + Sync + Clone + 'static,
+    {
+        workflow::check(&definition)?;
+        for node in &definition.nodes {
+            self.inner.validate(&node.config)?;
+        }
+
+        let workflow_id = Uuid::new_v4().to_string();
+        let run = Arc::new(WorkflowRun::new(workflow_id.clone(), &definition));
+        {
+            let mut workflows = self.inner.workflows.write().await;
+            workflow::evict_finished(&mut workflows, FINISHED_WORKFLOWS_KEPT);
+            workflows.insert(workflow_id.clone(), run.clone());
+        }
+        let manager = AgentManager {
+            inner: self.inner.clone(),
         };
+        tokio::spawn(workflow::drive(manager, definition, run, Arc::new(emit_event)));
+        Ok(workflow_id)
+    }
 
-        self.agents.write().await.insert(agent_id.clone(), handle);
+    /// Cancels a workflow: its running nodes are stopped and the rest never start.
+    pub async fn cancel_workflow(&self, workflow_id: &WorkflowId) -> Result<(), AgentError> {
+        let workflows = self.inner.workflows.read().await;
+        let run = workflows.get(workflow_id).ok_or(AgentError::NotFound)?;
+        run.cancel.send_replace(true);
+        Ok(())
+    }
 
-        Ok(agent_id)
+    pub async fn workflow(&self, workflow_id: &WorkflowId) -> Result<WorkflowInfo, AgentError> {
+        self.inner
+            .workflows
+            .read()
+            .await
+            .get(workflow_id)
+            .map(|run| run.info())
+            .ok_or(AgentError::NotFound)
+    }
+
+    /// Running workflows and the most recently finished ones, oldest first.
+    pub async fn list_workflows(&self) -> Vec<WorkflowInfo> {
+        let mut workflows: Vec<WorkflowInfo> = self
+            .inner
+            .workflows
+            .read()
+            .await
+            .values()
+            .map(|run| run.info())
+            .collect();
+        workflows.sort_by_key(|info| info.started_at);
+        workflows
+    }
+
+    /// Answers a `PermissionRequest` from a run in a gated permission mode.
+    pub fn respond_permission(
+        &self,
+        agent_id: &AgentId,
+        request_id: &str,
+        decision: PermissionDecision,
+    ) -> Result<(), AgentError> {
+        self.inner
+            .permissions
+            .respond(agent_id, request_id, decision)
     }
 
-    async fn process_output<R, F>(agent_id: AgentId, reader: R, emit_event: F)
+    /// Permission requests still waiting for an answer, oldest first.
+    pub fn pending_permissions(&self) -> Vec<PendingPermission> {
+        self.inner.permissions.pending()
+    }
+
+    /// The git worktree of a run started with `Isolation::Worktree`, including
+    /// runs from before a restart.
+    pub async fn worktree(&self, agent_id: &AgentId) -> Result<WorktreeInfo, AgentError> {
+        self.inner
+            .find_worktree(agent_id)
+            .await
+            .ok_or(AgentError::NotFound)
+    }
+
+    /// Merges a finished isolated run's branch into whatever its repository has
+    /// checked out, then deletes the worktree and branch. A conflicting merge is
+    /// aborted and returned as `AgentError::Git`, leaving the branch as it was.
+    pub async fn merge_worktree<F>(
+        &self,
+        agent_id: &AgentId,
+        emit_event: F,
+    ) -> Result<WorktreeInfo, AgentError>
     where
-        R: tokio::io::AsyncRead + Unpin,
-        F: Fn(AgentEvent) + Send + Sync,
+        F: Fn(AgentEvent) + Send + Sync + Clone + 'static,
     {
-        let reader = BufReader::new(reader);
-        let mut lines = reader.lines();
-        let mut last_tool_name: Option<String> = None;
+        let mut info = self.closable_worktree(agent_id).await?;
+        worktree::merge(&mut info).await?;
+        self.worktree_closed(agent_id, info, emit_event).await
+    }
+
+    /// Deletes a finished isolated run's worktree and branch without merging.
+    pub async fn discard_worktree<F>(
+        &self,
+        agent_id: &AgentId,
+        emit_event: F,
+    ) -> Result<WorktreeInfo, AgentError>
+    where
+        F: Fn(AgentEvent) + Send + Sync + Clone + 'static,
+    {
+        let mut info = self.closable_worktree(agent_id).await?;
+        worktree::discard(&mut info).await?;
+        self.worktree_closed(agent_id, info, emit_event).await
+    }
+
+    async fn closable_worktree(&self, agent_id: &AgentId) -> Result<WorktreeInfo, AgentError> {
+        let info = self.worktree(agent_id).await?;
+        if info.status == WorktreeStatus::Active || self.is_running(agent_id).await {
+            return Err(AgentError::AlreadyRunning);
+        }
+        if !info.is_open() {
+            return Err(AgentError::NotSupported(format!(
+                "{} was already merged or discarded",
+                info.branch
+            )));
+        }
+        Ok(info)
+    }
+
+    async fn worktree_closed<F>(
+        &self,
+        agent_id: &AgentId,
+        info: WorktreeInfo,
+        emit_event: F,
+    ) -> Result<WorktreeInfo, AgentError>
+    where
+        F: Fn(AgentEvent) + Send + Sync + Clone + 'static,
+    {
+        self.inner.set_worktree(agent_id, info.clone()).await;
+        // Runs that continued this one share the branch; their history should say so too.
+        let sharing: Vec<AgentId> = self
+            .inner
+            .worktrees
+            .read()
+            .await
+            .iter()
+            .filter(|(_, run)| run.info.repo == info.repo && run.info.branch == info.branch)
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in sharing {
+            let emit_event = self.inner.recording_emitter(id.clone(), emit_event.clone());
+            emit_event(AgentEvent::WorktreeChanged {
+                agent_id: id,
+                worktree: Box::new(info.clone()),
+            });
+        }
+        Ok(info)
+    }
+}
+
+impl Default for AgentManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Inner {
+    fn vault(&self) -> Option<Arc<SecretVault>> {
+        self.vault.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Rejects configs the chosen CLI (or any of its fallbacks) can't honour,
+    /// before anything is spawned or queued.
+    fn validate(&self, config: &AgentConfig) -> Result<(), AgentError> {
+        for key in config.env.keys() {
+            if key.is_empty() || key.contains(['=', '\0']) {
+                return Err(AgentError::NotSupported(format!(
+                    "Invalid environment variable name '{}'",
+                    key
+                )));
+            }
+        }
+        if config.isolation == Isolation::Worktree
+            && config.working_directory.is_none()
+            && config.parent_agent_id.is_none()
+        {
+            return Err(AgentError::NotSupported(
+                "Worktree isolation needs a working directory".to_string(),
+            ));
+        }
+        if let Some(profile) = &config.sandbox {
+            sandbox::check(profile)?;
+            // The permission endpoint is on the host's loopback, which neither
+            // restricted network mode can reach.
+            if profile.network != NetworkAccess::Full && config.permission_mode.is_gated() {
+                return Err(AgentError::NotSupported(
+                    "A sandbox with restricted network can't ask for permission".to_string(),
+                ));
+            }
+        }
+        let vault = self.vault();
+        for name in config.env.values().flat_map(|value| vault::references(value)) {
+            match &vault {
+                Some(vault) if vault.contains(name) => {}
+                Some(_) => return Err(AgentError::SecretNotFound(name.to_string())),
+                None => {
+                    return Err(AgentError::NotSupported(
+                        "No secrets vault is configured".to_string(),
+                    ))
+                }
+            }
+        }
+        for config in (0..=config.fallbacks.len()).filter_map(|i| fallback_config(config, i)) {
+            let backend = self.registry.resolve(config.cli.as_ref())?;
+            let capabilities = backend.capabilities();
+            if config.session_id.is_some() && !capabilities.resume {
+                return Err(AgentError::NotSupported(format!(
+                    "{} cannot resume sessions",
+                    backend.id()
+                )));
+            }
+            if config.interactive && !capabilities.live_input {
+                return Err(AgentError::NotSupported(format!(
+                    "{} does not accept live input",
+                    backend.id()
+                )));
+            }
+            if config.permission_mode.is_gated() && !capabilities.permission_prompts {
+                return Err(AgentError::NotSupported(format!(
+                    "{} cannot ask for permission; use the bypass permission mode",
+                    backend.id()
+                )));
+            }
+        }
+        Ok(())
+    }
 
-        while let Ok(Some(line)) = lines.next_line().await {
-            if line.trim().is_empty() {
-                continue;
+    /// Wraps `emit_event` so every event is scrubbed of vault secrets and appended
+    /// to the run's history before being forwarded to the client.
+    fn recording_emitter<F>(&self, agent_id: AgentId, emit_event: F) -> EventEmitter
+    where
+        F: Fn(AgentEvent) + Send + Sync + 'static,
+    {
+        let history = self.history.clone();
+        let vault = self.vault();
+        Arc::new(move |event: AgentEvent| {
+            let event = match &vault {
+                Some(vault) => vault.redact_event(event),
+                None => event,
+            };
+            if let Some(history) = &history {
+                if let Err(e) = history.record_event(&agent_id, &event) {
+                    eprintln!("[History] Failed to record event for {}: {}", agent_id, e);
+                }
             }
+            emit_event(event);
+        })
+    }
 
-            match Self::parse_line(&line) {
-                Some(message) => {
-                    if let Some(event) = Self::convert_message(&agent_id, message, &mut last_tool_name) {
-                        emit_event(event);
-                    }
+    /// Looks up `config.skills`; fails on the first that can't be found.
+    async fn resolve_skills(&self, config: &AgentConfig) -> Result<Vec<SkillDetail>, AgentError> {
+        if config.skills.is_empty() {
+            return Ok(Vec::new());
+        }
+        let repository = self
+            .skills
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+            .ok_or_else(|| {
+                AgentError::NotSupported("No skills directory is configured".to_string())
+            })?;
+        let working_directory = config.working_directory.as_deref().map(Path::new);
+        let mut skills: Vec<SkillDetail> = Vec::new();
+        for name in &config.skills {
+            let skill = repository.resolve(name, working_directory).await?;
+            if !skills.iter().any(|s| s.path == skill.path) {
+                skills.push(skill);
+            }
+        }
+        Ok(skills)
+    }
+
+    fn lock_run_skills(
+        &self,
+    ) -> std::sync::MutexGuard<'_, HashMap<AgentId, Arc<Vec<SkillDetail>>>> {
+        self.run_skills.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn run_skills(&self, agent_id: &AgentId) -> Arc<Vec<SkillDetail>> {
+        self.lock_run_skills().get(agent_id).cloned().unwrap_or_default()
+    }
+
+    /// Drops what a run kept while it was queued or running.
+    fn close_run(&self, agent_id: &AgentId) {
+        self.permissions.close(agent_id);
+        self.lock_run_skills().remove(agent_id);
+    }
+
+    /// `config` with the run's skills made available to `backend`. CLIs that find
+    /// skills themselves get `Skill` allowed and only the skills they wouldn't find
+    /// written into the prompt; others get every skill's instructions in their
+    /// system prompt, or in the prompt if they take none. A resumed session already
+    /// has them in its history, so its prompt is left alone.
+    async fn with_run_skills(
+        &self,
+        agent_id: &AgentId,
+        backend: &dyn CliBackend,
+        config: &AgentConfig,
+    ) -> AgentConfig {
+        let mut config = config.clone();
+        let run_skills = self.run_skills(agent_id);
+        if run_skills.is_empty() {
+            return config;
+        }
+
+        let capabilities = backend.capabilities();
+        let mut missing = Vec::new();
+        if capabilities.skills {
+            let dir = match &config.working_directory {
+                Some(dir) => Some(PathBuf::from(dir)),
+                None => std::env::current_dir().ok(),
+            };
+            for skill in run_skills.iter() {
+                if !skills::found_by_cli(skill, dir.as_deref()).await {
+                    missing.push(skill);
                 }
-                None => {
-                    // Log unparseable lines but don't fail
-                    eprintln!("Unparseable line: {}", line);
+            }
+            if let Some(tools) = config.allowed_tools.as_mut().filter(|t| !t.is_empty()) {
+                if !tools.iter().any(|tool| tool == "Skill") {
+                    tools.push("Skill".to_string());
                 }
             }
+        } else {
+            missing.extend(run_skills.iter());
+        }
+        if missing.is_empty() {
+            return config;
+        }
+
+        let text = skills::instructions(&missing);
+        if capabilities.system_prompt && !capabilities.skills {
+            config.system_prompt = Some(match config.system_prompt.take() {
+                Some(prompt) if !prompt.trim().is_empty() => format!("{}\n\n{}", prompt, text),
+                _ => text,
+            });
+        } else if config.session_id.is_none() {
+            config.prompt = format!("{}\n{}", text, config.prompt);
+        }
+        config
+    }
+
+    fn record_start(&self, agent_id: &AgentId, config: &AgentConfig) {
+        if let Some(history) = &self.history {
+            if let Err(e) = history.record_start(agent_id, config) {
+                eprintln!("[History] Failed to record start of {}: {}", agent_id, e);
+            }
+        }
+    }
+
+    fn record_finish(&self, agent_id: &AgentId, reason: StopReason, exit_code: Option<i32>) {
+        if let Some(history) = &self.history {
+            if let Err(e) = history.record_finish(agent_id, reason, exit_code) {
+                eprintln!("[History] Failed to finish run {}: {}", agent_id, e);
+            }
+        }
+    }
+
+    /// A run may spawn now if a global slot is free, its workspace has no active
+    /// or launching run, and no earlier run for the same workspace is still waiting.
+    async fn has_capacity(&self, queue: &VecDeque<QueuedRun>, workspace_id: &WorkspaceId) -> bool {
+        let agents = self.agents.read().await;
+        let launching = self.lock_launching();
+        agents.len() + launching.len() < self.max_concurrent.load(Ordering::SeqCst)
+            && !Self::workspace_busy(&agents, &launching, workspace_id)
+            && !queue.iter().any(|run| &run.config.workspace_id == workspace_id)
+    }
+
+    /// The oldest queued run that may spawn now, if a global slot is free.
+    async fn next_runnable(&self, queue: &VecDeque<QueuedRun>) -> Option<usize> {
+        let agents = self.agents.read().await;
+        let launching = self.lock_launching();
+        if agents.len() + launching.len() >= self.max_concurrent.load(Ordering::SeqCst) {
+            return None;
+        }
+        queue
+            .iter()
+            .position(|run| !Self::workspace_busy(&agents, &launching, &run.config.workspace_id))
+    }
+
+    fn workspace_busy(
+        agents: &HashMap<AgentId, AgentHandle>,
+        launching: &HashMap<AgentId, Launching>,
+        workspace_id: &WorkspaceId,
+    ) -> bool {
+        agents.values().any(|handle| &handle.workspace_id == workspace_id)
+            || launching.values().any(|run| &run.workspace_id == workspace_id)
+    }
+
+    fn lock_launching(&self) -> std::sync::MutexGuard<'_, HashMap<AgentId, Launching>> {
+        self.launching.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Holds a slot and `workspace_id` for a run while it is isolated and launched
+    /// without the queue lock. Call with the queue locked, after checking capacity.
+    fn reserve_launch(&self, agent_id: &AgentId, workspace_id: &WorkspaceId) {
+        self.lock_launching().insert(
+            agent_id.clone(),
+            Launching {
+                workspace_id: workspace_id.clone(),
+                cancelled: false,
+            },
+        );
+    }
+
+    /// Releases a launch's reservation once the run is attached, or has failed to
+    /// launch, and stops it if that was asked for meanwhile.
+    async fn end_launch(self: &Arc<Self>, agent_id: &AgentId) {
+        let launching = self.lock_launching().remove(agent_id);
+        if launching.is_some_and(|launching| launching.cancelled)
+            && self.shut_down(agent_id).await == Some(true)
+        {
+            self.clone().pump_queue().await;
         }
     }
 
-    fn parse_line(line: &str) -> Option<ClaudeMessage> {
-        serde_json::from_str(line).ok()
+    /// Shuts a running agent's process down. `None` if there is no such run,
+    /// `Some(false)` if it is already being stopped, reaped or between attempts.
+    async fn shut_down(&self, agent_id: &AgentId) -> Option<bool> {
+        let child = {
+            let mut agents = self.agents.write().await;
+            let handle = agents.get_mut(agent_id)?;
+            handle.cancelled.send_replace(true);
+            handle.child.take()
+        };
+        let Some(mut child) = child else {
+            return Some(false);
+        };
+        let exit = process::shutdown(&mut child, self.stop_grace()).await;
+        self.stopped(agent_id, exit).await;
+        Some(true)
     }
 
-    fn convert_message(
+    async fn launch(
+        &self,
         agent_id: &AgentId,
-        message: ClaudeMessage,
-        last_tool_name: &mut Option<String>,
-    ) -> Option<AgentEvent> {
-        match message {
-            ClaudeMessage::Init { session_id, model } => Some(AgentEvent::Init {
-                agent_id: agent_id.clone(),
-                session_id,
-                model,
-            }),
-            ClaudeMessage::Assistant { message } => {
-                let mut text_content = String::new();
-                let mut tool_event = None;
-
-                for block in message.content {
-                    match block {
-                        ContentBlock::Text { text } => {
-                            if !text_content.is_empty() {
-                                text_content.push('\n');
-                            }
-                            text_content.push_str(&text);
-                        }
-                        ContentBlock::ToolUse { name, input, .. } => {
-                            *last_tool_name = Some(name.clone());
-                            tool_event = Some(AgentEvent::ToolUse {
-                                agent_id: agent_id.clone(),
-                                tool_name: name,
-                                tool_input: input,
-                            });
-                        }
-                        ContentBlock::ToolResult { is_error, .. } => {
-                            if let Some(tool_name) = last_tool_name.take() {
-                                return Some(AgentEvent::ToolResult {
-                                    agent_id: agent_id.clone(),
-                                    tool_name,
-                                    success: !is_error,
-                                });
-                            }
-                        }
-                        ContentBlock::Unknown => {}
+        config: &AgentConfig,
+    ) -> Result<Launched, AgentError> {
+        let backend = self.registry.resolve(config.cli.as_ref())?;
+        let env = vault::resolve_env(config, self.vault().as_deref())?;
+        // Later attempts start from `launched.config`, so skills are added to a copy.
+        let original = config;
+        let config = &self.with_run_skills(agent_id, backend.as_ref(), config).await;
+
+        let binary = self.registry.binary_path(backend.as_ref());
+        let sandboxed = match &config.sandbox {
+            Some(profile) => {
+                // Without a working directory the CLI works where the server does.
+                let dir = match &config.working_directory {
+                    Some(dir) => PathBuf::from(dir),
+                    None => std::env::current_dir().map_err(|e| {
+                        AgentError::SpawnFailed(format!("No working directory to sandbox: {}", e))
+                    })?,
+                };
+                Some(sandbox::wrap(profile, backend.as_ref(), &binary, &dir).await?)
+            }
+            None => None,
+        };
+
+        let mut cmd = match &sandboxed {
+            Some(sandboxed) => {
+                let mut cmd = Command::new(&sandboxed.program);
+                cmd.args(&sandboxed.args);
+                cmd
+            }
+            None => Command::new(&binary),
+        };
+        cmd.args(backend.build_args(config));
+        if backend.capabilities().permission_prompts {
+            let url = self.permissions.endpoint(agent_id).await?;
+            cmd.args(backend.permission_args(config.permission_mode, url.as_deref()));
+        }
+        cmd.stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .stdin(if config.interactive { Stdio::piped() } else { Stdio::null() });
+        process::isolate(&mut cmd);
+        process::apply_env(&mut cmd, config, env);
+        if let Some(sandboxed) = &sandboxed {
+            cmd.envs(sandboxed.env.iter().cloned());
+        }
+
+        if let Some(dir) = &config.working_directory {
+            cmd.current_dir(dir);
+        }
+
+        let mut child = cmd.spawn().map_err(|e| {
+            // Whatever detection found may have been uninstalled since.
+            self.registry.forget(backend.id());
+            AgentError::SpawnFailed(e.to_string())
+        })?;
+        let spawned_at = Instant::now();
+
+        let stdin = child.stdin.take().map(|stdin| Arc::new(Mutex::new(stdin)));
+        if let Some(stdin) = &stdin {
+            if let Err(e) = AgentManager::write_input(stdin, backend.as_ref(), &config.prompt).await {
+                let _ = child.kill().await;
+                return Err(e);
+            }
+        }
+
+        let (sandbox, proxy) = match sandboxed {
+            Some(sandboxed) => (Some(sandboxed.profile), sandboxed.proxy),
+            None => (None, None),
+        };
+        Ok(Launched {
+            child,
+            backend,
+            stdin,
+            spawned_at,
+            config: original.clone(),
+            backend_index: 0,
+            fell_back: Vec::new(),
+            sandbox,
+            proxy,
+        })
+    }
+
+    /// Under `Isolation::Worktree`, checks out the run's worktree and returns the
+    /// config pointed at it. A run continuing an isolated run carries on in that
+    /// run's branch while it is open.
+    async fn isolate(
+        &self,
+        agent_id: &AgentId,
+        config: &AgentConfig,
+    ) -> Result<AgentConfig, AgentError> {
+        if config.isolation != Isolation::Worktree {
+            return Ok(config.clone());
+        }
+        let parent = match &config.parent_agent_id {
+            Some(parent_agent_id) => self.find_worktree(parent_agent_id).await,
+            None => None,
+        };
+        let (info, created) = match parent {
+            Some(parent) if parent.status == WorktreeStatus::Active => {
+                return Err(AgentError::AlreadyRunning)
+            }
+            Some(parent) if parent.is_open() && parent.path.exists() => (
+                WorktreeInfo {
+                    status: WorktreeStatus::Active,
+                    ..parent
+                },
+                false,
+            ),
+            Some(parent) if parent.is_open() => (worktree::restore(&parent).await?, false),
+            // Merged or discarded: start over from the repository itself.
+            Some(parent) => {
+                let dir = match &parent.subdir {
+                    Some(subdir) => parent.repo.join(subdir),
+                    None => parent.repo.clone(),
+                };
+                (worktree::create(agent_id, &dir).await?, true)
+            }
+            None => {
+                let dir = config.working_directory.as_deref().ok_or_else(|| {
+                    AgentError::NotSupported(
+                        "Worktree isolation needs a working directory".to_string(),
+                    )
+                })?;
+                (worktree::create(agent_id, Path::new(dir)).await?, true)
+            }
+        };
+
+        let mut config = config.clone();
+        config.working_directory = Some(info.working_directory().to_string_lossy().into_owned());
+        if let Some(profile) = &mut config.sandbox {
+            // Commits from inside the worktree write to the repository's objects and refs.
+            profile.writable.push(info.repo.join(".git"));
+        }
+        self.set_worktree(agent_id, info.clone()).await;
+        self.worktrees
+            .write()
+            .await
+            .insert(agent_id.clone(), RunWorktree { info, created });
+        Ok(config)
+    }
+
+    /// A run's worktree as last reported, from memory or its history.
+    async fn find_worktree(&self, agent_id: &AgentId) -> Option<WorktreeInfo> {
+        if let Some(run) = self.worktrees.read().await.get(agent_id) {
+            return Some(run.info.clone());
+        }
+        let history = self.history.as_ref()?;
+        history.get(agent_id).await.ok()?.summary.worktree
+    }
+
+    /// Records `info` for `agent_id` and for every other run sharing its branch.
+    async fn set_worktree(&self, agent_id: &AgentId, info: WorktreeInfo) {
+        let mut worktrees = self.worktrees.write().await;
+        for run in worktrees.values_mut() {
+            if run.info.repo == info.repo && run.info.branch == info.branch {
+                run.info = info.clone();
+            }
+        }
+        worktrees
+            .entry(agent_id.clone())
+            .or_insert(RunWorktree {
+                info,
+                created: false,
+            });
+    }
+
+    /// Commits what an isolated run changed to its branch and applies its
+    /// cleanup policy, then reports the worktree's new state.
+    async fn finish_worktree(&self, agent_id: &AgentId, emit_event: &EventEmitter) {
+        let Some(mut info) = self
+            .worktrees
+            .read()
+            .await
+            .get(agent_id)
+            .map(|run| run.info.clone())
+            .filter(|info| info.status == WorktreeStatus::Active)
+        else {
+            return;
+        };
+        let (cleanup, prompt) = match self.sessions.read().await.get(agent_id) {
+            Some(record) => (record.config.worktree_cleanup, record.config.prompt.clone()),
+            None => (WorktreeCleanup::Keep, String::new()),
+        };
+        let message = commit_message(agent_id, &prompt);
+        if let Err(e) = worktree::finish(&mut info, &message, cleanup).await {
+            eprintln!("[Git] Failed to finish worktree {}: {}", info.branch, e);
+            info.status = if info.path.exists() {
+                WorktreeStatus::Kept
+            } else {
+                WorktreeStatus::Removed
+            };
+        }
+        self.set_worktree(agent_id, info.clone()).await;
+        emit_event(AgentEvent::WorktreeChanged {
+            agent_id: agent_id.clone(),
+            worktree: Box::new(info),
+        });
+    }
+
+    /// Undoes `isolate` for a run that never spawned.
+    async fn abandon_worktree(&self, agent_id: &AgentId) {
+        let Some(RunWorktree { mut info, created }) = self.worktrees.write().await.remove(agent_id)
+        else {
+            return;
+        };
+        if created {
+            if let Err(e) = worktree::discard(&mut info).await {
+                eprintln!("[Git] Failed to discard worktree {}: {}", info.branch, e);
+            }
+        } else {
+            // Hand the continued run's worktree back as it was.
+            info.status = WorktreeStatus::Kept;
+            self.set_worktree(agent_id, info).await;
+            self.worktrees.write().await.remove(agent_id);
+        }
+    }
+
+    /// Launches backend `from` of the run's fallback chain, moving on to the next
+    /// one for as long as they fail to spawn.
+    async fn launch_chain(
+        &self,
+        agent_id: &AgentId,
+        config: &AgentConfig,
+        from: usize,
+    ) -> Result<Launched, AgentError> {
+        let mut fell_back = Vec::new();
+        let mut index = from;
+        let mut attempt_config = fallback_config(config, index).ok_or(AgentError::NotFound)?;
+        loop {
+            match self.launch(agent_id, &attempt_config).await {
+                Ok(mut launched) => {
+                    launched.backend_index = index;
+                    launched.fell_back = fell_back;
+                    return Ok(launched);
+                }
+                Err(AgentError::SpawnFailed(message)) => {
+                    let Some(next) = fallback_config(config, index + 1) else {
+                        return Err(AgentError::SpawnFailed(message));
+                    };
+                    eprintln!(
+                        "[CLI] Could not spawn {}: {}; falling back",
+                        attempt_config.cli.clone().unwrap_or_default().as_str(),
+                        message
+                    );
+                    fell_back.push(AgentEvent::FallingBack {
+                        agent_id: agent_id.clone(),
+                        from: attempt_config.cli.clone().unwrap_or_default(),
+                        to: next.cli.clone().unwrap_or_default(),
+                        model: next.model.clone(),
+                        reason: FailureClass::Unavailable,
+                        message: Some(message),
+                    });
+                    index += 1;
+                    attempt_config = next;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Registers a freshly spawned process and starts the task that reads its
+    /// output, cleans up after it exits and runs any retries.
+    async fn attach(
+        self: &Arc<Self>,
+        agent_id: AgentId,
+        config: AgentConfig,
+        launched: Launched,
+        emit_event: EventEmitter,
+    ) {
+        let Launched {
+            mut child,
+            backend,
+            stdin,
+            spawned_at,
+            config: attempt_config,
+            backend_index,
+            fell_back,
+            sandbox,
+            proxy,
+        } = launched;
+        let parser = backend.parser();
+        let failure = Arc::new(std::sync::Mutex::new(AttemptFailure::default()));
+        let emit_event = Self::observing_emitter(emit_event, failure.clone());
+
+        // Continuing the run later needs the backend that actually ran it.
+        self.sessions.write().await.insert(
+            agent_id.clone(),
+            SessionRecord {
+                config: attempt_config.clone(),
+                session_id: attempt_config.session_id.clone(),
+                started_at: unix_millis(),
+            },
+        );
+
+        for event in fell_back {
+            emit_event(event);
+        }
+        emit_event(AgentEvent::Started {
+            agent_id: agent_id.clone(),
+            workspace_id: config.workspace_id.clone(),
+            parent_agent_id: config.parent_agent_id.clone(),
+            cli: Some(attempt_config.cli.clone().unwrap_or_default()),
+            model: attempt_config.model.clone(),
+            worktree: self.worktrees.read().await.get(&agent_id).map(|w| Box::new(w.info.clone())),
+            sandbox,
+            skills: self.run_skills(&agent_id).iter().map(|s| s.info.clone()).collect(),
+        });
+
+        // Both pipes were requested in `launch`, so they are always present.
+        let stdout = child.stdout.take();
+        let stderr = child.stderr.take();
+        let pid = child.id();
+        let stderr_backend = backend.clone();
+        let (drained_tx, output_drained) = watch::channel(false);
+
+        // Register before the readers start so a fast exit can't race the insert.
+        self.agents.write().await.insert(
+            agent_id.clone(),
+            AgentHandle {
+                id: agent_id.clone(),
+                workspace_id: config.workspace_id.clone(),
+                backend,
+                child: Some(child),
+                pid,
+                stdin,
+                output_drained,
+                spawned_at,
+                attempt: 1,
+                cancelled: watch::channel(false).0,
+                emit_event: emit_event.clone(),
+                result_sent: false,
+                proxy,
+            },
+        );
+        let stderr_reader = Self::read_stderr(
+            agent_id.clone(),
+            stderr,
+            stderr_backend,
+            self.vault(),
+            failure.clone(),
+            emit_event.clone(),
+        );
+
+        let inner = self.clone();
+        tokio::spawn(async move {
+            let mut run = RunProgress {
+                config: attempt_config.clone(),
+                backend_index,
+                attempt: 1,
+                meter: UsageMeter::new(attempt_config.model.clone()),
+                reported: None,
+                failure,
+                stderr_reader,
+                last_exit: None,
+            };
+            let mut attempt = NextAttempt {
+                config: attempt_config,
+                stdout,
+                parser,
+                drained: drained_tx,
+            };
+            loop {
+                let end = match attempt.stdout.take() {
+                    Some(stdout) => {
+                        AgentManager::process_output(
+                            agent_id.clone(),
+                            stdout,
+                            attempt.parser,
+                            &attempt.config,
+                            &mut run.meter,
+                            &emit_event,
+                            &inner.sessions,
+                        )
+                        .await
                     }
+                    None => OutputEnd {
+                        limit: None,
+                        reported: None,
+                    },
+                };
+                let step = inner.complete(&agent_id, &config, end, &mut run).await;
+                let _ = attempt.drained.send(true);
+                let Some(step) = step else {
+                    break;
+                };
+                match inner.next_attempt(&agent_id, &config, step, &mut run).await {
+                    Some(next) => attempt = next,
+                    None => break,
                 }
+            }
+            inner.close_run(&agent_id);
+            inner.pump_queue().await;
+        });
+    }
 
-                // Prefer tool event over text if both present
-                if let Some(event) = tool_event {
-                    return Some(event);
+    /// Wraps `emit_event` so the failures each attempt reports are noted for the retry policy.
+    fn observing_emitter(
+        emit_event: EventEmitter,
+        failure: Arc<std::sync::Mutex<AttemptFailure>>,
+    ) -> EventEmitter {
+        Arc::new(move |event: AgentEvent| {
+            failure
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .observe(&event);
+            emit_event(event);
+        })
+    }
+
+    /// Classifies the CLI's stderr so warnings and progress output don't surface as
+    /// failures, noting the errors it reports in the attempt's `failure`.
+    fn read_stderr(
+        agent_id: AgentId,
+        stderr: Option<ChildStderr>,
+        backend: Arc<dyn CliBackend>,
+        vault: Option<Arc<SecretVault>>,
+        failure: Arc<std::sync::Mutex<AttemptFailure>>,
+        emit_event: EventEmitter,
+    ) -> Option<JoinHandle<()>> {
+        let stderr = stderr?;
+        Some(tokio::spawn(async move {
+            let reader = BufReader::new(stderr);
+            let mut lines = reader.lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                if let Some(classified) = backend.classify_stderr(&line) {
+                    match &vault {
+                        Some(vault) => eprintln!("[CLI stderr] {}", vault.redact(&line)),
+                        None => eprintln!("[CLI stderr] {}", line),
+                    }
+                    failure
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .observe_stderr(&classified);
+                    emit_event(classified.into_event(&agent_id));
                 }
+            }
+        }))
+    }
 
-                if !text_content.is_empty() {
-                    return Some(AgentEvent::Message {
-                        agent_id: agent_id.clone(),
-                        content: text_content,
-                    });
+    fn stop_grace(&self) -> Duration {
+        Duration::from_millis(self.stop_grace_ms.load(Ordering::SeqCst))
+    }
+
+    /// Reaps a process whose output has ended, or shuts it down first if a limit
+    /// tripped. If `stop_agent` already owns the shutdown, only reports the
+    /// result the CLI printed before it was stopped. A failed attempt is retried
+    /// or handed to the next fallback backend when the run's config calls for it.
+    async fn complete(
+        &self,
+        agent_id: &AgentId,
+        config: &AgentConfig,
+        end: OutputEnd,
+        run: &mut RunProgress,
+    ) -> Option<NextStep> {
+        if let Some(reported) = end.reported {
+            run.reported = Some(match run.reported.take() {
+                Some(earlier) => merge_results(earlier, reported),
+                None => reported,
+            });
+        }
+
+        let (mut child, spawned_at, cancelled, emit_event) = {
+            let mut agents = self.agents.write().await;
+            let handle = agents.get_mut(agent_id)?;
+            let Some(child) = handle.child.take() else {
+                (handle.emit_event)(Self::final_result(
+                    agent_id,
+                    handle.spawned_at,
+                    run.reported.is_some_and(|reported| reported.success),
+                    run.reported,
+                ));
+                handle.result_sent = true;
+                return None;
+            };
+            (
+                child,
+                handle.spawned_at,
+                handle.cancelled.subscribe(),
+                handle.emit_event.clone(),
+            )
+        };
+
+        let (reason, success, exit) = if let Some(reason) = end.limit {
+            eprintln!("[CLI] Agent {} hit its {:?} limit, stopping it", agent_id, reason);
+            let exit = process::shutdown(&mut child, self.stop_grace()).await;
+            (reason, false, Some(exit))
+        } else {
+            // The CLI is gone; don't leave its subprocesses orphaned.
+            let status = process::wait(&mut child).await;
+            let (reason, success, exit) = match status {
+                Ok(status) if status.success() => {
+                    (StopReason::Completed, true, Some(process::exit_info(status, false)))
+                }
+                Ok(status) => {
+                    eprintln!("[CLI] Process exited with status: {:?}", status);
+                    (StopReason::Error, false, Some(process::exit_info(status, false)))
                 }
+                Err(e) => {
+                    eprintln!("[CLI] Process error: {}", e);
+                    (StopReason::Error, false, None)
+                }
+            };
+            (reason, end.reported.map_or(success, |reported| reported.success), exit)
+        };
+        run.last_exit = exit.clone();
+        // Let the CLI's last words on stderr be classified before judging the failure.
+        if let Some(reader) = run.stderr_reader.take() {
+            let _ = tokio::time::timeout(OUTPUT_DRAIN_TIMEOUT, reader).await;
+        }
 
-                None
+        let mut error = None;
+        if !success && !*cancelled.borrow() {
+            let failure = std::mem::take(&mut *run.failure.lock().unwrap_or_else(|e| e.into_inner()));
+            let class = match reason {
+                StopReason::Timeout => FailureClass::Timeout,
+                StopReason::Stalled => FailureClass::Stalled,
+                _ => failure.class.unwrap_or(FailureClass::Error),
+            };
+            if let Some(policy) = config
+                .retry
+                .as_ref()
+                .filter(|policy| policy.should_retry(run.attempt, class))
+            {
+                run.meter.settle();
+                let delay = policy.delay(run.attempt, failure.retry_after);
+                eprintln!(
+                    "[CLI] Agent {} attempt {} failed ({:?}), retrying in {:?}",
+                    agent_id, run.attempt, class, delay
+                );
+                emit_event(AgentEvent::Retrying {
+                    agent_id: agent_id.clone(),
+                    attempt: run.attempt + 1,
+                    max_attempts: policy.max_attempts,
+                    delay_ms: delay.as_millis() as u64,
+                    reason: class,
+                });
+                return Some(NextStep::Retry(delay));
             }
-            ClaudeMessage::Result {
-                subtype,
-                duration_ms,
-            } => Some(AgentEvent::Result {
-                agent_id: agent_id.clone(),
-                success: subtype == "success",
-                duration_ms: duration_ms.unwrap_or(0),
-            }),
-            ClaudeMessage::Error { error } => Some(AgentEvent::Error {
+            if let Some(next) = fallback_config(config, run.backend_index + 1)
+                .filter(|_| config.fallback_on.contains(&class))
+            {
+                run.meter.settle();
+                eprintln!(
+                    "[CLI] Agent {} failed on {} ({:?}), falling back",
+                    agent_id,
+                    run.config.cli.clone().unwrap_or_default().as_str(),
+                    class
+                );
+                emit_event(AgentEvent::FallingBack {
+                    agent_id: agent_id.clone(),
+                    from: run.config.cli.clone().unwrap_or_default(),
+                    to: next.cli.clone().unwrap_or_default(),
+                    model: next.model,
+                    reason: class,
+                    message: None,
+                });
+                return Some(NextStep::Fallback);
+            }
+            error = failure.error;
+        }
+
+        self.agents.write().await.remove(agent_id);
+        // What went wrong, as the typed error with its hint on what to do about it.
+        if let Some(error) = error {
+            emit_event(AgentEvent::Error {
                 agent_id: agent_id.clone(),
-                message: error.message.unwrap_or_else(|| "Unknown error".to_string()),
-            }),
-            ClaudeMessage::Unknown => None,
+                message: error.to_string(),
+            });
         }
+        emit_event(Self::final_result(agent_id, spawned_at, success, run.reported));
+        self.finish_worktree(agent_id, &emit_event).await;
+        let exit_code = exit.as_ref().and_then(|exit| exit.code);
+        emit_event(AgentEvent::Stopped {
+            agent_id: agent_id.clone(),
+            reason: reason.clone(),
+            exit,
+        });
+        self.record_finish(agent_id, reason, exit_code);
+        None
     }
 
-    pub async fn stop_agent(&self, agent_id: &AgentId) -> Result<(), AgentError> {
-        let mut agents = self.agents.write().await;
-        if let Some(mut handle) = agents.remove(agent_id) {
-            let _ = handle.child.kill().await;
-            Ok(())
+    /// Launches the attempt `step` calls for: the same backend after a retry's
+    /// backoff (resuming the session when the policy allows), or the next
+    /// fallback backend straight away. Finishes the run instead if it is stopped
+    /// while waiting or nothing can be launched.
+    async fn next_attempt(
+        &self,
+        agent_id: &AgentId,
+        config: &AgentConfig,
+        step: NextStep,
+        run: &mut RunProgress,
+    ) -> Option<NextAttempt> {
+        let (mut cancelled, backend, emit_event, spawned_at) = {
+            let agents = self.agents.read().await;
+            let handle = agents.get(agent_id)?;
+            (
+                handle.cancelled.subscribe(),
+                handle.backend.clone(),
+                handle.emit_event.clone(),
+                handle.spawned_at,
+            )
+        };
+        if let NextStep::Retry(delay) = step {
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => {}
+                _ = cancelled.wait_for(|cancelled| *cancelled) => {}
+            }
+        }
+
+        let launched = if *cancelled.borrow() {
+            Err(None)
         } else {
-            Err(AgentError::NotFound)
+            match step {
+                NextStep::Retry(_) => {
+                    let next_config = self.retry_config(agent_id, &run.config, backend.as_ref()).await;
+                    self.launch(agent_id, &next_config).await.map(|mut launched| {
+                        launched.backend_index = run.backend_index;
+                        launched
+                    })
+                }
+                NextStep::Fallback => {
+                    self.launch_chain(agent_id, config, run.backend_index + 1)
+                        .await
+                }
+            }
+            .map_err(Some)
+        };
+
+        let mut launched = match launched {
+            Ok(launched) => launched,
+            Err(error) => {
+                self.agents.write().await.remove(agent_id);
+                let reason = match error {
+                    Some(error) => {
+                        emit_event(AgentEvent::Error {
+                            agent_id: agent_id.clone(),
+                            message: error.to_string(),
+                        });
+                        StopReason::Error
+                    }
+                    None => StopReason::Cancelled,
+                };
+                emit_event(Self::final_result(agent_id, spawned_at, false, run.reported));
+                self.finish_worktree(agent_id, &emit_event).await;
+                let exit = run.last_exit.take();
+                let exit_code = exit.as_ref().and_then(|exit| exit.code);
+                emit_event(AgentEvent::Stopped {
+                    agent_id: agent_id.clone(),
+                    reason: reason.clone(),
+                    exit,
+                });
+                self.record_finish(agent_id, reason, exit_code);
+                return None;
+            }
+        };
+
+        for event in std::mem::take(&mut launched.fell_back) {
+            emit_event(event);
+        }
+        if launched.backend_index == run.backend_index {
+            run.attempt += 1;
+        } else {
+            // A new backend starts its own session and its own retries.
+            run.attempt = 1;
+            run.backend_index = launched.backend_index;
+            run.config = launched.config.clone();
+            if let Some(model) = &run.config.model {
+                run.meter.set_model(model);
+            }
+            if let Some(record) = self.sessions.write().await.get_mut(agent_id) {
+                record.config = run.config.clone();
+                record.session_id = None;
+            }
         }
+
+        let stdout = launched.child.stdout.take();
+        let stderr = launched.child.stderr.take();
+        let (drained, output_drained) = watch::channel(false);
+        {
+            let mut agents = self.agents.write().await;
+            let handle = agents.get_mut(agent_id)?;
+            handle.pid = launched.child.id();
+            handle.child = Some(launched.child);
+            handle.backend = launched.backend.clone();
+            handle.stdin = launched.stdin;
+            handle.output_drained = output_drained;
+            handle.attempt = run.attempt;
+            handle.proxy = launched.proxy;
+        }
+        run.stderr_reader = Self::read_stderr(
+            agent_id.clone(),
+            stderr,
+            launched.backend.clone(),
+            self.vault(),
+            run.failure.clone(),
+            emit_event,
+        );
+
+        Some(NextAttempt {
+            config: launched.config,
+            stdout,
+            parser: launched.backend.parser(),
+            drained,
+        })
     }
 
-    pub async fn stop_all(&self) {
-        let mut agents = self.agents.write().await;
-        for (_, mut handle) in agents.drain() {
-            let _ = handle.child.kill().await;
+    /// The config for a retry on the same backend: the one the run is using, or
+    /// a continuation of the session the failed attempt started.
+    async fn retry_config(
+        &self,
+        agent_id: &AgentId,
+        config: &AgentConfig,
+        backend: &dyn CliBackend,
+    ) -> AgentConfig {
+        let mut next = config.clone();
+        let resume = config.retry.as_ref().is_some_and(|policy| policy.resume)
+            && backend.capabilities().resume;
+        if !resume {
+            return next;
+        }
+        let session_id = self
+            .sessions
+            .read()
+            .await
+            .get(agent_id)
+            .and_then(|record| record.session_id.clone());
+        if let Some(session_id) = session_id {
+            next.session_id = Some(session_id);
+            next.prompt = RESUME_PROMPT.to_string();
         }
+        next
     }
 
-    pub async fn list_agents(&self) -> Vec<AgentId> {
-        self.agents.read().await.keys().cloned().collect()
+    /// The run's single `Result`: the CLI's own numbers where it reported them,
+    /// the manager's measurements otherwise.
+    fn final_result(
+        agent_id: &AgentId,
+        spawned_at: Instant,
+        success: bool,
+        reported: Option<CliResult>,
+    ) -> AgentEvent {
+        let wall_clock_ms = spawned_at.elapsed().as_millis() as u64;
+        AgentEvent::Result {
+            agent_id: agent_id.clone(),
+            success,
+            duration_ms: reported
+                .and_then(|reported| reported.duration_ms)
+                .unwrap_or(wall_clock_ms),
+            wall_clock_ms,
+            api_duration_ms: reported.and_then(|reported| reported.api_duration_ms),
+        }
     }
 
-    pub async fn is_running(&self, agent_id: &AgentId) -> bool {
-        self.agents.read().await.contains_key(agent_id)
+    /// Spawns queued runs, oldest first, while there is capacity for them.
+    /// Boxed because spawned runs call back into it when they finish.
+    fn pump_queue(self: Arc<Self>) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        Box::pin(async move {
+            loop {
+                let run = {
+                    let mut queue = self.queue.lock().await;
+                    let Some(index) = self.next_runnable(&queue).await else {
+                        return;
+                    };
+                    let Some(run) = queue.remove(index) else {
+                        return;
+                    };
+                    self.reserve_launch(&run.agent_id, &run.config.workspace_id);
+                    Self::announce_positions(&queue, index);
+                    run
+                };
+                let agent_id = run.agent_id.clone();
+                (run.emit_event)(AgentEvent::Dequeued {
+                    agent_id: agent_id.clone(),
+                });
+
+                let launched = match self.isolate(&agent_id, &run.config).await {
+                    Ok(config) => self
+                        .launch_chain(&agent_id, &config, 0)
+                        .await
+                        .map(|launched| (config, launched)),
+                    Err(e) => Err(e),
+                };
+                match launched {
+                    Ok((config, launched)) => {
+                        self.attach(run.agent_id, config, launched, run.emit_event)
+                            .await;
+                    }
+                    Err(e) => {
+                        self.abandon_worktree(&agent_id).await;
+                        self.close_run(&agent_id);
+                        (run.emit_event)(AgentEvent::Error {
+                            agent_id: agent_id.clone(),
+                            message: e.to_string(),
+                        });
+                        (run.emit_event)(Self::final_result(
+                            &agent_id,
+                            Instant::now(),
+                            false,
+                            None,
+                        ));
+                        (run.emit_event)(AgentEvent::Stopped {
+                            agent_id: agent_id.clone(),
+                            reason: StopReason::Error,
+                            exit: None,
+                        });
+                        self.record_finish(&agent_id, StopReason::Error, None);
+                    }
+                }
+                self.end_launch(&agent_id).await;
+            }
+        })
     }
-}
 
-impl Default for AgentManager {
-    fn default() -> Self {
-        Self::new()
+    /// Re-emits `Queued` for every run at or after `from`, whose position just changed.
+    fn announce_positions(queue: &VecDeque<QueuedRun>, from: usize) {
+        for (i, run) in queue.iter().enumerate().skip(from) {
+            (run.emit_event)(AgentEvent::Queued {
+                agent_id: run.agent_id.clone(),
+                workspace_id: run.config.workspace_id.clone(),
+                position: i + 1,
+            });
+        }
+    }
+
+    /// Releases a run that `stop_agent` has shut down and reports the cancellation.
+    async fn stopped(&self, agent_id: &AgentId, exit: ProcessExit) {
+        // Let whatever the CLI printed while shutting down reach the client first.
+        let drained = self
+            .agents
+            .read()
+            .await
+            .get(agent_id)
+            .map(|handle| handle.output_drained.clone());
+        if let Some(mut drained) = drained {
+            let _ = tokio::time::timeout(OUTPUT_DRAIN_TIMEOUT, drained.wait_for(|done| *done)).await;
+        }
+
+        let Some(handle) = self.agents.write().await.remove(agent_id) else {
+            return;
+        };
+        // The output didn't end in time for `complete` to report the result.
+        if !handle.result_sent {
+            (handle.emit_event)(Self::final_result(agent_id, handle.spawned_at, false, None));
+        }
+        self.finish_worktree(agent_id, &handle.emit_event).await;
+        (handle.emit_event)(AgentEvent::Stopped {
+            agent_id: agent_id.clone(),
+            reason: StopReason::Cancelled,
+            exit: Some(exit.clone()),
+        });
+        self.record_finish(agent_id, StopReason::Cancelled, exit.code);
+    }
+
+    fn cancelled_queued(&self, run: QueuedRun) {
+        self.close_run(&run.agent_id);
+        (run.emit_event)(Self::final_result(&run.agent_id, Instant::now(), false, None));
+        (run.emit_event)(AgentEvent::Stopped {
+            agent_id: run.agent_id.clone(),
+            reason: StopReason::Cancelled,
+            exit: None,
+        });
+        self.record_finish(&run.agent_id, StopReason::Cancelled, None);
     }
 }
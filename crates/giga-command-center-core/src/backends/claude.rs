@@ -0,0 +1,112 @@
+use crate::permissions::{PermissionMode, MCP_SERVER, PROMPT_TOOL};
+use crate::types::AgentConfig;
+
+use super::{push_opt, Capabilities, CliBackend, OutputParser, StreamJsonParser};
+
+/// Claude Code (`claude`).
+pub struct ClaudeBackend;
+
+impl CliBackend for ClaudeBackend {
+    fn id(&self) -> &str {
+        "claude"
+    }
+
+    fn name(&self) -> &str {
+        "Claude Code"
+    }
+
+    fn binary(&self) -> &str {
+        "claude"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            resume: true,
+            live_input: true,
+            max_turns: true,
+            system_prompt: true,
+            allowed_tools: true,
+            modes: false,
+            permission_prompts: true,
+            skills: true,
+        }
+    }
+
+    fn api_hosts(&self) -> Vec<&str> {
+        vec!["api.anthropic.com", "console.anthropic.com", "statsig.anthropic.com"]
+    }
+
+    fn state_paths(&self) -> Vec<&str> {
+        vec![".claude", ".claude.json"]
+    }
+
+    fn build_args(&self, config: &AgentConfig) -> Vec<String> {
+        // Interactive runs read the prompt (and every later message) from stdin.
+        let mut args = if config.interactive {
+            vec![
+                "-p".to_string(),
+                "--input-format".to_string(),
+                "stream-json".to_string(),
+            ]
+        } else {
+            vec!["-p".to_string(), config.prompt.clone()]
+        };
+        args.extend([
+            "--output-format".to_string(),
+            "stream-json".to_string(),
+            "--verbose".to_string(),
+        ]);
+        push_opt(&mut args, "--resume", config.session_id.as_ref());
+        if let Some(max_turns) = config.max_turns {
+            args.push("--max-turns".to_string());
+            args.push(max_turns.to_string());
+        }
+        push_opt(&mut args, "--model", config.model.as_ref());
+        push_opt(&mut args, "--system-prompt", config.system_prompt.as_ref());
+        if let Some(tools) = &config.allowed_tools {
+            if !tools.is_empty() {
+                args.push("--allowedTools".to_string());
+                args.push(tools.join(","));
+            }
+        }
+        args
+    }
+
+    fn permission_args(&self, mode: PermissionMode, prompt_url: Option<&str>) -> Vec<String> {
+        let mode = match mode {
+            PermissionMode::Default => "default",
+            PermissionMode::AcceptEdits => "acceptEdits",
+            PermissionMode::Plan => "plan",
+            PermissionMode::Bypass => "bypassPermissions",
+        };
+        let mut args = vec!["--permission-mode".to_string(), mode.to_string()];
+        if let Some(url) = prompt_url {
+            let servers = serde_json::json!({
+                "mcpServers": { MCP_SERVER: { "type": "http", "url": url } },
+            });
+            args.extend([
+                "--mcp-config".to_string(),
+                servers.to_string(),
+                "--permission-prompt-tool".to_string(),
+                PROMPT_TOOL.to_string(),
+            ]);
+        }
+        args
+    }
+
+    fn input_line(&self, text: &str) -> Option<String> {
+        // `--input-format stream-json`: one user message per line
+        let message = serde_json::json!({
+            "type": "user",
+            "message": {
+                "role": "user",
+                "content": [{ "type": "text", "text": text }],
+            },
+        });
+        Some(format!("{}\n", message))
+    }
+
+    fn parser(&self) -> Box<dyn OutputParser> {
+        Box::new(StreamJsonParser::new())
+    }
+}
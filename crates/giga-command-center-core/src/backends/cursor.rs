@@ -0,0 +1,281 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use crate::types::*;
+
+use super::{push_opt, Capabilities, CliBackend, CliOutput, CliResult, OutputParser};
+
+/// Cursor Agent (`agent`). See: https://cursor.com/docs/cli/overview
+pub struct CursorBackend;
+
+impl CliBackend for CursorBackend {
+    fn id(&self) -> &str {
+        "cursor"
+    }
+
+    fn name(&self) -> &str {
+        "Cursor Agent"
+    }
+
+    fn binary(&self) -> &str {
+        "agent"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            resume: true,
+            modes: true,
+            ..Default::default()
+        }
+    }
+
+    fn api_hosts(&self) -> Vec<&str> {
+        vec!["*.cursor.sh"]
+    }
+
+    fn state_paths(&self) -> Vec<&str> {
+        vec![".cursor", ".config/cursor"]
+    }
+
+    fn build_args(&self, config: &AgentConfig) -> Vec<String> {
+        // Modes: agent (default), plan, ask. Non-interactive: -p, --model, --output-format
+        let mut args = vec![
+            "-p".to_string(),
+            config.prompt.clone(),
+            "--output-format".to_string(),
+            "stream-json".to_string(),
+        ];
+        push_opt(&mut args, "--resume", config.session_id.as_ref());
+        push_opt(&mut args, "--model", config.model.as_ref());
+        if let Some(mode) = &config.mode {
+            let m = mode.trim().to_lowercase();
+            if ["agent", "plan", "ask"].contains(&m.as_str()) {
+                args.push("--mode".to_string());
+                args.push(m);
+            }
+        }
+        args
+    }
+
+    fn parser(&self) -> Box<dyn OutputParser> {
+        Box::new(CursorParser::new())
+    }
+}
+
+/// One line of Cursor Agent's `--output-format stream-json`.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum CursorEvent {
+    System {
+        #[serde(default)]
+        subtype: Option<String>,
+        #[serde(default)]
+        session_id: Option<String>,
+        #[serde(default)]
+        model: Option<String>,
+    },
+    Assistant {
+        message: CursorMessage,
+    },
+    /// `subtype` is `started` or `completed`; `tool_call` holds a single key naming
+    /// the tool (`readToolCall`, `shellToolCall`, ... or `function`).
+    ToolCall {
+        subtype: String,
+        call_id: String,
+        tool_call: serde_json::Value,
+    },
+    Result {
+        #[serde(default)]
+        subtype: Option<String>,
+        #[serde(default)]
+        is_error: bool,
+        #[serde(default)]
+        duration_ms: Option<u64>,
+        #[serde(default)]
+        duration_api_ms: Option<u64>,
+    },
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, Deserialize)]
+struct CursorMessage {
+    content: Vec<ContentBlock>,
+}
+
+/// Parser for Cursor Agent's stream-json, which shares Claude's envelope but
+/// reports tools as separate `tool_call` events.
+#[derive(Debug, Default)]
+pub struct CursorParser {
+    tool_names: HashMap<String, String>,
+}
+
+impl CursorParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// The tool's name, its arguments and, once completed, whether it succeeded.
+fn describe_tool_call(tool_call: &serde_json::Value) -> Option<(String, serde_json::Value, Option<bool>)> {
+    let (key, call) = tool_call.as_object()?.iter().next()?;
+    let name = match key.as_str() {
+        "function" => call.get("name")?.as_str()?.to_string(),
+        key => key.strip_suffix("ToolCall").unwrap_or(key).to_string(),
+    };
+    let args = match call.get("args").or_else(|| call.get("arguments")) {
+        // `function` calls carry their arguments as a JSON-encoded string.
+        Some(serde_json::Value::String(raw)) => {
+            serde_json::from_str(raw).unwrap_or_else(|_| serde_json::Value::String(raw.clone()))
+        }
+        Some(args) => args.clone(),
+        None => serde_json::Value::Null,
+    };
+    let success = call
+        .get("result")
+        .map(|result| result.get("success").is_some());
+    Some((name, args, success))
+}
+
+impl OutputParser for CursorParser {
+    fn parse_line(&mut self, agent_id: &AgentId, line: &str) -> Vec<CliOutput> {
+        if line.trim().is_empty() {
+            return vec![];
+        }
+        let event = match serde_json::from_str::<CursorEvent>(line) {
+            Ok(event) => event,
+            Err(e) => {
+                // Neither the line nor the error message (which can quote it) is
+                // logged, as either may hold secrets
+                eprintln!(
+                    "Unparseable line ({} bytes): {:?} error at column {}",
+                    line.len(),
+                    e.classify(),
+                    e.column()
+                );
+                return vec![];
+            }
+        };
+
+        let event = match event {
+            CursorEvent::System {
+                subtype,
+                session_id: Some(session_id),
+                model,
+            } if subtype.as_deref() == Some("init") => AgentEvent::Init {
+                agent_id: agent_id.clone(),
+                session_id,
+                model: model.unwrap_or_default(),
+            },
+            CursorEvent::Assistant { message } => {
+                let text: Vec<String> = message
+                    .content
+                    .into_iter()
+                    .filter_map(|block| match block {
+                        ContentBlock::Text { text } => Some(text),
+                        _ => None,
+                    })
+                    .collect();
+                if text.is_empty() {
+                    return vec![];
+                }
+                AgentEvent::Message {
+                    agent_id: agent_id.clone(),
+                    content: text.join("\n"),
+                }
+            }
+            CursorEvent::ToolCall {
+                subtype,
+                call_id,
+                tool_call,
+            } => {
+                let Some((name, args, success)) = describe_tool_call(&tool_call) else {
+                    return vec![];
+                };
+                if subtype == "started" {
+                    self.tool_names.insert(call_id, name.clone());
+                    AgentEvent::ToolUse {
+                        agent_id: agent_id.clone(),
+                        tool_name: name,
+                        tool_input: args,
+                    }
+                } else {
+                    AgentEvent::ToolResult {
+                        agent_id: agent_id.clone(),
+                        tool_name: self.tool_names.remove(&call_id).unwrap_or(name),
+                        success: success.unwrap_or(true),
+                    }
+                }
+            }
+            CursorEvent::Result {
+                subtype,
+                is_error,
+                duration_ms,
+                duration_api_ms,
+            } => {
+                return vec![CliOutput::Result(CliResult {
+                    success: !is_error && subtype.as_deref().unwrap_or("success") == "success",
+                    duration_ms,
+                    api_duration_ms: duration_api_ms,
+                    total_cost_usd: None,
+                    usage: None,
+                })];
+            }
+            CursorEvent::System { .. } | CursorEvent::Unknown => return vec![],
+        };
+        vec![CliOutput::Event(event)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::super::parse_all;
+    use super::*;
+
+    #[test]
+    fn parses_a_recorded_run() {
+        let lines = [
+            r#"{"type":"system","subtype":"init","apiKeySource":"login","cwd":"/w","session_id":"8f1e","model":"Claude 4 Sonnet","permissionMode":"default"}"#,
+            r#"{"type":"user","message":{"role":"user","content":[{"type":"text","text":"What's in README.md?"}]},"session_id":"8f1e"}"#,
+            r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"text","text":"I'll read it."}]},"session_id":"8f1e"}"#,
+            r#"{"type":"tool_call","subtype":"started","call_id":"toolu_1","tool_call":{"readToolCall":{"args":{"path":"README.md"}}},"session_id":"8f1e"}"#,
+            r#"{"type":"tool_call","subtype":"completed","call_id":"toolu_1","tool_call":{"readToolCall":{"args":{"path":"README.md"},"result":{"success":{"content":"Demo","totalLines":1}}}},"session_id":"8f1e"}"#,
+            r#"{"type":"tool_call","subtype":"started","call_id":"toolu_2","tool_call":{"function":{"name":"grep","arguments":"{\"pattern\":\"TODO\"}"}},"session_id":"8f1e"}"#,
+            r#"{"type":"tool_call","subtype":"completed","call_id":"toolu_2","tool_call":{"function":{"name":"grep","arguments":"{\"pattern\":\"TODO\"}","result":{"error":{"errorMessage":"rg failed"}}}},"session_id":"8f1e"}"#,
+            r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"text","text":"It's a demo."}]},"session_id":"8f1e"}"#,
+            r#"{"type":"result","subtype":"success","duration_ms":5234,"duration_api_ms":5100,"is_error":false,"result":"It's a demo.","session_id":"8f1e","request_id":"r1"}"#,
+        ];
+        let (events, results) = parse_all(&mut CursorParser::new(), &lines);
+        assert_eq!(
+            events,
+            [
+                json!({"type": "Init", "agent_id": "a", "session_id": "8f1e", "model": "Claude 4 Sonnet"}),
+                json!({"type": "Message", "agent_id": "a", "content": "I'll read it."}),
+                json!({"type": "ToolUse", "agent_id": "a", "tool_name": "read", "tool_input": {"path": "README.md"}}),
+                json!({"type": "ToolResult", "agent_id": "a", "tool_name": "read", "success": true}),
+                json!({"type": "ToolUse", "agent_id": "a", "tool_name": "grep", "tool_input": {"pattern": "TODO"}}),
+                json!({"type": "ToolResult", "agent_id": "a", "tool_name": "grep", "success": false}),
+                json!({"type": "Message", "agent_id": "a", "content": "It's a demo."}),
+            ]
+        );
+        let [result] = results[..] else {
+            panic!("expected one result, got {:?}", results);
+        };
+        assert!(result.success);
+        assert_eq!(result.duration_ms, Some(5234));
+        assert_eq!(result.api_duration_ms, Some(5100));
+        // Cursor doesn't report token usage.
+        assert_eq!(result.usage, None);
+    }
+
+    #[test]
+    fn reports_failed_runs() {
+        let lines = [r#"{"type":"result","subtype":"error","is_error":true,"duration_ms":10}"#];
+        let (events, results) = parse_all(&mut CursorParser::new(), &lines);
+        assert!(events.is_empty());
+        assert!(!results[0].success);
+    }
+}
@@ -0,0 +1,54 @@
+use crate::types::AgentConfig;
+
+use super::{push_opt, Capabilities, CliBackend, OutputParser, PlainTextParser};
+
+/// DeepSeek CLI (`deepseek`). See: https://github.com/PierrunoYT/deepseek-cli
+/// Install: pip install deepseek-cli
+pub struct DeepSeekBackend;
+
+impl CliBackend for DeepSeekBackend {
+    fn id(&self) -> &str {
+        "deepseek"
+    }
+
+    fn name(&self) -> &str {
+        "DeepSeek CLI"
+    }
+
+    fn binary(&self) -> &str {
+        "deepseek"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+
+    fn api_hosts(&self) -> Vec<&str> {
+        vec!["api.deepseek.com"]
+    }
+
+    fn state_paths(&self) -> Vec<&str> {
+        vec![".deepseek"]
+    }
+
+    fn build_args(&self, config: &AgentConfig) -> Vec<String> {
+        // Inline mode: -q or --query for query, -m or --model for model selection
+        // Models: deepseek-chat, deepseek-coder, deepseek-reasoner
+        // Streaming is enabled by default
+        // System prompts are prepended to the query since there's no separate flag
+        let mut query = config.prompt.clone();
+        if let Some(sp) = &config.system_prompt {
+            if !sp.is_empty() {
+                query = format!("System: {}\n\nUser: {}", sp, query);
+            }
+        }
+
+        let mut args = vec!["-q".to_string(), query];
+        push_opt(&mut args, "-m", config.model.as_ref());
+        args
+    }
+
+    fn parser(&self) -> Box<dyn OutputParser> {
+        Box::new(PlainTextParser::new())
+    }
+}
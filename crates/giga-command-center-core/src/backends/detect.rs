@@ -0,0 +1,138 @@
+//! Finding installed agent CLIs: where each binary lives, which version it is
+//! and what the backend supports.
+
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+
+use super::{Capabilities, CliBackend};
+
+/// How long a CLI's probe may take before it counts as unavailable.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What detection found out about one backend's CLI.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CliInfo {
+    /// Backend id, as used in `AgentConfig::cli`.
+    pub id: String,
+    pub name: String,
+    /// The binary was found and its probe succeeded.
+    pub available: bool,
+    /// Where the binary was found, or the configured override.
+    pub path: Option<PathBuf>,
+    /// Version the CLI reported, e.g. `"1.0.43"`.
+    pub version: Option<String>,
+    /// The path came from an override rather than a `PATH` search.
+    pub overridden: bool,
+    pub capabilities: Capabilities,
+    /// Why the CLI is unavailable.
+    pub error: Option<String>,
+}
+
+/// Probes `backend` at `path` (the override when `overridden`, otherwise the
+/// first of its binaries on `PATH`).
+pub(crate) async fn probe(
+    backend: Arc<dyn CliBackend>,
+    path: Option<PathBuf>,
+    overridden: bool,
+) -> CliInfo {
+    let mut info = CliInfo {
+        id: backend.id().to_string(),
+        name: backend.name().to_string(),
+        available: false,
+        path: path.clone(),
+        version: None,
+        overridden,
+        capabilities: backend.capabilities(),
+        error: None,
+    };
+    let Some(path) = path else {
+        info.error = Some(format!(
+            "{} not found on PATH",
+            backend.binaries().join(" or ")
+        ));
+        return info;
+    };
+
+    let output = Command::new(&path)
+        .args(backend.probe_args())
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output();
+    match tokio::time::timeout(PROBE_TIMEOUT, output).await {
+        Ok(Ok(output)) if output.status.success() => {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            info.available = true;
+            info.version = backend
+                .parse_version(&stdout)
+                .or_else(|| backend.parse_version(&stderr));
+        }
+        Ok(Ok(output)) => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            info.error = Some(match stderr.lines().find(|line| !line.trim().is_empty()) {
+                Some(line) => format!("Probe failed ({}): {}", output.status, line.trim()),
+                None => format!("Probe failed ({})", output.status),
+            });
+        }
+        Ok(Err(e)) => info.error = Some(format!("Could not run {}: {}", path.display(), e)),
+        Err(_) => {
+            info.error = Some(format!(
+                "Probe timed out after {}s",
+                PROBE_TIMEOUT.as_secs()
+            ))
+        }
+    }
+    info
+}
+
+/// The first of `names` that is an executable file in a `PATH` directory.
+pub(crate) fn find_on_path(names: &[&str]) -> Option<PathBuf> {
+    let dirs: Vec<PathBuf> = std::env::split_paths(&std::env::var_os("PATH")?).collect();
+    names.iter().find_map(|name| {
+        dirs.iter().find_map(|dir| {
+            let candidate = dir.join(name);
+            if is_executable(&candidate) {
+                return Some(candidate);
+            }
+            #[cfg(windows)]
+            for ext in ["exe", "cmd", "bat"] {
+                let candidate = candidate.with_extension(ext);
+                if candidate.is_file() {
+                    return Some(candidate);
+                }
+            }
+            None
+        })
+    })
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .map(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// The first version-looking word in a CLI's `--version` output, e.g.
+/// `1.0.43` from `1.0.43 (Claude Code)` or `0.9.1` from `kilo v0.9.1`.
+pub fn version_from(output: &str) -> Option<String> {
+    output.split_whitespace().find_map(|word| {
+        let word = word
+            .trim_matches(|c: char| !c.is_ascii_alphanumeric())
+            .trim_start_matches(['v', 'V']);
+        let starts_with_digit = word.starts_with(|c: char| c.is_ascii_digit());
+        (starts_with_digit && word.contains('.')).then(|| word.to_string())
+    })
+}
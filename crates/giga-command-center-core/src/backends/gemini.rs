@@ -0,0 +1,343 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use crate::types::*;
+use crate::usage::TokenUsage;
+
+use super::{push_opt, Capabilities, CliBackend, CliOutput, CliResult, OutputParser};
+
+/// Gemini CLI (`gemini`). See: https://github.com/google-gemini/gemini-cli
+pub struct GeminiBackend;
+
+impl CliBackend for GeminiBackend {
+    fn id(&self) -> &str {
+        "gemini"
+    }
+
+    fn name(&self) -> &str {
+        "Gemini CLI"
+    }
+
+    fn binary(&self) -> &str {
+        "gemini"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            system_prompt: true,
+            ..Default::default()
+        }
+    }
+
+    fn api_hosts(&self) -> Vec<&str> {
+        vec![
+            "generativelanguage.googleapis.com",
+            "cloudcode-pa.googleapis.com",
+            "oauth2.googleapis.com",
+        ]
+    }
+
+    fn state_paths(&self) -> Vec<&str> {
+        vec![".gemini"]
+    }
+
+    fn build_args(&self, config: &AgentConfig) -> Vec<String> {
+        // Similar to other CLIs: -p, -m (for model), --output-format
+        let mut args = vec![
+            "-p".to_string(),
+            config.prompt.clone(),
+            "--output-format".to_string(),
+            "stream-json".to_string(),
+        ];
+        push_opt(&mut args, "-m", config.model.as_ref());
+        push_opt(&mut args, "--system-prompt", config.system_prompt.as_ref());
+        args
+    }
+
+    fn parser(&self) -> Box<dyn OutputParser> {
+        Box::new(GeminiParser::new())
+    }
+}
+
+/// One line of Gemini's `--output-format stream-json`.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum GeminiEvent {
+    Init {
+        session_id: String,
+        #[serde(default)]
+        model: String,
+    },
+    Message {
+        role: String,
+        content: String,
+        /// Assistant replies stream as deltas of one message.
+        #[serde(default)]
+        delta: bool,
+    },
+    ToolUse {
+        tool_name: String,
+        tool_id: String,
+        #[serde(default)]
+        parameters: serde_json::Value,
+    },
+    ToolResult {
+        tool_id: String,
+        status: String,
+        #[serde(default)]
+        error: Option<GeminiError>,
+    },
+    Error {
+        #[serde(default)]
+        severity: Option<String>,
+        message: String,
+    },
+    Result {
+        status: String,
+        #[serde(default)]
+        error: Option<GeminiError>,
+        #[serde(default)]
+        stats: Option<GeminiStats>,
+    },
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, Deserialize)]
+struct GeminiError {
+    #[serde(default)]
+    message: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GeminiStats {
+    #[serde(default)]
+    input_tokens: u64,
+    #[serde(default)]
+    output_tokens: u64,
+    #[serde(default)]
+    cached: u64,
+    #[serde(default)]
+    duration_ms: Option<u64>,
+}
+
+/// Parser for Gemini's stream-json. Assistant deltas are joined into one
+/// `Message` per reply, emitted when the reply is interrupted or the run ends.
+#[derive(Debug, Default)]
+pub struct GeminiParser {
+    reply: String,
+    tool_names: HashMap<String, String>,
+}
+
+impl GeminiParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn flush_reply(&mut self, agent_id: &AgentId, outputs: &mut Vec<CliOutput>) {
+        if !self.reply.is_empty() {
+            outputs.push(CliOutput::Event(AgentEvent::Message {
+                agent_id: agent_id.clone(),
+                content: std::mem::take(&mut self.reply),
+            }));
+        }
+    }
+}
+
+impl OutputParser for GeminiParser {
+    fn parse_line(&mut self, agent_id: &AgentId, line: &str) -> Vec<CliOutput> {
+        if line.trim().is_empty() {
+            return vec![];
+        }
+        let event = match serde_json::from_str::<GeminiEvent>(line) {
+            Ok(event) => event,
+            Err(e) => {
+                // Neither the line nor the error message (which can quote it) is
+                // logged, as either may hold secrets
+                eprintln!(
+                    "Unparseable line ({} bytes): {:?} error at column {}",
+                    line.len(),
+                    e.classify(),
+                    e.column()
+                );
+                return vec![];
+            }
+        };
+
+        let mut outputs = Vec::new();
+        if let GeminiEvent::Message {
+            role,
+            content,
+            delta,
+        } = &event
+        {
+            if role == "assistant" {
+                if !delta {
+                    self.flush_reply(agent_id, &mut outputs);
+                }
+                self.reply.push_str(content);
+                if !delta {
+                    self.flush_reply(agent_id, &mut outputs);
+                }
+            }
+            return outputs;
+        }
+
+        self.flush_reply(agent_id, &mut outputs);
+        match event {
+            GeminiEvent::Init { session_id, model } => {
+                outputs.push(CliOutput::Event(AgentEvent::Init {
+                    agent_id: agent_id.clone(),
+                    session_id,
+                    model,
+                }));
+            }
+            GeminiEvent::ToolUse {
+                tool_name,
+                tool_id,
+                parameters,
+            } => {
+                self.tool_names.insert(tool_id, tool_name.clone());
+                outputs.push(CliOutput::Event(AgentEvent::ToolUse {
+                    agent_id: agent_id.clone(),
+                    tool_name,
+                    tool_input: parameters,
+                }));
+            }
+            GeminiEvent::ToolResult {
+                tool_id,
+                status,
+                error,
+            } => {
+                let tool_name = self.tool_names.remove(&tool_id).unwrap_or(tool_id);
+                if let Some(message) = error.and_then(|error| error.message) {
+                    outputs.push(CliOutput::Event(AgentEvent::Error {
+                        agent_id: agent_id.clone(),
+                        message: format!("{}: {}", tool_name, message),
+                    }));
+                }
+                outputs.push(CliOutput::Event(AgentEvent::ToolResult {
+                    agent_id: agent_id.clone(),
+                    tool_name,
+                    success: status == "success",
+                }));
+            }
+            GeminiEvent::Error { severity, message } => {
+                let message = match severity.as_deref() {
+                    Some("warning") => format!("Warning: {}", message),
+                    _ => message,
+                };
+                outputs.push(CliOutput::Event(AgentEvent::Error {
+                    agent_id: agent_id.clone(),
+                    message,
+                }));
+            }
+            GeminiEvent::Result {
+                status,
+                error,
+                stats,
+            } => {
+                if let Some(message) = error.and_then(|error| error.message) {
+                    outputs.push(CliOutput::Event(AgentEvent::Error {
+                        agent_id: agent_id.clone(),
+                        message,
+                    }));
+                }
+                outputs.push(CliOutput::Result(CliResult {
+                    success: status == "success",
+                    duration_ms: stats.as_ref().and_then(|stats| stats.duration_ms),
+                    api_duration_ms: None,
+                    total_cost_usd: None,
+                    usage: stats.map(|stats| TokenUsage {
+                        // Gemini counts cached tokens as part of the input.
+                        input_tokens: stats.input_tokens.saturating_sub(stats.cached),
+                        output_tokens: stats.output_tokens,
+                        cache_creation_input_tokens: 0,
+                        cache_read_input_tokens: stats.cached,
+                    }),
+                }));
+            }
+            GeminiEvent::Message { .. } | GeminiEvent::Unknown => {}
+        }
+        outputs
+    }
+
+    fn finish(&mut self, agent_id: &AgentId) -> Vec<CliOutput> {
+        let mut outputs = Vec::new();
+        self.flush_reply(agent_id, &mut outputs);
+        outputs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::super::parse_all;
+    use super::*;
+
+    #[test]
+    fn parses_a_recorded_run() {
+        let lines = [
+            r#"{"type":"init","timestamp":"2025-10-10T12:00:00.000Z","session_id":"c2a1","model":"gemini-2.5-pro"}"#,
+            r#"{"type":"message","timestamp":"2025-10-10T12:00:00.010Z","role":"user","content":"Read a.rs"}"#,
+            r#"{"type":"message","timestamp":"2025-10-10T12:00:01.000Z","role":"assistant","content":"Let me ","delta":true}"#,
+            r#"{"type":"message","timestamp":"2025-10-10T12:00:01.100Z","role":"assistant","content":"look.","delta":true}"#,
+            r#"{"type":"tool_use","timestamp":"2025-10-10T12:00:01.200Z","tool_name":"read_file","tool_id":"read_file-1","parameters":{"absolute_path":"/w/a.rs"}}"#,
+            r#"{"type":"tool_result","timestamp":"2025-10-10T12:00:01.300Z","tool_id":"read_file-1","status":"error","output":"","error":{"type":"file_not_found","message":"File not found: /w/a.rs"}}"#,
+            "",
+            r#"{"type":"message","timestamp":"2025-10-10T12:00:02.000Z","role":"assistant","content":"a.rs doesn't exist.","delta":true}"#,
+            r#"{"type":"result","timestamp":"2025-10-10T12:00:02.100Z","status":"success","stats":{"total_tokens":1650,"input_tokens":1500,"output_tokens":150,"cached":1000,"duration_ms":2100,"tool_calls":1}}"#,
+        ];
+        let (events, results) = parse_all(&mut GeminiParser::new(), &lines);
+        assert_eq!(
+            events,
+            [
+                json!({"type": "Init", "agent_id": "a", "session_id": "c2a1", "model": "gemini-2.5-pro"}),
+                json!({"type": "Message", "agent_id": "a", "content": "Let me look."}),
+                json!({"type": "ToolUse", "agent_id": "a", "tool_name": "read_file", "tool_input": {"absolute_path": "/w/a.rs"}}),
+                json!({"type": "Error", "agent_id": "a", "message": "read_file: File not found: /w/a.rs"}),
+                json!({"type": "ToolResult", "agent_id": "a", "tool_name": "read_file", "success": false}),
+                json!({"type": "Message", "agent_id": "a", "content": "a.rs doesn't exist."}),
+            ]
+        );
+        let [result] = results[..] else {
+            panic!("expected one result, got {:?}", results);
+        };
+        assert!(result.success);
+        assert_eq!(result.duration_ms, Some(2100));
+        assert_eq!(
+            result.usage,
+            Some(TokenUsage {
+                input_tokens: 500,
+                output_tokens: 150,
+                cache_creation_input_tokens: 0,
+                cache_read_input_tokens: 1000,
+            })
+        );
+    }
+
+    #[test]
+    fn flushes_an_unfinished_reply_and_reports_failures() {
+        let lines = [
+            r#"{"type":"message","role":"assistant","content":"Partial","delta":true}"#,
+            r#"{"type":"error","severity":"warning","message":"Loop detected"}"#,
+            r#"{"type":"message","role":"assistant","content":"Still going","delta":true}"#,
+            r#"{"type":"result","status":"error","error":{"type":"FatalTurnLimitedError","message":"Reached max turns"}}"#,
+            "not json",
+        ];
+        let (events, results) = parse_all(&mut GeminiParser::new(), &lines);
+        assert_eq!(
+            events,
+            [
+                json!({"type": "Message", "agent_id": "a", "content": "Partial"}),
+                json!({"type": "Error", "agent_id": "a", "message": "Warning: Loop detected"}),
+                json!({"type": "Message", "agent_id": "a", "content": "Still going"}),
+                json!({"type": "Error", "agent_id": "a", "message": "Reached max turns"}),
+            ]
+        );
+        assert!(!results[0].success);
+        assert_eq!(results[0].usage, None);
+    }
+}
@@ -0,0 +1,49 @@
+use crate::types::AgentConfig;
+
+use super::{push_opt, Capabilities, CliBackend, OutputParser, PlainTextParser};
+
+/// Grok CLI (`grok`). See: https://github.com/superagent-ai/grok-cli
+/// Install: bun add -g @vibe-kit/grok-cli or npm install -g @vibe-kit/grok-cli
+pub struct GrokBackend;
+
+impl CliBackend for GrokBackend {
+    fn id(&self) -> &str {
+        "grok"
+    }
+
+    fn name(&self) -> &str {
+        "Grok CLI"
+    }
+
+    fn binary(&self) -> &str {
+        "grok"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            system_prompt: true,
+            ..Default::default()
+        }
+    }
+
+    fn api_hosts(&self) -> Vec<&str> {
+        vec!["api.x.ai"]
+    }
+
+    fn state_paths(&self) -> Vec<&str> {
+        vec![".grok"]
+    }
+
+    fn build_args(&self, config: &AgentConfig) -> Vec<String> {
+        // Headless mode: -p or --prompt, --model
+        // Note: Grok CLI doesn't document --output-format, so we omit it
+        let mut args = vec!["-p".to_string(), config.prompt.clone()];
+        push_opt(&mut args, "--model", config.model.as_ref());
+        push_opt(&mut args, "--system-prompt", config.system_prompt.as_ref());
+        args
+    }
+
+    fn parser(&self) -> Box<dyn OutputParser> {
+        Box::new(PlainTextParser::new())
+    }
+}
@@ -0,0 +1,56 @@
+use crate::types::AgentConfig;
+
+use super::{push_opt, Capabilities, CliBackend, OutputParser, StreamJsonParser};
+
+/// Kilo (`kilo`, or `kilocode` from older releases). See: https://github.com/Kilo-Org/kilocode
+pub struct KiloBackend;
+
+impl CliBackend for KiloBackend {
+    fn id(&self) -> &str {
+        "kilo"
+    }
+
+    fn name(&self) -> &str {
+        "Kilo"
+    }
+
+    fn binary(&self) -> &str {
+        "kilo"
+    }
+
+    fn binaries(&self) -> Vec<&str> {
+        vec!["kilo", "kilocode"]
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            system_prompt: true,
+            ..Default::default()
+        }
+    }
+
+    fn api_hosts(&self) -> Vec<&str> {
+        vec!["api.kilocode.ai", "openrouter.ai"]
+    }
+
+    fn state_paths(&self) -> Vec<&str> {
+        vec![".kilocode"]
+    }
+
+    fn build_args(&self, config: &AgentConfig) -> Vec<String> {
+        // Similar to Claude/Cursor: -p, --model, --output-format
+        let mut args = vec![
+            "-p".to_string(),
+            config.prompt.clone(),
+            "--output-format".to_string(),
+            "stream-json".to_string(),
+        ];
+        push_opt(&mut args, "--model", config.model.as_ref());
+        push_opt(&mut args, "--system-prompt", config.system_prompt.as_ref());
+        args
+    }
+
+    fn parser(&self) -> Box<dyn OutputParser> {
+        Box::new(StreamJsonParser::new())
+    }
+}
@@ -0,0 +1,406 @@
+//! Agent CLIs the manager can drive. Each backend is one [`CliBackend`]
+//! implementation; [`CliRegistry`] maps `AgentConfig::cli` ids to them.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinSet;
+
+use crate::permissions::PermissionMode;
+use crate::stderr::{self, StderrLine};
+use crate::types::*;
+use crate::usage::TokenUsage;
+
+mod claude;
+mod cursor;
+mod deepseek;
+mod detect;
+mod gemini;
+mod grok;
+mod kilo;
+mod plain_text;
+mod stream_json;
+
+pub use claude::ClaudeBackend;
+pub use cursor::{CursorBackend, CursorParser};
+pub use deepseek::DeepSeekBackend;
+pub use detect::{version_from, CliInfo};
+pub(crate) use detect::find_on_path;
+pub use gemini::{GeminiBackend, GeminiParser};
+pub use grok::GrokBackend;
+pub use kilo::KiloBackend;
+pub use plain_text::PlainTextParser;
+pub use stream_json::StreamJsonParser;
+
+/// What a backend's CLI can do beyond a single prompt-in, output-out run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Capabilities {
+    /// Continue an earlier conversation by session id.
+    pub resume: bool,
+    /// Read further user messages from stdin while running.
+    pub live_input: bool,
+    /// Honour `AgentConfig::max_turns`.
+    pub max_turns: bool,
+    /// Take `AgentConfig::system_prompt` as a separate flag.
+    pub system_prompt: bool,
+    /// Restrict tools with `AgentConfig::allowed_tools`.
+    pub allowed_tools: bool,
+    /// Honour `AgentConfig::mode`.
+    pub modes: bool,
+    /// Ask a permission-prompt tool before using tools, for gated
+    /// `AgentConfig::permission_mode`s.
+    pub permission_prompts: bool,
+    /// Find skills on its own, in `~/.claude/skills`, the working directory's
+    /// `.claude/skills` and installed plugins.
+    pub skills: bool,
+}
+
+/// An agent CLI: how to invoke it, what it supports and how to read its output.
+///
+/// Register custom implementations with [`CliRegistry::register`] (or
+/// `AgentManager::with_backend`) and select them with `CliType::Other(id)`.
+pub trait CliBackend: Send + Sync {
+    /// Id matched against `AgentConfig::cli`, e.g. `"claude"`.
+    fn id(&self) -> &str;
+
+    /// Human-readable name for UIs.
+    fn name(&self) -> &str;
+
+    /// Executable to spawn, looked up on `PATH`.
+    fn binary(&self) -> &str;
+
+    /// Executables to look for on `PATH`, in order of preference. Override when
+    /// the CLI has been published under more than one name.
+    fn binaries(&self) -> Vec<&str> {
+        vec![self.binary()]
+    }
+
+    fn capabilities(&self) -> Capabilities;
+
+    /// Hosts the CLI reaches its model through: all a sandboxed run with
+    /// `NetworkAccess::ModelApi` may connect to, besides the profile's own.
+    fn api_hosts(&self) -> Vec<&str> {
+        Vec::new()
+    }
+
+    /// Where under the home directory the CLI keeps its credentials, settings
+    /// and sessions; a sandboxed run may write there.
+    fn state_paths(&self) -> Vec<&str> {
+        Vec::new()
+    }
+
+    /// Command-line arguments for a run of `config`.
+    fn build_args(&self, config: &AgentConfig) -> Vec<String>;
+
+    /// Arguments selecting `mode`, appended to `build_args`. `prompt_url` is the
+    /// MCP endpoint serving the permission-prompt tool, given for gated modes.
+    /// Only called for backends whose capabilities include `permission_prompts`.
+    fn permission_args(&self, _mode: PermissionMode, _prompt_url: Option<&str>) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Arguments for a cheap invocation that only succeeds when the CLI is installed.
+    fn probe_args(&self) -> Vec<String> {
+        vec!["--version".to_string()]
+    }
+
+    /// The version from the probe's output (stdout, then stderr).
+    fn parse_version(&self, output: &str) -> Option<String> {
+        detect::version_from(output)
+    }
+
+    /// Encodes a user message for the CLI's stdin, newline-terminated.
+    /// Only called for backends whose capabilities include `live_input`.
+    fn input_line(&self, _text: &str) -> Option<String> {
+        None
+    }
+
+    /// A fresh parser for one run's stdout.
+    fn parser(&self) -> Box<dyn OutputParser>;
+
+    /// Classifies one line of the CLI's stderr. Override to recognise wording
+    /// the generic heuristics in [`stderr::classify`] miss.
+    fn classify_stderr(&self, line: &str) -> Option<StderrLine> {
+        stderr::classify(line)
+    }
+}
+
+/// Turns a CLI's stdout, one line at a time, into events for the manager.
+pub trait OutputParser: Send {
+    /// Called for every line, including blank ones.
+    fn parse_line(&mut self, agent_id: &AgentId, line: &str) -> Vec<CliOutput>;
+
+    /// Called once stdout has closed (or the run hit a limit), to flush anything buffered.
+    fn finish(&mut self, _agent_id: &AgentId) -> Vec<CliOutput> {
+        Vec::new()
+    }
+}
+
+/// One item of parsed CLI output.
+#[derive(Debug, Clone)]
+pub enum CliOutput {
+    /// Forwarded to the client as-is.
+    Event(AgentEvent),
+    /// Tokens used by one model response. Repeats of the same `message_id` are ignored.
+    Usage {
+        message_id: Option<String>,
+        usage: TokenUsage,
+    },
+    /// The CLI's own summary of a finished turn. The manager folds these into the
+    /// run's single `Result` event.
+    Result(CliResult),
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CliResult {
+    pub success: bool,
+    pub duration_ms: Option<u64>,
+    pub api_duration_ms: Option<u64>,
+    pub total_cost_usd: Option<f64>,
+    pub usage: Option<TokenUsage>,
+}
+
+/// The backends runs can use, keyed by id. Clones share the same set, binary
+/// overrides and detection results.
+#[derive(Clone)]
+pub struct CliRegistry {
+    backends: Arc<RwLock<Vec<Arc<dyn CliBackend>>>>,
+    overrides: Arc<RwLock<HashMap<String, PathBuf>>>,
+    detected: Arc<RwLock<HashMap<String, CliInfo>>>,
+}
+
+impl CliRegistry {
+    /// A registry with every built-in backend.
+    pub fn new() -> Self {
+        let registry = Self::empty();
+        registry.register(Arc::new(ClaudeBackend));
+        registry.register(Arc::new(CursorBackend));
+        registry.register(Arc::new(KiloBackend));
+        registry.register(Arc::new(GeminiBackend));
+        registry.register(Arc::new(GrokBackend));
+        registry.register(Arc::new(DeepSeekBackend));
+        registry
+    }
+
+    pub fn empty() -> Self {
+        Self {
+            backends: Arc::new(RwLock::new(Vec::new())),
+            overrides: Arc::new(RwLock::new(HashMap::new())),
+            detected: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    /// Adds `backend`, replacing any registered backend with the same id.
+    pub fn register(&self, backend: Arc<dyn CliBackend>) {
+        self.forget(backend.id());
+        let mut backends = self.backends.write().unwrap_or_else(|e| e.into_inner());
+        match backends.iter_mut().find(|b| b.id() == backend.id()) {
+            Some(existing) => *existing = backend,
+            None => backends.push(backend),
+        }
+    }
+
+    pub fn get(&self, id: &str) -> Option<Arc<dyn CliBackend>> {
+        self.backends
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .find(|backend| backend.id() == id)
+            .cloned()
+    }
+
+    /// All registered backends, in registration order.
+    pub fn backends(&self) -> Vec<Arc<dyn CliBackend>> {
+        self.backends
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// The backend for a config's `cli` (Claude when unset).
+    pub fn resolve(&self, cli: Option<&CliType>) -> Result<Arc<dyn CliBackend>, AgentError> {
+        let id = cli.map_or(CliType::Claude.as_str(), CliType::as_str);
+        self.get(id)
+            .ok_or_else(|| AgentError::NotSupported(format!("Unknown CLI '{}'", id)))
+    }
+
+    /// Runs backend `id` from `path` instead of searching `PATH` for it.
+    pub fn set_binary(&self, id: &str, path: impl Into<PathBuf>) {
+        self.overrides
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(id.to_string(), path.into());
+        self.forget(id);
+    }
+
+    /// Goes back to searching `PATH` for backend `id`.
+    pub fn clear_binary(&self, id: &str) {
+        self.overrides
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(id);
+        self.forget(id);
+    }
+
+    /// Applies `AGENT_CLI_PATH_<ID>` environment variables (e.g.
+    /// `AGENT_CLI_PATH_KILO=/opt/kilo/bin/kilocode`) as binary overrides.
+    pub fn load_env_overrides(&self) {
+        for backend in self.backends() {
+            let var = format!(
+                "AGENT_CLI_PATH_{}",
+                backend.id().to_uppercase().replace('-', "_")
+            );
+            if let Some(path) = std::env::var_os(&var).filter(|path| !path.is_empty()) {
+                self.set_binary(backend.id(), path);
+            }
+        }
+    }
+
+    /// The executable a run of `backend` spawns: its override, else where
+    /// detection found it, else the first of its binaries on `PATH`. Falls back
+    /// to the bare binary name so spawning reports the CLI as missing.
+    pub fn binary_path(&self, backend: &dyn CliBackend) -> PathBuf {
+        if let Some(path) = self.override_for(backend.id()) {
+            return path;
+        }
+        let detected = self
+            .detected
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(backend.id())
+            .and_then(|info| info.path.clone());
+        detected
+            .or_else(|| detect::find_on_path(&backend.binaries()))
+            .unwrap_or_else(|| PathBuf::from(backend.binary()))
+    }
+
+    /// Every registered backend's CLI: whether it's installed, where, which
+    /// version and what it supports. Results are cached; `refresh` probes again.
+    pub async fn detect(&self, refresh: bool) -> Vec<CliInfo> {
+        let backends = self.backends();
+        let mut results: HashMap<String, CliInfo> = if refresh {
+            HashMap::new()
+        } else {
+            self.detected
+                .read()
+                .unwrap_or_else(|e| e.into_inner())
+                .clone()
+        };
+
+        let mut probes = JoinSet::new();
+        for backend in backends.iter().filter(|b| !results.contains_key(b.id())) {
+            let (path, overridden) = self.locate(backend.as_ref());
+            probes.spawn(detect::probe(backend.clone(), path, overridden));
+        }
+        while let Some(probed) = probes.join_next().await {
+            if let Ok(info) = probed {
+                self.detected
+                    .write()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .insert(info.id.clone(), info.clone());
+                results.insert(info.id.clone(), info);
+            }
+        }
+
+        backends
+            .iter()
+            .filter_map(|backend| results.remove(backend.id()))
+            .collect()
+    }
+
+    /// Detection for one backend; `None` when no backend has that id.
+    pub async fn detect_one(&self, id: &str, refresh: bool) -> Option<CliInfo> {
+        let backend = self.get(id)?;
+        if !refresh {
+            let cached = self
+                .detected
+                .read()
+                .unwrap_or_else(|e| e.into_inner())
+                .get(id)
+                .cloned();
+            if cached.is_some() {
+                return cached;
+            }
+        }
+        let (path, overridden) = self.locate(backend.as_ref());
+        let info = detect::probe(backend, path, overridden).await;
+        self.detected
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(id.to_string(), info.clone());
+        Some(info)
+    }
+
+    /// Whether the backend's CLI is installed, from the cached detection result.
+    pub async fn is_available(&self, id: &str) -> bool {
+        self.detect_one(id, false)
+            .await
+            .is_some_and(|info| info.available)
+    }
+
+    /// Drops the cached detection result for `id`, e.g. after its binary failed to spawn.
+    pub fn forget(&self, id: &str) {
+        self.detected
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(id);
+    }
+
+    /// Where to probe `backend`, and whether that's an override.
+    fn locate(&self, backend: &dyn CliBackend) -> (Option<PathBuf>, bool) {
+        match self.override_for(backend.id()) {
+            Some(path) => (Some(path), true),
+            None => (detect::find_on_path(&backend.binaries()), false),
+        }
+    }
+
+    fn override_for(&self, id: &str) -> Option<PathBuf> {
+        self.overrides
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(id)
+            .cloned()
+    }
+}
+
+impl Default for CliRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Appends `flag value` when `value` is set and non-empty.
+pub(crate) fn push_opt(args: &mut Vec<String>, flag: &str, value: Option<&String>) {
+    if let Some(value) = value.filter(|value| !value.is_empty()) {
+        args.push(flag.to_string());
+        args.push(value.clone());
+    }
+}
+
+/// Feeds `lines` through `parser` as agent `a` and finishes it: the events as
+/// JSON, then the CLI results.
+#[cfg(test)]
+pub(crate) fn parse_all(
+    parser: &mut dyn OutputParser,
+    lines: &[&str],
+) -> (Vec<serde_json::Value>, Vec<CliResult>) {
+    let agent_id: AgentId = "a".to_string();
+    let mut outputs: Vec<CliOutput> = lines
+        .iter()
+        .flat_map(|line| parser.parse_line(&agent_id, line))
+        .collect();
+    outputs.extend(parser.finish(&agent_id));
+    let mut events = Vec::new();
+    let mut results = Vec::new();
+    for output in outputs {
+        match output {
+            CliOutput::Event(event) => events.push(serde_json::to_value(event).unwrap()),
+            CliOutput::Result(result) => results.push(result),
+            CliOutput::Usage { .. } => panic!("unexpected per-message usage"),
+        }
+    }
+    (events, results)
+}
@@ -0,0 +1,108 @@
+use crate::stderr::strip_ansi;
+use crate::types::*;
+
+use super::{CliOutput, OutputParser};
+
+/// Longest run of lines held back before it is emitted even without a paragraph break.
+const MAX_BUFFERED_LINES: usize = 40;
+
+/// Parser for CLIs that print plain text (Grok, DeepSeek). Lines are grouped into
+/// one `Message` per paragraph; blank lines inside fenced code blocks don't split it.
+#[derive(Debug, Default)]
+pub struct PlainTextParser {
+    lines: Vec<String>,
+    in_code_block: bool,
+}
+
+impl PlainTextParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn flush(&mut self, agent_id: &AgentId) -> Vec<CliOutput> {
+        if self.lines.is_empty() {
+            return vec![];
+        }
+        let content = self.lines.join("\n");
+        self.lines.clear();
+        vec![CliOutput::Event(AgentEvent::Message {
+            agent_id: agent_id.clone(),
+            content,
+        })]
+    }
+}
+
+impl OutputParser for PlainTextParser {
+    fn parse_line(&mut self, agent_id: &AgentId, line: &str) -> Vec<CliOutput> {
+        let line = strip_ansi(line);
+        let line = line.trim_end();
+
+        if line.trim_start().starts_with("```") {
+            self.in_code_block = !self.in_code_block;
+        }
+        if line.trim().is_empty() && !self.in_code_block {
+            return self.flush(agent_id);
+        }
+
+        self.lines.push(line.to_string());
+        if self.lines.len() >= MAX_BUFFERED_LINES && !self.in_code_block {
+            return self.flush(agent_id);
+        }
+        vec![]
+    }
+
+    fn finish(&mut self, agent_id: &AgentId) -> Vec<CliOutput> {
+        self.in_code_block = false;
+        self.flush(agent_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::super::parse_all;
+    use super::*;
+
+    fn message(content: &str) -> serde_json::Value {
+        json!({"type": "Message", "agent_id": "a", "content": content})
+    }
+
+    #[test]
+    fn groups_paragraphs_and_code_blocks() {
+        let lines = [
+            "\u{1b}[1mPlan\u{1b}[0m",
+            "Add a main function.   ",
+            "",
+            "",
+            "```rust",
+            "fn main() {",
+            "",
+            "}",
+            "```",
+            "Done.",
+        ];
+        let (events, results) = parse_all(&mut PlainTextParser::new(), &lines);
+        assert_eq!(
+            events,
+            [
+                message("Plan\nAdd a main function."),
+                message("```rust\nfn main() {\n\n}\n```\nDone."),
+            ]
+        );
+        // Plain text carries no result or usage; the manager times the run.
+        assert!(results.is_empty());
+    }
+
+    #[test]
+    fn splits_long_paragraphs() {
+        let lines: Vec<String> = (0..45).map(|i| format!("line {}", i)).collect();
+        let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+        let (events, _) = parse_all(&mut PlainTextParser::new(), &lines);
+        assert_eq!(events.len(), 2);
+        assert_eq!(
+            events[1],
+            message("line 40\nline 41\nline 42\nline 43\nline 44")
+        );
+    }
+}
@@ -0,0 +1,134 @@
+use crate::types::*;
+
+use super::{CliOutput, CliResult, OutputParser};
+
+/// Parser for Claude's `--output-format stream-json`: one JSON message per line.
+#[derive(Debug, Default)]
+pub struct StreamJsonParser {
+    last_tool_name: Option<String>,
+}
+
+impl StreamJsonParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn convert_message(&mut self, agent_id: &AgentId, message: ClaudeMessage) -> Vec<CliOutput> {
+        match message {
+            ClaudeMessage::Init { session_id, model } => vec![CliOutput::Event(AgentEvent::Init {
+                agent_id: agent_id.clone(),
+                session_id,
+                model,
+            })],
+            ClaudeMessage::System {
+                subtype,
+                session_id: Some(session_id),
+                model,
+            } if subtype == "init" => vec![CliOutput::Event(AgentEvent::Init {
+                agent_id: agent_id.clone(),
+                session_id,
+                model: model.unwrap_or_default(),
+            })],
+            ClaudeMessage::System { .. } => vec![],
+            ClaudeMessage::Assistant { message } => {
+                let mut outputs: Vec<CliOutput> =
+                    self.convert_content(agent_id, message.content).into_iter().collect();
+                if let Some(usage) = message.usage {
+                    outputs.push(CliOutput::Usage {
+                        message_id: message.id,
+                        usage,
+                    });
+                }
+                outputs
+            }
+            ClaudeMessage::Result {
+                subtype,
+                duration_ms,
+                duration_api_ms,
+                total_cost_usd,
+                usage,
+            } => vec![CliOutput::Result(CliResult {
+                success: subtype == "success",
+                duration_ms,
+                api_duration_ms: duration_api_ms,
+                total_cost_usd,
+                usage,
+            })],
+            ClaudeMessage::Error { error } => vec![CliOutput::Event(AgentEvent::Error {
+                agent_id: agent_id.clone(),
+                message: error.message.unwrap_or_else(|| "Unknown error".to_string()),
+            })],
+            ClaudeMessage::Unknown => vec![],
+        }
+    }
+
+    fn convert_content(&mut self, agent_id: &AgentId, content: Vec<ContentBlock>) -> Option<CliOutput> {
+        let mut text_content = String::new();
+        let mut tool_event = None;
+
+        for block in content {
+            match block {
+                ContentBlock::Text { text } => {
+                    if !text_content.is_empty() {
+                        text_content.push('\n');
+                    }
+                    text_content.push_str(&text);
+                }
+                ContentBlock::ToolUse { name, input, .. } => {
+                    self.last_tool_name = Some(name.clone());
+                    tool_event = Some(AgentEvent::ToolUse {
+                        agent_id: agent_id.clone(),
+                        tool_name: name,
+                        tool_input: input,
+                    });
+                }
+                ContentBlock::ToolResult { is_error, .. } => {
+                    if let Some(tool_name) = self.last_tool_name.take() {
+                        return Some(CliOutput::Event(AgentEvent::ToolResult {
+                            agent_id: agent_id.clone(),
+                            tool_name,
+                            success: !is_error,
+                        }));
+                    }
+                }
+                ContentBlock::Unknown => {}
+            }
+        }
+
+        // Prefer tool event over text if both present
+        if let Some(event) = tool_event {
+            return Some(CliOutput::Event(event));
+        }
+
+        if !text_content.is_empty() {
+            return Some(CliOutput::Event(AgentEvent::Message {
+                agent_id: agent_id.clone(),
+                content: text_content,
+            }));
+        }
+
+        None
+    }
+}
+
+impl OutputParser for StreamJsonParser {
+    fn parse_line(&mut self, agent_id: &AgentId, line: &str) -> Vec<CliOutput> {
+        if line.trim().is_empty() {
+            return vec![];
+        }
+        match serde_json::from_str::<ClaudeMessage>(line) {
+            Ok(message) => self.convert_message(agent_id, message),
+            Err(e) => {
+                // Neither the line nor the error message (which can quote it) is
+                // logged, as either may hold secrets
+                eprintln!(
+                    "Unparseable line ({} bytes): {:?} error at column {}",
+                    line.len(),
+                    e.classify(),
+                    e.column()
+                );
+                vec![]
+            }
+        }
+    }
+}
@@ -0,0 +1,480 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+use crate::sandbox::SandboxProfile;
+use crate::types::*;
+use crate::usage::{TokenUsage, UsageReport};
+use crate::worktree::WorktreeInfo;
+
+/// Milliseconds since the Unix epoch, used for every persisted timestamp.
+pub(crate) fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// One line of a run's append-only JSONL log.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum HistoryLine {
+    Started {
+        agent_id: AgentId,
+        config: Box<AgentConfig>,
+        started_at: u64,
+    },
+    Event {
+        at: u64,
+        event: Box<AgentEvent>,
+    },
+    Finished {
+        ended_at: u64,
+        reason: StopReason,
+        #[serde(default)]
+        exit_code: Option<i32>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordedEvent {
+    pub at: u64,
+    pub event: AgentEvent,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunSummary {
+    pub agent_id: AgentId,
+    pub config: AgentConfig,
+    pub started_at: u64,
+    /// `None` while the run is still active (or if the process died without a final record).
+    pub ended_at: Option<u64>,
+    pub reason: Option<StopReason>,
+    pub exit_code: Option<i32>,
+    /// CLI session id reported by the run's `Init` event, used to resume it later.
+    pub session_id: Option<String>,
+    /// The backend the run last ran on, which differs from `config.cli` after a fallback.
+    #[serde(default)]
+    pub cli: Option<CliType>,
+    #[serde(default)]
+    pub model: Option<String>,
+    /// The run's git worktree, as last reported, under `Isolation::Worktree`.
+    #[serde(default)]
+    pub worktree: Option<WorktreeInfo>,
+    /// The effective sandbox from the run's `Started` event.
+    #[serde(default)]
+    pub sandbox: Option<SandboxProfile>,
+    /// Totals from the run's last `Usage` event.
+    #[serde(default)]
+    pub usage: TokenUsage,
+    #[serde(default)]
+    pub cost_usd: Option<f64>,
+    pub event_count: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunRecord {
+    #[serde(flatten)]
+    pub summary: RunSummary,
+    pub events: Vec<RecordedEvent>,
+}
+
+/// A run's summary as of the first `log_len` bytes of its log, kept beside the
+/// log so listing runs only reads what was appended since.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SummaryIndex {
+    summary: RunSummary,
+    log_len: u64,
+}
+
+/// Durable record of every agent run, stored as one JSONL file per run, with a
+/// `<id>.summary.json` index that `list` brings up to date as it goes.
+///
+/// Writes are synchronous appends so they can happen from inside event emitters;
+/// reads are async and tolerate a truncated trailing line.
+pub struct HistoryStore {
+    dir: PathBuf,
+}
+
+impl HistoryStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Opens the store under the platform data directory
+    /// (e.g. `~/.local/share/giga-command-center/history`).
+    pub fn open_default() -> Result<Self, AgentError> {
+        let dir = dirs::data_dir()
+            .ok_or_else(|| AgentError::StorageError("Could not find data directory".to_string()))?
+            .join("giga-command-center")
+            .join("history");
+        std::fs::create_dir_all(&dir).map_err(|e| {
+            AgentError::StorageError(format!("Failed to create history directory: {}", e))
+        })?;
+        Ok(Self::new(dir))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn record_start(&self, agent_id: &AgentId, config: &AgentConfig) -> Result<(), AgentError> {
+        self.append(
+            agent_id,
+            &HistoryLine::Started {
+                agent_id: agent_id.clone(),
+                config: Box::new(config.clone()),
+                started_at: unix_millis(),
+            },
+        )
+    }
+
+    pub fn record_event(&self, agent_id: &AgentId, event: &AgentEvent) -> Result<(), AgentError> {
+        self.append(
+            agent_id,
+            &HistoryLine::Event {
+                at: unix_millis(),
+                event: Box::new(event.clone()),
+            },
+        )
+    }
+
+    pub fn record_finish(
+        &self,
+        agent_id: &AgentId,
+        reason: StopReason,
+        exit_code: Option<i32>,
+    ) -> Result<(), AgentError> {
+        self.append(
+            agent_id,
+            &HistoryLine::Finished {
+                ended_at: unix_millis(),
+                reason,
+                exit_code,
+            },
+        )
+    }
+
+    /// All recorded runs, newest first, without their event logs.
+    pub async fn list(&self) -> Result<Vec<RunSummary>, AgentError> {
+        if !self.dir.exists() {
+            return Ok(vec![]);
+        }
+
+        let mut runs = Vec::new();
+        let mut entries = fs::read_dir(&self.dir).await.map_err(|e| {
+            AgentError::StorageError(format!("Failed to read history directory: {}", e))
+        })?;
+
+        while let Some(entry) = entries.next_entry().await.map_err(|e| {
+            AgentError::StorageError(format!("Failed to read directory entry: {}", e))
+        })? {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("jsonl") {
+                continue;
+            }
+            if let Some(summary) = Self::summary(&path).await {
+                runs.push(summary);
+            }
+        }
+
+        runs.sort_by_key(|run| std::cmp::Reverse(run.started_at));
+        Ok(runs)
+    }
+
+    /// The summary of the run logged at `path`, from its index plus whatever
+    /// complete lines were appended after the part the index covers.
+    async fn summary(path: &Path) -> Option<RunSummary> {
+        let index_path = path.with_extension("summary.json");
+        let index = fs::read(&index_path)
+            .await
+            .ok()
+            .and_then(|json| serde_json::from_slice::<SummaryIndex>(&json).ok());
+
+        let mut file = fs::File::open(path).await.ok()?;
+        let log_len = file.metadata().await.ok()?.len();
+        let (mut record, offset) = match index {
+            Some(index) if index.log_len <= log_len => (
+                Some(RunRecord {
+                    summary: index.summary,
+                    events: Vec::new(),
+                }),
+                index.log_len,
+            ),
+            // No index yet, or the log was replaced since.
+            _ => (None, 0),
+        };
+        if offset == log_len {
+            return record.map(|record| record.summary);
+        }
+
+        file.seek(std::io::SeekFrom::Start(offset)).await.ok()?;
+        let mut appended = Vec::new();
+        file.read_to_end(&mut appended).await.ok()?;
+        // A line still being written is left for the next listing.
+        let complete = appended
+            .iter()
+            .rposition(|&b| b == b'\n')
+            .map_or(0, |i| i + 1);
+        for line in String::from_utf8_lossy(&appended[..complete]).lines() {
+            Self::apply_line(&mut record, line, false);
+        }
+        let summary = record?.summary;
+
+        if complete > 0 {
+            let index = SummaryIndex {
+                summary: summary.clone(),
+                log_len: offset + complete as u64,
+            };
+            // Only an optimisation: a stale or missing index just means more reading.
+            if let Ok(json) = serde_json::to_vec(&index) {
+                let temp = path.with_extension("summary.json.tmp");
+                if fs::write(&temp, json).await.is_ok() {
+                    let _ = fs::rename(&temp, &index_path).await;
+                }
+            }
+        }
+        Some(summary)
+    }
+
+    /// Token and cost totals over every recorded run, per workspace and per day.
+    pub async fn usage_report(&self) -> Result<UsageReport, AgentError> {
+        Ok(UsageReport::from_runs(&self.list().await?))
+    }
+
+    pub async fn get(&self, agent_id: &AgentId) -> Result<RunRecord, AgentError> {
+        let path = self.run_path(agent_id).ok_or(AgentError::NotFound)?;
+        let content = match fs::read_to_string(&path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Err(AgentError::NotFound),
+            Err(e) => {
+                return Err(AgentError::StorageError(format!(
+                    "Failed to read run '{}': {}",
+                    agent_id, e
+                )))
+            }
+        };
+        Self::parse_record(&content).ok_or_else(|| {
+            AgentError::StorageError(format!("Run '{}' has no start record", agent_id))
+        })
+    }
+
+    pub async fn delete(&self, agent_id: &AgentId) -> Result<(), AgentError> {
+        let path = self.run_path(agent_id).ok_or(AgentError::NotFound)?;
+        let _ = fs::remove_file(path.with_extension("summary.json")).await;
+        match fs::remove_file(&path).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(AgentError::NotFound),
+            Err(e) => Err(AgentError::StorageError(format!(
+                "Failed to delete run '{}': {}",
+                agent_id, e
+            ))),
+        }
+    }
+
+    /// Agent ids are UUIDs; anything else could escape the history directory.
+    fn run_path(&self, agent_id: &AgentId) -> Option<PathBuf> {
+        let valid = !agent_id.is_empty()
+            && agent_id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-');
+        valid.then(|| self.dir.join(format!("{}.jsonl", agent_id)))
+    }
+
+    fn append(&self, agent_id: &AgentId, line: &HistoryLine) -> Result<(), AgentError> {
+        let path = self.run_path(agent_id).ok_or(AgentError::NotFound)?;
+        let mut json = serde_json::to_string(line)
+            .map_err(|e| AgentError::StorageError(format!("Failed to encode history line: {}", e)))?;
+        json.push('\n');
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| AgentError::StorageError(format!("Failed to open run log: {}", e)))?;
+        file.write_all(json.as_bytes())
+            .map_err(|e| AgentError::StorageError(format!("Failed to write run log: {}", e)))
+    }
+
+    fn parse_record(content: &str) -> Option<RunRecord> {
+        let mut record: Option<RunRecord> = None;
+        for line in content.lines() {
+            Self::apply_line(&mut record, line, true);
+        }
+        record
+    }
+
+    /// Folds one log line into `record`, keeping the event itself only if
+    /// `keep_events` is set.
+    fn apply_line(record: &mut Option<RunRecord>, line: &str, keep_events: bool) {
+        // A crash mid-write can leave a partial last line; skip anything unreadable.
+        let Ok(line) = serde_json::from_str::<HistoryLine>(line) else {
+            return;
+        };
+        match line {
+            HistoryLine::Started {
+                agent_id,
+                config,
+                started_at,
+            } => {
+                *record = Some(RunRecord {
+                    summary: RunSummary {
+                        agent_id,
+                        config: *config,
+                        started_at,
+                        ended_at: None,
+                        reason: None,
+                        exit_code: None,
+                        session_id: None,
+                        cli: None,
+                        model: None,
+                        worktree: None,
+                        sandbox: None,
+                        usage: TokenUsage::default(),
+                        cost_usd: None,
+                        event_count: 0,
+                    },
+                    events: Vec::new(),
+                });
+            }
+            HistoryLine::Event { at, event } => {
+                if let Some(record) = record.as_mut() {
+                    match event.as_ref() {
+                        AgentEvent::Init { session_id, .. } => {
+                            record.summary.session_id = Some(session_id.clone());
+                        }
+                        AgentEvent::Started {
+                            cli,
+                            model,
+                            worktree,
+                            sandbox,
+                            ..
+                        } => {
+                            record.summary.cli = cli.clone();
+                            record.summary.model = model.clone();
+                            record.summary.worktree = worktree.as_deref().cloned();
+                            record.summary.sandbox = sandbox.clone();
+                        }
+                        AgentEvent::WorktreeChanged { worktree, .. } => {
+                            record.summary.worktree = Some(worktree.as_ref().clone());
+                        }
+                        // A session from an earlier backend can't be resumed on the next.
+                        AgentEvent::FallingBack { to, model, .. } => {
+                            record.summary.cli = Some(to.clone());
+                            record.summary.model = model.clone();
+                            record.summary.session_id = None;
+                        }
+                        AgentEvent::Usage {
+                            total,
+                            total_cost_usd,
+                            ..
+                        } => {
+                            record.summary.usage = *total;
+                            record.summary.cost_usd = *total_cost_usd;
+                        }
+                        _ => {}
+                    }
+                    if keep_events {
+                        record.events.push(RecordedEvent { at, event: *event });
+                    }
+                    record.summary.event_count += 1;
+                }
+            }
+            HistoryLine::Finished {
+                ended_at,
+                reason,
+                exit_code,
+            } => {
+                if let Some(record) = record.as_mut() {
+                    record.summary.ended_at = Some(ended_at);
+                    record.summary.reason = Some(reason);
+                    record.summary.exit_code = exit_code;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TempStore(HistoryStore);
+
+    impl Drop for TempStore {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(self.0.dir());
+        }
+    }
+
+    fn store() -> TempStore {
+        let dir = std::env::temp_dir().join(format!("history-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        TempStore(HistoryStore::new(dir))
+    }
+
+    fn init(agent_id: &AgentId, session_id: &str) -> AgentEvent {
+        AgentEvent::Init {
+            agent_id: agent_id.clone(),
+            session_id: session_id.to_string(),
+            model: "sonnet".to_string(),
+        }
+    }
+
+    fn json(summary: &RunSummary) -> serde_json::Value {
+        serde_json::to_value(summary).unwrap()
+    }
+
+    #[tokio::test]
+    async fn list_catches_up_with_appended_lines() {
+        let store = store();
+        let history = &store.0;
+        let agent_id: AgentId = "run-1".to_string();
+        let config = serde_json::from_value(serde_json::json!({
+            "workspaceId": "w",
+            "prompt": "hi",
+        }))
+        .unwrap();
+        history.record_start(&agent_id, &config).unwrap();
+        history
+            .record_event(&agent_id, &init(&agent_id, "s1"))
+            .unwrap();
+
+        let listed = history.list().await.unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].session_id.as_deref(), Some("s1"));
+        assert_eq!(listed[0].event_count, 1);
+        assert!(history.dir().join("run-1.summary.json").exists());
+
+        history
+            .record_event(&agent_id, &init(&agent_id, "s2"))
+            .unwrap();
+        history
+            .record_finish(&agent_id, StopReason::Completed, Some(0))
+            .unwrap();
+        // A line still being written isn't read yet.
+        let log = history.dir().join("run-1.jsonl");
+        let mut file = OpenOptions::new().append(true).open(&log).unwrap();
+        file.write_all(b"{\"kind\":\"event\"").unwrap();
+
+        let listed = history.list().await.unwrap();
+        let full = history.get(&agent_id).await.unwrap().summary;
+        assert_eq!(json(&listed[0]), json(&full));
+        assert_eq!(listed[0].session_id.as_deref(), Some("s2"));
+        assert_eq!(listed[0].event_count, 2);
+        assert_eq!(listed[0].exit_code, Some(0));
+
+        history.delete(&agent_id).await.unwrap();
+        assert!(history.list().await.unwrap().is_empty());
+        assert!(!history.dir().join("run-1.summary.json").exists());
+    }
+}
@@ -1,6 +1,50 @@
 pub mod agent_manager;
-pub mod cli_builders;
+pub mod backends;
+pub mod history;
+pub mod permissions;
+mod process;
+pub mod retry;
+pub mod sandbox;
+pub mod skill_bundles;
+mod skill_watcher;
+pub mod skills;
+pub mod stderr;
 pub mod types;
+pub mod usage;
+pub mod vault;
+pub mod workflow;
+pub mod workspaces;
+pub mod worktree;
 
-pub use agent_manager::AgentManager;
+pub use agent_manager::{AgentManager, DEFAULT_MAX_CONCURRENT, DEFAULT_STOP_GRACE_PERIOD};
+pub use backends::{
+    Capabilities, CliBackend, CliInfo, CliOutput, CliRegistry, CliResult, OutputParser,
+};
+pub use history::{HistoryStore, RecordedEvent, RunRecord, RunSummary};
+pub use permissions::{
+    PendingPermission, PermissionBehavior, PermissionDecision, PermissionMode,
+    DEFAULT_APPROVAL_TIMEOUT,
+};
+pub use retry::{BackendChoice, FailureClass, RetryPolicy};
+pub use sandbox::{NetworkAccess, SandboxProfile, SandboxRunner};
+pub use skill_bundles::{
+    BundleFile, BundleFormat, BundleManifest, GitSkillSource, ImportOptions, MANIFEST_FILE,
+    MAX_BUNDLE_ENTRIES, MAX_BUNDLE_FILE_SIZE, MAX_BUNDLE_SIZE,
+};
+pub use skills::{
+    NewSkill, SkillDetail, SkillInfo, SkillLocation, SkillSource, SkillUpdate, SkillsRepository,
+    SKILL_FILE,
+};
+pub use stderr::{StderrClass, StderrLine};
 pub use types::*;
+pub use usage::{
+    estimate_cost, price_for, DailyUsage, ModelPrice, TokenUsage, UsageReport, UsageTotals,
+    WorkspaceUsage,
+};
+pub use vault::{SecretEntry, SecretVault};
+pub use workflow::{
+    NodeInfo, NodeStatus, WorkflowDefinition, WorkflowId, WorkflowInfo, WorkflowNode,
+    WorkflowStatus,
+};
+pub use workspaces::{NewWorkspace, Workspace, WorkspaceSpec, WorkspaceStore};
+pub use worktree::{Isolation, WorktreeCleanup, WorktreeInfo, WorktreeStatus};
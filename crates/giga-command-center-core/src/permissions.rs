@@ -0,0 +1,684 @@
+//! Approving a run's tool use from the UI. Runs in a gated `PermissionMode` are
+//! given a permission-prompt tool, served over MCP from a loopback HTTP
+//! endpoint. Each call becomes a `PermissionRequest` event and waits until a
+//! client answers it or the run's approval timeout picks the default.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{oneshot, OnceCell};
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+use crate::agent_manager::EventEmitter;
+use crate::history::unix_millis;
+use crate::types::*;
+
+/// How long a permission request waits for an answer when the run doesn't say.
+pub const DEFAULT_APPROVAL_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// MCP server name and tool the CLI is pointed at with `--permission-prompt-tool`.
+pub(crate) const MCP_SERVER: &str = "giga_command_center";
+pub(crate) const PROMPT_TOOL: &str = "mcp__giga_command_center__approve";
+
+/// Largest MCP request body the endpoint accepts.
+const MAX_BODY: usize = 4 * 1024 * 1024;
+
+/// Which tool uses the CLI asks about before running them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PermissionMode {
+    /// Anything not allowed by the CLI's settings is asked about.
+    Default,
+    /// File edits go ahead; everything else is asked about.
+    AcceptEdits,
+    /// Read-only planning; the CLI asks before acting on the plan.
+    Plan,
+    /// Nothing is asked about.
+    #[default]
+    Bypass,
+}
+
+impl PermissionMode {
+    /// Whether the run needs someone to answer its permission requests.
+    pub fn is_gated(self) -> bool {
+        self != PermissionMode::Bypass
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PermissionBehavior {
+    Allow,
+    #[default]
+    Deny,
+}
+
+/// A client's answer to a `PermissionRequest`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PermissionDecision {
+    pub behavior: PermissionBehavior,
+    /// Why, passed to the CLI when denying.
+    #[serde(default)]
+    pub message: Option<String>,
+}
+
+/// A permission request still waiting for an answer.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingPermission {
+    pub agent_id: AgentId,
+    pub request_id: String,
+    pub tool_name: String,
+    pub input: Value,
+    pub requested_at: u64,
+    /// When the run's default decision applies.
+    pub expires_at: u64,
+}
+
+/// A gated run's approval settings.
+#[derive(Clone)]
+struct RunApprovals {
+    agent_id: AgentId,
+    emit_event: EventEmitter,
+    timeout: Duration,
+    default: PermissionBehavior,
+}
+
+struct Waiting {
+    pending: PendingPermission,
+    answer: oneshot::Sender<PermissionDecision>,
+}
+
+#[derive(Default)]
+struct BrokerState {
+    /// Gated runs by the token in their endpoint's URL.
+    runs: HashMap<String, RunApprovals>,
+    /// Unanswered requests by request id.
+    waiting: HashMap<String, Waiting>,
+}
+
+/// Serves the permission-prompt tool for every gated run and routes answers
+/// back to the CLI waiting on them.
+pub(crate) struct PermissionBroker {
+    state: Arc<Mutex<BrokerState>>,
+    endpoint: OnceCell<(SocketAddr, JoinHandle<()>)>,
+}
+
+impl PermissionBroker {
+    pub fn new() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(BrokerState::default())),
+            endpoint: OnceCell::new(),
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, BrokerState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Starts taking permission requests for `agent_id` if its config is gated.
+    pub fn open(&self, agent_id: &AgentId, config: &AgentConfig, emit_event: EventEmitter) {
+        if !config.permission_mode.is_gated() {
+            return;
+        }
+        let timeout = config
+            .approval_timeout_secs
+            .map_or(DEFAULT_APPROVAL_TIMEOUT, Duration::from_secs);
+        self.state().runs.insert(
+            Uuid::new_v4().simple().to_string(),
+            RunApprovals {
+                agent_id: agent_id.clone(),
+                emit_event,
+                timeout,
+                default: config.approval_default,
+            },
+        );
+    }
+
+    /// The MCP endpoint serving `agent_id`'s permission-prompt tool, if the run
+    /// is gated. The endpoint starts listening on first use.
+    pub async fn endpoint(&self, agent_id: &AgentId) -> Result<Option<String>, AgentError> {
+        let token = self
+            .state()
+            .runs
+            .iter()
+            .find(|(_, run)| &run.agent_id == agent_id)
+            .map(|(token, _)| token.clone());
+        let Some(token) = token else {
+            return Ok(None);
+        };
+        let (addr, _) = self
+            .endpoint
+            .get_or_try_init(|| async {
+                let listener = TcpListener::bind("127.0.0.1:0").await?;
+                let addr = listener.local_addr()?;
+                let task = tokio::spawn(serve(listener, self.state.clone()));
+                Ok::<_, std::io::Error>((addr, task))
+            })
+            .await
+            .map_err(|e| {
+                AgentError::SpawnFailed(format!("Could not start the permission endpoint: {}", e))
+            })?;
+        Ok(Some(format!("http://{}/mcp/{}", addr, token)))
+    }
+
+    /// Stops taking requests for `agent_id`. Requests still waiting are denied.
+    pub fn close(&self, agent_id: &AgentId) {
+        let mut state = self.state();
+        state.runs.retain(|_, run| &run.agent_id != agent_id);
+        state
+            .waiting
+            .retain(|_, waiting| &waiting.pending.agent_id != agent_id);
+    }
+
+    /// Answers one of `agent_id`'s pending requests.
+    pub fn respond(
+        &self,
+        agent_id: &AgentId,
+        request_id: &str,
+        decision: PermissionDecision,
+    ) -> Result<(), AgentError> {
+        let (waiting, emit_event) = {
+            let mut state = self.state();
+            let belongs = state
+                .waiting
+                .get(request_id)
+                .is_some_and(|waiting| &waiting.pending.agent_id == agent_id);
+            if !belongs {
+                return Err(AgentError::NotFound);
+            }
+            let waiting = state
+                .waiting
+                .remove(request_id)
+                .ok_or(AgentError::NotFound)?;
+            let emit_event = state
+                .runs
+                .values()
+                .find(|run| &run.agent_id == agent_id)
+                .map(|run| run.emit_event.clone());
+            (waiting, emit_event)
+        };
+        let behavior = decision.behavior;
+        // The CLI may have given up on the request in the meantime.
+        let _ = waiting.answer.send(decision);
+        if let Some(emit_event) = emit_event {
+            emit_event(AgentEvent::PermissionResolved {
+                agent_id: agent_id.clone(),
+                request_id: request_id.to_string(),
+                behavior,
+                timed_out: false,
+            });
+        }
+        Ok(())
+    }
+
+    /// Every unanswered request, oldest first.
+    pub fn pending(&self) -> Vec<PendingPermission> {
+        let mut pending: Vec<PendingPermission> = self
+            .state()
+            .waiting
+            .values()
+            .map(|waiting| waiting.pending.clone())
+            .collect();
+        pending.sort_by_key(|pending| pending.requested_at);
+        pending
+    }
+}
+
+impl Drop for PermissionBroker {
+    fn drop(&mut self) {
+        if let Some((_, task)) = self.endpoint.get() {
+            task.abort();
+        }
+    }
+}
+
+/// Asks for approval of one tool use on behalf of the run holding `token`, and
+/// returns the permission-prompt tool's answer for the CLI.
+async fn approve(state: &Mutex<BrokerState>, token: &str, arguments: &Value) -> Option<Value> {
+    let tool_name = arguments["tool_name"]
+        .as_str()
+        .unwrap_or_default()
+        .to_string();
+    let input = arguments.get("input").cloned().unwrap_or_else(|| json!({}));
+    let request_id = Uuid::new_v4().to_string();
+    let (answer, answered) = oneshot::channel();
+
+    let run = {
+        let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+        let run = state.runs.get(token)?.clone();
+        let requested_at = unix_millis();
+        let pending = PendingPermission {
+            agent_id: run.agent_id.clone(),
+            request_id: request_id.clone(),
+            tool_name: tool_name.clone(),
+            input: input.clone(),
+            requested_at,
+            expires_at: requested_at + run.timeout.as_millis() as u64,
+        };
+        state
+            .waiting
+            .insert(request_id.clone(), Waiting { pending, answer });
+        run
+    };
+    let RunApprovals {
+        agent_id,
+        emit_event,
+        timeout,
+        default,
+    } = run;
+    emit_event(AgentEvent::PermissionRequest {
+        agent_id: agent_id.clone(),
+        request_id: request_id.clone(),
+        tool_name,
+        input: input.clone(),
+        timeout_secs: timeout.as_secs(),
+    });
+
+    let decision = match tokio::time::timeout(timeout, answered).await {
+        Ok(Ok(decision)) => decision,
+        Ok(Err(_)) => PermissionDecision {
+            behavior: PermissionBehavior::Deny,
+            message: Some("The run ended before the request was answered".to_string()),
+        },
+        Err(_) => {
+            state
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .waiting
+                .remove(&request_id);
+            emit_event(AgentEvent::PermissionResolved {
+                agent_id,
+                request_id,
+                behavior: default,
+                timed_out: true,
+            });
+            PermissionDecision {
+                behavior: default,
+                message: Some(format!("No answer within {}s", timeout.as_secs())),
+            }
+        }
+    };
+
+    Some(match decision.behavior {
+        PermissionBehavior::Allow => json!({ "behavior": "allow", "updatedInput": input }),
+        PermissionBehavior::Deny => json!({
+            "behavior": "deny",
+            "message": decision.message.unwrap_or_else(|| "Denied by the user".to_string()),
+        }),
+    })
+}
+
+/// Accepts MCP connections until the broker is dropped.
+async fn serve(listener: TcpListener, state: Arc<Mutex<BrokerState>>) {
+    while let Ok((stream, _)) = listener.accept().await {
+        tokio::spawn(connection(stream, state.clone()));
+    }
+}
+
+struct HttpRequest {
+    method: String,
+    path: String,
+    body: Vec<u8>,
+    close: bool,
+}
+
+/// Serves requests on one keep-alive connection.
+async fn connection(stream: TcpStream, state: Arc<Mutex<BrokerState>>) {
+    let mut stream = BufReader::new(stream);
+    while let Some(request) = read_request(&mut stream).await {
+        let (status, body) = handle(&state, &request).await;
+        let mut response = format!(
+            "HTTP/1.1 {}\r\nContent-Length: {}\r\n",
+            status,
+            body.as_ref().map_or(0, String::len)
+        );
+        if body.is_some() {
+            response.push_str("Content-Type: application/json\r\n");
+        }
+        response.push_str("\r\n");
+        response.push_str(body.as_deref().unwrap_or_default());
+        if stream.write_all(response.as_bytes()).await.is_err() || request.close {
+            return;
+        }
+    }
+}
+
+async fn read_request(stream: &mut BufReader<TcpStream>) -> Option<HttpRequest> {
+    let mut line = String::new();
+    stream.read_line(&mut line).await.ok()?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next()?.to_string();
+    let path = parts.next()?.to_string();
+
+    let mut length = 0;
+    let mut close = false;
+    loop {
+        line.clear();
+        if stream.read_line(&mut line).await.ok()? == 0 {
+            return None;
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let (name, value) = header.split_once(':')?;
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("content-length") {
+            length = value.parse().ok()?;
+        } else if name.eq_ignore_ascii_case("connection") {
+            close = value.eq_ignore_ascii_case("close");
+        }
+    }
+    if length > MAX_BODY {
+        return None;
+    }
+    let mut body = vec![0; length];
+    stream.read_exact(&mut body).await.ok()?;
+    Some(HttpRequest {
+        method,
+        path,
+        body,
+        close,
+    })
+}
+
+/// Answers one MCP (Streamable HTTP) request with a status line and JSON body.
+async fn handle(
+    state: &Mutex<BrokerState>,
+    request: &HttpRequest,
+) -> (&'static str, Option<String>) {
+    let Some(token) = request.path.strip_prefix("/mcp/") else {
+        return ("404 Not Found", None);
+    };
+    if !state
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .runs
+        .contains_key(token)
+    {
+        return ("404 Not Found", None);
+    }
+    if request.method != "POST" {
+        // No server-initiated messages, so no event stream to open.
+        return ("405 Method Not Allowed", None);
+    }
+    let Ok(message) = serde_json::from_slice::<Value>(&request.body) else {
+        let error = json!({
+            "jsonrpc": "2.0",
+            "id": null,
+            "error": { "code": -32700, "message": "Parse error" },
+        });
+        return ("400 Bad Request", Some(error.to_string()));
+    };
+    let Some(id) = message.get("id").cloned() else {
+        // Notifications need no answer.
+        return ("202 Accepted", None);
+    };
+
+    let params = &message["params"];
+    let result = match message["method"].as_str().unwrap_or_default() {
+        "initialize" => Ok(json!({
+            "protocolVersion": params["protocolVersion"].as_str().unwrap_or("2025-03-26"),
+            "capabilities": { "tools": {} },
+            "serverInfo": { "name": MCP_SERVER, "version": env!("CARGO_PKG_VERSION") },
+        })),
+        "ping" => Ok(json!({})),
+        "tools/list" => Ok(json!({
+            "tools": [{
+                "name": "approve",
+                "description": "Asks the user of Giga Command Center to allow or deny a tool use.",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "tool_name": { "type": "string" },
+                        "input": { "type": "object" },
+                        "tool_use_id": { "type": "string" },
+                    },
+                    "required": ["tool_name", "input"],
+                },
+            }],
+        })),
+        "tools/call" if params["name"] == "approve" => {
+            match approve(state, token, &params["arguments"]).await {
+                Some(answer) => Ok(json!({
+                    "content": [{ "type": "text", "text": answer.to_string() }],
+                })),
+                None => Err((-32603, "The run has ended")),
+            }
+        }
+        "tools/call" => Err((-32602, "Unknown tool")),
+        _ => Err((-32601, "Method not found")),
+    };
+
+    let response = match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err((code, message)) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": code, "message": message },
+        }),
+    };
+    ("200 OK", Some(response.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gated(timeout_secs: u64) -> AgentConfig {
+        serde_json::from_value(json!({
+            "workspaceId": "w",
+            "prompt": "p",
+            "permissionMode": "default",
+            "approvalTimeoutSecs": timeout_secs,
+        }))
+        .unwrap()
+    }
+
+    fn recorder() -> (EventEmitter, Arc<Mutex<Vec<AgentEvent>>>) {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        (
+            Arc::new(move |event| sink.lock().unwrap().push(event)),
+            events,
+        )
+    }
+
+    /// Sends one HTTP request to `url` and returns the status code and JSON body.
+    async fn send(url: &str, method: &str, body: &str) -> (u16, Option<Value>) {
+        let rest = url.strip_prefix("http://").unwrap();
+        let (addr, path) = rest.split_at(rest.find('/').unwrap());
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let request = format!(
+            "{} {} HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            method,
+            path,
+            addr,
+            body.len(),
+            body
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status = head.split_whitespace().nth(1).unwrap().parse().unwrap();
+        (status, serde_json::from_str(body).ok())
+    }
+
+    fn call(tool_name: &str) -> String {
+        json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "tools/call",
+            "params": {
+                "name": "approve",
+                "arguments": { "tool_name": tool_name, "input": { "command": "ls" } },
+            },
+        })
+        .to_string()
+    }
+
+    /// The permission-prompt tool's answer in a `tools/call` response.
+    fn answer(body: Option<Value>) -> Value {
+        let text = body.unwrap()["result"]["content"][0]["text"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        serde_json::from_str(&text).unwrap()
+    }
+
+    async fn wait_for_request(broker: &PermissionBroker) -> PendingPermission {
+        for _ in 0..500 {
+            if let Some(pending) = broker.pending().pop() {
+                return pending;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("no permission request arrived");
+    }
+
+    #[tokio::test]
+    async fn endpoint_rejects_unknown_tokens_and_bad_requests() {
+        let broker = PermissionBroker::new();
+        let agent_id: AgentId = "agent".to_string();
+        let (emit, _) = recorder();
+        broker.open(&agent_id, &gated(60), emit);
+        let url = broker.endpoint(&agent_id).await.unwrap().unwrap();
+        let (base, _) = url.rsplit_once('/').unwrap();
+
+        let ping = r#"{"jsonrpc":"2.0","id":7,"method":"ping"}"#;
+        let (status, body) = send(&url, "POST", ping).await;
+        assert_eq!(status, 200);
+        assert_eq!(body.unwrap()["id"], 7);
+
+        let wrong = format!("{}/{}", base, Uuid::new_v4().simple());
+        assert_eq!(send(&wrong, "POST", ping).await.0, 404);
+        let outside = format!("{}/../other", base.trim_end_matches("/mcp"));
+        assert_eq!(send(&outside, "POST", ping).await.0, 404);
+        assert_eq!(send(&url, "GET", "").await.0, 405);
+
+        let (status, body) = send(&url, "POST", "{not json").await;
+        assert_eq!(status, 400);
+        assert_eq!(body.unwrap()["error"]["code"], -32700);
+
+        let unknown = r#"{"jsonrpc":"2.0","id":1,"method":"tools/call","params":{"name":"rm"}}"#;
+        let (status, body) = send(&url, "POST", unknown).await;
+        assert_eq!(status, 200);
+        assert_eq!(body.unwrap()["error"]["code"], -32602);
+
+        broker.close(&agent_id);
+        assert_eq!(send(&url, "POST", ping).await.0, 404);
+        assert_eq!(broker.endpoint(&agent_id).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn answers_reach_the_cli() {
+        let broker = Arc::new(PermissionBroker::new());
+        let agent_id: AgentId = "agent".to_string();
+        let (emit, events) = recorder();
+        broker.open(&agent_id, &gated(60), emit);
+        let url = broker.endpoint(&agent_id).await.unwrap().unwrap();
+
+        let request = tokio::spawn({
+            let url = url.clone();
+            async move { send(&url, "POST", &call("Bash")).await }
+        });
+        let pending = wait_for_request(&broker).await;
+        assert_eq!(pending.tool_name, "Bash");
+        assert!(matches!(
+            broker.respond(
+                &"other".to_string(),
+                &pending.request_id,
+                PermissionDecision {
+                    behavior: PermissionBehavior::Allow,
+                    message: None,
+                }
+            ),
+            Err(AgentError::NotFound)
+        ));
+        let allow = PermissionDecision {
+            behavior: PermissionBehavior::Allow,
+            message: None,
+        };
+        broker
+            .respond(&agent_id, &pending.request_id, allow)
+            .unwrap();
+        let (status, body) = request.await.unwrap();
+        assert_eq!(status, 200);
+        assert_eq!(
+            answer(body),
+            json!({ "behavior": "allow", "updatedInput": { "command": "ls" } })
+        );
+
+        let request = tokio::spawn(async move { send(&url, "POST", &call("Write")).await });
+        let pending = wait_for_request(&broker).await;
+        let deny = PermissionDecision {
+            behavior: PermissionBehavior::Deny,
+            message: Some("Not in this repo".to_string()),
+        };
+        broker
+            .respond(&agent_id, &pending.request_id, deny)
+            .unwrap();
+        let (_, body) = request.await.unwrap();
+        assert_eq!(
+            answer(body),
+            json!({ "behavior": "deny", "message": "Not in this repo" })
+        );
+        assert!(broker.pending().is_empty());
+
+        let resolved: Vec<(PermissionBehavior, bool)> = events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|event| match event {
+                AgentEvent::PermissionResolved {
+                    behavior,
+                    timed_out,
+                    ..
+                } => Some((*behavior, *timed_out)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            resolved,
+            [
+                (PermissionBehavior::Allow, false),
+                (PermissionBehavior::Deny, false)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn unanswered_requests_time_out_to_the_default() {
+        let broker = PermissionBroker::new();
+        let agent_id: AgentId = "agent".to_string();
+        let (emit, events) = recorder();
+        broker.open(&agent_id, &gated(1), emit);
+        let url = broker.endpoint(&agent_id).await.unwrap().unwrap();
+
+        let (status, body) = send(&url, "POST", &call("Bash")).await;
+        assert_eq!(status, 200);
+        assert_eq!(
+            answer(body),
+            json!({ "behavior": "deny", "message": "No answer within 1s" })
+        );
+        assert!(broker.pending().is_empty());
+        assert!(events.lock().unwrap().iter().any(|event| matches!(
+            event,
+            AgentEvent::PermissionResolved {
+                behavior: PermissionBehavior::Deny,
+                timed_out: true,
+                ..
+            }
+        )));
+    }
+}
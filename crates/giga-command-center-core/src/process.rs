@@ -0,0 +1,141 @@
+use std::process::ExitStatus;
+use std::time::Duration;
+use tokio::process::{Child, Command};
+
+use crate::types::{AgentConfig, ProcessExit};
+
+/// Puts the CLI in its own process group, so stopping it also reaches the
+/// shells, test runners and MCP servers it spawned.
+pub(crate) fn isolate(cmd: &mut Command) {
+    #[cfg(unix)]
+    cmd.process_group(0);
+    #[cfg(not(unix))]
+    let _ = cmd;
+}
+
+/// What a CLI keeps of the server's environment under `AgentConfig::env_clear`.
+const BASE_ENV: &[&str] = &[
+    "PATH", "HOME", "USER", "LOGNAME", "SHELL", "LANG", "LC_*", "TERM", "TZ", "TMPDIR",
+    "TEMP", "TMP", "SYSTEMROOT", "USERPROFILE", "APPDATA", "LOCALAPPDATA",
+];
+
+/// Sets up the CLI's environment: the server's own (or only its allowed part,
+/// under `env_clear`) with the run's resolved `env` on top.
+pub(crate) fn apply_env(cmd: &mut Command, config: &AgentConfig, env: Vec<(String, String)>) {
+    if config.env_clear {
+        cmd.env_clear();
+        let allowed = |key: &str| {
+            BASE_ENV
+                .iter()
+                .copied()
+                .chain(config.env_allow.iter().map(String::as_str))
+                .any(|pattern| match pattern.strip_suffix('*') {
+                    Some(prefix) => key.starts_with(prefix),
+                    None => key == pattern,
+                })
+        };
+        for (key, value) in std::env::vars_os() {
+            if key.to_str().is_some_and(allowed) {
+                cmd.env(key, value);
+            }
+        }
+    }
+    cmd.envs(env);
+}
+
+/// Stops `child` gracefully: SIGINT so the CLI can flush its session, SIGTERM
+/// after half the grace period, and SIGKILL for the whole group after the rest.
+/// Anything left in the group once the CLI has exited is killed as well.
+#[cfg(unix)]
+pub(crate) async fn shutdown(child: &mut Child, grace: Duration) -> ProcessExit {
+    let Some(pgid) = child.id() else {
+        // Already reaped; nothing left to signal.
+        return match child.wait().await {
+            Ok(status) => exit_info(status, false),
+            Err(_) => ProcessExit::default(),
+        };
+    };
+
+    for signal in [libc::SIGINT, libc::SIGTERM] {
+        signal_group(pgid, signal);
+        if let Ok(Ok(status)) = tokio::time::timeout(grace / 2, wait(child)).await {
+            return exit_info(status, false);
+        }
+    }
+
+    signal_group(pgid, libc::SIGKILL);
+    match child.wait().await {
+        Ok(status) => exit_info(status, true),
+        Err(_) => ProcessExit {
+            forced: true,
+            ..Default::default()
+        },
+    }
+}
+
+#[cfg(not(unix))]
+pub(crate) async fn shutdown(child: &mut Child, _grace: Duration) -> ProcessExit {
+    let _ = child.kill().await;
+    match child.wait().await {
+        Ok(status) => exit_info(status, true),
+        Err(_) => ProcessExit {
+            forced: true,
+            ..Default::default()
+        },
+    }
+}
+
+/// Waits for `child` to exit, then SIGKILLs whatever is left of its process
+/// group before reaping it: until it is reaped, the CLI's pid (the group's id)
+/// can't be reused by an unrelated process.
+#[cfg(unix)]
+pub(crate) async fn wait(child: &mut Child) -> std::io::Result<ExitStatus> {
+    if let Some(pgid) = child.id() {
+        if let Ok(Ok(())) = tokio::task::spawn_blocking(move || exited(pgid)).await {
+            signal_group(pgid, libc::SIGKILL);
+        }
+    }
+    child.wait().await
+}
+
+#[cfg(not(unix))]
+pub(crate) async fn wait(child: &mut Child) -> std::io::Result<ExitStatus> {
+    child.wait().await
+}
+
+/// Blocks until `pid` has exited, leaving it unreaped.
+#[cfg(unix)]
+fn exited(pid: u32) -> std::io::Result<()> {
+    loop {
+        let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+        let flags = libc::WEXITED | libc::WNOWAIT;
+        if unsafe { libc::waitid(libc::P_PID, pid as libc::id_t, &mut info, flags) } == 0 {
+            return Ok(());
+        }
+        let error = std::io::Error::last_os_error();
+        if error.kind() != std::io::ErrorKind::Interrupted {
+            return Err(error);
+        }
+    }
+}
+
+#[cfg(unix)]
+fn signal_group(pgid: u32, signal: libc::c_int) {
+    // ESRCH just means the group is already gone.
+    unsafe {
+        libc::killpg(pgid as libc::pid_t, signal);
+    }
+}
+
+pub(crate) fn exit_info(status: ExitStatus, forced: bool) -> ProcessExit {
+    #[cfg(unix)]
+    let signal = std::os::unix::process::ExitStatusExt::signal(&status);
+    #[cfg(not(unix))]
+    let signal = None;
+
+    ProcessExit {
+        code: status.code(),
+        signal,
+        forced,
+    }
+}
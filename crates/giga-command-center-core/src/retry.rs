@@ -0,0 +1,263 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::stderr::{self, StderrClass, StderrLine};
+use crate::types::*;
+
+/// Why an attempt failed, as far as the manager can tell from its events and exit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureClass {
+    /// Rate limited or the API was overloaded.
+    RateLimit,
+    /// Connection reset, DNS failure, gateway error and the like.
+    Network,
+    /// `timeout_secs` elapsed.
+    Timeout,
+    /// No output for `idle_timeout_secs`.
+    Stalled,
+    /// Credentials were rejected.
+    Auth,
+    /// A usage or billing limit was reached.
+    Quota,
+    /// The CLI couldn't be started, e.g. because it isn't installed.
+    Unavailable,
+    /// Any other failure, including a non-zero exit with nothing recognisable on stderr.
+    Error,
+}
+
+impl FailureClass {
+    /// The failure an event reports, if any. `Error` events are classified by
+    /// their message, so API errors parsers pass through are recognised too.
+    pub fn of_event(event: &AgentEvent) -> Option<FailureClass> {
+        match event {
+            AgentEvent::AuthFailed { .. } => Some(FailureClass::Auth),
+            AgentEvent::RateLimited { .. } => Some(FailureClass::RateLimit),
+            AgentEvent::QuotaExhausted { .. } => Some(FailureClass::Quota),
+            AgentEvent::Error { message, .. } => {
+                Some(match stderr::classify(message).map(|line| line.class) {
+                    Some(StderrClass::AuthFailure) => FailureClass::Auth,
+                    Some(StderrClass::RateLimit) => FailureClass::RateLimit,
+                    Some(StderrClass::QuotaExhausted) => FailureClass::Quota,
+                    Some(StderrClass::Network) => FailureClass::Network,
+                    _ => FailureClass::Error,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// The failure a typed error stands for.
+    fn of_error(error: &AgentError) -> FailureClass {
+        match error {
+            AgentError::AuthenticationFailed(_) => FailureClass::Auth,
+            AgentError::RateLimited { .. } => FailureClass::RateLimit,
+            AgentError::QuotaExhausted { .. } => FailureClass::Quota,
+            _ => FailureClass::Error,
+        }
+    }
+
+    /// When an attempt reports several failures, the most telling one wins.
+    fn rank(self) -> u8 {
+        match self {
+            FailureClass::Error => 0,
+            FailureClass::Network => 1,
+            FailureClass::RateLimit => 2,
+            FailureClass::Timeout | FailureClass::Stalled => 3,
+            FailureClass::Auth | FailureClass::Quota | FailureClass::Unavailable => 4,
+        }
+    }
+
+    pub(crate) fn max(self, other: FailureClass) -> FailureClass {
+        if other.rank() > self.rank() {
+            other
+        } else {
+            self
+        }
+    }
+}
+
+/// A backend to fall back to, from `AgentConfig::fallbacks`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackendChoice {
+    pub cli: CliType,
+    /// Model to run on this backend; the CLI's default when unset.
+    #[serde(default)]
+    pub model: Option<String>,
+}
+
+/// Failures that move a run on to its next fallback backend unless set otherwise.
+pub fn default_fallback_on() -> Vec<FailureClass> {
+    vec![
+        FailureClass::Auth,
+        FailureClass::Quota,
+        FailureClass::RateLimit,
+    ]
+}
+
+/// How `AgentManager` retries a failed run. Every attempt keeps the run's agent id;
+/// clients see a `Retrying` event between attempts and a single `Result` at the end.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RetryPolicy {
+    /// Attempts in total, including the first.
+    pub max_attempts: u32,
+    /// Wait before the second attempt.
+    pub initial_delay_ms: u64,
+    /// Each further wait is the previous one times this.
+    pub multiplier: f64,
+    /// Upper bound on the backoff (a rate limit's own retry-after is honoured as is).
+    pub max_delay_ms: u64,
+    /// Failures worth another attempt.
+    pub retry_on: Vec<FailureClass>,
+    /// Continue the failed attempt's CLI session, when the backend can resume and
+    /// the session had started, instead of starting over.
+    pub resume: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_delay_ms: 2_000,
+            multiplier: 2.0,
+            max_delay_ms: 60_000,
+            retry_on: vec![
+                FailureClass::RateLimit,
+                FailureClass::Network,
+                FailureClass::Stalled,
+            ],
+            resume: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Whether a run whose attempt number `attempt` failed with `class` gets another.
+    pub fn should_retry(&self, attempt: u32, class: FailureClass) -> bool {
+        attempt < self.max_attempts && self.retry_on.contains(&class)
+    }
+
+    /// Wait after failed attempt number `attempt` (from 1), or the server's
+    /// `retry_after` when it gave one.
+    pub fn delay(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        if let Some(retry_after) = retry_after {
+            return retry_after;
+        }
+        let exponent = attempt.saturating_sub(1).min(32) as i32;
+        let delay = self.initial_delay_ms as f64 * self.multiplier.max(1.0).powi(exponent);
+        Duration::from_millis(delay.min(self.max_delay_ms as f64) as u64)
+    }
+}
+
+/// The failures one attempt's events have reported so far.
+#[derive(Debug, Default)]
+pub(crate) struct AttemptFailure {
+    pub(crate) class: Option<FailureClass>,
+    /// From the last `RateLimited` event that said how long to wait.
+    pub(crate) retry_after: Option<Duration>,
+    /// The most telling problem the attempt's stderr reported, as a typed error.
+    pub(crate) error: Option<AgentError>,
+}
+
+impl AttemptFailure {
+    pub(crate) fn observe(&mut self, event: &AgentEvent) {
+        if let Some(class) = FailureClass::of_event(event) {
+            self.class = Some(self.class.map_or(class, |seen| seen.max(class)));
+        }
+        if let AgentEvent::RateLimited {
+            retry_after_secs: Some(secs),
+            ..
+        } = event
+        {
+            self.retry_after = Some(Duration::from_secs(*secs));
+        }
+    }
+
+    pub(crate) fn observe_stderr(&mut self, line: &StderrLine) {
+        let Some(error) = line.error() else {
+            return;
+        };
+        let rank = |error: &AgentError| FailureClass::of_error(error).rank();
+        if self.error.as_ref().is_none_or(|seen| rank(&error) >= rank(seen)) {
+            self.error = Some(error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delay_backs_off_up_to_the_cap() {
+        let policy = RetryPolicy {
+            max_delay_ms: 10_000,
+            ..RetryPolicy::default()
+        };
+        let delays: Vec<u64> = (1..=6)
+            .map(|attempt| policy.delay(attempt, None).as_millis() as u64)
+            .collect();
+        assert_eq!(delays, [2_000, 4_000, 8_000, 10_000, 10_000, 10_000]);
+        assert_eq!(policy.delay(u32::MAX, None), Duration::from_secs(10));
+
+        // A multiplier below 1 never shrinks the wait.
+        let flat = RetryPolicy {
+            multiplier: 0.5,
+            ..RetryPolicy::default()
+        };
+        assert_eq!(flat.delay(3, None), Duration::from_secs(2));
+    }
+
+    #[test]
+    fn retry_after_overrides_the_backoff() {
+        let policy = RetryPolicy::default();
+        let retry_after = Some(Duration::from_secs(90));
+        assert_eq!(policy.delay(1, retry_after), Duration::from_secs(90));
+        // Even past the cap: the server knows when it will take requests again.
+        assert_eq!(policy.delay(5, retry_after), Duration::from_secs(90));
+    }
+
+    #[test]
+    fn should_retry_until_attempts_run_out() {
+        let policy = RetryPolicy::default();
+        assert!(policy.should_retry(1, FailureClass::Network));
+        assert!(policy.should_retry(2, FailureClass::Network));
+        assert!(!policy.should_retry(3, FailureClass::Network));
+
+        let once = RetryPolicy {
+            max_attempts: 1,
+            ..RetryPolicy::default()
+        };
+        assert!(!once.should_retry(1, FailureClass::RateLimit));
+    }
+
+    #[test]
+    fn should_retry_only_listed_failures() {
+        let policy = RetryPolicy::default();
+        for class in [
+            FailureClass::RateLimit,
+            FailureClass::Network,
+            FailureClass::Stalled,
+        ] {
+            assert!(policy.should_retry(1, class), "{:?}", class);
+        }
+        for class in [
+            FailureClass::Timeout,
+            FailureClass::Auth,
+            FailureClass::Quota,
+            FailureClass::Unavailable,
+            FailureClass::Error,
+        ] {
+            assert!(!policy.should_retry(1, class), "{:?}", class);
+        }
+
+        let none = RetryPolicy {
+            retry_on: Vec::new(),
+            ..RetryPolicy::default()
+        };
+        assert!(!none.should_retry(1, FailureClass::Network));
+    }
+}
@@ -0,0 +1,659 @@
+//! Linux sandboxes for spawned CLIs. Agents usually run with their CLI's
+//! permission prompts bypassed, so a sandbox is what keeps one to its working
+//! directory and, optionally, off the network.
+
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::task::JoinHandle;
+
+use crate::backends::{self, CliBackend};
+use crate::types::*;
+
+/// System directories every bwrap sandbox can read.
+const SYSTEM_PATHS: &[&str] = &[
+    "/usr",
+    "/bin",
+    "/sbin",
+    "/lib",
+    "/lib32",
+    "/lib64",
+    "/etc",
+    "/opt",
+    "/run/systemd/resolve",
+];
+
+/// Longest request head the API proxy reads before giving up on a client.
+const MAX_PROXY_HEAD: usize = 8 * 1024;
+
+/// Where the API proxy's socket directory is mounted inside the sandbox.
+const PROXY_MOUNT: &str = "/run/giga-proxy";
+
+/// Loopback port inside the sandbox that socat forwards to the proxy socket.
+const PROXY_PORT: u16 = 3128;
+
+/// Starts the socat bridge (`$1`) from the sandbox's loopback to the proxy
+/// socket, waits for it to listen, then runs the CLI (the remaining args).
+const BRIDGE_SCRIPT: &str = r#"bridge="$1"; shift
+"$bridge" TCP-LISTEN:{port},bind=127.0.0.1,fork,reuseaddr UNIX-CONNECT:{socket} &
+i=0
+while [ "$i" -lt 500 ] && ! grep -q '0100007F:{hex_port} 00000000:0000 0A' /proc/net/tcp; do
+    i=$((i + 1))
+    sleep 0.01
+done
+exec "$@"
+"#;
+
+/// How a run is sandboxed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SandboxRunner {
+    /// bubblewrap: only the system, the CLI's install and its state are visible
+    /// besides the working directory.
+    #[default]
+    Bwrap,
+    /// `unshare` namespaces: isolates processes (and the network under
+    /// `NetworkAccess::None`) but not the filesystem.
+    Unshare,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum NetworkAccess {
+    #[default]
+    Full,
+    /// No network at all, not even loopback.
+    None,
+    /// Only the backend's model API (and `allow_hosts`). The sandbox gets its
+    /// own network namespace whose only way out is a loopback port bridged to
+    /// a proxy on the host that tunnels to allowed hosts, so CLIs that ignore
+    /// `HTTPS_PROXY` can't connect anywhere. Needs bwrap and socat.
+    ModelApi,
+}
+
+/// A sandbox for a run, usually one per workspace. `Started` reports the
+/// effective profile: the paths and hosts the sandbox actually allowed.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SandboxProfile {
+    #[serde(default)]
+    pub runner: SandboxRunner,
+    #[serde(default)]
+    pub network: NetworkAccess,
+    /// Writable besides the working directory and the CLI's own state.
+    #[serde(default)]
+    pub writable: Vec<PathBuf>,
+    /// Readable besides the system directories and the CLI's install.
+    #[serde(default)]
+    pub read_only: Vec<PathBuf>,
+    /// Reachable under `NetworkAccess::ModelApi` besides the backend's API
+    /// hosts; `*.example.com` matches subdomains.
+    #[serde(default)]
+    pub allow_hosts: Vec<String>,
+}
+
+/// A CLI command wrapped in its sandbox.
+pub(crate) struct Sandboxed {
+    pub program: PathBuf,
+    /// Arguments before the CLI's own, ending with the CLI's binary.
+    pub args: Vec<OsString>,
+    pub env: Vec<(String, String)>,
+    pub profile: SandboxProfile,
+    pub proxy: Option<ApiProxy>,
+}
+
+/// Rejects a profile this machine can't enforce.
+pub(crate) fn check(profile: &SandboxProfile) -> Result<(), AgentError> {
+    if !cfg!(target_os = "linux") {
+        return Err(AgentError::NotSupported(
+            "Sandboxing is only available on Linux".to_string(),
+        ));
+    }
+    if profile.runner == SandboxRunner::Unshare && profile.network == NetworkAccess::ModelApi {
+        return Err(AgentError::NotSupported(
+            "The unshare sandbox can't limit the network to the model API; use bwrap".to_string(),
+        ));
+    }
+    if profile.network == NetworkAccess::ModelApi {
+        bridge_path()?;
+    }
+    runner_path(profile.runner).map(|_| ())
+}
+
+fn runner_path(runner: SandboxRunner) -> Result<PathBuf, AgentError> {
+    let name = match runner {
+        SandboxRunner::Bwrap => "bwrap",
+        SandboxRunner::Unshare => "unshare",
+    };
+    backends::find_on_path(&[name])
+        .ok_or_else(|| AgentError::NotSupported(format!("{} not found on PATH", name)))
+}
+
+fn bridge_path() -> Result<PathBuf, AgentError> {
+    backends::find_on_path(&["socat"]).ok_or_else(|| {
+        AgentError::NotSupported(
+            "Limiting the network to the model API needs socat on PATH".to_string(),
+        )
+    })
+}
+
+/// Wraps `binary` (the CLI `backend` runs) in `profile`'s sandbox, with
+/// `working_directory` writable.
+pub(crate) async fn wrap(
+    profile: &SandboxProfile,
+    backend: &dyn CliBackend,
+    binary: &Path,
+    working_directory: &Path,
+) -> Result<Sandboxed, AgentError> {
+    let program = runner_path(profile.runner)?;
+    let mut effective = profile.clone();
+    let mut args: Vec<OsString> = Vec::new();
+    let mut env = Vec::new();
+    let mut proxy = None;
+
+    match profile.runner {
+        SandboxRunner::Unshare => {
+            args.extend(
+                [
+                    "--user",
+                    "--map-current-user",
+                    "--pid",
+                    "--fork",
+                    "--kill-child",
+                ]
+                .map(OsString::from),
+            );
+            if profile.network == NetworkAccess::None {
+                args.push("--net".into());
+            }
+            args.push("--".into());
+            // Nothing is mounted: the filesystem looks as it does outside.
+            effective.read_only.clear();
+            effective.writable.clear();
+        }
+        SandboxRunner::Bwrap => {
+            for path in SYSTEM_PATHS {
+                args.extend(["--ro-bind-try", path, path].map(OsString::from));
+            }
+            args.extend(
+                ["--proc", "/proc", "--dev", "/dev", "--tmpfs", "/tmp"].map(OsString::from),
+            );
+            let home = dirs::home_dir();
+            if let Some(home) = &home {
+                args.extend(["--tmpfs".into(), home.into()]);
+            }
+
+            let mut bridge = None;
+            if profile.network == NetworkAccess::ModelApi {
+                let mut hosts: Vec<String> =
+                    backend.api_hosts().into_iter().map(String::from).collect();
+                hosts.extend(profile.allow_hosts.iter().cloned());
+                let started = ApiProxy::start(hosts.clone()).await.map_err(|e| {
+                    AgentError::SpawnFailed(format!("Could not start the API proxy: {}", e))
+                })?;
+                args.extend([
+                    "--bind".into(),
+                    started.dir.clone().into(),
+                    PROXY_MOUNT.into(),
+                ]);
+                let url = format!("http://127.0.0.1:{}", PROXY_PORT);
+                for key in [
+                    "HTTPS_PROXY",
+                    "https_proxy",
+                    "HTTP_PROXY",
+                    "http_proxy",
+                    "ALL_PROXY",
+                ] {
+                    env.push((key.to_string(), url.clone()));
+                }
+                effective.allow_hosts = hosts;
+                proxy = Some(started);
+                bridge = Some(bridge_path()?);
+            }
+
+            let mut read_only = install_paths(binary);
+            if let Some(dir) = bridge.as_deref().and_then(Path::parent) {
+                push_dir(&mut read_only, dir);
+            }
+            read_only.extend(profile.read_only.iter().cloned());
+            for path in &read_only {
+                args.extend(["--ro-bind-try".into(), path.into(), path.into()]);
+            }
+
+            let mut writable: Vec<PathBuf> = home
+                .iter()
+                .flat_map(|home| {
+                    backend
+                        .state_paths()
+                        .into_iter()
+                        .map(|path| home.join(path))
+                })
+                .collect();
+            writable.extend(profile.writable.iter().cloned());
+            for path in &writable {
+                args.extend(["--bind-try".into(), path.into(), path.into()]);
+            }
+            args.extend([
+                "--bind".into(),
+                working_directory.into(),
+                working_directory.into(),
+                "--chdir".into(),
+                working_directory.into(),
+            ]);
+            writable.push(working_directory.to_path_buf());
+
+            args.extend(
+                [
+                    "--die-with-parent",
+                    "--unshare-pid",
+                    "--unshare-ipc",
+                    "--unshare-uts",
+                ]
+                .map(OsString::from),
+            );
+            if profile.network != NetworkAccess::Full {
+                args.push("--unshare-net".into());
+            }
+            args.push("--".into());
+            if let Some(bridge) = bridge {
+                let script = BRIDGE_SCRIPT
+                    .replace("{port}", &PROXY_PORT.to_string())
+                    .replace("{hex_port}", &format!("{:04X}", PROXY_PORT))
+                    .replace("{socket}", &format!("{}/{}", PROXY_MOUNT, PROXY_SOCKET));
+                args.extend(["/bin/sh".into(), "-c".into(), script.into(), "sh".into()]);
+                args.push(bridge.into());
+            }
+            effective.read_only = read_only;
+            effective.writable = writable;
+        }
+    }
+    args.push(binary.into());
+
+    Ok(Sandboxed {
+        program,
+        args,
+        env,
+        profile: effective,
+        proxy,
+    })
+}
+
+/// The directories a CLI needs to run, wherever its launcher links to: each
+/// link's directory, the package the launcher resolves into (an npm
+/// `bin/claude` is a link into `lib/node_modules/...`) and the install of the
+/// interpreter its shebang names, such as a node under nvm or volta.
+fn install_paths(binary: &Path) -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if let Some(parent) = binary.parent() {
+        push_dir(&mut dirs, parent);
+    }
+    let mut path = binary.to_path_buf();
+    for _ in 0..40 {
+        let Ok(link) = std::fs::read_link(&path) else {
+            break;
+        };
+        let Some(parent) = path.parent().and_then(|dir| dir.canonicalize().ok()) else {
+            break;
+        };
+        push_dir(&mut dirs, &parent);
+        path = parent.join(link);
+    }
+
+    let Ok(target) = binary.canonicalize() else {
+        return dirs;
+    };
+    if let Some(root) = package_root(&target).filter(|root| !is_system_path(root)) {
+        push_dir(&mut dirs, &root);
+    }
+    if let Some(parent) = target.parent() {
+        push_dir(&mut dirs, parent);
+    }
+    if let Some(interpreter) = interpreter(&target).and_then(|path| path.canonicalize().ok()) {
+        let bin = interpreter.parent();
+        // A versioned install keeps its libraries next to `bin`.
+        let install = bin
+            .filter(|bin| bin.file_name().is_some_and(|name| name == "bin"))
+            .and_then(Path::parent)
+            .filter(|dir| !is_system_path(dir))
+            .or(bin);
+        if let Some(install) = install.filter(|dir| !is_system_path(dir)) {
+            push_dir(&mut dirs, install);
+        }
+    }
+    dirs
+}
+
+/// The package `script` belongs to: the nearest directory with a
+/// `package.json`, or the one directly under `node_modules`.
+fn package_root(script: &Path) -> Option<PathBuf> {
+    let home = dirs::home_dir();
+    script
+        .ancestors()
+        .skip(1)
+        .take_while(|dir| dir.parent().is_some() && Some(*dir) != home.as_deref())
+        .find(|dir| {
+            dir.join("package.json").is_file()
+                || dir
+                    .parent()
+                    .and_then(Path::file_name)
+                    .is_some_and(|name| name == "node_modules")
+        })
+        .map(Path::to_path_buf)
+}
+
+/// The interpreter `script`'s shebang runs, looked up on PATH for `env`.
+fn interpreter(script: &Path) -> Option<PathBuf> {
+    use std::io::Read;
+
+    let mut head = [0u8; 256];
+    let read = std::fs::File::open(script).ok()?.read(&mut head).ok()?;
+    let line = head[..read].strip_prefix(b"#!")?;
+    let line = String::from_utf8_lossy(line);
+    let line = line.lines().next()?;
+    let mut words = line.split_whitespace();
+    let program = PathBuf::from(words.next()?);
+    if program.file_name().is_some_and(|name| name == "env") {
+        let name = words.find(|word| !word.starts_with('-'))?;
+        backends::find_on_path(&[name])
+    } else {
+        Some(program)
+    }
+}
+
+fn is_system_path(dir: &Path) -> bool {
+    dir.parent().is_none() || SYSTEM_PATHS.iter().any(|system| dir.starts_with(system))
+}
+
+fn push_dir(dirs: &mut Vec<PathBuf>, dir: &Path) {
+    if !dir.as_os_str().is_empty() && !dirs.iter().any(|known| dir.starts_with(known)) {
+        dirs.push(dir.to_path_buf());
+    }
+}
+
+/// Name of the API proxy's socket in its directory.
+const PROXY_SOCKET: &str = "proxy.sock";
+
+/// An HTTP `CONNECT` proxy on a Unix socket that only tunnels to allowed
+/// hosts. The socket's directory is private to the user and is mounted into
+/// the sandbox. Stops accepting connections and removes the directory when
+/// dropped.
+pub(crate) struct ApiProxy {
+    dir: PathBuf,
+    task: JoinHandle<()>,
+}
+
+impl ApiProxy {
+    #[cfg(unix)]
+    async fn start(hosts: Vec<String>) -> std::io::Result<Self> {
+        use std::os::unix::fs::DirBuilderExt;
+
+        let dir = std::env::temp_dir().join(format!("giga-proxy-{}", uuid::Uuid::new_v4()));
+        std::fs::DirBuilder::new().mode(0o700).create(&dir)?;
+        let listener = match tokio::net::UnixListener::bind(dir.join(PROXY_SOCKET)) {
+            Ok(listener) => listener,
+            Err(e) => {
+                let _ = std::fs::remove_dir_all(&dir);
+                return Err(e);
+            }
+        };
+        let hosts = Arc::new(hosts);
+        let task = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(tunnel(stream, hosts.clone()));
+            }
+        });
+        Ok(Self { dir, task })
+    }
+
+    #[cfg(not(unix))]
+    async fn start(_hosts: Vec<String>) -> std::io::Result<Self> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "the API proxy needs Unix sockets",
+        ))
+    }
+}
+
+impl Drop for ApiProxy {
+    fn drop(&mut self) {
+        self.task.abort();
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+async fn tunnel<S>(stream: S, hosts: Arc<Vec<String>>)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut client = BufReader::new(stream);
+    let mut head = String::new();
+    loop {
+        match client.read_line(&mut head).await {
+            Ok(0) | Err(_) => return,
+            Ok(_) if head.ends_with("\r\n\r\n") || head.ends_with("\n\n") => break,
+            Ok(_) if head.len() > MAX_PROXY_HEAD => return,
+            Ok(_) => {}
+        }
+    }
+
+    let mut request = head.split_whitespace();
+    let (Some(method), Some(target)) = (request.next(), request.next()) else {
+        return;
+    };
+    if !method.eq_ignore_ascii_case("CONNECT") {
+        let _ = client
+            .write_all(b"HTTP/1.1 405 Method Not Allowed\r\nContent-Length: 0\r\n\r\n")
+            .await;
+        return;
+    }
+    let host = target.rsplit_once(':').map_or(target, |(host, _)| host);
+    if !hosts.iter().any(|allowed| host_matches(allowed, host)) {
+        eprintln!("[Sandbox] Blocked connection to {}", target);
+        let _ = client
+            .write_all(b"HTTP/1.1 403 Forbidden\r\nContent-Length: 0\r\n\r\n")
+            .await;
+        return;
+    }
+
+    let Ok(mut upstream) = TcpStream::connect(target).await else {
+        let _ = client
+            .write_all(b"HTTP/1.1 502 Bad Gateway\r\nContent-Length: 0\r\n\r\n")
+            .await;
+        return;
+    };
+    if client
+        .write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n")
+        .await
+        .is_ok()
+    {
+        let _ = tokio::io::copy_bidirectional(&mut client, &mut upstream).await;
+    }
+}
+
+fn host_matches(allowed: &str, host: &str) -> bool {
+    let (allowed, host) = (allowed.to_ascii_lowercase(), host.to_ascii_lowercase());
+    match allowed.strip_prefix("*.") {
+        Some(domain) => host
+            .strip_suffix(domain)
+            .is_some_and(|sub| sub.ends_with('.')),
+        None => allowed == host,
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::{TcpListener, UnixStream};
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            let path = std::env::temp_dir().join(format!("giga-sandbox-{}", uuid::Uuid::new_v4()));
+            std::fs::create_dir_all(&path).unwrap();
+            Self(path.canonicalize().unwrap())
+        }
+
+        fn file(&self, path: &str, contents: &str) -> PathBuf {
+            let path = self.0.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, contents).unwrap();
+            path
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// An npm global install: `bin/tool` links into the package, whose
+    /// script runs a node installed outside the system directories.
+    fn npm_install(dir: &TempDir) -> PathBuf {
+        let node = dir.file("node/v20/bin/node", "");
+        dir.file("node/v20/lib/node_modules/npm/package.json", "{}");
+        dir.file("lib/node_modules/tool/package.json", "{}");
+        dir.file(
+            "lib/node_modules/tool/dist/cli.js",
+            &format!("#!{}\nconsole.log('hi')\n", node.display()),
+        );
+        let launcher = dir.0.join("bin/tool");
+        std::fs::create_dir_all(launcher.parent().unwrap()).unwrap();
+        std::os::unix::fs::symlink("../lib/node_modules/tool/dist/cli.js", &launcher).unwrap();
+        launcher
+    }
+
+    #[test]
+    fn install_paths_follow_a_node_launcher() {
+        let dir = TempDir::new();
+        let launcher = npm_install(&dir);
+        assert_eq!(
+            install_paths(&launcher),
+            vec![
+                dir.0.join("bin"),
+                dir.0.join("lib/node_modules/tool"),
+                dir.0.join("node/v20"),
+            ]
+        );
+
+        let binary = dir.file("native/claude", "\x7fELF");
+        assert_eq!(install_paths(&binary), vec![dir.0.join("native")]);
+    }
+
+    #[tokio::test]
+    async fn wrap_binds_a_symlinked_launcher_read_only() {
+        let dir = TempDir::new();
+        let launcher = npm_install(&dir);
+        let bwrap = dir.file("runner/bwrap", "#!/bin/sh\n");
+        std::fs::set_permissions(&bwrap, std::os::unix::fs::PermissionsExt::from_mode(0o755))
+            .unwrap();
+        let mut path = std::env::split_paths(&std::env::var_os("PATH").unwrap_or_default())
+            .collect::<Vec<_>>();
+        path.insert(0, dir.0.join("runner"));
+        std::env::set_var("PATH", std::env::join_paths(path).unwrap());
+
+        let work = dir.0.join("work");
+        std::fs::create_dir_all(&work).unwrap();
+        let profile = SandboxProfile::default();
+        let sandboxed = wrap(&profile, &backends::ClaudeBackend, &launcher, &work)
+            .await
+            .unwrap();
+
+        let args: Vec<String> = sandboxed
+            .args
+            .iter()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect();
+        let read_only: Vec<&str> = args
+            .windows(3)
+            .filter(|bind| bind[0] == "--ro-bind-try" && bind[1] == bind[2])
+            .map(|bind| bind[1].as_str())
+            .filter(|path| path.starts_with(dir.0.to_str().unwrap()))
+            .collect();
+        let expected = [
+            dir.0.join("bin"),
+            dir.0.join("lib/node_modules/tool"),
+            dir.0.join("node/v20"),
+        ];
+        assert_eq!(
+            read_only,
+            expected
+                .iter()
+                .map(|p| p.to_str().unwrap())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(sandboxed.profile.read_only, expected);
+        assert_eq!(args.last().map(String::as_str), launcher.to_str());
+        assert_eq!(sandboxed.program, bwrap);
+    }
+
+    #[test]
+    fn host_matching() {
+        assert!(host_matches("api.anthropic.com", "API.anthropic.com"));
+        assert!(host_matches(
+            "*.googleapis.com",
+            "generativelanguage.googleapis.com"
+        ));
+        assert!(!host_matches("*.googleapis.com", "googleapis.com"));
+        assert!(!host_matches("*.googleapis.com", "evilgoogleapis.com"));
+        assert!(!host_matches(
+            "api.anthropic.com",
+            "api.anthropic.com.evil.net"
+        ));
+    }
+
+    async fn request(proxy: &ApiProxy, head: &str) -> (UnixStream, String) {
+        let mut stream = UnixStream::connect(proxy.dir.join(PROXY_SOCKET))
+            .await
+            .unwrap();
+        stream.write_all(head.as_bytes()).await.unwrap();
+        let mut reply = Vec::new();
+        while !reply.ends_with(b"\r\n\r\n") {
+            let mut byte = [0u8];
+            if stream.read(&mut byte).await.unwrap() == 0 {
+                break;
+            }
+            reply.push(byte[0]);
+        }
+        (stream, String::from_utf8(reply).unwrap())
+    }
+
+    #[tokio::test]
+    async fn proxy_tunnels_only_to_allowed_hosts() {
+        let upstream = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = upstream.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut stream, _) = upstream.accept().await.unwrap();
+            let mut buf = [0u8; 4];
+            stream.read_exact(&mut buf).await.unwrap();
+            stream.write_all(&buf).await.unwrap();
+        });
+        let proxy = ApiProxy::start(vec!["127.0.0.1".to_string()])
+            .await
+            .unwrap();
+
+        let (_, reply) = request(&proxy, "CONNECT example.com:443 HTTP/1.1\r\n\r\n").await;
+        assert!(reply.starts_with("HTTP/1.1 403"), "{}", reply);
+
+        let (_, reply) = request(&proxy, "GET http://127.0.0.1/ HTTP/1.1\r\n\r\n").await;
+        assert!(reply.starts_with("HTTP/1.1 405"), "{}", reply);
+
+        let head = format!("CONNECT 127.0.0.1:{} HTTP/1.1\r\nHost: x\r\n\r\n", port);
+        let (mut stream, reply) = request(&proxy, &head).await;
+        assert!(reply.starts_with("HTTP/1.1 200"), "{}", reply);
+        stream.write_all(b"ping").await.unwrap();
+        let mut echoed = [0u8; 4];
+        stream.read_exact(&mut echoed).await.unwrap();
+        assert_eq!(&echoed, b"ping");
+
+        let dir = proxy.dir.clone();
+        drop(proxy);
+        assert!(!dir.exists());
+    }
+}
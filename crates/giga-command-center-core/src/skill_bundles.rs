@@ -0,0 +1,1135 @@
+//! Skill bundles: a skill directory, helper scripts included, packed as a zip or
+//! gzipped tar with a `manifest.json`, and unpacked again into the user's skills.
+//!
+//! Bundles come from elsewhere, so unpacking trusts nothing in them: entries
+//! must stay inside the skill, symlinks must point inside it too, files and the
+//! whole bundle are capped in size, and an existing skill is only replaced when
+//! asked to.
+
+use std::collections::BTreeMap;
+use std::io::{Cursor, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::process::Stdio;
+
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::process::Command;
+use uuid::Uuid;
+
+use crate::history::unix_millis;
+use crate::skills::{self, SkillDetail, SkillLocation, SkillsRepository, SKILL_FILE};
+use crate::types::*;
+
+/// The manifest at the root of every exported bundle.
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+const MANIFEST_VERSION: u32 = 1;
+
+/// Largest single file a bundle may hold.
+pub const MAX_BUNDLE_FILE_SIZE: u64 = 10 * 1024 * 1024;
+
+/// Largest bundle, counting the unpacked size of all its files.
+pub const MAX_BUNDLE_SIZE: u64 = 50 * 1024 * 1024;
+
+/// Most entries a bundle may hold.
+pub const MAX_BUNDLE_ENTRIES: usize = 1000;
+
+/// Longest symlink target accepted.
+const MAX_LINK_LEN: u64 = 4096;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BundleFormat {
+    #[default]
+    Zip,
+    /// Written gzipped; plain tar (e.g. from `git archive`) is read too.
+    Tar,
+}
+
+impl BundleFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            BundleFormat::Zip => "zip",
+            BundleFormat::Tar => "tar.gz",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            BundleFormat::Zip => "application/zip",
+            BundleFormat::Tar => "application/gzip",
+        }
+    }
+}
+
+/// What a bundle holds, written to `manifest.json`. Its files sit under `name/`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleManifest {
+    pub version: u32,
+    pub name: String,
+    pub description: String,
+    pub files: Vec<BundleFile>,
+    /// Unix millis.
+    pub exported_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleFile {
+    /// Relative to the skill directory, `/`-separated.
+    pub path: String,
+    pub size: u64,
+    /// Symlinks record where they point instead of a size.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link: Option<String>,
+}
+
+/// How to unpack a bundle.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportOptions {
+    /// Name for the imported skill; defaults to the manifest's, the bundle
+    /// directory's or the frontmatter's.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Replace a skill that already has the name.
+    #[serde(default)]
+    pub overwrite: bool,
+}
+
+/// A skill in a local git repository, imported as committed at `rev`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GitSkillSource {
+    /// The repository (or any directory inside it).
+    pub path: String,
+    /// The skill's directory within the repository; its root by default.
+    #[serde(default)]
+    pub subdir: Option<String>,
+    /// Commit, branch or tag; `HEAD` by default.
+    #[serde(default)]
+    pub rev: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+enum Entry {
+    File { data: Vec<u8>, executable: bool },
+    Symlink(String),
+}
+
+impl SkillsRepository {
+    /// Packs the skill `name` of `location` with everything in its directory.
+    pub async fn export(
+        &self,
+        name: &str,
+        location: &SkillLocation,
+        format: BundleFormat,
+    ) -> Result<Vec<u8>, AgentError> {
+        let detail = self.get_at(name, location).await?;
+        let dir = PathBuf::from(&detail.path);
+        tokio::task::spawn_blocking(move || {
+            let entries = collect_dir(&dir)?;
+            let manifest = BundleManifest {
+                version: MANIFEST_VERSION,
+                name: dir_name(&dir),
+                description: detail.info.description,
+                files: entries
+                    .iter()
+                    .map(|(path, entry)| BundleFile {
+                        path: path_string(path),
+                        size: match entry {
+                            Entry::File { data, .. } => data.len() as u64,
+                            Entry::Symlink(_) => 0,
+                        },
+                        link: match entry {
+                            Entry::Symlink(target) => Some(target.clone()),
+                            Entry::File { .. } => None,
+                        },
+                    })
+                    .collect(),
+                exported_at: unix_millis(),
+            };
+            match format {
+                BundleFormat::Zip => write_zip(&manifest, &entries),
+                BundleFormat::Tar => write_tar(&manifest, &entries),
+            }
+        })
+        .await
+        .map_err(|e| AgentError::StorageError(format!("Export failed: {}", e)))?
+    }
+
+    /// Unpacks `bundle` into the user's skills.
+    pub async fn import(
+        &self,
+        bundle: Vec<u8>,
+        format: BundleFormat,
+        options: ImportOptions,
+    ) -> Result<SkillDetail, AgentError> {
+        if bundle.len() as u64 > MAX_BUNDLE_SIZE {
+            return Err(too_large("The bundle"));
+        }
+        let entries = tokio::task::spawn_blocking(move || match format {
+            BundleFormat::Zip => read_zip(&bundle),
+            BundleFormat::Tar => read_tar(&bundle),
+        })
+        .await
+        .map_err(|e| AgentError::StorageError(format!("Import failed: {}", e)))??;
+        self.install(entries, options).await
+    }
+
+    /// Imports the skill at `source.subdir` of a local repository, as committed.
+    pub async fn import_git(
+        &self,
+        source: GitSkillSource,
+        options: ImportOptions,
+    ) -> Result<SkillDetail, AgentError> {
+        let rev = source.rev.as_deref().unwrap_or("HEAD");
+        if rev.is_empty() || rev.starts_with('-') || rev.contains(|c: char| c.is_whitespace()) {
+            return Err(AgentError::Git(format!("Invalid revision '{}'", rev)));
+        }
+        let tree = match source.subdir.as_deref().filter(|s| !s.is_empty()) {
+            Some(subdir) => format!("{}:{}", rev, path_string(&bundle_path(subdir)?)),
+            None => rev.to_string(),
+        };
+
+        let mut child = Command::new("git")
+            .arg("-C")
+            .arg(&source.path)
+            .args(["archive", "--format=tar", &tree])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| AgentError::Git(format!("Could not run git: {}", e)))?;
+        let mut archive = Vec::new();
+        if let Some(stdout) = child.stdout.take() {
+            stdout
+                .take(MAX_BUNDLE_SIZE + 1)
+                .read_to_end(&mut archive)
+                .await
+                .map_err(|e| AgentError::Git(format!("Could not read git archive: {}", e)))?;
+        }
+        if archive.len() as u64 > MAX_BUNDLE_SIZE {
+            return Err(too_large("The skill"));
+        }
+        let output = child
+            .wait_with_output()
+            .await
+            .map_err(|e| AgentError::Git(format!("Could not run git: {}", e)))?;
+        if !output.status.success() {
+            return Err(AgentError::Git(format!(
+                "git archive failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        let entries = tokio::task::spawn_blocking(move || read_tar(&archive))
+            .await
+            .map_err(|e| AgentError::StorageError(format!("Import failed: {}", e)))??;
+        self.install(entries, options).await
+    }
+
+    /// Finds the skill among `entries` and writes it beside the others: to a
+    /// staging directory first, swapped in once complete.
+    async fn install(
+        &self,
+        entries: BTreeMap<PathBuf, Entry>,
+        options: ImportOptions,
+    ) -> Result<SkillDetail, AgentError> {
+        let (bundle_name, mut entries) = skill_entries(entries)?;
+        let skill_md = match entries.get(Path::new(SKILL_FILE)) {
+            Some(Entry::File { data, .. }) => String::from_utf8(data.clone()).map_err(|_| {
+                AgentError::InvalidSkill(format!("{} isn't valid UTF-8", SKILL_FILE))
+            })?,
+            _ => {
+                return Err(AgentError::InvalidSkill(format!(
+                    "The bundle has no {}",
+                    SKILL_FILE
+                )))
+            }
+        };
+        let (frontmatter, markdown) = skills::split_skill(&skill_md, SKILL_FILE)?;
+
+        let declared = skills::frontmatter_str(&frontmatter, "name");
+        let name = options
+            .name
+            .or(bundle_name)
+            .or_else(|| declared.clone())
+            .ok_or_else(|| AgentError::InvalidSkill("Name the skill to import".to_string()))?;
+        skills::validate_name(&name)?;
+        // Imported under another name, the skill's frontmatter follows, as on rename.
+        if declared.as_deref() != Some(name.as_str()) {
+            let description = skills::frontmatter_str(&frontmatter, "description")
+                .unwrap_or_else(|| format!("Custom skill: {}", name));
+            let content = skills::render_skill(frontmatter, &name, &description, &markdown)?;
+            entries.insert(
+                PathBuf::from(SKILL_FILE),
+                Entry::File {
+                    data: content.into_bytes(),
+                    executable: false,
+                },
+            );
+        }
+        let target = skills::skill_dir(self.root(), &name)?;
+        let exists = fs::try_exists(&target).await.unwrap_or(false);
+        if exists && !options.overwrite {
+            return Err(AgentError::SkillExists(name));
+        }
+
+        fs::create_dir_all(self.root())
+            .await
+            .map_err(|e| skills::storage_error("Failed to create skills directory", e))?;
+        let staging = self.root().join(format!(".import-{}", Uuid::new_v4()));
+        if let Err(e) = write_entries(&staging, &entries).await {
+            let _ = fs::remove_dir_all(&staging).await;
+            return Err(e);
+        }
+
+        let replaced = self.root().join(format!(".replaced-{}", Uuid::new_v4()));
+        if exists {
+            if let Err(e) = fs::rename(&target, &replaced).await {
+                let _ = fs::remove_dir_all(&staging).await;
+                return Err(skills::storage_error("Failed to replace skill", e));
+            }
+        }
+        if let Err(e) = fs::rename(&staging, &target).await {
+            if exists {
+                let _ = fs::rename(&replaced, &target).await;
+            }
+            let _ = fs::remove_dir_all(&staging).await;
+            return Err(skills::storage_error("Failed to install skill", e));
+        }
+        if exists {
+            if let Err(e) = fs::remove_dir_all(&replaced).await {
+                eprintln!(
+                    "[Skills] Failed to remove replaced skill {}: {}",
+                    replaced.display(),
+                    e
+                );
+            }
+        }
+        self.get(&name).await
+    }
+}
+
+fn too_large(what: &str) -> AgentError {
+    AgentError::InvalidSkill(format!(
+        "{} is larger than {} MiB",
+        what,
+        MAX_BUNDLE_SIZE / (1024 * 1024)
+    ))
+}
+
+fn dir_name(dir: &Path) -> String {
+    dir.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+fn path_string(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// A bundle entry's path, relative and without `.` parts. Absolute paths, `..`,
+/// backslashes and control characters are refused; `None` for the root itself.
+fn bundle_path(raw: &str) -> Result<PathBuf, AgentError> {
+    let unsafe_path =
+        || AgentError::InvalidSkill(format!("Unsafe path in bundle: '{}'", raw.escape_debug()));
+    if raw.contains(|c: char| c.is_control() || c == '\\') {
+        return Err(unsafe_path());
+    }
+    let mut path = PathBuf::new();
+    for component in Path::new(raw).components() {
+        match component {
+            Component::Normal(part) => path.push(part),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => {
+                return Err(unsafe_path())
+            }
+        }
+    }
+    Ok(path)
+}
+
+/// Checks the symlinks among `entries` (paths relative to the skill). Nothing
+/// may sit inside a symlinked directory, and each link's target, resolved from
+/// the link's directory, must stay inside the skill without passing through
+/// another link: `..` after a link would climb from wherever that link points,
+/// so chained links could otherwise escape one lexically safe step at a time.
+fn check_links(entries: &BTreeMap<PathBuf, Entry>) -> Result<(), AgentError> {
+    let is_link = |path: &Path| matches!(entries.get(path), Some(Entry::Symlink(_)));
+    for (path, entry) in entries {
+        if let Some(link) = path.ancestors().skip(1).find(|dir| is_link(dir)) {
+            return Err(AgentError::InvalidSkill(format!(
+                "'{}' is inside the symlink '{}'",
+                path_string(path),
+                path_string(link)
+            )));
+        }
+        let Entry::Symlink(target) = entry else {
+            continue;
+        };
+        let escapes =
+            || AgentError::InvalidSkill(format!("'{}' links outside the skill", path_string(path)));
+        if target.is_empty() || target.contains(['\\', '\0']) {
+            return Err(escapes());
+        }
+        let parent = path.parent().unwrap_or(Path::new(""));
+        let mut resolved = PathBuf::new();
+        let mut components = parent
+            .components()
+            .chain(Path::new(target).components())
+            .peekable();
+        while let Some(component) = components.next() {
+            match component {
+                Component::Normal(part) => {
+                    resolved.push(part);
+                    if components.peek().is_some() && is_link(&resolved) {
+                        return Err(AgentError::InvalidSkill(format!(
+                            "'{}' links through the symlink '{}'",
+                            path_string(path),
+                            path_string(&resolved)
+                        )));
+                    }
+                }
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    if !resolved.pop() {
+                        return Err(escapes());
+                    }
+                }
+                Component::RootDir | Component::Prefix(_) => return Err(escapes()),
+            }
+        }
+        if resolved.as_os_str().is_empty() {
+            return Err(escapes());
+        }
+    }
+    Ok(())
+}
+
+/// Adds an entry, enforcing the entry, file and total size limits.
+fn add_entry(
+    entries: &mut BTreeMap<PathBuf, Entry>,
+    total: &mut u64,
+    path: PathBuf,
+    entry: Entry,
+) -> Result<(), AgentError> {
+    if entries.len() >= MAX_BUNDLE_ENTRIES {
+        return Err(AgentError::InvalidSkill(format!(
+            "The bundle has more than {} files",
+            MAX_BUNDLE_ENTRIES
+        )));
+    }
+    if let Entry::File { data, .. } = &entry {
+        if data.len() as u64 > MAX_BUNDLE_FILE_SIZE {
+            return Err(AgentError::InvalidSkill(format!(
+                "'{}' is larger than {} MiB",
+                path_string(&path),
+                MAX_BUNDLE_FILE_SIZE / (1024 * 1024)
+            )));
+        }
+        *total += data.len() as u64;
+        if *total > MAX_BUNDLE_SIZE {
+            return Err(too_large("The bundle"));
+        }
+    }
+    if entries.insert(path.clone(), entry).is_some() {
+        return Err(AgentError::InvalidSkill(format!(
+            "'{}' appears twice in the bundle",
+            path_string(&path)
+        )));
+    }
+    Ok(())
+}
+
+/// Reads at most `limit` bytes, failing if `reader` holds more.
+fn read_limited(reader: impl Read, limit: u64, path: &Path) -> Result<Vec<u8>, AgentError> {
+    let mut data = Vec::new();
+    reader
+        .take(limit + 1)
+        .read_to_end(&mut data)
+        .map_err(|e| AgentError::InvalidSkill(format!("Unreadable bundle entry: {}", e)))?;
+    if data.len() as u64 > limit {
+        return Err(AgentError::InvalidSkill(format!(
+            "'{}' is larger than {} MiB",
+            path_string(path),
+            limit / (1024 * 1024)
+        )));
+    }
+    Ok(data)
+}
+
+fn read_tar(bundle: &[u8]) -> Result<BTreeMap<PathBuf, Entry>, AgentError> {
+    let invalid =
+        |e: std::io::Error| AgentError::InvalidSkill(format!("Unreadable tar bundle: {}", e));
+    let reader: Box<dyn Read + '_> = if bundle.starts_with(&[0x1f, 0x8b]) {
+        Box::new(flate2::read::GzDecoder::new(bundle))
+    } else {
+        Box::new(bundle)
+    };
+    let mut archive = tar::Archive::new(reader);
+    let mut entries = BTreeMap::new();
+    let mut total = 0;
+    for entry in archive.entries().map_err(invalid)? {
+        let entry = entry.map_err(invalid)?;
+        let raw = entry.path_bytes();
+        let path = bundle_path(&String::from_utf8_lossy(&raw))?;
+        let header = entry.header();
+        match header.entry_type() {
+            tar::EntryType::Directory | tar::EntryType::XGlobalHeader => continue,
+            tar::EntryType::Regular | tar::EntryType::Continuous => {
+                if path.as_os_str().is_empty() {
+                    continue;
+                }
+                let executable = header.mode().map(|mode| mode & 0o111 != 0).unwrap_or(false);
+                let data = read_limited(entry, MAX_BUNDLE_FILE_SIZE, &path)?;
+                add_entry(
+                    &mut entries,
+                    &mut total,
+                    path,
+                    Entry::File { data, executable },
+                )?;
+            }
+            tar::EntryType::Symlink => {
+                let target = entry
+                    .link_name_bytes()
+                    .map(|target| String::from_utf8_lossy(&target).to_string())
+                    .unwrap_or_default();
+                add_entry(&mut entries, &mut total, path, Entry::Symlink(target))?;
+            }
+            other => {
+                return Err(AgentError::InvalidSkill(format!(
+                    "'{}' is a {:?} entry; bundles hold only files, directories and symlinks",
+                    path_string(&path),
+                    other
+                )))
+            }
+        }
+    }
+    Ok(entries)
+}
+
+fn read_zip(bundle: &[u8]) -> Result<BTreeMap<PathBuf, Entry>, AgentError> {
+    let invalid = |e: zip::result::ZipError| {
+        AgentError::InvalidSkill(format!("Unreadable zip bundle: {}", e))
+    };
+    let mut archive = zip::ZipArchive::new(Cursor::new(bundle)).map_err(invalid)?;
+    if archive.len() > MAX_BUNDLE_ENTRIES * 2 {
+        return Err(AgentError::InvalidSkill(format!(
+            "The bundle has more than {} files",
+            MAX_BUNDLE_ENTRIES
+        )));
+    }
+    let mut entries = BTreeMap::new();
+    let mut total = 0;
+    for i in 0..archive.len() {
+        let file = archive.by_index(i).map_err(invalid)?;
+        let path = bundle_path(&String::from_utf8_lossy(file.name_raw()))?;
+        if file.is_dir() || path.as_os_str().is_empty() {
+            continue;
+        }
+        if file.is_symlink() {
+            let target = read_limited(file, MAX_LINK_LEN, &path)?;
+            let target = String::from_utf8_lossy(&target).to_string();
+            add_entry(&mut entries, &mut total, path, Entry::Symlink(target))?;
+        } else {
+            let executable = file
+                .unix_mode()
+                .map(|mode| mode & 0o111 != 0)
+                .unwrap_or(false);
+            let data = read_limited(file, MAX_BUNDLE_FILE_SIZE, &path)?;
+            add_entry(
+                &mut entries,
+                &mut total,
+                path,
+                Entry::File { data, executable },
+            )?;
+        }
+    }
+    Ok(entries)
+}
+
+/// The skill's own entries, relative to its directory, and the name the bundle
+/// gives it. The skill is where the manifest says, or wherever `SKILL.md` is:
+/// at the root or in the one top-level directory.
+fn skill_entries(
+    mut entries: BTreeMap<PathBuf, Entry>,
+) -> Result<(Option<String>, BTreeMap<PathBuf, Entry>), AgentError> {
+    let manifest = match entries.remove(Path::new(MANIFEST_FILE)) {
+        Some(Entry::File { data, .. }) => Some(
+            serde_json::from_slice::<BundleManifest>(&data).map_err(|e| {
+                AgentError::InvalidSkill(format!("Invalid {}: {}", MANIFEST_FILE, e))
+            })?,
+        ),
+        Some(Entry::Symlink(_)) => {
+            return Err(AgentError::InvalidSkill(format!(
+                "{} is a symlink",
+                MANIFEST_FILE
+            )))
+        }
+        None => None,
+    };
+
+    let prefix = match &manifest {
+        Some(manifest) => {
+            skills::skill_dir(Path::new(""), &manifest.name)?;
+            PathBuf::from(&manifest.name)
+        }
+        None if entries.contains_key(Path::new(SKILL_FILE)) => PathBuf::new(),
+        None => {
+            let mut tops = entries.keys().filter_map(|path| path.components().next());
+            let first = tops.next();
+            match first {
+                Some(top)
+                    if tops.all(|other| other == top)
+                        && entries.contains_key(&Path::new(top.as_os_str()).join(SKILL_FILE)) =>
+                {
+                    PathBuf::from(top.as_os_str())
+                }
+                _ => {
+                    return Err(AgentError::InvalidSkill(format!(
+                        "The bundle has no {}",
+                        SKILL_FILE
+                    )))
+                }
+            }
+        }
+    };
+
+    let mut skill = BTreeMap::new();
+    for (path, entry) in entries {
+        let Ok(relative) = path.strip_prefix(&prefix) else {
+            continue;
+        };
+        if relative.as_os_str().is_empty() {
+            continue;
+        }
+        skill.insert(relative.to_path_buf(), entry);
+    }
+    check_links(&skill)?;
+
+    if let Some(manifest) = &manifest {
+        for file in &manifest.files {
+            let listed = match skill.get(&bundle_path(&file.path)?) {
+                Some(Entry::File { data, .. }) => {
+                    file.link.is_none() && data.len() as u64 == file.size
+                }
+                Some(Entry::Symlink(target)) => file.link.as_deref() == Some(target.as_str()),
+                None => false,
+            };
+            if !listed {
+                return Err(AgentError::InvalidSkill(format!(
+                    "The bundle doesn't match its manifest at '{}'",
+                    file.path
+                )));
+            }
+        }
+    }
+
+    let name = match manifest {
+        Some(manifest) => Some(manifest.name),
+        None if prefix.as_os_str().is_empty() => None,
+        None => Some(prefix.to_string_lossy().to_string()),
+    };
+    Ok((name, skill))
+}
+
+/// Writes `entries` under the new directory `dir`: every file first, then the
+/// symlinks, so no file is ever written through a link. Each parent is checked
+/// to resolve inside `dir`, and nothing is written over an existing path.
+async fn write_entries(dir: &Path, entries: &BTreeMap<PathBuf, Entry>) -> Result<(), AgentError> {
+    let write_error = |e| skills::storage_error("Failed to write skill", e);
+    fs::create_dir(dir).await.map_err(write_error)?;
+    let dir = fs::canonicalize(dir).await.map_err(write_error)?;
+    let files = entries
+        .iter()
+        .filter(|(_, entry)| matches!(entry, Entry::File { .. }));
+    let links = entries
+        .iter()
+        .filter(|(_, entry)| matches!(entry, Entry::Symlink(_)));
+
+    for (path, entry) in files.chain(links) {
+        let target = dir.join(path);
+        let parent = target.parent().unwrap_or(&dir);
+        fs::create_dir_all(parent).await.map_err(write_error)?;
+        if !fs::canonicalize(parent)
+            .await
+            .map_err(write_error)?
+            .starts_with(&dir)
+        {
+            return Err(AgentError::InvalidSkill(format!(
+                "'{}' would be written outside the skill",
+                path_string(path)
+            )));
+        }
+        match entry {
+            Entry::File { data, executable } => {
+                let mut options = fs::OpenOptions::new();
+                options.write(true).create_new(true);
+                #[cfg(unix)]
+                options.custom_flags(libc::O_NOFOLLOW).mode(if *executable {
+                    0o755
+                } else {
+                    0o644
+                });
+                #[cfg(not(unix))]
+                let _ = executable;
+                let mut file = options.open(&target).await.map_err(write_error)?;
+                file.write_all(data).await.map_err(write_error)?;
+                file.flush().await.map_err(write_error)?;
+                // The mode given on creation is narrowed by the umask
+                #[cfg(unix)]
+                {
+                    use std::os::unix::fs::PermissionsExt;
+                    let mode = if *executable { 0o755 } else { 0o644 };
+                    file.set_permissions(std::fs::Permissions::from_mode(mode))
+                        .await
+                        .map_err(write_error)?;
+                }
+            }
+            #[cfg(unix)]
+            Entry::Symlink(link) => {
+                fs::symlink(link, &target).await.map_err(write_error)?;
+            }
+            #[cfg(not(unix))]
+            Entry::Symlink(_) => {
+                return Err(AgentError::NotSupported(
+                    "Symlinks in skill bundles need a Unix system".to_string(),
+                ))
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Everything under `dir`, by path relative to it. Symlinks are kept as links,
+/// and only if they point inside `dir`.
+fn collect_dir(dir: &Path) -> Result<BTreeMap<PathBuf, Entry>, AgentError> {
+    let read_error = |e| skills::storage_error("Failed to read skill", e);
+    let mut entries = BTreeMap::new();
+    let mut total = 0;
+    let mut pending = vec![PathBuf::new()];
+    while let Some(relative) = pending.pop() {
+        for item in std::fs::read_dir(dir.join(&relative)).map_err(read_error)? {
+            let item = item.map_err(read_error)?;
+            let path = relative.join(item.file_name());
+            let metadata = item.path().symlink_metadata().map_err(read_error)?;
+            if metadata.file_type().is_symlink() {
+                let target = std::fs::read_link(item.path()).map_err(read_error)?;
+                let target = path_string(&target);
+                add_entry(&mut entries, &mut total, path, Entry::Symlink(target))?;
+            } else if metadata.is_dir() {
+                pending.push(path);
+            } else if metadata.is_file() {
+                #[cfg(unix)]
+                let executable = {
+                    use std::os::unix::fs::PermissionsExt;
+                    metadata.permissions().mode() & 0o111 != 0
+                };
+                #[cfg(not(unix))]
+                let executable = false;
+                let data = std::fs::read(item.path()).map_err(read_error)?;
+                add_entry(
+                    &mut entries,
+                    &mut total,
+                    path,
+                    Entry::File { data, executable },
+                )?;
+            }
+        }
+    }
+    check_links(&entries)?;
+    Ok(entries)
+}
+
+fn tar_header(kind: tar::EntryType, size: u64, mode: u32, mtime: u64) -> tar::Header {
+    let mut header = tar::Header::new_gnu();
+    header.set_entry_type(kind);
+    header.set_size(size);
+    header.set_mode(mode);
+    header.set_mtime(mtime);
+    header
+}
+
+fn write_tar(
+    manifest: &BundleManifest,
+    entries: &BTreeMap<PathBuf, Entry>,
+) -> Result<Vec<u8>, AgentError> {
+    let write_error = |e| skills::storage_error("Failed to write bundle", e);
+    let encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    let mut builder = tar::Builder::new(encoder);
+    let mtime = manifest.exported_at / 1000;
+
+    let manifest_json = serde_json::to_vec_pretty(manifest)
+        .map_err(|e| AgentError::StorageError(format!("Failed to write manifest: {}", e)))?;
+    let mut header = tar_header(
+        tar::EntryType::Regular,
+        manifest_json.len() as u64,
+        0o644,
+        mtime,
+    );
+    builder
+        .append_data(&mut header, MANIFEST_FILE, manifest_json.as_slice())
+        .map_err(write_error)?;
+    for (path, entry) in entries {
+        let path = format!("{}/{}", manifest.name, path_string(path));
+        match entry {
+            Entry::File { data, executable } => {
+                let mode = if *executable { 0o755 } else { 0o644 };
+                let mut header =
+                    tar_header(tar::EntryType::Regular, data.len() as u64, mode, mtime);
+                builder
+                    .append_data(&mut header, &path, data.as_slice())
+                    .map_err(write_error)?;
+            }
+            Entry::Symlink(target) => {
+                let mut header = tar_header(tar::EntryType::Symlink, 0, 0o777, mtime);
+                builder
+                    .append_link(&mut header, &path, target)
+                    .map_err(write_error)?;
+            }
+        }
+    }
+
+    builder
+        .into_inner()
+        .and_then(|encoder| encoder.finish())
+        .map_err(write_error)
+}
+
+fn write_zip(
+    manifest: &BundleManifest,
+    entries: &BTreeMap<PathBuf, Entry>,
+) -> Result<Vec<u8>, AgentError> {
+    let write_error = |e: zip::result::ZipError| {
+        AgentError::StorageError(format!("Failed to write bundle: {}", e))
+    };
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated);
+    let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+
+    let manifest_json = serde_json::to_vec_pretty(manifest)
+        .map_err(|e| AgentError::StorageError(format!("Failed to write manifest: {}", e)))?;
+    writer
+        .start_file(MANIFEST_FILE, options.unix_permissions(0o644))
+        .map_err(write_error)?;
+    writer
+        .write_all(&manifest_json)
+        .map_err(|e| skills::storage_error("Failed to write bundle", e))?;
+    for (path, entry) in entries {
+        let path = format!("{}/{}", manifest.name, path_string(path));
+        match entry {
+            Entry::File { data, executable } => {
+                let mode = if *executable { 0o755 } else { 0o644 };
+                writer
+                    .start_file(path, options.unix_permissions(mode))
+                    .map_err(write_error)?;
+                writer
+                    .write_all(data)
+                    .map_err(|e| skills::storage_error("Failed to write bundle", e))?;
+            }
+            Entry::Symlink(target) => {
+                writer
+                    .add_symlink(path, target, options)
+                    .map_err(write_error)?;
+            }
+        }
+    }
+
+    writer
+        .finish()
+        .map(|cursor| cursor.into_inner())
+        .map_err(write_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SKILL: &[u8] = b"---\nname: tool\ndescription: A tool\n---\nUse it.\n";
+
+    /// A tar with the names and link targets exactly as given, which
+    /// `tar::Builder`'s own path checks would otherwise refuse to write.
+    fn raw_tar(entries: &[(&str, &[u8], Option<&str>)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, data, link) in entries {
+            let mut header = tar::Header::new_gnu();
+            let gnu = header.as_gnu_mut().unwrap();
+            gnu.name[..name.len()].copy_from_slice(name.as_bytes());
+            if let Some(link) = link {
+                gnu.linkname[..link.len()].copy_from_slice(link.as_bytes());
+            }
+            header.set_entry_type(match link {
+                Some(_) => tar::EntryType::Symlink,
+                None => tar::EntryType::Regular,
+            });
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append(&header, *data).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    struct Fixture {
+        base: PathBuf,
+        repo: SkillsRepository,
+    }
+
+    impl Fixture {
+        fn new() -> Self {
+            let base = std::env::temp_dir().join(format!("skill-bundles-{}", Uuid::new_v4()));
+            std::fs::create_dir_all(base.join("user")).unwrap();
+            let repo =
+                SkillsRepository::new(base.join("user")).with_plugins_dir(base.join("plugins"));
+            Self { base, repo }
+        }
+
+        async fn import(&self, entries: &[(&str, &[u8], Option<&str>)]) -> Result<(), AgentError> {
+            self.repo
+                .import(
+                    raw_tar(entries),
+                    BundleFormat::Tar,
+                    ImportOptions::default(),
+                )
+                .await
+                .map(|_| ())
+        }
+
+        /// Everything left in the skills root, staging directories included.
+        fn user_entries(&self) -> Vec<String> {
+            std::fs::read_dir(self.base.join("user"))
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+                .collect()
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.base);
+        }
+    }
+
+    fn assert_invalid(result: Result<(), AgentError>) {
+        assert!(
+            matches!(result, Err(AgentError::InvalidSkill(_))),
+            "{:?}",
+            result
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_traversal_and_absolute_paths() {
+        let fixture = Fixture::new();
+        for name in ["tool/../../escape", "../escape", "/tmp/escape", "tool/a\\b"] {
+            assert_invalid(
+                fixture
+                    .import(&[("tool/SKILL.md", SKILL, None), (name, b"x", None)])
+                    .await,
+            );
+        }
+        assert!(fixture.user_entries().is_empty());
+        assert!(!fixture.base.join("escape").exists());
+    }
+
+    #[tokio::test]
+    async fn rejects_links_out_of_the_skill() {
+        let fixture = Fixture::new();
+        for target in [
+            "../outside",
+            "../../outside",
+            "/etc/passwd",
+            "sub/../..",
+            ".",
+        ] {
+            assert_invalid(
+                fixture
+                    .import(&[
+                        ("tool/SKILL.md", SKILL, None),
+                        ("tool/a", b"", Some(target)),
+                    ])
+                    .await,
+            );
+        }
+        assert!(fixture.user_entries().is_empty());
+    }
+
+    #[tokio::test]
+    async fn rejects_chained_links() {
+        let fixture = Fixture::new();
+        // Each link stays inside lexically; followed in turn, `a` is three
+        // levels above `0x`.
+        assert_invalid(
+            fixture
+                .import(&[
+                    ("tool/SKILL.md", SKILL, None),
+                    ("tool/deep/deep2/deep3/0b", b"", Some("../../../0x")),
+                    ("tool/deep/deep2/deep3/a", b"", Some("0b/../../..")),
+                    ("tool/deep/deep2/deep3/a/settings.json", b"{}", None),
+                ])
+                .await,
+        );
+        assert_invalid(
+            fixture
+                .import(&[
+                    ("tool/SKILL.md", SKILL, None),
+                    ("tool/deep/up", b"", Some("..")),
+                    ("tool/deep/a", b"", Some("up/up")),
+                ])
+                .await,
+        );
+        assert!(fixture.user_entries().is_empty());
+    }
+
+    #[tokio::test]
+    async fn rejects_entries_inside_links() {
+        let fixture = Fixture::new();
+        assert_invalid(
+            fixture
+                .import(&[
+                    ("tool/SKILL.md", SKILL, None),
+                    ("tool/scripts/run.sh", b"echo", None),
+                    ("tool/bin", b"", Some("scripts")),
+                    ("tool/bin/run.sh", b"echo", None),
+                ])
+                .await,
+        );
+        assert!(fixture.user_entries().is_empty());
+    }
+
+    #[tokio::test]
+    async fn keeps_links_inside_the_skill() {
+        let fixture = Fixture::new();
+        fixture
+            .import(&[
+                ("tool/SKILL.md", SKILL, None),
+                ("tool/scripts/run.sh", b"echo", None),
+                ("tool/run", b"", Some("scripts/run.sh")),
+                ("tool/docs/run", b"", Some("../scripts/run.sh")),
+            ])
+            .await
+            .unwrap();
+        let dir = fixture.base.join("user/tool");
+        assert_eq!(std::fs::read(dir.join("run")).unwrap(), b"echo");
+        assert_eq!(std::fs::read(dir.join("docs/run")).unwrap(), b"echo");
+        assert_eq!(fixture.user_entries(), ["tool"]);
+    }
+
+    #[tokio::test]
+    async fn enforces_size_and_entry_limits() {
+        let fixture = Fixture::new();
+        let big = vec![0u8; MAX_BUNDLE_FILE_SIZE as usize + 1];
+        assert_invalid(
+            fixture
+                .import(&[("tool/SKILL.md", SKILL, None), ("tool/big", &big, None)])
+                .await,
+        );
+
+        let names: Vec<String> = (0..MAX_BUNDLE_ENTRIES)
+            .map(|i| format!("tool/f{}", i))
+            .collect();
+        let mut entries: Vec<(&str, &[u8], Option<&str>)> = vec![("tool/SKILL.md", SKILL, None)];
+        entries.extend(names.iter().map(|name| (name.as_str(), &b""[..], None)));
+        assert_invalid(fixture.import(&entries).await);
+
+        assert_invalid(
+            fixture
+                .repo
+                .import(
+                    vec![0; MAX_BUNDLE_SIZE as usize + 1],
+                    BundleFormat::Tar,
+                    ImportOptions::default(),
+                )
+                .await
+                .map(|_| ()),
+        );
+        assert!(fixture.user_entries().is_empty());
+    }
+
+    #[tokio::test]
+    async fn rejects_duplicate_entries() {
+        let fixture = Fixture::new();
+        assert_invalid(
+            fixture
+                .import(&[
+                    ("tool/SKILL.md", SKILL, None),
+                    ("tool/a", b"one", None),
+                    ("tool/./a", b"two", None),
+                ])
+                .await,
+        );
+    }
+
+    #[tokio::test]
+    async fn name_collisions_need_overwrite() {
+        let fixture = Fixture::new();
+        let bundle = |body: &'static [u8]| {
+            raw_tar(&[("tool/SKILL.md", SKILL, None), ("tool/notes", body, None)])
+        };
+        fixture
+            .repo
+            .import(bundle(b"one"), BundleFormat::Tar, ImportOptions::default())
+            .await
+            .unwrap();
+
+        let again = fixture
+            .repo
+            .import(bundle(b"two"), BundleFormat::Tar, ImportOptions::default())
+            .await;
+        assert!(
+            matches!(again, Err(AgentError::SkillExists(_))),
+            "{:?}",
+            again
+        );
+        let notes = fixture.base.join("user/tool/notes");
+        assert_eq!(std::fs::read(&notes).unwrap(), b"one");
+
+        let overwrite = ImportOptions {
+            overwrite: true,
+            ..Default::default()
+        };
+        fixture
+            .repo
+            .import(bundle(b"two"), BundleFormat::Tar, overwrite)
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read(&notes).unwrap(), b"two");
+        assert_eq!(fixture.user_entries(), ["tool"]);
+    }
+
+    #[tokio::test]
+    async fn round_trips_through_both_formats() {
+        let fixture = Fixture::new();
+        fixture
+            .import(&[
+                ("tool/SKILL.md", SKILL, None),
+                ("tool/scripts/run.sh", b"echo", None),
+                ("tool/run", b"", Some("scripts/run.sh")),
+            ])
+            .await
+            .unwrap();
+        for format in [BundleFormat::Zip, BundleFormat::Tar] {
+            let bundle = fixture
+                .repo
+                .export("tool", &SkillLocation::default(), format)
+                .await
+                .unwrap();
+            let options = ImportOptions {
+                name: Some("copy".to_string()),
+                overwrite: true,
+            };
+            let copy = fixture.repo.import(bundle, format, options).await.unwrap();
+            assert_eq!(copy.info.name, "copy");
+            let dir = fixture.base.join("user/copy");
+            assert_eq!(std::fs::read(dir.join("run")).unwrap(), b"echo");
+        }
+    }
+}
@@ -0,0 +1,374 @@
+//! Live watching of the skill directories: the user's, each installed plugin's
+//! and every project listed since the watch started. Changes are picked up with
+//! `notify` (inotify on Linux), collected for a moment, and compared against an
+//! in-memory index of each directory's skills; the difference is emitted as
+//! `SkillsChanged`.
+//!
+//! A directory that doesn't exist yet is waited for by watching its nearest
+//! existing parent, so e.g. the first skill of a project is noticed too.
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::mpsc;
+
+use crate::skills::{self, SkillInfo, SkillSource, SkillsRepository, INSTALLED_PLUGINS_FILE};
+use crate::types::*;
+
+/// How long a burst of file changes is collected before it is compared.
+const DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// The running watch of a [`SkillsRepository`]; it ends once the repository and
+/// all its clones are dropped.
+pub(crate) struct SkillWatch {
+    tx: mpsc::UnboundedSender<WatchMessage>,
+    projects: Mutex<HashSet<PathBuf>>,
+}
+
+impl std::fmt::Debug for SkillWatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SkillWatch").finish_non_exhaustive()
+    }
+}
+
+impl SkillWatch {
+    /// Also watches the project skills in `root`.
+    pub(crate) fn add_project(&self, root: PathBuf) {
+        let mut projects = self.projects.lock().unwrap_or_else(|e| e.into_inner());
+        if projects.insert(root.clone()) {
+            let _ = self.tx.send(WatchMessage::Project(root));
+        }
+    }
+}
+
+enum WatchMessage {
+    Fs(notify::Result<notify::Event>),
+    Project(PathBuf),
+}
+
+/// A watched skills directory and what it held when last scanned.
+struct RootIndex {
+    source: SkillSource,
+    plugin: Option<String>,
+    working_directory: Option<String>,
+    /// Directory name to the skill and its `SKILL.md`.
+    skills: BTreeMap<String, (SkillInfo, String)>,
+}
+
+struct Watch<F> {
+    /// Reads the same directories, without holding the watch open.
+    reader: SkillsRepository,
+    watcher: RecommendedWatcher,
+    watched: BTreeMap<PathBuf, RecursiveMode>,
+    roots: BTreeMap<PathBuf, RootIndex>,
+    emit_event: F,
+}
+
+impl SkillsRepository {
+    /// Watches the skill directories and emits `SkillsChanged` whenever a skill
+    /// is added, modified (any file in its directory) or removed, by whatever
+    /// means. Project skills are watched for every working directory passed to
+    /// [`SkillsRepository::list`] from then on. Must be called within a Tokio
+    /// runtime; a repository is watched at most once.
+    pub async fn watch<F>(&self, emit_event: F) -> Result<(), AgentError>
+    where
+        F: Fn(AgentEvent) + Send + Sync + 'static,
+    {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let events = tx.downgrade();
+        let watcher = notify::recommended_watcher(move |event| {
+            if let Some(tx) = events.upgrade() {
+                let _ = tx.send(WatchMessage::Fs(event));
+            }
+        })
+        .map_err(|e| AgentError::StorageError(format!("Failed to watch skills: {}", e)))?;
+
+        let state = SkillWatch {
+            tx,
+            projects: Mutex::new(HashSet::new()),
+        };
+        if self.watch_state().set(state).is_err() {
+            return Err(AgentError::NotSupported(
+                "Skills are already being watched".to_string(),
+            ));
+        }
+
+        let mut reader = SkillsRepository::new(self.root());
+        if let Some(dir) = self.plugins_dir() {
+            reader = reader.with_plugins_dir(dir);
+        }
+        let mut watch = Watch {
+            reader,
+            watcher,
+            watched: BTreeMap::new(),
+            roots: BTreeMap::new(),
+            emit_event,
+        };
+        watch.add_root(self.root().to_path_buf(), SkillSource::User, None);
+        for (plugin, root) in watch.reader.plugin_roots().await {
+            watch.add_root(root, SkillSource::Plugin, Some(plugin));
+        }
+        // Watch first, then index: a change in between is seen again, not lost
+        watch.sync_watches();
+        let roots: Vec<PathBuf> = watch.roots.keys().cloned().collect();
+        for root in roots {
+            watch.rescan(&root, &BTreeSet::new()).await;
+        }
+        tokio::spawn(watch.run(rx));
+        Ok(())
+    }
+}
+
+impl<F> Watch<F>
+where
+    F: Fn(AgentEvent) + Send + Sync + 'static,
+{
+    async fn run(mut self, mut rx: mpsc::UnboundedReceiver<WatchMessage>) {
+        while let Some(first) = rx.recv().await {
+            tokio::time::sleep(DEBOUNCE).await;
+            let mut messages = vec![first];
+            while let Ok(message) = rx.try_recv() {
+                messages.push(message);
+            }
+
+            let mut paths = Vec::new();
+            let mut rescan_all = false;
+            let mut quiet = Vec::new();
+            for message in messages {
+                match message {
+                    WatchMessage::Fs(Ok(event)) => {
+                        rescan_all |= event.need_rescan();
+                        if !matches!(event.kind, EventKind::Access(_)) {
+                            paths.extend(event.paths);
+                        }
+                    }
+                    WatchMessage::Fs(Err(e)) => {
+                        eprintln!("[Skills] Watch error: {}", e);
+                        rescan_all = true;
+                    }
+                    // A newly listed project is indexed, not announced
+                    WatchMessage::Project(root) => {
+                        if !self.roots.contains_key(&root) {
+                            self.add_root(root.clone(), SkillSource::Project, None);
+                            quiet.push(root);
+                        }
+                    }
+                }
+            }
+
+            let mut changed = Vec::new();
+            let known: BTreeSet<PathBuf> = self.roots.keys().cloned().collect();
+            if self.plugins_changed(&paths) {
+                changed.extend(self.reload_plugins().await);
+            }
+            // A watched directory that was itself replaced needs a new watch
+            for path in &paths {
+                if self.watched.contains_key(path) {
+                    let _ = self.watcher.unwatch(path);
+                    self.watched.remove(path);
+                }
+            }
+            self.sync_watches();
+
+            for root in &quiet {
+                self.rescan(root, &BTreeSet::new()).await;
+            }
+            let roots: Vec<PathBuf> = self.roots.keys().cloned().collect();
+            for root in roots {
+                if quiet.contains(&root) {
+                    continue;
+                }
+                let affected = rescan_all
+                    || !known.contains(&root)
+                    || paths
+                        .iter()
+                        .any(|path| path.starts_with(&root) || root.starts_with(path));
+                if affected {
+                    changed.extend(self.rescan(&root, &touched_skills(&root, &paths)).await);
+                }
+            }
+            for event in changed {
+                (self.emit_event)(event);
+            }
+        }
+    }
+
+    fn add_root(&mut self, root: PathBuf, source: SkillSource, plugin: Option<String>) {
+        // Project roots are `<working directory>/.claude/skills`
+        let working_directory = (source == SkillSource::Project)
+            .then(|| root.parent().and_then(Path::parent))
+            .flatten()
+            .map(|dir| dir.to_string_lossy().to_string());
+        self.roots.insert(
+            root,
+            RootIndex {
+                source,
+                plugin,
+                working_directory,
+                skills: BTreeMap::new(),
+            },
+        );
+    }
+
+    fn plugins_changed(&self, paths: &[PathBuf]) -> bool {
+        let Some(dir) = self.reader.plugins_dir() else {
+            return false;
+        };
+        let installed = dir.join(INSTALLED_PLUGINS_FILE);
+        paths
+            .iter()
+            .any(|path| *path == installed || dir.starts_with(path))
+    }
+
+    /// Follows plugins being installed and uninstalled: a new plugin's root is
+    /// added (its skills are announced by the rescan that follows), a removed
+    /// one's skills are announced as removed here.
+    async fn reload_plugins(&mut self) -> Vec<AgentEvent> {
+        let current: BTreeMap<PathBuf, String> = self
+            .reader
+            .plugin_roots()
+            .await
+            .into_iter()
+            .map(|(plugin, root)| (root, plugin))
+            .collect();
+        let gone: Vec<PathBuf> = self
+            .roots
+            .iter()
+            .filter(|(root, index)| {
+                index.source == SkillSource::Plugin && current.get(*root) != index.plugin.as_ref()
+            })
+            .map(|(root, _)| root.clone())
+            .collect();
+
+        let mut events = Vec::new();
+        for root in gone {
+            let Some(index) = self.roots.remove(&root) else {
+                continue;
+            };
+            if !index.skills.is_empty() {
+                events.push(AgentEvent::SkillsChanged {
+                    working_directory: None,
+                    added: Vec::new(),
+                    modified: Vec::new(),
+                    removed: index.skills.into_values().map(|(info, _)| info).collect(),
+                });
+            }
+        }
+        for (root, plugin) in current {
+            if !self.roots.contains_key(&root) {
+                self.add_root(root, SkillSource::Plugin, Some(plugin));
+            }
+        }
+        events
+    }
+
+    /// Watches every existing root recursively, and the nearest existing parent
+    /// of the missing ones (and of the plugins directory) until they appear.
+    fn sync_watches(&mut self) {
+        let mut wanted: BTreeMap<PathBuf, RecursiveMode> = BTreeMap::new();
+        let mut want = |path: &Path, mode: RecursiveMode| {
+            let (path, mode) = if path.is_dir() {
+                (path.to_path_buf(), mode)
+            } else {
+                match path.ancestors().skip(1).find(|dir| dir.is_dir()) {
+                    Some(dir) => (dir.to_path_buf(), RecursiveMode::NonRecursive),
+                    None => return,
+                }
+            };
+            let entry = wanted.entry(path).or_insert(mode);
+            if mode == RecursiveMode::Recursive {
+                *entry = mode;
+            }
+        };
+        for root in self.roots.keys() {
+            want(root, RecursiveMode::Recursive);
+        }
+        if let Some(dir) = self.reader.plugins_dir() {
+            want(dir, RecursiveMode::NonRecursive);
+        }
+
+        let stale: Vec<PathBuf> = self
+            .watched
+            .iter()
+            .filter(|(path, mode)| wanted.get(*path) != Some(*mode))
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in stale {
+            let _ = self.watcher.unwatch(&path);
+            self.watched.remove(&path);
+        }
+        for (path, mode) in wanted {
+            if self.watched.contains_key(&path) {
+                continue;
+            }
+            match self.watcher.watch(&path, mode) {
+                Ok(()) => {
+                    self.watched.insert(path, mode);
+                }
+                Err(e) => eprintln!("[Skills] Failed to watch {}: {}", path.display(), e),
+            }
+        }
+    }
+
+    /// Rereads `root` into the index; the difference, if any, as `SkillsChanged`.
+    /// Skills in `touched` count as modified even if `SKILL.md` is unchanged.
+    async fn rescan(&mut self, root: &Path, touched: &BTreeSet<String>) -> Option<AgentEvent> {
+        let index = self.roots.get_mut(root)?;
+        let skills = match skills::read_skills(root, index.source, index.plugin.as_deref()).await {
+            Ok(skills) => skills,
+            Err(e) => {
+                eprintln!("[Skills] Failed to rescan {}: {}", root.display(), e);
+                return None;
+            }
+        };
+        let scanned: BTreeMap<String, (SkillInfo, String)> = skills
+            .into_iter()
+            .map(|(dir, info, content)| (dir, (info, content)))
+            .collect();
+
+        let mut added = Vec::new();
+        let mut modified = Vec::new();
+        for (dir, (info, content)) in &scanned {
+            match index.skills.get(dir) {
+                None => added.push(info.clone()),
+                Some((_, old)) if old != content || touched.contains(dir) => {
+                    modified.push(info.clone())
+                }
+                Some(_) => {}
+            }
+        }
+        let removed: Vec<SkillInfo> = index
+            .skills
+            .iter()
+            .filter(|(dir, _)| !scanned.contains_key(*dir))
+            .map(|(_, (info, _))| info.clone())
+            .collect();
+        index.skills = scanned;
+
+        if added.is_empty() && modified.is_empty() && removed.is_empty() {
+            return None;
+        }
+        Some(AgentEvent::SkillsChanged {
+            working_directory: index.working_directory.clone(),
+            added,
+            modified,
+            removed,
+        })
+    }
+}
+
+/// The skill directories under `root` that had a file inside them change.
+fn touched_skills(root: &Path, paths: &[PathBuf]) -> BTreeSet<String> {
+    paths
+        .iter()
+        .filter_map(|path| {
+            let mut components = path.strip_prefix(root).ok()?.components();
+            let dir = components.next()?;
+            components.next()?;
+            Some(dir.as_os_str().to_string_lossy().to_string())
+        })
+        .collect()
+}
@@ -0,0 +1,672 @@
+//! Claude skills: one directory per skill, each with a `SKILL.md` whose YAML
+//! frontmatter names and describes it. Skills are found in three places:
+//!
+//! - user: `~/.claude/skills`, the only one skills are written to
+//! - project: `<working directory>/.claude/skills`
+//! - plugin: `<install path>/skills` of every plugin listed in
+//!   `~/.claude/plugins/installed_plugins.json`
+
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value as YamlValue};
+use tokio::fs;
+
+use crate::skill_watcher::SkillWatch;
+use crate::types::*;
+
+/// The file every skill directory holds.
+pub const SKILL_FILE: &str = "SKILL.md";
+
+/// Lists the installed plugins, inside the plugins directory.
+pub(crate) const INSTALLED_PLUGINS_FILE: &str = "installed_plugins.json";
+
+const MAX_NAME_LEN: usize = 64;
+const MAX_DESCRIPTION_LEN: usize = 1024;
+
+/// Where a skill was found.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SkillSource {
+    #[default]
+    User,
+    Project,
+    Plugin,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SkillInfo {
+    /// How `AgentConfig::skills` names it: the skill's directory, prefixed with
+    /// `plugin:` for a plugin's skill.
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    #[serde(default)]
+    pub source: SkillSource,
+    /// The plugin providing the skill, for `SkillSource::Plugin`.
+    #[serde(default)]
+    pub plugin: Option<String>,
+    /// `allowed-tools`, written either as a list or as one comma-separated string.
+    #[serde(default)]
+    pub allowed_tools: Option<Vec<String>>,
+    /// Every other frontmatter key, as written.
+    #[serde(default)]
+    pub metadata: BTreeMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkillDetail {
+    pub info: SkillInfo,
+    pub markdown: String,
+    pub path: String,
+}
+
+/// Which skills directory to look a skill up in; the user's by default.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SkillLocation {
+    #[serde(default)]
+    pub source: SkillSource,
+    /// Required for `SkillSource::Plugin`.
+    #[serde(default)]
+    pub plugin: Option<String>,
+    /// Required for `SkillSource::Project`.
+    #[serde(default)]
+    pub working_directory: Option<String>,
+}
+
+/// A skill to create.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewSkill {
+    pub name: String,
+    pub description: String,
+    #[serde(default)]
+    pub markdown: String,
+}
+
+/// New contents for an existing skill. Frontmatter keys other than `name` and
+/// `description` are kept.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkillUpdate {
+    pub description: String,
+    #[serde(default)]
+    pub markdown: String,
+}
+
+/// Reads the user, project and plugin skills, and writes the user's.
+#[derive(Debug, Clone)]
+pub struct SkillsRepository {
+    root: PathBuf,
+    plugins_dir: Option<PathBuf>,
+    /// Set once [`SkillsRepository::watch`] is running; shared by clones.
+    watch: Arc<OnceLock<SkillWatch>>,
+}
+
+impl SkillsRepository {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            plugins_dir: None,
+            watch: Arc::new(OnceLock::new()),
+        }
+    }
+
+    /// Also reads the skills of the plugins installed under `dir`.
+    pub fn with_plugins_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.plugins_dir = Some(dir.into());
+        self
+    }
+
+    /// The directories Claude Code reads: `~/.claude/skills` and the plugins in
+    /// `~/.claude/plugins`. The skills directory is created on the first write.
+    pub fn open_default() -> Result<Self, AgentError> {
+        let home = dirs::home_dir()
+            .ok_or_else(|| AgentError::StorageError("Could not find home directory".to_string()))?;
+        let claude = home.join(".claude");
+        Ok(Self::new(claude.join("skills")).with_plugins_dir(claude.join("plugins")))
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub(crate) fn plugins_dir(&self) -> Option<&Path> {
+        self.plugins_dir.as_deref()
+    }
+
+    pub(crate) fn watch_state(&self) -> &OnceLock<SkillWatch> {
+        &self.watch
+    }
+
+    /// Every skill visible from `working_directory` (or only the user and plugin
+    /// skills without one), sorted by source, then name.
+    pub async fn list(
+        &self,
+        working_directory: Option<&Path>,
+    ) -> Result<Vec<SkillInfo>, AgentError> {
+        let mut skills = scan_skills(&self.root, SkillSource::User, None).await?;
+        if let Some(dir) = working_directory {
+            if let Some(watch) = self.watch.get() {
+                watch.add_project(project_root(dir));
+            }
+            skills.extend(scan_skills(&project_root(dir), SkillSource::Project, None).await?);
+        }
+        for (plugin, dir) in self.plugin_roots().await {
+            skills.extend(scan_skills(&dir, SkillSource::Plugin, Some(&plugin)).await?);
+        }
+        skills.sort_by(|a, b| (a.source, &a.plugin, &a.name).cmp(&(b.source, &b.plugin, &b.name)));
+        Ok(skills)
+    }
+
+    /// The skill in directory `name` of the user's skills.
+    pub async fn get(&self, name: &str) -> Result<SkillDetail, AgentError> {
+        self.get_at(name, &SkillLocation::default()).await
+    }
+
+    /// The skill in directory `name` of `location`.
+    pub async fn get_at(
+        &self,
+        name: &str,
+        location: &SkillLocation,
+    ) -> Result<SkillDetail, AgentError> {
+        let root = match location.source {
+            SkillSource::User => self.root.clone(),
+            SkillSource::Project => {
+                let dir = location.working_directory.as_deref().ok_or_else(|| {
+                    AgentError::InvalidSkill("Project skills need a working directory".to_string())
+                })?;
+                project_root(Path::new(dir))
+            }
+            SkillSource::Plugin => {
+                let plugin = location.plugin.as_deref().ok_or_else(|| {
+                    AgentError::InvalidSkill("Plugin skills need a plugin name".to_string())
+                })?;
+                self.plugin_roots()
+                    .await
+                    .into_iter()
+                    .find(|(name, _)| name == plugin)
+                    .map(|(_, dir)| dir)
+                    .ok_or(AgentError::NotFound)?
+            }
+        };
+        let dir = skill_dir(&root, name)?;
+        let content = read_skill(&dir).await?;
+        let (frontmatter, markdown) = split_skill(&content, name)?;
+        Ok(SkillDetail {
+            info: skill_info(
+                &dir,
+                frontmatter,
+                location.source,
+                location.plugin.as_deref(),
+            ),
+            markdown,
+            path: dir.to_string_lossy().to_string(),
+        })
+    }
+
+    /// The skill an `AgentConfig::skills` entry names: `plugin:name` for a plugin's
+    /// skill, otherwise the first of the project's (under `working_directory`), the
+    /// user's and the plugins' skills with directory `name`.
+    pub async fn resolve(
+        &self,
+        name: &str,
+        working_directory: Option<&Path>,
+    ) -> Result<SkillDetail, AgentError> {
+        let mut locations = Vec::new();
+        if let Some((plugin, skill)) = name.split_once(':') {
+            locations.push((
+                skill,
+                SkillLocation {
+                    source: SkillSource::Plugin,
+                    plugin: Some(plugin.to_string()),
+                    working_directory: None,
+                },
+            ));
+        } else {
+            if let Some(dir) = working_directory {
+                locations.push((
+                    name,
+                    SkillLocation {
+                        source: SkillSource::Project,
+                        plugin: None,
+                        working_directory: Some(dir.to_string_lossy().to_string()),
+                    },
+                ));
+            }
+            locations.push((name, SkillLocation::default()));
+            for (plugin, _) in self.plugin_roots().await {
+                locations.push((
+                    name,
+                    SkillLocation {
+                        source: SkillSource::Plugin,
+                        plugin: Some(plugin),
+                        working_directory: None,
+                    },
+                ));
+            }
+        }
+
+        for (skill, location) in &locations {
+            match self.get_at(skill, location).await {
+                Err(AgentError::NotFound) => continue,
+                found => return found,
+            }
+        }
+        Err(AgentError::SkillNotFound(name.to_string()))
+    }
+
+    pub async fn create(&self, skill: NewSkill) -> Result<SkillDetail, AgentError> {
+        validate_name(&skill.name)?;
+        validate_description(&skill.description)?;
+        let dir = skill_dir(&self.root, &skill.name)?;
+        if fs::try_exists(&dir).await.unwrap_or(false) {
+            return Err(AgentError::SkillExists(skill.name));
+        }
+        fs::create_dir_all(&dir)
+            .await
+            .map_err(|e| storage_error("Failed to create skill directory", e))?;
+        let content = render_skill(
+            Mapping::new(),
+            &skill.name,
+            &skill.description,
+            &skill.markdown,
+        )?;
+        write_skill(&dir, &content).await?;
+        self.get(&skill.name).await
+    }
+
+    pub async fn update(&self, name: &str, update: SkillUpdate) -> Result<SkillDetail, AgentError> {
+        validate_description(&update.description)?;
+        let dir = skill_dir(&self.root, name)?;
+        let content = read_skill(&dir).await?;
+        let (frontmatter, _) = split_skill(&content, name)?;
+        let content = render_skill(frontmatter, name, &update.description, &update.markdown)?;
+        write_skill(&dir, &content).await?;
+        self.get(name).await
+    }
+
+    /// Moves skill `name` to `new_name`, updating the `name` in its frontmatter.
+    pub async fn rename(&self, name: &str, new_name: &str) -> Result<SkillDetail, AgentError> {
+        validate_name(new_name)?;
+        let dir = skill_dir(&self.root, name)?;
+        let content = read_skill(&dir).await?;
+        let new_dir = skill_dir(&self.root, new_name)?;
+        if new_name != name && fs::try_exists(&new_dir).await.unwrap_or(false) {
+            return Err(AgentError::SkillExists(new_name.to_string()));
+        }
+        let (frontmatter, markdown) = split_skill(&content, name)?;
+        let description = frontmatter_str(&frontmatter, "description").unwrap_or_default();
+        let content = render_skill(frontmatter, new_name, &description, &markdown)?;
+        fs::rename(&dir, &new_dir)
+            .await
+            .map_err(|e| storage_error("Failed to rename skill directory", e))?;
+        write_skill(&new_dir, &content).await?;
+        self.get(new_name).await
+    }
+
+    /// Deletes skill `name`'s directory, including any files beside `SKILL.md`.
+    pub async fn delete(&self, name: &str) -> Result<(), AgentError> {
+        let dir = skill_dir(&self.root, name)?;
+        read_skill(&dir).await?;
+        fs::remove_dir_all(&dir)
+            .await
+            .map_err(|e| storage_error("Failed to delete skill", e))
+    }
+
+    /// Each installed plugin's name and skills directory. Plugins are keyed
+    /// `name@marketplace`, with one install record or a list of them (one per
+    /// scope); a missing or unreadable list means no plugin skills.
+    pub(crate) async fn plugin_roots(&self) -> Vec<(String, PathBuf)> {
+        let Some(plugins_dir) = &self.plugins_dir else {
+            return Vec::new();
+        };
+        let Ok(content) = fs::read_to_string(plugins_dir.join(INSTALLED_PLUGINS_FILE)).await else {
+            return Vec::new();
+        };
+        let Ok(installed) = serde_json::from_str::<serde_json::Value>(&content) else {
+            eprintln!("[Skills] Ignoring unreadable {}", INSTALLED_PLUGINS_FILE);
+            return Vec::new();
+        };
+
+        let mut roots = Vec::new();
+        let Some(plugins) = installed.get("plugins").and_then(|p| p.as_object()) else {
+            return roots;
+        };
+        for (key, records) in plugins {
+            let name = key.split('@').next().unwrap_or(key).to_string();
+            let records = match records {
+                serde_json::Value::Array(records) => records.iter().collect(),
+                record => vec![record],
+            };
+            for record in records {
+                if let Some(path) = record.get("installPath").and_then(|p| p.as_str()) {
+                    let dir = PathBuf::from(path).join("skills");
+                    if !roots.iter().any(|(_, d)| *d == dir) {
+                        roots.push((name.clone(), dir));
+                    }
+                }
+            }
+        }
+        roots
+    }
+}
+
+/// Whether a CLI that finds skills itself (see `Capabilities::skills`) will find
+/// `skill` when run in `working_directory`. A project skill may be missing there,
+/// e.g. when the run works in a worktree and the skill isn't committed.
+pub(crate) async fn found_by_cli(skill: &SkillDetail, working_directory: Option<&Path>) -> bool {
+    if skill.info.source != SkillSource::Project {
+        return true;
+    }
+    let (Some(dir), Some(name)) = (working_directory, Path::new(&skill.path).file_name()) else {
+        return false;
+    };
+    fs::try_exists(project_root(dir).join(name).join(SKILL_FILE))
+        .await
+        .unwrap_or(false)
+}
+
+/// `skills`' instructions, for CLIs that don't read `SKILL.md` themselves.
+pub(crate) fn instructions(skills: &[&SkillDetail]) -> String {
+    let mut text = String::from(
+        "# Skills\n\nUse the following skills when the task calls for them, following their instructions.\n",
+    );
+    for skill in skills {
+        text.push_str(&format!(
+            "\n## {}\n\n{}\n\n{}\n",
+            skill.info.name,
+            skill.info.description.trim(),
+            skill.markdown.trim()
+        ));
+    }
+    text
+}
+
+pub(crate) fn project_root(working_directory: &Path) -> PathBuf {
+    working_directory.join(".claude").join("skills")
+}
+
+/// `name`'s directory under `root`. Only a single plain path component is
+/// accepted (no separators, `.` or `..`, leading dot, surrounding spaces or
+/// control characters), so no name can point outside `root` or at a hidden entry.
+pub(crate) fn skill_dir(root: &Path, name: &str) -> Result<PathBuf, AgentError> {
+    let plain = !name.is_empty()
+        && name.trim() == name
+        && !name.starts_with('.')
+        && !name.contains(|c: char| c.is_control() || matches!(c, '/' | '\\' | ':'))
+        && matches!(
+            Path::new(name).components().collect::<Vec<_>>()[..],
+            [Component::Normal(component)] if component == name
+        );
+    if !plain {
+        return Err(AgentError::InvalidSkill(format!(
+            "Invalid skill name '{}'",
+            name.escape_debug()
+        )));
+    }
+    Ok(root.join(name))
+}
+
+/// Every directory under `root` with a readable `SKILL.md`. Skills whose
+/// frontmatter isn't valid YAML are left out.
+async fn scan_skills(
+    root: &Path,
+    source: SkillSource,
+    plugin: Option<&str>,
+) -> Result<Vec<SkillInfo>, AgentError> {
+    Ok(read_skills(root, source, plugin)
+        .await?
+        .into_iter()
+        .map(|(_, info, _)| info)
+        .collect())
+}
+
+/// Every valid skill directly under `root`: its directory name, the skill and its
+/// `SKILL.md`. Hidden directories (such as an import being staged) are not skills.
+pub(crate) async fn read_skills(
+    root: &Path,
+    source: SkillSource,
+    plugin: Option<&str>,
+) -> Result<Vec<(String, SkillInfo, String)>, AgentError> {
+    let mut entries = match fs::read_dir(root).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(storage_error("Failed to read skills directory", e)),
+    };
+
+    let mut skills = Vec::new();
+    while let Some(entry) = entries
+        .next_entry()
+        .await
+        .map_err(|e| storage_error("Failed to read directory entry", e))?
+    {
+        let path = entry.path();
+        let metadata = entry
+            .metadata()
+            .await
+            .map_err(|e| storage_error("Failed to read entry metadata", e))?;
+        let dir_name = entry.file_name().to_string_lossy().to_string();
+        if !metadata.is_dir() || dir_name.starts_with('.') {
+            continue;
+        }
+        let Ok(content) = fs::read_to_string(path.join(SKILL_FILE)).await else {
+            continue;
+        };
+        match split_skill(&content, &dir_name) {
+            Ok((frontmatter, _)) => {
+                let info = skill_info(&path, frontmatter, source, plugin);
+                skills.push((dir_name, info, content))
+            }
+            Err(e) => eprintln!("[Skills] Skipping {}: {}", path.display(), e),
+        }
+    }
+    Ok(skills)
+}
+
+pub(crate) fn storage_error(context: &str, e: std::io::Error) -> AgentError {
+    AgentError::StorageError(format!("{}: {}", context, e))
+}
+
+async fn read_skill(dir: &Path) -> Result<String, AgentError> {
+    match fs::read_to_string(dir.join(SKILL_FILE)).await {
+        Ok(content) => Ok(content),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(AgentError::NotFound),
+        Err(e) => Err(storage_error("Failed to read skill file", e)),
+    }
+}
+
+/// Replaces `SKILL.md` atomically.
+async fn write_skill(dir: &Path, content: &str) -> Result<(), AgentError> {
+    let path = dir.join(SKILL_FILE);
+    let tmp = dir.join(format!("{}.tmp", SKILL_FILE));
+    fs::write(&tmp, content)
+        .await
+        .map_err(|e| storage_error("Failed to write skill file", e))?;
+    fs::rename(&tmp, &path)
+        .await
+        .map_err(|e| storage_error("Failed to write skill file", e))
+}
+
+/// Claude's rule for skill names: lowercase letters, digits and hyphens, at most 64.
+pub(crate) fn validate_name(name: &str) -> Result<(), AgentError> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_NAME_LEN
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+    if valid {
+        Ok(())
+    } else {
+        Err(AgentError::InvalidSkill(format!(
+            "Invalid skill name '{}': use lowercase letters, digits and '-' (at most {})",
+            name, MAX_NAME_LEN
+        )))
+    }
+}
+
+fn validate_description(description: &str) -> Result<(), AgentError> {
+    if description.trim().is_empty() {
+        return Err(AgentError::InvalidSkill(
+            "The skill needs a description".to_string(),
+        ));
+    }
+    if description.len() > MAX_DESCRIPTION_LEN {
+        return Err(AgentError::InvalidSkill(format!(
+            "The description is longer than {} characters",
+            MAX_DESCRIPTION_LEN
+        )));
+    }
+    Ok(())
+}
+
+/// The frontmatter (empty if there is none) and the markdown after it. A block
+/// that is never closed, isn't valid YAML or isn't a mapping is an error.
+pub(crate) fn split_skill(content: &str, name: &str) -> Result<(Mapping, String), AgentError> {
+    let lines: Vec<&str> = content.lines().collect();
+    if lines.first().map(|line| line.trim_end()) != Some("---") {
+        return Ok((Mapping::new(), content.to_string()));
+    }
+    let end = lines
+        .iter()
+        .skip(1)
+        .position(|line| line.trim_end() == "---")
+        .ok_or_else(|| {
+            AgentError::InvalidSkill(format!("{} of '{}' has no closing '---'", SKILL_FILE, name))
+        })?
+        + 1;
+
+    let yaml = lines[1..end].join("\n");
+    let frontmatter = if yaml.trim().is_empty() {
+        Mapping::new()
+    } else {
+        let invalid = |reason: String| {
+            AgentError::InvalidSkill(format!(
+                "Invalid frontmatter in {} of '{}': {}",
+                SKILL_FILE, name, reason
+            ))
+        };
+        match serde_yaml::from_str(&yaml).map_err(|e| invalid(e.to_string()))? {
+            YamlValue::Mapping(mapping) => mapping,
+            YamlValue::Null => Mapping::new(),
+            _ => return Err(invalid("expected key: value pairs".to_string())),
+        }
+    };
+    Ok((frontmatter, lines[end + 1..].join("\n").trim().to_string()))
+}
+
+fn skill_info(
+    dir: &Path,
+    frontmatter: Mapping,
+    source: SkillSource,
+    plugin: Option<&str>,
+) -> SkillInfo {
+    let dir_name = dir
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let description = if frontmatter.is_empty() {
+        format!("Custom skill: {}", dir_name)
+    } else {
+        frontmatter_str(&frontmatter, "description").unwrap_or_default()
+    };
+    let id = match plugin {
+        Some(plugin) => format!("{}:{}", plugin, dir_name),
+        None => dir_name.clone(),
+    };
+    let name = frontmatter_str(&frontmatter, "name").unwrap_or(dir_name);
+    let allowed_tools = frontmatter.get("allowed-tools").map(tool_list);
+
+    let metadata = frontmatter
+        .into_iter()
+        .filter_map(|(key, value)| {
+            let key = key.as_str()?.to_string();
+            if matches!(key.as_str(), "name" | "description" | "allowed-tools") {
+                return None;
+            }
+            Some((key, serde_json::to_value(value).unwrap_or_default()))
+        })
+        .collect();
+
+    SkillInfo {
+        id,
+        name,
+        description,
+        source,
+        plugin: plugin.map(str::to_string),
+        allowed_tools,
+        metadata,
+    }
+}
+
+/// A scalar frontmatter value as text; `None` if missing or not a scalar.
+pub(crate) fn frontmatter_str(frontmatter: &Mapping, key: &str) -> Option<String> {
+    match frontmatter.get(key)? {
+        YamlValue::String(s) => Some(s.clone()),
+        YamlValue::Number(n) => Some(n.to_string()),
+        YamlValue::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/// `allowed-tools` as a list, whether written as one (`[Read, Grep]`) or as a
+/// string (`Read, Bash(git log:*)`). Commas inside parentheses don't split.
+fn tool_list(value: &YamlValue) -> Vec<String> {
+    match value {
+        YamlValue::Sequence(items) => items
+            .iter()
+            .filter_map(|item| item.as_str())
+            .map(|tool| tool.trim().to_string())
+            .filter(|tool| !tool.is_empty())
+            .collect(),
+        YamlValue::String(s) => {
+            let mut tools = Vec::new();
+            let mut depth = 0usize;
+            let mut start = 0;
+            for (i, c) in s.char_indices() {
+                match c {
+                    '(' => depth += 1,
+                    ')' => depth = depth.saturating_sub(1),
+                    ',' if depth == 0 => {
+                        tools.push(&s[start..i]);
+                        start = i + 1;
+                    }
+                    _ => {}
+                }
+            }
+            tools.push(&s[start..]);
+            tools
+                .into_iter()
+                .map(str::trim)
+                .filter(|tool| !tool.is_empty())
+                .map(str::to_string)
+                .collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// `SKILL.md` with `name` and `description` set in `frontmatter`; other keys keep
+/// their order and values.
+pub(crate) fn render_skill(
+    mut frontmatter: Mapping,
+    name: &str,
+    description: &str,
+    markdown: &str,
+) -> Result<String, AgentError> {
+    frontmatter.insert("name".into(), name.into());
+    frontmatter.insert("description".into(), description.trim().into());
+    let yaml = serde_yaml::to_string(&frontmatter)
+        .map_err(|e| AgentError::InvalidSkill(format!("Failed to write frontmatter: {}", e)))?;
+
+    let mut content = format!("---\n{}---\n\n", yaml);
+    let markdown = markdown.trim();
+    if !markdown.is_empty() {
+        content.push_str(markdown);
+        content.push('\n');
+    }
+    Ok(content)
+}
@@ -0,0 +1,441 @@
+use serde::{Deserialize, Serialize};
+
+use crate::types::*;
+
+/// What a line of CLI stderr is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StderrClass {
+    /// Progress output, debug logs and anything else that isn't a problem.
+    Diagnostic,
+    /// Warnings and deprecation notices; the run carries on.
+    Warning,
+    /// Missing, invalid or expired credentials.
+    AuthFailure,
+    /// Too many requests or an overloaded API; worth retrying later.
+    RateLimit,
+    /// Usage or billing limit reached; retrying won't help until it resets.
+    QuotaExhausted,
+    /// Connection failures and gateway errors between the CLI and its API.
+    Network,
+    /// Any other error.
+    Fatal,
+}
+
+/// One classified stderr line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StderrLine {
+    pub class: StderrClass,
+    /// The line without ANSI styling or progress-bar redraws.
+    pub message: String,
+    /// Seconds to wait before retrying, if a rate-limit message said so.
+    pub retry_after_secs: Option<u64>,
+    /// When an exhausted quota resets (Unix seconds), if the message said so.
+    pub resets_at: Option<u64>,
+}
+
+impl StderrLine {
+    /// The event reporting this line to clients.
+    pub fn into_event(self, agent_id: &AgentId) -> AgentEvent {
+        let agent_id = agent_id.clone();
+        match self.class {
+            StderrClass::Diagnostic => AgentEvent::Log {
+                agent_id,
+                level: LogLevel::Info,
+                message: self.message,
+            },
+            StderrClass::Warning => AgentEvent::Log {
+                agent_id,
+                level: LogLevel::Warning,
+                message: self.message,
+            },
+            StderrClass::AuthFailure => AgentEvent::AuthFailed {
+                agent_id,
+                message: self.message,
+            },
+            StderrClass::RateLimit => AgentEvent::RateLimited {
+                agent_id,
+                message: self.message,
+                retry_after_secs: self.retry_after_secs,
+            },
+            StderrClass::QuotaExhausted => AgentEvent::QuotaExhausted {
+                agent_id,
+                message: self.message,
+                resets_at: self.resets_at,
+            },
+            StderrClass::Network | StderrClass::Fatal => AgentEvent::Error {
+                agent_id,
+                message: format!("CLI: {}", self.message),
+            },
+        }
+    }
+
+    /// The typed error for a recognized problem; `None` for diagnostics and warnings.
+    pub fn error(&self) -> Option<AgentError> {
+        match self.class {
+            StderrClass::Diagnostic | StderrClass::Warning => None,
+            StderrClass::AuthFailure => {
+                Some(AgentError::AuthenticationFailed(self.message.clone()))
+            }
+            StderrClass::RateLimit => Some(AgentError::RateLimited {
+                message: self.message.clone(),
+                retry_after_secs: self.retry_after_secs,
+            }),
+            StderrClass::QuotaExhausted => Some(AgentError::QuotaExhausted {
+                message: self.message.clone(),
+                resets_at: self.resets_at,
+            }),
+            StderrClass::Network | StderrClass::Fatal => {
+                Some(AgentError::ProcessError(self.message.clone()))
+            }
+        }
+    }
+}
+
+/// Classifies one line of stderr by the wording agent CLIs and their APIs use.
+/// Returns `None` for lines with nothing left to show once cleaned up.
+pub fn classify(line: &str) -> Option<StderrLine> {
+    // Progress bars redraw with `\r`; only the last frame matters.
+    let line = line.rsplit('\r').find(|frame| !frame.trim().is_empty())?;
+    let message = strip_ansi(line).trim().to_string();
+    if message.is_empty() {
+        return None;
+    }
+    let lower = message.to_lowercase();
+
+    let class = if lower.starts_with("warn") {
+        StderrClass::Warning
+    } else if is_auth_failure(&lower) {
+        StderrClass::AuthFailure
+    } else if is_quota_exhausted(&lower) {
+        StderrClass::QuotaExhausted
+    } else if is_rate_limit(&lower) {
+        StderrClass::RateLimit
+    } else if is_network_failure(&lower) {
+        StderrClass::Network
+    } else if is_fatal(&lower) {
+        StderrClass::Fatal
+    } else if lower.contains("warning") || lower.contains("deprecat") {
+        StderrClass::Warning
+    } else {
+        StderrClass::Diagnostic
+    };
+
+    Some(StderrLine {
+        retry_after_secs: (class == StderrClass::RateLimit)
+            .then(|| retry_after(&lower))
+            .flatten(),
+        resets_at: (class == StderrClass::QuotaExhausted)
+            .then(|| reset_time(&message))
+            .flatten(),
+        class,
+        message,
+    })
+}
+
+fn is_auth_failure(line: &str) -> bool {
+    has_status(line, 401)
+        || has_any_word(
+            line,
+            &[
+                "unauthorized",
+                "unauthenticated",
+                "authentication failed",
+                "authentication_error",
+                "invalid api key",
+                "invalid x-api-key",
+                "api key not found",
+                "missing api key",
+                "api key is missing",
+                "not logged in",
+                "please log in",
+                "please login",
+                "run /login",
+                "login required",
+                "token has expired",
+                "token expired",
+                "invalid credentials",
+            ],
+        )
+}
+
+fn is_quota_exhausted(line: &str) -> bool {
+    has_status(line, 402)
+        || has_any_word(
+            line,
+            &[
+                "insufficient_quota",
+                "exceeded your current quota",
+                "billing_hard_limit",
+                "billing_hard_limit_reached",
+                "usage limit reached",
+                "credit balance is too low",
+                "insufficient credits",
+                "out of credits",
+                "payment required",
+                "limit will reset",
+            ],
+        )
+}
+
+fn is_rate_limit(line: &str) -> bool {
+    has_status(line, 429)
+        || has_status(line, 529)
+        || has_any_word(
+            line,
+            &[
+                "rate limit",
+                "rate limited",
+                "rate-limited",
+                "rate_limit_error",
+                "rate_limit_exceeded",
+                "too many requests",
+                "overloaded",
+                "overloaded_error",
+                "resource_exhausted",
+            ],
+        )
+}
+
+fn is_network_failure(line: &str) -> bool {
+    [502, 503, 504].iter().any(|code| has_status(line, *code))
+        || has_any_word(
+            line,
+            &[
+                "econnreset",
+                "econnrefused",
+                "etimedout",
+                "enotfound",
+                "eai_again",
+                "socket hang up",
+                "connection reset",
+                "connection refused",
+                "connection closed",
+                "network error",
+                "fetch failed",
+                "getaddrinfo",
+                "bad gateway",
+                "service unavailable",
+                "gateway timeout",
+            ],
+        )
+}
+
+fn is_fatal(line: &str) -> bool {
+    // `TypeError: ...`, `java.lang.IllegalStateException: ...`
+    let raised = line.split_whitespace().any(|word| {
+        word.len() > "error:".len() && (word.ends_with("error:") || word.ends_with("exception:"))
+    });
+    raised
+        || [
+            "error",
+            "fatal",
+            "panic",
+            "panicked",
+            "traceback",
+            "exception",
+            "failed",
+            "enoent",
+            "command not found",
+            "segmentation fault",
+        ]
+        .iter()
+        .any(|needle| {
+            words(line, needle).any(|start| {
+                // "0 errors, 0 failed" is a tally, not a failure.
+                let before = line[..start].trim_end();
+                !before.ends_with(|c: char| c.is_ascii_digit())
+            })
+        })
+}
+
+/// Whether `line` reports HTTP status `code`: the number on its own, right
+/// after "status", "HTTP", "code" or "error:".
+fn has_status(line: &str, code: u16) -> bool {
+    let code = code.to_string();
+    let found = words(line, &code).any(|start| {
+        let before = line[..start]
+            .trim_end_matches(|c: char| c.is_whitespace() || matches!(c, '=' | '"' | '\'' | '('));
+        let (before, colon) = match before.strip_suffix(':') {
+            Some(before) => (before.trim_end_matches(['"', '\'']), true),
+            None => (before, false),
+        };
+        let word = before
+            .rsplit(|c: char| !(c.is_alphanumeric() || matches!(c, '_' | '/' | '.')))
+            .next()
+            .unwrap_or_default();
+        matches!(
+            word,
+            "status" | "status_code" | "statuscode" | "code" | "http"
+        ) || word.starts_with("http/")
+            || (colon && word == "error")
+    });
+    found
+}
+
+fn has_any_word(line: &str, phrases: &[&str]) -> bool {
+    phrases
+        .iter()
+        .any(|phrase| words(line, phrase).next().is_some())
+}
+
+/// Where `phrase` starts in `line` as whole words rather than inside longer
+/// ones: "error" matches "error: x" but not "errors" or "error_handling".
+fn words<'a>(line: &'a str, phrase: &'a str) -> impl Iterator<Item = usize> + 'a {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    line.match_indices(phrase)
+        .map(|(start, _)| start)
+        .filter(move |&start| {
+            !line[..start].chars().next_back().is_some_and(is_word)
+                && !line[start + phrase.len()..]
+                    .chars()
+                    .next()
+                    .is_some_and(is_word)
+        })
+}
+
+/// Parses "retry after 30s", "retry-after: 30" or "try again in 30 seconds".
+fn retry_after(line: &str) -> Option<u64> {
+    ["retry after", "retry-after", "try again in", "retry in"]
+        .iter()
+        .find_map(|marker| {
+            let rest = &line[line.find(marker)? + marker.len()..];
+            let digits: String = rest
+                .trim_start_matches(|c: char| c == ':' || c.is_whitespace())
+                .chars()
+                .take_while(|c| c.is_ascii_digit())
+                .collect();
+            let value: u64 = digits.parse().ok()?;
+            let unit = rest.trim_start_matches(|c: char| !c.is_ascii_alphabetic());
+            Some(if unit.starts_with("min") {
+                value * 60
+            } else {
+                value
+            })
+        })
+}
+
+/// Claude reports an exhausted plan as `Claude AI usage limit reached|<unix seconds>`.
+fn reset_time(line: &str) -> Option<u64> {
+    let (_, tail) = line.rsplit_once('|')?;
+    tail.trim().parse().ok()
+}
+
+/// Removes ANSI escape sequences (colours, cursor movement) that some CLIs print
+/// even when their output isn't a terminal.
+pub(crate) fn strip_ansi(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\u{1b}' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            // CSI: ESC [ params... final byte in '@'..='~'
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // OSC: ESC ] ... terminated by BEL or ESC \
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\u{7}' {
+                        break;
+                    }
+                    if c == '\u{1b}' && chars.peek() == Some(&'\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_cli_stderr() {
+        use StderrClass::*;
+
+        let cases = [
+            ("Invalid API key · Please run /login", AuthFailure),
+            (
+                r#"API Error: 401 {"type":"error","error":{"type":"authentication_error","message":"invalid x-api-key"}}"#,
+                AuthFailure,
+            ),
+            ("Error: Not logged in. Run `cursor-agent login` first.", AuthFailure),
+            ("Claude AI usage limit reached|1760000000", QuotaExhausted),
+            (
+                r#"Error: 429 You exceeded your current quota, please check your plan and billing details. {"code":"insufficient_quota"}"#,
+                QuotaExhausted,
+            ),
+            (r#"{"error":{"code":"billing_hard_limit_reached"}}"#, QuotaExhausted),
+            (
+                r#"API Error: 529 {"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#,
+                RateLimit,
+            ),
+            (
+                "[API Error: got status: 429 Too Many Requests. Please retry after 30s]",
+                RateLimit,
+            ),
+            ("Error: HTTP 503 Service Unavailable", Network),
+            (
+                "FetchError: request to https://api.anthropic.com/v1/messages failed, reason: getaddrinfo ENOTFOUND api.anthropic.com",
+                Network,
+            ),
+            ("error: unexpected argument '--foo' found", Fatal),
+            ("TypeError: Cannot read properties of undefined (reading 'map')", Fatal),
+            ("thread 'main' panicked at src/main.rs:10:5:", Fatal),
+            ("fatal: not a git repository (or any of the parent directories): .git", Fatal),
+            (
+                "(node:4242) [DEP0040] DeprecationWarning: The `punycode` module is deprecated.",
+                Warning,
+            ),
+            ("WARN: falling back to the default model", Warning),
+            // Near misses that used to be misread.
+            ("Tests: 12 passed, 0 errors, 0 failed", Diagnostic),
+            ("Processed 429 files in 401ms", Diagnostic),
+            ("Reading quota settings from ~/.config/tool/quota.json", Diagnostic),
+            ("Loaded billing module", Diagnostic),
+            ("Compiling error_handling v0.1.0", Diagnostic),
+            ("request_id=req_01HXYZ4015 status=ok", Diagnostic),
+            ("Checked 3 files, found no errors", Diagnostic),
+            ("Opening src/unauthorized_page.tsx", Diagnostic),
+        ];
+        for (line, class) in cases {
+            assert_eq!(
+                classify(line).map(|line| line.class),
+                Some(class),
+                "{}",
+                line
+            );
+        }
+    }
+
+    #[test]
+    fn reads_retry_and_reset_times() {
+        let line = classify("Rate limited: retry after 30s").unwrap();
+        assert_eq!(line.retry_after_secs, Some(30));
+        let line = classify("Too many requests, try again in 2 minutes").unwrap();
+        assert_eq!(line.retry_after_secs, Some(120));
+        let line = classify("Claude AI usage limit reached|1760000000").unwrap();
+        assert_eq!(line.resets_at, Some(1_760_000_000));
+    }
+
+    #[test]
+    fn cleans_up_progress_and_colour() {
+        let line = classify("\u{1b}[31mError:\u{1b}[0m boom\r").unwrap();
+        assert_eq!(line.message, "Error: boom");
+        assert_eq!(line.class, StderrClass::Fatal);
+        assert!(classify("\u{1b}[2K\r   ").is_none());
+    }
+}
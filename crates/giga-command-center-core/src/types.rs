@@ -1,5 +1,16 @@
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
+use crate::permissions::{PermissionBehavior, PermissionMode};
+use crate::retry::{default_fallback_on, BackendChoice, FailureClass, RetryPolicy};
+use crate::sandbox::SandboxProfile;
+use crate::skills::SkillInfo;
+use crate::usage::TokenUsage;
+use crate::workflow::{NodeStatus, WorkflowId, WorkflowStatus};
+use crate::workspaces::Workspace;
+use crate::worktree::{Isolation, WorktreeCleanup, WorktreeInfo};
+
 pub type AgentId = String;
 pub type WorkspaceId = String;
 
@@ -19,6 +30,24 @@ pub enum CliType {
     Gemini,
     Grok,
     DeepSeek,
+    /// Any other backend registered with `CliRegistry`, by id.
+    #[serde(untagged)]
+    Other(String),
+}
+
+impl CliType {
+    /// The id of the backend that runs this CLI.
+    pub fn as_str(&self) -> &str {
+        match self {
+            CliType::Claude => "claude",
+            CliType::Cursor => "cursor",
+            CliType::Kilo => "kilo",
+            CliType::Gemini => "gemini",
+            CliType::Grok => "grok",
+            CliType::DeepSeek => "deepseek",
+            CliType::Other(id) => id,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -26,7 +55,8 @@ pub enum CliType {
 pub struct AgentConfig {
     pub workspace_id: WorkspaceId,
     pub prompt: String,
-    /// Which CLI to use: `claude` (default), `cursor`, `kilo`, `gemini`, `grok`, or `deepseek`.
+    /// Which CLI to use: `claude` (default), `cursor`, `kilo`, `gemini`, `grok`, `deepseek`,
+    /// or the id of a custom registered backend.
     #[serde(default)]
     pub cli: Option<CliType>,
     /// Cursor-only: `agent` (default), `plan`, or `ask`. Ignored for Claude and Kilo.
@@ -40,14 +70,127 @@ pub struct AgentConfig {
     pub system_prompt: Option<String>,
     #[serde(default)]
     pub model: Option<String>,
+    /// CLI session to resume instead of starting a new conversation (Claude and Cursor only).
+    #[serde(default)]
+    pub session_id: Option<String>,
+    /// The earlier run this one continues, set by `AgentManager::continue_session`.
+    #[serde(default)]
+    pub parent_agent_id: Option<AgentId>,
+    /// Keep stdin open so `AgentManager::send_input` can steer the run (Claude only).
+    /// The CLI keeps running between turns until its input is closed or it is stopped.
+    #[serde(default)]
+    pub interactive: bool,
+    /// Kill the run after this many seconds of wall-clock time (counted from spawn).
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+    /// Cap on agentic turns, passed to CLIs that support it (Claude `--max-turns`).
+    #[serde(default)]
+    pub max_turns: Option<u32>,
+    /// Kill the run if its CLI prints nothing on stdout for this many seconds.
+    /// In interactive runs this includes time spent waiting for the next input.
+    #[serde(default)]
+    pub idle_timeout_secs: Option<u64>,
+    /// Retry the run when it fails in one of the policy's failure classes.
+    #[serde(default)]
+    pub retry: Option<RetryPolicy>,
+    /// Backends to try, in order, when `cli` can't be spawned or the run fails in one
+    /// of the `fallback_on` classes (after any retries). Each starts a fresh session.
+    #[serde(default)]
+    pub fallbacks: Vec<BackendChoice>,
+    #[serde(default = "default_fallback_on")]
+    pub fallback_on: Vec<FailureClass>,
+    /// Extra environment variables for the CLI, e.g. a workspace's own `ANTHROPIC_API_KEY`.
+    /// Values may reference vault secrets as `${secret:NAME}`; only the reference is
+    /// ever stored or reported.
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Start the CLI without the server's environment, keeping only the basics
+    /// (`PATH`, `HOME`, locale, temp dirs) and the variables named in `env_allow`.
+    #[serde(default)]
+    pub env_clear: bool,
+    /// Server variables passed through when `env_clear` is set. A trailing `*`
+    /// matches a prefix, e.g. `"HTTPS_PROXY"` or `"AWS_*"`.
+    #[serde(default)]
+    pub env_allow: Vec<String>,
+    /// `worktree` runs the CLI in a fresh git worktree and branch of `working_directory`'s
+    /// repository, so runs sharing a directory don't overwrite each other's edits.
+    #[serde(default)]
+    pub isolation: Isolation,
+    /// What happens to the worktree once the run ends.
+    #[serde(default)]
+    pub worktree_cleanup: WorktreeCleanup,
+    /// Runs the CLI in a Linux sandbox (usually the workspace's profile).
+    #[serde(default)]
+    pub sandbox: Option<SandboxProfile>,
+    /// Which tool uses the CLI asks about first. Gated modes emit a `PermissionRequest`
+    /// per question and wait for `AgentManager::respond_permission`.
+    #[serde(default)]
+    pub permission_mode: PermissionMode,
+    /// How long a permission request waits for an answer (default 5 minutes).
+    #[serde(default)]
+    pub approval_timeout_secs: Option<u64>,
+    /// The answer a permission request gets when nobody answers in time.
+    #[serde(default)]
+    pub approval_default: PermissionBehavior,
+    /// Skills for the run, by directory name, or `plugin:name` for a plugin's skill.
+    /// Bare names are looked up in the project's `.claude/skills` first, then the
+    /// user's, then the installed plugins'. CLIs that don't find skills themselves
+    /// get their instructions in the system prompt (or the prompt).
+    #[serde(default)]
+    pub skills: Vec<String>,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum AgentEvent {
+    /// The run is waiting for its workspace or a global slot. `position` is 1-based
+    /// and re-announced whenever runs ahead of it leave the queue.
+    Queued {
+        agent_id: AgentId,
+        workspace_id: WorkspaceId,
+        position: usize,
+    },
+    /// The run left the queue and is about to spawn.
+    Dequeued {
+        agent_id: AgentId,
+    },
     Started {
         agent_id: AgentId,
         workspace_id: WorkspaceId,
+        #[serde(default)]
+        parent_agent_id: Option<AgentId>,
+        /// The backend the run spawned on, which differs from `AgentConfig::cli`
+        /// if it had to fall back.
+        #[serde(default)]
+        cli: Option<CliType>,
+        #[serde(default)]
+        model: Option<String>,
+        /// The git worktree and branch the run works in, under `Isolation::Worktree`.
+        #[serde(default)]
+        worktree: Option<Box<WorktreeInfo>>,
+        /// The sandbox the CLI was spawned in, with the paths and hosts it allows.
+        #[serde(default)]
+        sandbox: Option<SandboxProfile>,
+        /// The skills `AgentConfig::skills` resolved to.
+        #[serde(default)]
+        skills: Vec<SkillInfo>,
+    },
+    /// A run's worktree was finished, merged back or discarded.
+    WorktreeChanged {
+        agent_id: AgentId,
+        worktree: Box<WorktreeInfo>,
+    },
+    /// The run moved on from `from` to the next backend in `AgentConfig::fallbacks`.
+    FallingBack {
+        agent_id: AgentId,
+        from: CliType,
+        to: CliType,
+        #[serde(default)]
+        model: Option<String>,
+        reason: FailureClass,
+        /// Why `from` couldn't be spawned, for `Unavailable`.
+        #[serde(default)]
+        message: Option<String>,
     },
     Init {
         agent_id: AgentId,
@@ -58,6 +201,11 @@ pub enum AgentEvent {
         agent_id: AgentId,
         content: String,
     },
+    /// A user message sent to a running interactive agent.
+    Input {
+        agent_id: AgentId,
+        content: String,
+    },
     ToolUse {
         agent_id: AgentId,
         tool_name: String,
@@ -68,10 +216,80 @@ pub enum AgentEvent {
         tool_name: String,
         success: bool,
     },
+    /// The CLI is waiting to hear whether it may use a tool. Answer with
+    /// `AgentManager::respond_permission` within `timeout_secs`.
+    PermissionRequest {
+        agent_id: AgentId,
+        request_id: String,
+        tool_name: String,
+        input: serde_json::Value,
+        timeout_secs: u64,
+    },
+    /// A permission request was answered, or got the run's default after timing out.
+    PermissionResolved {
+        agent_id: AgentId,
+        request_id: String,
+        behavior: PermissionBehavior,
+        #[serde(default)]
+        timed_out: bool,
+    },
+    /// Token usage reported since the previous `Usage` event, with running totals for the run.
+    Usage {
+        agent_id: AgentId,
+        usage: TokenUsage,
+        total: TokenUsage,
+        /// `None` if the CLI reported no cost and the model has no known price.
+        total_cost_usd: Option<f64>,
+        /// `true` while the cost is estimated from the pricing table rather than reported.
+        estimated: bool,
+    },
+    /// Emitted exactly once per run, just before `Stopped`.
     Result {
         agent_id: AgentId,
         success: bool,
+        /// The CLI's own duration when it reported one, otherwise `wall_clock_ms`.
         duration_ms: u64,
+        /// Time from spawn to exit, as measured by the manager.
+        #[serde(default)]
+        wall_clock_ms: u64,
+        /// Time spent waiting on the model API, if the CLI reported it.
+        #[serde(default)]
+        api_duration_ms: Option<u64>,
+    },
+    /// An attempt failed and the run will try again after `delay_ms`.
+    /// `attempt` is the number of the attempt about to start (2 for the first retry).
+    Retrying {
+        agent_id: AgentId,
+        attempt: u32,
+        max_attempts: u32,
+        delay_ms: u64,
+        reason: FailureClass,
+    },
+    /// CLI stderr that isn't an error: progress, debug output and warnings.
+    Log {
+        agent_id: AgentId,
+        level: LogLevel,
+        message: String,
+    },
+    /// The CLI rejected its credentials; the user needs to log in again.
+    AuthFailed {
+        agent_id: AgentId,
+        message: String,
+    },
+    /// The model API is rate limiting or overloaded.
+    RateLimited {
+        agent_id: AgentId,
+        message: String,
+        #[serde(default)]
+        retry_after_secs: Option<u64>,
+    },
+    /// A usage, credit or billing limit was reached.
+    QuotaExhausted {
+        agent_id: AgentId,
+        message: String,
+        /// When the limit resets (Unix seconds), if the CLI said.
+        #[serde(default)]
+        resets_at: Option<u64>,
     },
     Error {
         agent_id: AgentId,
@@ -80,7 +298,80 @@ pub enum AgentEvent {
     Stopped {
         agent_id: AgentId,
         reason: StopReason,
+        /// How the process ended; `None` if it never spawned (e.g. cancelled while queued).
+        #[serde(default)]
+        exit: Option<ProcessExit>,
+    },
+    /// A workflow node's inputs all succeeded and its run `agent_id` started (or queued).
+    NodeStarted {
+        workflow_id: WorkflowId,
+        node_id: String,
+        agent_id: AgentId,
+    },
+    /// A workflow node is done: its run ended, or it never started.
+    NodeFinished {
+        workflow_id: WorkflowId,
+        node_id: String,
+        #[serde(default)]
+        agent_id: Option<AgentId>,
+        status: NodeStatus,
+        /// Why the node's run couldn't be started.
+        #[serde(default)]
+        error: Option<String>,
     },
+    /// Every node of the workflow is done.
+    WorkflowFinished {
+        workflow_id: WorkflowId,
+        status: WorkflowStatus,
+    },
+    /// A stored workspace was created or changed (including a connection added
+    /// or removed at its other end).
+    WorkspaceChanged {
+        workspace: Box<Workspace>,
+    },
+    WorkspaceDeleted {
+        workspace_id: WorkspaceId,
+    },
+    /// Skills were added, modified or removed on disk, by this app or anything
+    /// else. Sent once per skills directory and burst of changes.
+    SkillsChanged {
+        /// The project whose `.claude/skills` changed; `None` for user and plugin skills.
+        #[serde(default)]
+        working_directory: Option<String>,
+        #[serde(default)]
+        added: Vec<SkillInfo>,
+        #[serde(default)]
+        modified: Vec<SkillInfo>,
+        #[serde(default)]
+        removed: Vec<SkillInfo>,
+    },
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProcessExit {
+    /// Exit code, if the process exited on its own.
+    pub code: Option<i32>,
+    /// Terminating signal (Unix only), if the process was killed by one.
+    pub signal: Option<i32>,
+    /// `true` if the process group had to be SIGKILLed after the stop grace period.
+    pub forced: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Info,
+    Warning,
+}
+
+/// A run waiting to spawn, as returned by `AgentManager::list_queue`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueueEntry {
+    pub agent_id: AgentId,
+    pub workspace_id: WorkspaceId,
+    pub position: usize,
+    pub queued_at: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -89,6 +380,10 @@ pub enum StopReason {
     Completed,
     Cancelled,
     Error,
+    /// `timeout_secs` elapsed.
+    Timeout,
+    /// No output for `idle_timeout_secs`.
+    Stalled,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -99,6 +394,24 @@ pub enum AgentError {
     NotFound,
     AlreadyRunning,
     CliNotAvailable,
+    StorageError(String),
+    NotSupported(String),
+    AuthenticationFailed(String),
+    RateLimited {
+        message: String,
+        retry_after_secs: Option<u64>,
+    },
+    QuotaExhausted {
+        message: String,
+        resets_at: Option<u64>,
+    },
+    SecretNotFound(String),
+    Git(String),
+    InvalidWorkflow(String),
+    InvalidWorkspace(String),
+    InvalidSkill(String),
+    SkillExists(String),
+    SkillNotFound(String),
 }
 
 impl std::fmt::Display for AgentError {
@@ -109,6 +422,42 @@ impl std::fmt::Display for AgentError {
             AgentError::NotFound => write!(f, "Agent not found"),
             AgentError::AlreadyRunning => write!(f, "Agent already running"),
             AgentError::CliNotAvailable => write!(f, "Claude CLI not available"),
+            AgentError::StorageError(msg) => write!(f, "Storage error: {}", msg),
+            AgentError::NotSupported(msg) => write!(f, "Not supported: {}", msg),
+            AgentError::AuthenticationFailed(msg) => write!(
+                f,
+                "Authentication failed: {} (log in to the CLI or check its API key)",
+                msg
+            ),
+            AgentError::RateLimited {
+                message,
+                retry_after_secs: Some(secs),
+            } => write!(f, "Rate limited: {} (retry in {}s)", message, secs),
+            AgentError::RateLimited { message, .. } => {
+                write!(f, "Rate limited: {} (retry later)", message)
+            }
+            AgentError::QuotaExhausted {
+                message,
+                resets_at: Some(at),
+            } => write!(
+                f,
+                "Quota exhausted: {} (resets at Unix time {})",
+                message, at
+            ),
+            AgentError::QuotaExhausted { message, .. } => write!(
+                f,
+                "Quota exhausted: {} (wait for the limit to reset or check billing)",
+                message
+            ),
+            AgentError::SecretNotFound(name) => {
+                write!(f, "Secret '{}' not found in the vault", name)
+            }
+            AgentError::Git(msg) => write!(f, "Git error: {}", msg),
+            AgentError::InvalidWorkflow(msg) => write!(f, "Invalid workflow: {}", msg),
+            AgentError::InvalidWorkspace(msg) => write!(f, "Invalid workspace: {}", msg),
+            AgentError::InvalidSkill(msg) => write!(f, "Invalid skill: {}", msg),
+            AgentError::SkillExists(name) => write!(f, "Skill '{}' already exists", name),
+            AgentError::SkillNotFound(name) => write!(f, "Skill '{}' not found", name),
         }
     }
 }
@@ -123,6 +472,15 @@ pub enum ClaudeMessage {
         session_id: String,
         model: String,
     },
+    /// Current Claude releases report the session as `{"type":"system","subtype":"init",...}`.
+    #[serde(rename = "system")]
+    System {
+        subtype: String,
+        #[serde(default)]
+        session_id: Option<String>,
+        #[serde(default)]
+        model: Option<String>,
+    },
     #[serde(rename = "assistant")]
     Assistant {
         message: AssistantMessage,
@@ -132,6 +490,12 @@ pub enum ClaudeMessage {
         subtype: String,
         #[serde(default)]
         duration_ms: Option<u64>,
+        #[serde(default)]
+        duration_api_ms: Option<u64>,
+        #[serde(default)]
+        total_cost_usd: Option<f64>,
+        #[serde(default)]
+        usage: Option<TokenUsage>,
     },
     #[serde(rename = "error")]
     Error {
@@ -143,8 +507,12 @@ pub enum ClaudeMessage {
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct AssistantMessage {
+    #[serde(default)]
+    pub id: Option<String>,
     pub role: String,
     pub content: Vec<ContentBlock>,
+    #[serde(default)]
+    pub usage: Option<TokenUsage>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -176,16 +544,3 @@ pub struct ErrorInfo {
     pub message: Option<String>,
 }
 
-// Skills types
-#[derive(Debug, Clone, Serialize)]
-pub struct SkillInfo {
-    pub name: String,
-    pub description: String,
-}
-
-#[derive(Debug, Clone, Serialize)]
-pub struct SkillDetail {
-    pub info: SkillInfo,
-    pub markdown: String,
-    pub path: String,
-}
@@ -0,0 +1,400 @@
+use std::collections::HashMap;
+use std::ops::{Add, AddAssign};
+
+use serde::{Deserialize, Serialize};
+
+use crate::history::RunSummary;
+use crate::types::*;
+
+/// Token counts in the shape Claude reports them in its `usage` blocks.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    #[serde(default)]
+    pub input_tokens: u64,
+    #[serde(default)]
+    pub output_tokens: u64,
+    #[serde(default)]
+    pub cache_creation_input_tokens: u64,
+    #[serde(default)]
+    pub cache_read_input_tokens: u64,
+}
+
+impl TokenUsage {
+    pub fn is_empty(&self) -> bool {
+        *self == TokenUsage::default()
+    }
+
+    fn saturating_sub(self, other: TokenUsage) -> TokenUsage {
+        TokenUsage {
+            input_tokens: self.input_tokens.saturating_sub(other.input_tokens),
+            output_tokens: self.output_tokens.saturating_sub(other.output_tokens),
+            cache_creation_input_tokens: self
+                .cache_creation_input_tokens
+                .saturating_sub(other.cache_creation_input_tokens),
+            cache_read_input_tokens: self
+                .cache_read_input_tokens
+                .saturating_sub(other.cache_read_input_tokens),
+        }
+    }
+}
+
+impl Add for TokenUsage {
+    type Output = TokenUsage;
+
+    fn add(self, other: TokenUsage) -> TokenUsage {
+        TokenUsage {
+            input_tokens: self.input_tokens + other.input_tokens,
+            output_tokens: self.output_tokens + other.output_tokens,
+            cache_creation_input_tokens: self.cache_creation_input_tokens
+                + other.cache_creation_input_tokens,
+            cache_read_input_tokens: self.cache_read_input_tokens + other.cache_read_input_tokens,
+        }
+    }
+}
+
+impl AddAssign for TokenUsage {
+    fn add_assign(&mut self, other: TokenUsage) {
+        *self = *self + other;
+    }
+}
+
+/// List prices in USD per million tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelPrice {
+    pub input: f64,
+    pub output: f64,
+    pub cache_write: f64,
+    pub cache_read: f64,
+}
+
+const fn price(input: f64, output: f64, cache_write: f64, cache_read: f64) -> ModelPrice {
+    ModelPrice {
+        input,
+        output,
+        cache_write,
+        cache_read,
+    }
+}
+
+/// Matched by prefix against the model id, first match wins, so more specific
+/// entries come first. Only used when a CLI reports tokens but no cost.
+const PRICES: &[(&str, ModelPrice)] = &[
+    ("claude-opus-4-5", price(5.0, 25.0, 6.25, 0.5)),
+    ("claude-opus-4", price(15.0, 75.0, 18.75, 1.5)),
+    ("claude-sonnet-4", price(3.0, 15.0, 3.75, 0.3)),
+    ("claude-3-7-sonnet", price(3.0, 15.0, 3.75, 0.3)),
+    ("claude-haiku-4-5", price(1.0, 5.0, 1.25, 0.1)),
+    ("claude-3-5-haiku", price(0.8, 4.0, 1.0, 0.08)),
+    // Claude CLI aliases, which follow the current model of each family.
+    ("opus", price(5.0, 25.0, 6.25, 0.5)),
+    ("sonnet", price(3.0, 15.0, 3.75, 0.3)),
+    ("haiku", price(1.0, 5.0, 1.25, 0.1)),
+    ("gemini-2.5-pro", price(1.25, 10.0, 1.25, 0.31)),
+    ("gemini-2.5-flash-lite", price(0.1, 0.4, 0.1, 0.025)),
+    ("gemini-2.5-flash", price(0.3, 2.5, 0.3, 0.075)),
+    ("grok-4", price(3.0, 15.0, 3.0, 0.75)),
+    ("grok-code-fast", price(0.2, 1.5, 0.2, 0.02)),
+    ("deepseek-chat", price(0.27, 1.1, 0.27, 0.07)),
+    ("deepseek-reasoner", price(0.55, 2.19, 0.55, 0.14)),
+];
+
+pub fn price_for(model: &str) -> Option<ModelPrice> {
+    let model = model.to_ascii_lowercase();
+    PRICES
+        .iter()
+        .find(|(prefix, _)| model.starts_with(prefix))
+        .map(|(_, price)| *price)
+}
+
+/// Estimated cost of `usage` on `model`, or `None` if the model has no known price.
+pub fn estimate_cost(model: &str, usage: &TokenUsage) -> Option<f64> {
+    let price = price_for(model)?;
+    let per_token = |tokens: u64, per_million: f64| tokens as f64 * per_million / 1_000_000.0;
+    Some(
+        per_token(usage.input_tokens, price.input)
+            + per_token(usage.output_tokens, price.output)
+            + per_token(usage.cache_creation_input_tokens, price.cache_write)
+            + per_token(usage.cache_read_input_tokens, price.cache_read),
+    )
+}
+
+/// Keeps a run's running token and cost totals while its output is read.
+///
+/// Per-message usage is estimated from the pricing table as it arrives; each
+/// CLI `result` then replaces the estimate for its turn with reported numbers,
+/// or adds an estimate for any tokens only the result accounted for.
+pub(crate) struct UsageMeter {
+    model: Option<String>,
+    total: TokenUsage,
+    cost_usd: Option<f64>,
+    estimated: bool,
+    /// Totals as of the last `result`, which later turns build on.
+    settled: (TokenUsage, Option<f64>),
+    last_message_id: Option<String>,
+}
+
+impl UsageMeter {
+    pub(crate) fn new(model: Option<String>) -> Self {
+        Self {
+            model,
+            total: TokenUsage::default(),
+            cost_usd: None,
+            estimated: false,
+            settled: (TokenUsage::default(), None),
+            last_message_id: None,
+        }
+    }
+
+    pub(crate) fn set_model(&mut self, model: &str) {
+        if !model.is_empty() {
+            self.model = Some(model.to_string());
+        }
+    }
+
+    /// Usage from one assistant message. Claude repeats the same message's usage
+    /// on every content block it streams separately, so repeats are ignored.
+    pub(crate) fn message(
+        &mut self,
+        agent_id: &AgentId,
+        message_id: Option<&str>,
+        usage: TokenUsage,
+    ) -> Option<AgentEvent> {
+        if message_id.is_some() && message_id == self.last_message_id.as_deref() {
+            return None;
+        }
+        self.last_message_id = message_id.map(str::to_string);
+        if usage.is_empty() {
+            return None;
+        }
+
+        self.total += usage;
+        if let Some(cost) = self.model.as_deref().and_then(|model| estimate_cost(model, &usage)) {
+            self.cost_usd = Some(self.cost_usd.unwrap_or(0.0) + cost);
+            self.estimated = true;
+        }
+        Some(self.event(agent_id, usage))
+    }
+
+    /// The totals a CLI `result` reported for the turn that just ended.
+    pub(crate) fn result(
+        &mut self,
+        agent_id: &AgentId,
+        usage: Option<TokenUsage>,
+        cost_usd: Option<f64>,
+    ) -> Option<AgentEvent> {
+        if usage.is_none() && cost_usd.is_none() {
+            return None;
+        }
+
+        let (settled_usage, settled_cost) = self.settled;
+        let total = usage.map_or(self.total, |usage| settled_usage + usage);
+        let delta = total.saturating_sub(self.total);
+        self.total = total;
+        match cost_usd {
+            Some(cost) => {
+                self.cost_usd = Some(settled_cost.unwrap_or(0.0) + cost);
+                self.estimated = false;
+            }
+            // Backends that only report tokens (e.g. Gemini) are priced here.
+            None => {
+                if let Some(cost) = self.model.as_deref().and_then(|model| estimate_cost(model, &delta)) {
+                    self.cost_usd = Some(self.cost_usd.unwrap_or(0.0) + cost);
+                    self.estimated = true;
+                }
+            }
+        }
+        self.settled = (self.total, self.cost_usd);
+        self.last_message_id = None;
+        Some(self.event(agent_id, delta))
+    }
+
+    /// Keeps what a failed attempt used when a retry starts: the next attempt's
+    /// `result` only covers its own invocation.
+    pub(crate) fn settle(&mut self) {
+        self.settled = (self.total, self.cost_usd);
+        self.last_message_id = None;
+    }
+
+    fn event(&self, agent_id: &AgentId, usage: TokenUsage) -> AgentEvent {
+        AgentEvent::Usage {
+            agent_id: agent_id.clone(),
+            usage,
+            total: self.total,
+            total_cost_usd: self.cost_usd,
+            estimated: self.estimated,
+        }
+    }
+}
+
+/// Token and cost totals over a set of runs.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageTotals {
+    pub runs: usize,
+    pub tokens: TokenUsage,
+    pub cost_usd: f64,
+    /// Runs whose cost is unknown (no reported cost and no price for their model).
+    pub unpriced_runs: usize,
+}
+
+impl UsageTotals {
+    fn add(&mut self, run: &RunSummary) {
+        self.runs += 1;
+        self.tokens += run.usage;
+        match run.cost_usd {
+            Some(cost) => self.cost_usd += cost,
+            None if !run.usage.is_empty() => self.unpriced_runs += 1,
+            None => {}
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceUsage {
+    pub workspace_id: WorkspaceId,
+    #[serde(flatten)]
+    pub totals: UsageTotals,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DailyUsage {
+    /// UTC day the runs started on, as `YYYY-MM-DD`.
+    pub date: String,
+    #[serde(flatten)]
+    pub totals: UsageTotals,
+}
+
+/// Usage across recorded runs, per workspace (costliest first, then by id) and per
+/// day (newest first).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageReport {
+    pub total: UsageTotals,
+    pub workspaces: Vec<WorkspaceUsage>,
+    pub days: Vec<DailyUsage>,
+}
+
+impl UsageReport {
+    pub fn from_runs(runs: &[RunSummary]) -> Self {
+        let mut total = UsageTotals::default();
+        let mut workspaces: HashMap<&WorkspaceId, UsageTotals> = HashMap::new();
+        let mut days: HashMap<String, UsageTotals> = HashMap::new();
+
+        for run in runs {
+            total.add(run);
+            workspaces
+                .entry(&run.config.workspace_id)
+                .or_default()
+                .add(run);
+            days.entry(utc_date(run.started_at)).or_default().add(run);
+        }
+
+        let mut workspaces: Vec<WorkspaceUsage> = workspaces
+            .into_iter()
+            .map(|(workspace_id, totals)| WorkspaceUsage {
+                workspace_id: workspace_id.clone(),
+                totals,
+            })
+            .collect();
+        workspaces.sort_by(|a, b| {
+            b.totals
+                .cost_usd
+                .total_cmp(&a.totals.cost_usd)
+                .then_with(|| a.workspace_id.cmp(&b.workspace_id))
+        });
+
+        let mut days: Vec<DailyUsage> = days
+            .into_iter()
+            .map(|(date, totals)| DailyUsage { date, totals })
+            .collect();
+        days.sort_by(|a, b| b.date.cmp(&a.date));
+
+        Self {
+            total,
+            workspaces,
+            days,
+        }
+    }
+}
+
+/// Formats a Unix timestamp in milliseconds as a UTC `YYYY-MM-DD` date.
+fn utc_date(unix_millis: u64) -> String {
+    // Civil-from-days conversion (proleptic Gregorian calendar).
+    let days = (unix_millis / 86_400_000) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(workspace_id: &str, started_at: u64, cost_usd: f64) -> RunSummary {
+        RunSummary {
+            agent_id: format!("{}-{}", workspace_id, started_at),
+            config: serde_json::from_value(serde_json::json!({
+                "workspaceId": workspace_id,
+                "prompt": "",
+            }))
+            .unwrap(),
+            started_at,
+            ended_at: None,
+            reason: None,
+            exit_code: None,
+            session_id: None,
+            cli: None,
+            model: None,
+            worktree: None,
+            sandbox: None,
+            usage: TokenUsage::default(),
+            cost_usd: Some(cost_usd),
+            event_count: 0,
+        }
+    }
+
+    #[test]
+    fn utc_date_of_known_days() {
+        for (millis, date) in [
+            (0, "1970-01-01"),
+            (86_399_999, "1970-01-01"),
+            (951_782_400_000, "2000-02-29"),
+            (1_709_164_800_000, "2024-02-29"),
+            (1_709_251_199_999, "2024-02-29"),
+            (946_684_799_999, "1999-12-31"),
+            (946_684_800_000, "2000-01-01"),
+            (1_703_980_800_000, "2023-12-31"),
+            (4_107_542_400_000, "2100-03-01"),
+        ] {
+            assert_eq!(utc_date(millis), date, "{}", millis);
+        }
+    }
+
+    #[test]
+    fn workspaces_tied_on_cost_sort_by_id() {
+        let report = UsageReport::from_runs(&[
+            run("b", 0, 1.0),
+            run("c", 0, 2.0),
+            run("a", 0, 1.0),
+            run("d", 86_400_000, 1.0),
+        ]);
+        let order: Vec<&str> = report
+            .workspaces
+            .iter()
+            .map(|usage| usage.workspace_id.as_str())
+            .collect();
+        assert_eq!(order, ["c", "a", "b", "d"]);
+        let days: Vec<&str> = report.days.iter().map(|day| day.date.as_str()).collect();
+        assert_eq!(days, ["1970-01-02", "1970-01-01"]);
+    }
+}
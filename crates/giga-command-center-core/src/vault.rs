@@ -0,0 +1,570 @@
+//! Secrets kept encrypted on disk, for `AgentConfig::env` values to reference as
+//! `${secret:NAME}`. Values never leave the vault except into a spawned CLI's
+//! environment, and anything a run reports is scrubbed of them first.
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+
+use crate::history::unix_millis;
+use crate::types::*;
+
+/// Environment variable holding the vault key (base64, 32 bytes), used instead
+/// of the key file when set.
+pub const VAULT_KEY_ENV: &str = "AGENT_VAULT_KEY";
+
+/// Shown in place of a secret wherever a run's output would have revealed it.
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Values shorter than this aren't redacted: scrubbing every "1" or "yes" from a
+/// run's output would hide far more than the secret.
+const MIN_REDACTED_LEN: usize = 6;
+
+const FORMAT_VERSION: u32 = 1;
+/// Binds the ciphertext to this file format.
+const ASSOCIATED_DATA: &[u8] = b"giga-command-center vault v1";
+
+/// A stored secret, without its value.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SecretEntry {
+    pub name: String,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StoredSecret {
+    value: String,
+    created_at: u64,
+    updated_at: u64,
+}
+
+/// The vault file: one encrypted JSON map of every secret.
+#[derive(Serialize, Deserialize)]
+struct VaultFile {
+    version: u32,
+    nonce: String,
+    ciphertext: String,
+}
+
+pub struct SecretVault {
+    path: PathBuf,
+    cipher: XChaCha20Poly1305,
+    secrets: RwLock<BTreeMap<String, StoredSecret>>,
+}
+
+impl std::fmt::Debug for SecretVault {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SecretVault")
+            .field("path", &self.path)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SecretVault {
+    /// Opens (or creates) the vault at `path`, encrypted with `key`.
+    pub fn open(path: impl Into<PathBuf>, key: [u8; 32]) -> Result<Self, AgentError> {
+        let vault = Self {
+            path: path.into(),
+            cipher: XChaCha20Poly1305::new(Key::from_slice(&key)),
+            secrets: RwLock::new(BTreeMap::new()),
+        };
+        let secrets = vault.load()?;
+        *vault.secrets.write().unwrap_or_else(|e| e.into_inner()) = secrets;
+        Ok(vault)
+    }
+
+    /// Opens the vault under the platform data directory
+    /// (e.g. `~/.local/share/giga-command-center/vault.json`). The key comes from
+    /// `AGENT_VAULT_KEY`, or from `vault.key` beside it, created on first use.
+    pub fn open_default() -> Result<Self, AgentError> {
+        let dir = dirs::data_dir()
+            .ok_or_else(|| AgentError::StorageError("Could not find data directory".to_string()))?
+            .join("giga-command-center");
+        std::fs::create_dir_all(&dir).map_err(|e| {
+            AgentError::StorageError(format!("Failed to create vault directory: {}", e))
+        })?;
+        let key = match std::env::var(VAULT_KEY_ENV) {
+            Ok(encoded) => decode_key(encoded.trim()).ok_or_else(|| {
+                AgentError::StorageError(format!("{} must be 32 bytes of base64", VAULT_KEY_ENV))
+            })?,
+            Err(_) => load_or_create_key(&dir.join("vault.key"))?,
+        };
+        Self::open(dir.join("vault.json"), key)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Every secret's name and timestamps, sorted by name. Values are never listed.
+    pub fn list(&self) -> Vec<SecretEntry> {
+        self.secrets
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .map(|(name, secret)| SecretEntry {
+                name: name.clone(),
+                created_at: secret.created_at,
+                updated_at: secret.updated_at,
+            })
+            .collect()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.secrets
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .contains_key(name)
+    }
+
+    /// Adds or replaces secret `name`. Values shorter than six characters are
+    /// stored but not redacted from output.
+    pub fn set(&self, name: &str, value: &str) -> Result<SecretEntry, AgentError> {
+        validate_name(name)?;
+        if value.is_empty() {
+            return Err(AgentError::NotSupported(
+                "Secret value must not be empty".to_string(),
+            ));
+        }
+        let mut secrets = self.secrets.write().unwrap_or_else(|e| e.into_inner());
+        let now = unix_millis();
+        let created_at = secrets.get(name).map_or(now, |secret| secret.created_at);
+        let mut updated = secrets.clone();
+        updated.insert(
+            name.to_string(),
+            StoredSecret {
+                value: value.to_string(),
+                created_at,
+                updated_at: now,
+            },
+        );
+        self.save(&updated)?;
+        *secrets = updated;
+        Ok(SecretEntry {
+            name: name.to_string(),
+            created_at,
+            updated_at: now,
+        })
+    }
+
+    pub fn remove(&self, name: &str) -> Result<(), AgentError> {
+        let mut secrets = self.secrets.write().unwrap_or_else(|e| e.into_inner());
+        if !secrets.contains_key(name) {
+            return Err(AgentError::SecretNotFound(name.to_string()));
+        }
+        let mut updated = secrets.clone();
+        updated.remove(name);
+        self.save(&updated)?;
+        *secrets = updated;
+        Ok(())
+    }
+
+    /// Replaces every `${secret:NAME}` in `value` with the secret's value.
+    pub(crate) fn expand(&self, value: &str) -> Result<String, AgentError> {
+        let secrets = self.secrets.read().unwrap_or_else(|e| e.into_inner());
+        let mut out = String::with_capacity(value.len());
+        let mut rest = value;
+        while let Some((before, name, after)) = next_reference(rest) {
+            let secret = secrets
+                .get(name)
+                .ok_or_else(|| AgentError::SecretNotFound(name.to_string()))?;
+            out.push_str(before);
+            out.push_str(&secret.value);
+            rest = after;
+        }
+        out.push_str(rest);
+        Ok(out)
+    }
+
+    /// Replaces any secret value in `text` with [`REDACTED`].
+    pub fn redact(&self, text: &str) -> String {
+        let secrets = self.secrets.read().unwrap_or_else(|e| e.into_inner());
+        redact_with(&secrets, text).unwrap_or_else(|| text.to_string())
+    }
+
+    /// `event` with any secret value in its text replaced by [`REDACTED`].
+    pub fn redact_event(&self, event: AgentEvent) -> AgentEvent {
+        let secrets = self.secrets.read().unwrap_or_else(|e| e.into_inner());
+        if secrets.is_empty() {
+            return event;
+        }
+        let Ok(mut value) = serde_json::to_value(&event) else {
+            return event;
+        };
+        if !redact_json(&secrets, &mut value) {
+            return event;
+        }
+        serde_json::from_value(value.clone()).unwrap_or_else(|_| AgentEvent::Error {
+            agent_id: value["agent_id"].as_str().unwrap_or_default().to_string(),
+            message: REDACTED.to_string(),
+        })
+    }
+
+    fn load(&self) -> Result<BTreeMap<String, StoredSecret>, AgentError> {
+        let content = match std::fs::read_to_string(&self.path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+            Err(e) => {
+                return Err(AgentError::StorageError(format!(
+                    "Failed to read vault: {}",
+                    e
+                )))
+            }
+        };
+        let file: VaultFile = serde_json::from_str(&content)
+            .map_err(|e| AgentError::StorageError(format!("Invalid vault file: {}", e)))?;
+        if file.version != FORMAT_VERSION {
+            return Err(AgentError::StorageError(format!(
+                "Unsupported vault version {}",
+                file.version
+            )));
+        }
+        let nonce = BASE64
+            .decode(&file.nonce)
+            .ok()
+            .filter(|nonce| nonce.len() == 24)
+            .ok_or_else(|| AgentError::StorageError("Invalid vault nonce".to_string()))?;
+        let ciphertext = BASE64
+            .decode(&file.ciphertext)
+            .map_err(|_| AgentError::StorageError("Invalid vault ciphertext".to_string()))?;
+        let plaintext = self
+            .cipher
+            .decrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: &ciphertext,
+                    aad: ASSOCIATED_DATA,
+                },
+            )
+            .map_err(|_| {
+                AgentError::StorageError(
+                    "Could not decrypt vault (wrong key or corrupted file)".to_string(),
+                )
+            })?;
+        serde_json::from_slice(&plaintext)
+            .map_err(|e| AgentError::StorageError(format!("Invalid vault contents: {}", e)))
+    }
+
+    /// Encrypts `secrets` under a fresh nonce and replaces the vault file atomically.
+    fn save(&self, secrets: &BTreeMap<String, StoredSecret>) -> Result<(), AgentError> {
+        let plaintext = serde_json::to_vec(secrets)
+            .map_err(|e| AgentError::StorageError(format!("Failed to encode vault: {}", e)))?;
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: &plaintext,
+                    aad: ASSOCIATED_DATA,
+                },
+            )
+            .map_err(|_| AgentError::StorageError("Failed to encrypt vault".to_string()))?;
+        let file = VaultFile {
+            version: FORMAT_VERSION,
+            nonce: BASE64.encode(nonce),
+            ciphertext: BASE64.encode(ciphertext),
+        };
+        let content = serde_json::to_vec_pretty(&file)
+            .map_err(|e| AgentError::StorageError(format!("Failed to encode vault: {}", e)))?;
+        let tmp = self.path.with_extension("json.tmp");
+        write_private(&tmp, &content)
+            .and_then(|_| std::fs::rename(&tmp, &self.path))
+            .map_err(|e| AgentError::StorageError(format!("Failed to write vault: {}", e)))
+    }
+}
+
+/// Secret names follow environment variable rules, so `${secret:NAME}` is unambiguous.
+fn validate_name(name: &str) -> Result<(), AgentError> {
+    let valid = !name.is_empty()
+        && name.len() <= 128
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.');
+    if valid {
+        Ok(())
+    } else {
+        Err(AgentError::NotSupported(format!(
+            "Invalid secret name '{}': use letters, digits, '_', '-' or '.'",
+            name
+        )))
+    }
+}
+
+/// Splits `value` around its first `${secret:NAME}`: text before, the name, text after.
+fn next_reference(value: &str) -> Option<(&str, &str, &str)> {
+    const OPEN: &str = "${secret:";
+    let start = value.find(OPEN)?;
+    let name_start = start + OPEN.len();
+    let len = value[name_start..].find('}')?;
+    Some((
+        &value[..start],
+        &value[name_start..name_start + len],
+        &value[name_start + len + 1..],
+    ))
+}
+
+/// Names of the secrets `value` references.
+pub(crate) fn references(value: &str) -> Vec<&str> {
+    let mut names = Vec::new();
+    let mut rest = value;
+    while let Some((_, name, after)) = next_reference(rest) {
+        names.push(name);
+        rest = after;
+    }
+    names
+}
+
+/// `text` with secrets replaced, or `None` if it contained none.
+fn redact_with(secrets: &BTreeMap<String, StoredSecret>, text: &str) -> Option<String> {
+    let mut values: Vec<&str> = secrets
+        .values()
+        .map(|secret| secret.value.as_str())
+        .filter(|value| value.chars().count() >= MIN_REDACTED_LEN)
+        .collect();
+    // Longest first, so a secret containing another is redacted whole.
+    values.sort_unstable_by_key(|value| std::cmp::Reverse(value.len()));
+    let mut redacted: Option<String> = None;
+    for value in values {
+        let current = redacted.as_deref().unwrap_or(text);
+        if current.contains(value) {
+            redacted = Some(current.replace(value, REDACTED));
+        }
+    }
+    redacted
+}
+
+/// Redacts every string in `value`; returns whether anything changed.
+fn redact_json(secrets: &BTreeMap<String, StoredSecret>, value: &mut serde_json::Value) -> bool {
+    match value {
+        serde_json::Value::String(text) => match redact_with(secrets, text) {
+            Some(redacted) => {
+                *text = redacted;
+                true
+            }
+            None => false,
+        },
+        serde_json::Value::Array(items) => {
+            let mut changed = false;
+            for item in items {
+                changed |= redact_json(secrets, item);
+            }
+            changed
+        }
+        serde_json::Value::Object(fields) => {
+            let mut changed = false;
+            for field in fields.values_mut() {
+                changed |= redact_json(secrets, field);
+            }
+            changed
+        }
+        _ => false,
+    }
+}
+
+fn decode_key(encoded: &str) -> Option<[u8; 32]> {
+    BASE64.decode(encoded).ok()?.try_into().ok()
+}
+
+fn load_or_create_key(path: &Path) -> Result<[u8; 32], AgentError> {
+    match std::fs::read_to_string(path) {
+        Ok(encoded) => decode_key(encoded.trim()).ok_or_else(|| {
+            AgentError::StorageError(format!("Invalid vault key in {}", path.display()))
+        }),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            let key: [u8; 32] = XChaCha20Poly1305::generate_key(&mut OsRng).into();
+            write_private(path, BASE64.encode(key).as_bytes()).map_err(|e| {
+                AgentError::StorageError(format!("Failed to write vault key: {}", e))
+            })?;
+            Ok(key)
+        }
+        Err(e) => Err(AgentError::StorageError(format!(
+            "Failed to read vault key: {}",
+            e
+        ))),
+    }
+}
+
+/// Writes `content` to `path`, readable by the current user only.
+fn write_private(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+    file.write_all(content)?;
+    file.sync_all()
+}
+
+/// The environment a run's CLI gets on top of (or, with `env_clear`, instead of)
+/// the server's own: `config.env` with secret references expanded.
+pub(crate) fn resolve_env(
+    config: &AgentConfig,
+    vault: Option<&SecretVault>,
+) -> Result<Vec<(String, String)>, AgentError> {
+    config
+        .env
+        .iter()
+        .map(|(key, value)| {
+            let value = if references(value).is_empty() {
+                value.clone()
+            } else {
+                vault
+                    .ok_or_else(|| {
+                        AgentError::NotSupported("No secrets vault is configured".to_string())
+                    })?
+                    .expand(value)?
+            };
+            Ok((key.clone(), value))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            let path = std::env::temp_dir().join(format!("giga-vault-{}", uuid::Uuid::new_v4()));
+            std::fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    const KEY: [u8; 32] = [7; 32];
+
+    fn vault(dir: &TempDir, secrets: &[(&str, &str)]) -> SecretVault {
+        let vault = SecretVault::open(dir.0.join("vault.json"), KEY).unwrap();
+        for (name, value) in secrets {
+            vault.set(name, value).unwrap();
+        }
+        vault
+    }
+
+    #[test]
+    fn secrets_survive_reopening() {
+        let dir = TempDir::new();
+        vault(
+            &dir,
+            &[("API_KEY", "sk-live-123456"), ("OTHER", "hunter22")],
+        );
+        let content = std::fs::read_to_string(dir.0.join("vault.json")).unwrap();
+        assert!(!content.contains("sk-live-123456"));
+
+        let reopened = SecretVault::open(dir.0.join("vault.json"), KEY).unwrap();
+        let names: Vec<String> = reopened
+            .list()
+            .into_iter()
+            .map(|entry| entry.name)
+            .collect();
+        assert_eq!(names, ["API_KEY", "OTHER"]);
+        assert_eq!(
+            reopened.expand("${secret:API_KEY}").unwrap(),
+            "sk-live-123456"
+        );
+
+        reopened.remove("OTHER").unwrap();
+        let reopened = SecretVault::open(dir.0.join("vault.json"), KEY).unwrap();
+        assert!(!reopened.contains("OTHER"));
+    }
+
+    #[test]
+    fn wrong_key_fails_to_open() {
+        let dir = TempDir::new();
+        vault(&dir, &[("API_KEY", "sk-live-123456")]);
+        let error = SecretVault::open(dir.0.join("vault.json"), [8; 32]).unwrap_err();
+        assert!(
+            matches!(&error, AgentError::StorageError(message) if message.contains("wrong key")),
+            "{:?}",
+            error
+        );
+    }
+
+    #[test]
+    fn expand_replaces_references() {
+        let dir = TempDir::new();
+        let vault = vault(&dir, &[("USER", "alice-admin"), ("PASS", "s3cr3t-pw")]);
+        assert_eq!(
+            vault.expand("${secret:USER}:${secret:PASS}@host").unwrap(),
+            "alice-admin:s3cr3t-pw@host"
+        );
+        assert_eq!(vault.expand("no references").unwrap(), "no references");
+        assert_eq!(vault.expand("${secret:USER").unwrap(), "${secret:USER");
+        assert!(matches!(
+            vault.expand("${secret:USER} ${secret:MISSING}"),
+            Err(AgentError::SecretNotFound(name)) if name == "MISSING"
+        ));
+        assert_eq!(
+            references("${secret:A}-${secret:B}-${secret:A}"),
+            ["A", "B", "A"]
+        );
+    }
+
+    #[test]
+    fn redact_event_scrubs_nested_tool_input() {
+        let dir = TempDir::new();
+        let vault = vault(&dir, &[("TOKEN", "ghp_abcdef123456")]);
+        let event = AgentEvent::ToolUse {
+            agent_id: "agent".to_string(),
+            tool_name: "Bash".to_string(),
+            tool_input: serde_json::json!({
+                "command": "curl -H 'Authorization: ghp_abcdef123456' https://api.github.com",
+                "env": [{"name": "GH_TOKEN", "value": "ghp_abcdef123456"}],
+                "timeout": 30,
+            }),
+        };
+        let AgentEvent::ToolUse { tool_input, .. } = vault.redact_event(event) else {
+            panic!("redaction changed the event type");
+        };
+        assert_eq!(
+            tool_input,
+            serde_json::json!({
+                "command": "curl -H 'Authorization: [REDACTED]' https://api.github.com",
+                "env": [{"name": "GH_TOKEN", "value": "[REDACTED]"}],
+                "timeout": 30,
+            })
+        );
+    }
+
+    #[test]
+    fn short_values_are_not_redacted() {
+        let dir = TempDir::new();
+        let vault = vault(
+            &dir,
+            &[
+                ("DEBUG", "1"),
+                ("REGION", "eu"),
+                ("KEY", "sk-12345"),
+                ("KEY_LONG", "sk-12345-extra"),
+            ],
+        );
+        assert_eq!(
+            vault.redact("eu-west-1: sk-12345-extra then sk-12345"),
+            "eu-west-1: [REDACTED] then [REDACTED]"
+        );
+        assert_eq!(vault.expand("${secret:DEBUG}").unwrap(), "1");
+    }
+}
@@ -0,0 +1,677 @@
+//! Workflows: a DAG of agent runs where each node starts once every node it
+//! takes input from has succeeded, with their final output in its prompt.
+//! The graph runs in the manager, so it carries on without a client attached.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, watch};
+
+use crate::agent_manager::{AgentManager, EventEmitter};
+use crate::history::unix_millis;
+use crate::types::*;
+
+pub type WorkflowId = String;
+
+/// Placeholder in a node's prompt replaced by every input's output; without one
+/// the inputs are prepended. `{{input:<node id>}}` is replaced by one input's.
+const INPUT_PLACEHOLDER: &str = "{{input}}";
+
+/// A graph of runs to execute, as posted by a client.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkflowDefinition {
+    #[serde(default)]
+    pub name: Option<String>,
+    pub nodes: Vec<WorkflowNode>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkflowNode {
+    /// Unique within the workflow, e.g. the workspace's id.
+    pub id: String,
+    /// Labels the node's output in downstream prompts; defaults to `id`.
+    #[serde(default)]
+    pub name: Option<String>,
+    pub config: AgentConfig,
+    /// Nodes whose final output this node's prompt receives.
+    #[serde(default)]
+    pub inputs: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NodeStatus {
+    /// Waiting for its inputs.
+    Pending,
+    Running,
+    Succeeded,
+    Failed,
+    /// Stopped, or never started because the workflow was cancelled.
+    Cancelled,
+    /// Never started because an input didn't succeed.
+    Skipped,
+}
+
+impl NodeStatus {
+    fn is_done(self) -> bool {
+        !matches!(self, NodeStatus::Pending | NodeStatus::Running)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WorkflowStatus {
+    Running,
+    /// Every node succeeded.
+    Succeeded,
+    /// A node failed; nodes downstream of it were skipped.
+    Failed,
+    Cancelled,
+}
+
+/// A node's progress, as returned by `AgentManager::workflow`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeInfo {
+    pub id: String,
+    pub status: NodeStatus,
+    /// The node's run, once started.
+    #[serde(default)]
+    pub agent_id: Option<AgentId>,
+    /// The run's messages, joined; what downstream nodes receive.
+    #[serde(default)]
+    pub output: Option<String>,
+    /// Why the node failed to start.
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkflowInfo {
+    pub id: WorkflowId,
+    #[serde(default)]
+    pub name: Option<String>,
+    pub status: WorkflowStatus,
+    pub nodes: Vec<NodeInfo>,
+    pub started_at: u64,
+    #[serde(default)]
+    pub finished_at: Option<u64>,
+}
+
+/// A workflow the manager is running or has run.
+pub(crate) struct WorkflowRun {
+    pub info: Mutex<WorkflowInfo>,
+    pub cancel: watch::Sender<bool>,
+}
+
+impl WorkflowRun {
+    pub fn new(id: WorkflowId, definition: &WorkflowDefinition) -> Self {
+        let nodes = definition
+            .nodes
+            .iter()
+            .map(|node| NodeInfo {
+                id: node.id.clone(),
+                status: NodeStatus::Pending,
+                agent_id: None,
+                output: None,
+                error: None,
+            })
+            .collect();
+        Self {
+            info: Mutex::new(WorkflowInfo {
+                id,
+                name: definition.name.clone(),
+                status: WorkflowStatus::Running,
+                nodes,
+                started_at: unix_millis(),
+                finished_at: None,
+            }),
+            cancel: watch::channel(false).0,
+        }
+    }
+
+    pub fn info(&self) -> WorkflowInfo {
+        self.info.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+/// Drops all but the `keep` most recently finished runs from `runs`; running
+/// ones always stay.
+pub(crate) fn evict_finished(runs: &mut HashMap<WorkflowId, Arc<WorkflowRun>>, keep: usize) {
+    let mut finished: Vec<(u64, WorkflowId)> = runs
+        .iter()
+        .filter_map(|(id, run)| Some((run.info().finished_at?, id.clone())))
+        .collect();
+    if finished.len() <= keep {
+        return;
+    }
+    finished.sort_unstable_by(|a, b| b.cmp(a));
+    for (_, id) in &finished[keep..] {
+        runs.remove(id);
+    }
+}
+
+/// Rejects graphs that can't run: duplicate or unknown node ids and cycles.
+pub(crate) fn check(definition: &WorkflowDefinition) -> Result<(), AgentError> {
+    if definition.nodes.is_empty() {
+        return Err(AgentError::InvalidWorkflow(
+            "The workflow has no nodes".to_string(),
+        ));
+    }
+    let mut ids = HashSet::new();
+    for node in &definition.nodes {
+        if node.id.is_empty() {
+            return Err(AgentError::InvalidWorkflow(
+                "A node has an empty id".to_string(),
+            ));
+        }
+        if !ids.insert(node.id.as_str()) {
+            return Err(AgentError::InvalidWorkflow(format!(
+                "Node '{}' is defined twice",
+                node.id
+            )));
+        }
+    }
+    for node in &definition.nodes {
+        if let Some(input) = node
+            .inputs
+            .iter()
+            .find(|input| !ids.contains(input.as_str()))
+        {
+            return Err(AgentError::InvalidWorkflow(format!(
+                "Node '{}' takes input from unknown node '{}'",
+                node.id, input
+            )));
+        }
+    }
+
+    // Kahn's algorithm: whatever never becomes ready is on or behind a cycle.
+    let index: HashMap<&str, usize> = definition
+        .nodes
+        .iter()
+        .enumerate()
+        .map(|(i, node)| (node.id.as_str(), i))
+        .collect();
+    let mut waiting: Vec<usize> = definition
+        .nodes
+        .iter()
+        .map(|node| node.inputs.iter().collect::<HashSet<_>>().len())
+        .collect();
+    let mut ready: VecDeque<usize> = (0..waiting.len()).filter(|&i| waiting[i] == 0).collect();
+    let mut done = 0;
+    while let Some(i) = ready.pop_front() {
+        done += 1;
+        for (j, node) in definition.nodes.iter().enumerate() {
+            if node.inputs.iter().any(|input| index[input.as_str()] == i) {
+                waiting[j] -= 1;
+                if waiting[j] == 0 {
+                    ready.push_back(j);
+                }
+            }
+        }
+    }
+    if done < definition.nodes.len() {
+        let stuck: Vec<&str> = definition
+            .nodes
+            .iter()
+            .zip(&waiting)
+            .filter(|(_, &waiting)| waiting > 0)
+            .map(|(node, _)| node.id.as_str())
+            .collect();
+        return Err(AgentError::InvalidWorkflow(format!(
+            "The workflow has a cycle through {}",
+            stuck.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+/// `node`'s prompt with its inputs' outputs filled in. Placeholders are
+/// replaced in one pass over the prompt as written, so any in an output stay
+/// as they are; `{{input:<id>}}` for a node that isn't an input is left alone.
+fn build_prompt(node: &WorkflowNode, outputs: &[(&WorkflowNode, &str)]) -> String {
+    let context = outputs
+        .iter()
+        .map(|(input, output)| {
+            format!(
+                "--- Input from \"{}\" ---\n{}",
+                input.name.as_deref().unwrap_or(&input.id),
+                output
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    let mut prompt = String::with_capacity(node.config.prompt.len());
+    let mut rest = node.config.prompt.as_str();
+    let mut substituted = false;
+    while let Some(start) = rest.find("{{") {
+        prompt.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let value = rest.find("}}").and_then(|end| {
+            let placeholder = &rest[..end + 2];
+            let value = if placeholder == INPUT_PLACEHOLDER {
+                Some(context.as_str())
+            } else {
+                let id = placeholder.strip_prefix("{{input:")?.strip_suffix("}}")?;
+                outputs
+                    .iter()
+                    .find(|(input, _)| input.id == id)
+                    .map(|(_, output)| *output)
+            };
+            value.map(|value| (value, placeholder.len()))
+        });
+        match value {
+            Some((value, len)) => {
+                prompt.push_str(value);
+                rest = &rest[len..];
+                substituted = true;
+            }
+            None => {
+                prompt.push_str("{{");
+                rest = &rest[2..];
+            }
+        }
+    }
+    prompt.push_str(rest);
+
+    if substituted || context.is_empty() {
+        prompt
+    } else {
+        format!(
+            "Here is context from previous workflow steps:\n\n{}\n\n---\n\nNow, your task:\n{}",
+            context, prompt
+        )
+    }
+}
+
+/// How a node's run ended, as seen in its events.
+struct NodeEnd {
+    node: usize,
+    success: bool,
+    cancelled: bool,
+    output: String,
+}
+
+/// Runs `definition` on `manager` until every node is done or the workflow is
+/// cancelled, then emits `WorkflowFinished`.
+pub(crate) async fn drive(
+    manager: AgentManager,
+    definition: WorkflowDefinition,
+    run: Arc<WorkflowRun>,
+    emit_event: EventEmitter,
+) {
+    let workflow_id = run.info().id;
+    let nodes = &definition.nodes;
+    let mut status = vec![NodeStatus::Pending; nodes.len()];
+    let mut outputs: Vec<Option<String>> = vec![None; nodes.len()];
+    let mut agents: Vec<Option<AgentId>> = vec![None; nodes.len()];
+    let mut cancel = run.cancel.subscribe();
+    let (end_tx, mut end_rx) = mpsc::unbounded_channel::<NodeEnd>();
+
+    let update = |node: usize, f: &dyn Fn(&mut NodeInfo)| {
+        let mut info = run.info.lock().unwrap_or_else(|e| e.into_inner());
+        f(&mut info.nodes[node]);
+    };
+    let finish_node = |node: usize, node_status: NodeStatus, agent_id, error: Option<String>| {
+        update(node, &|info| {
+            info.status = node_status;
+            info.error = error.clone();
+        });
+        emit_event(AgentEvent::NodeFinished {
+            workflow_id: workflow_id.clone(),
+            node_id: nodes[node].id.clone(),
+            agent_id,
+            status: node_status,
+            error,
+        });
+    };
+
+    loop {
+        // Start or skip every pending node whose inputs are all done.
+        let cancelled = *cancel.borrow();
+        let mut progressed = true;
+        while progressed {
+            progressed = false;
+            for (i, node) in nodes.iter().enumerate() {
+                if status[i] != NodeStatus::Pending {
+                    continue;
+                }
+                let inputs: Vec<usize> = node
+                    .inputs
+                    .iter()
+                    .filter_map(|input| nodes.iter().position(|n| &n.id == input))
+                    .collect();
+                if cancelled {
+                    status[i] = NodeStatus::Cancelled;
+                    finish_node(i, NodeStatus::Cancelled, None, None);
+                    continue;
+                }
+                if !inputs.iter().all(|&input| status[input].is_done()) {
+                    continue;
+                }
+                progressed = true;
+                if !inputs
+                    .iter()
+                    .all(|&input| status[input] == NodeStatus::Succeeded)
+                {
+                    status[i] = NodeStatus::Skipped;
+                    finish_node(i, NodeStatus::Skipped, None, None);
+                    continue;
+                }
+
+                let input_outputs: Vec<(&WorkflowNode, &str)> = inputs
+                    .iter()
+                    .map(|&input| (&nodes[input], outputs[input].as_deref().unwrap_or_default()))
+                    .collect();
+                let mut config = node.config.clone();
+                config.prompt = build_prompt(node, &input_outputs);
+                match manager
+                    .start_agent(config, node_emitter(i, emit_event.clone(), end_tx.clone()))
+                    .await
+                {
+                    Ok(agent_id) => {
+                        status[i] = NodeStatus::Running;
+                        agents[i] = Some(agent_id.clone());
+                        update(i, &|info| {
+                            info.status = NodeStatus::Running;
+                            info.agent_id = Some(agent_id.clone());
+                        });
+                        emit_event(AgentEvent::NodeStarted {
+                            workflow_id: workflow_id.clone(),
+                            node_id: node.id.clone(),
+                            agent_id,
+                        });
+                    }
+                    Err(e) => {
+                        eprintln!(
+                            "[Workflow] Node {} of {} failed to start: {}",
+                            node.id, workflow_id, e
+                        );
+                        status[i] = NodeStatus::Failed;
+                        finish_node(i, NodeStatus::Failed, None, Some(e.to_string()));
+                    }
+                }
+            }
+        }
+
+        if !status.contains(&NodeStatus::Running) {
+            break;
+        }
+        tokio::select! {
+            Some(end) = end_rx.recv() => {
+                let node_status = if end.success {
+                    NodeStatus::Succeeded
+                } else if end.cancelled {
+                    NodeStatus::Cancelled
+                } else {
+                    NodeStatus::Failed
+                };
+                status[end.node] = node_status;
+                update(end.node, &|info| info.output = Some(end.output.clone()));
+                outputs[end.node] = Some(end.output);
+                finish_node(end.node, node_status, agents[end.node].clone(), None);
+            }
+            Ok(()) = cancel.changed() => {
+                for agent_id in status
+                    .iter()
+                    .zip(&agents)
+                    .filter(|(status, _)| **status == NodeStatus::Running)
+                    .filter_map(|(_, agent_id)| agent_id.as_ref())
+                {
+                    // Each run reports its own end, which finishes its node.
+                    if let Err(e) = manager.stop_agent(agent_id).await {
+                        eprintln!("[Workflow] Could not stop {}: {}", agent_id, e);
+                    }
+                }
+            }
+        }
+    }
+
+    let workflow_status = if *cancel.borrow() {
+        WorkflowStatus::Cancelled
+    } else if status.iter().all(|&s| s == NodeStatus::Succeeded) {
+        WorkflowStatus::Succeeded
+    } else if status.contains(&NodeStatus::Failed) {
+        WorkflowStatus::Failed
+    } else {
+        WorkflowStatus::Cancelled
+    };
+    {
+        let mut info = run.info.lock().unwrap_or_else(|e| e.into_inner());
+        info.status = workflow_status;
+        info.finished_at = Some(unix_millis());
+    }
+    emit_event(AgentEvent::WorkflowFinished {
+        workflow_id,
+        status: workflow_status,
+    });
+}
+
+/// Forwards a node's run events to the client, collecting its messages and
+/// reporting its end to the driver.
+fn node_emitter(
+    node: usize,
+    emit_event: EventEmitter,
+    end_tx: mpsc::UnboundedSender<NodeEnd>,
+) -> impl Fn(AgentEvent) + Send + Sync + Clone + 'static {
+    let messages = Arc::new(Mutex::new(Vec::<String>::new()));
+    let succeeded = Arc::new(Mutex::new(false));
+    move |event: AgentEvent| {
+        let end = match &event {
+            // Only the attempt that finishes the run makes the node's output.
+            AgentEvent::Started { .. }
+            | AgentEvent::Retrying { .. }
+            | AgentEvent::FallingBack { .. } => {
+                messages.lock().unwrap_or_else(|e| e.into_inner()).clear();
+                *succeeded.lock().unwrap_or_else(|e| e.into_inner()) = false;
+                None
+            }
+            AgentEvent::Message { content, .. } => {
+                messages
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .push(content.clone());
+                None
+            }
+            AgentEvent::Result { success, .. } => {
+                *succeeded.lock().unwrap_or_else(|e| e.into_inner()) = *success;
+                None
+            }
+            AgentEvent::Stopped { reason, .. } => Some(NodeEnd {
+                node,
+                success: matches!(reason, StopReason::Completed)
+                    && *succeeded.lock().unwrap_or_else(|e| e.into_inner()),
+                cancelled: matches!(reason, StopReason::Cancelled),
+                output: messages
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .join("\n\n"),
+            }),
+            _ => None,
+        };
+        emit_event(event);
+        // After the run's own `Stopped`, so clients see it before `NodeFinished`.
+        if let Some(end) = end {
+            let _ = end_tx.send(end);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(id: &str, inputs: &[&str], prompt: &str) -> WorkflowNode {
+        WorkflowNode {
+            id: id.to_string(),
+            name: None,
+            config: serde_json::from_value(serde_json::json!({
+                "workspaceId": id,
+                "prompt": prompt,
+            }))
+            .unwrap(),
+            inputs: inputs.iter().map(|input| input.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn node_output_is_the_last_attempts_messages() {
+        let (end_tx, mut end_rx) = mpsc::unbounded_channel();
+        let emit = node_emitter(3, Arc::new(|_| {}), end_tx);
+        let agent_id: AgentId = "agent".to_string();
+        let message = |content: &str| AgentEvent::Message {
+            agent_id: agent_id.clone(),
+            content: content.to_string(),
+        };
+
+        emit(message("partial answer"));
+        emit(AgentEvent::Retrying {
+            agent_id: agent_id.clone(),
+            attempt: 2,
+            max_attempts: 3,
+            delay_ms: 0,
+            reason: crate::retry::FailureClass::Network,
+        });
+        emit(message("first"));
+        emit(message("second"));
+        emit(AgentEvent::Result {
+            agent_id: agent_id.clone(),
+            success: true,
+            duration_ms: 1,
+            wall_clock_ms: 1,
+            api_duration_ms: None,
+        });
+        emit(AgentEvent::Stopped {
+            agent_id: agent_id.clone(),
+            reason: StopReason::Completed,
+            exit: None,
+        });
+
+        let end = end_rx.try_recv().unwrap();
+        assert_eq!((end.node, end.success, end.cancelled), (3, true, false));
+        assert_eq!(end.output, "first\n\nsecond");
+    }
+
+    #[test]
+    fn evict_finished_keeps_running_and_newest_runs() {
+        let definition = WorkflowDefinition {
+            name: None,
+            nodes: vec![node("a", &[], "")],
+        };
+        let mut runs = HashMap::new();
+        for (id, finished_at) in [
+            ("old", Some(1)),
+            ("running", None),
+            ("new", Some(3)),
+            ("mid", Some(2)),
+        ] {
+            let run = WorkflowRun::new(id.to_string(), &definition);
+            run.info.lock().unwrap().finished_at = finished_at;
+            runs.insert(id.to_string(), Arc::new(run));
+        }
+
+        evict_finished(&mut runs, 2);
+        let mut left: Vec<&str> = runs.keys().map(String::as_str).collect();
+        left.sort_unstable();
+        assert_eq!(left, ["mid", "new", "running"]);
+
+        evict_finished(&mut runs, 0);
+        assert_eq!(runs.keys().collect::<Vec<_>>(), ["running"]);
+    }
+
+    fn checked(nodes: Vec<WorkflowNode>) -> Result<(), String> {
+        check(&WorkflowDefinition { name: None, nodes }).map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn check_accepts_a_dag() {
+        assert_eq!(
+            checked(vec![
+                node("a", &[], ""),
+                node("b", &["a"], ""),
+                node("c", &["a", "b", "a"], ""),
+            ]),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn check_rejects_bad_graphs() {
+        let cases = [
+            (vec![], "no nodes"),
+            (vec![node("a", &["a"], "")], "cycle through a"),
+            (
+                vec![
+                    node("a", &[], ""),
+                    node("b", &["d"], ""),
+                    node("c", &["b"], ""),
+                    node("d", &["c"], ""),
+                ],
+                "cycle through b, c, d",
+            ),
+            (
+                vec![node("a", &[], ""), node("b", &["z"], "")],
+                "Node 'b' takes input from unknown node 'z'",
+            ),
+            (
+                vec![node("a", &[], ""), node("a", &[], "")],
+                "Node 'a' is defined twice",
+            ),
+            (vec![node("", &[], "")], "empty id"),
+        ];
+        for (nodes, expected) in cases {
+            let error = checked(nodes).unwrap_err();
+            assert!(error.contains(expected), "{}", error);
+        }
+    }
+
+    #[test]
+    fn build_prompt_fills_placeholders() {
+        let a = node("a", &[], "");
+        let b = node("b", &[], "");
+        let outputs = [(&a, "from a"), (&b, "from b")];
+
+        let target = node("c", &["a", "b"], "A: {{input:a}}, B: {{input:b}}");
+        assert_eq!(build_prompt(&target, &outputs), "A: from a, B: from b");
+
+        let target = node("c", &["a", "b"], "Review:\n{{input}}");
+        assert_eq!(
+            build_prompt(&target, &outputs),
+            "Review:\n--- Input from \"a\" ---\nfrom a\n\n--- Input from \"b\" ---\nfrom b"
+        );
+    }
+
+    #[test]
+    fn build_prompt_prepends_inputs_without_placeholders() {
+        let mut a = node("a", &[], "");
+        a.name = Some("Planner".to_string());
+        let target = node("b", &["a"], "Do it");
+        assert_eq!(
+            build_prompt(&target, &[(&a, "the plan")]),
+            "Here is context from previous workflow steps:\n\n--- Input from \"Planner\" ---\n\
+             the plan\n\n---\n\nNow, your task:\nDo it"
+        );
+        assert_eq!(build_prompt(&target, &[]), "Do it");
+    }
+
+    #[test]
+    fn build_prompt_leaves_placeholders_in_outputs() {
+        let a = node("a", &[], "");
+        let b = node("b", &[], "");
+        let outputs = [(&a, "{{input}} {{input:b}}"), (&b, "from b")];
+        let target = node(
+            "c",
+            &["a", "b"],
+            "{{input:a}} | {{input:b}} {{input:z}} {{ {{input:b",
+        );
+        assert_eq!(
+            build_prompt(&target, &outputs),
+            "{{input}} {{input:b}} | from b {{input:z}} {{ {{input:b"
+        );
+    }
+}
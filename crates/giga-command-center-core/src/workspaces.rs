@@ -0,0 +1,617 @@
+//! Workspaces as the canvas draws them (geometry, CLI settings, workflow
+//! template and connections), kept in one JSON file so every client and every
+//! restart sees the same layout. Run state (the workspace's current agent, its
+//! last output) stays with the clients.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::history::unix_millis;
+use crate::permissions::PermissionMode;
+use crate::sandbox::SandboxProfile;
+use crate::types::*;
+
+const FORMAT_VERSION: u32 = 1;
+
+/// Everything a client can set on a workspace.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceSpec {
+    pub name: String,
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub cli: Option<CliType>,
+    /// Cursor-only: `agent`, `plan`, or `ask`.
+    #[serde(default)]
+    pub mode: Option<String>,
+    #[serde(default)]
+    pub sandbox: Option<SandboxProfile>,
+    #[serde(default)]
+    pub permission_mode: Option<PermissionMode>,
+    /// Skills every run in this workspace starts with (`AgentConfig::skills`).
+    #[serde(default)]
+    pub skills: Vec<String>,
+    /// The prompt workflow runs of this workspace start with.
+    #[serde(default)]
+    pub task_template: Option<String>,
+    /// Workspaces whose output feeds into this one.
+    #[serde(default)]
+    pub input_connections: Vec<WorkspaceId>,
+    /// Workspaces this one feeds into.
+    #[serde(default)]
+    pub output_connections: Vec<WorkspaceId>,
+    /// Start a run once every input has succeeded.
+    #[serde(default)]
+    pub auto_run: bool,
+}
+
+/// A stored workspace.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Workspace {
+    pub id: WorkspaceId,
+    #[serde(flatten)]
+    pub spec: WorkspaceSpec,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+/// The body of a create request: a spec and, optionally, the id to store it
+/// under (clients that create workspaces optimistically pick their own).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewWorkspace {
+    #[serde(default)]
+    pub id: Option<WorkspaceId>,
+    #[serde(flatten)]
+    pub spec: WorkspaceSpec,
+}
+
+#[derive(Serialize, Deserialize)]
+struct WorkspacesFile {
+    version: u32,
+    workspaces: Vec<Workspace>,
+}
+
+pub struct WorkspaceStore {
+    path: PathBuf,
+    workspaces: RwLock<BTreeMap<WorkspaceId, Workspace>>,
+}
+
+impl std::fmt::Debug for WorkspaceStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WorkspaceStore")
+            .field("path", &self.path)
+            .finish_non_exhaustive()
+    }
+}
+
+impl WorkspaceStore {
+    /// Opens (or creates) the store at `path`.
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, AgentError> {
+        let store = Self {
+            path: path.into(),
+            workspaces: RwLock::new(BTreeMap::new()),
+        };
+        let workspaces = store.load()?;
+        *store.workspaces.write().unwrap_or_else(|e| e.into_inner()) = workspaces;
+        Ok(store)
+    }
+
+    /// Opens the store under the platform data directory
+    /// (e.g. `~/.local/share/giga-command-center/workspaces.json`).
+    pub fn open_default() -> Result<Self, AgentError> {
+        let dir = dirs::data_dir()
+            .ok_or_else(|| AgentError::StorageError("Could not find data directory".to_string()))?
+            .join("giga-command-center");
+        std::fs::create_dir_all(&dir).map_err(|e| {
+            AgentError::StorageError(format!("Failed to create workspace directory: {}", e))
+        })?;
+        Self::open(dir.join("workspaces.json"))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Every workspace, oldest first.
+    pub fn list(&self) -> Vec<Workspace> {
+        let mut workspaces: Vec<Workspace> = self
+            .workspaces
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .values()
+            .cloned()
+            .collect();
+        workspaces.sort_by(|a, b| a.created_at.cmp(&b.created_at).then(a.id.cmp(&b.id)));
+        workspaces
+    }
+
+    pub fn get(&self, id: &str) -> Result<Workspace, AgentError> {
+        self.workspaces
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(id)
+            .cloned()
+            .ok_or(AgentError::NotFound)
+    }
+
+    /// Stores a new workspace. Its connections are mirrored onto the workspaces
+    /// at their other end; each workspace that changed is announced with
+    /// `WorkspaceChanged`.
+    pub fn create<F>(&self, new: NewWorkspace, emit_event: F) -> Result<Workspace, AgentError>
+    where
+        F: Fn(AgentEvent),
+    {
+        let id = match new.id {
+            Some(id) => {
+                validate_id(&id)?;
+                id
+            }
+            None => Uuid::new_v4().to_string(),
+        };
+        let mut workspaces = self.workspaces.write().unwrap_or_else(|e| e.into_inner());
+        if workspaces.contains_key(&id) {
+            return Err(AgentError::InvalidWorkspace(format!(
+                "A workspace with id '{}' already exists",
+                id
+            )));
+        }
+        let now = unix_millis();
+        let mut updated = workspaces.clone();
+        updated.insert(
+            id.clone(),
+            Workspace {
+                id: id.clone(),
+                spec: WorkspaceSpec::default(),
+                created_at: now,
+                updated_at: now,
+            },
+        );
+        let changed = apply(&mut updated, &id, new.spec, now)?;
+        self.save(&updated)?;
+        *workspaces = updated;
+        drop(workspaces);
+        let workspace = changed[0].clone();
+        announce(changed, &emit_event);
+        Ok(workspace)
+    }
+
+    /// Replaces workspace `id`'s settings, mirroring connection changes as `create` does.
+    pub fn update<F>(
+        &self,
+        id: &str,
+        spec: WorkspaceSpec,
+        emit_event: F,
+    ) -> Result<Workspace, AgentError>
+    where
+        F: Fn(AgentEvent),
+    {
+        let mut workspaces = self.workspaces.write().unwrap_or_else(|e| e.into_inner());
+        if !workspaces.contains_key(id) {
+            return Err(AgentError::NotFound);
+        }
+        let mut updated = workspaces.clone();
+        let changed = apply(&mut updated, id, spec, unix_millis())?;
+        self.save(&updated)?;
+        *workspaces = updated;
+        drop(workspaces);
+        let workspace = changed[0].clone();
+        announce(changed, &emit_event);
+        Ok(workspace)
+    }
+
+    /// Removes workspace `id` and every connection to it; emits `WorkspaceDeleted`,
+    /// then `WorkspaceChanged` for each workspace that lost a connection.
+    pub fn delete<F>(&self, id: &str, emit_event: F) -> Result<(), AgentError>
+    where
+        F: Fn(AgentEvent),
+    {
+        let mut workspaces = self.workspaces.write().unwrap_or_else(|e| e.into_inner());
+        if !workspaces.contains_key(id) {
+            return Err(AgentError::NotFound);
+        }
+        let now = unix_millis();
+        let mut updated = workspaces.clone();
+        updated.remove(id);
+        let mut changed = Vec::new();
+        for workspace in updated.values_mut() {
+            let before = (
+                workspace.spec.input_connections.len(),
+                workspace.spec.output_connections.len(),
+            );
+            workspace.spec.input_connections.retain(|other| other != id);
+            workspace
+                .spec
+                .output_connections
+                .retain(|other| other != id);
+            let after = (
+                workspace.spec.input_connections.len(),
+                workspace.spec.output_connections.len(),
+            );
+            if before != after {
+                workspace.updated_at = now;
+                changed.push(workspace.clone());
+            }
+        }
+        self.save(&updated)?;
+        *workspaces = updated;
+        drop(workspaces);
+        emit_event(AgentEvent::WorkspaceDeleted {
+            workspace_id: id.to_string(),
+        });
+        for workspace in changed {
+            emit_event(AgentEvent::WorkspaceChanged {
+                workspace: Box::new(workspace),
+            });
+        }
+        Ok(())
+    }
+
+    fn load(&self) -> Result<BTreeMap<WorkspaceId, Workspace>, AgentError> {
+        let content = match std::fs::read_to_string(&self.path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+            Err(e) => {
+                return Err(AgentError::StorageError(format!(
+                    "Failed to read workspaces: {}",
+                    e
+                )))
+            }
+        };
+        let file: WorkspacesFile = serde_json::from_str(&content)
+            .map_err(|e| AgentError::StorageError(format!("Invalid workspaces file: {}", e)))?;
+        if file.version != FORMAT_VERSION {
+            return Err(AgentError::StorageError(format!(
+                "Unsupported workspaces file version {}",
+                file.version
+            )));
+        }
+        Ok(file
+            .workspaces
+            .into_iter()
+            .map(|workspace| (workspace.id.clone(), workspace))
+            .collect())
+    }
+
+    /// Replaces the workspaces file atomically.
+    fn save(&self, workspaces: &BTreeMap<WorkspaceId, Workspace>) -> Result<(), AgentError> {
+        let file = WorkspacesFile {
+            version: FORMAT_VERSION,
+            workspaces: workspaces.values().cloned().collect(),
+        };
+        let content = serde_json::to_vec_pretty(&file)
+            .map_err(|e| AgentError::StorageError(format!("Failed to encode workspaces: {}", e)))?;
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, content)
+            .and_then(|_| std::fs::rename(&tmp, &self.path))
+            .map_err(|e| AgentError::StorageError(format!("Failed to write workspaces: {}", e)))
+    }
+}
+
+/// Client-chosen ids: 1–64 letters, digits, `-` or `_` (what nanoid and UUIDs produce).
+fn validate_id(id: &str) -> Result<(), AgentError> {
+    let valid = !id.is_empty()
+        && id.len() <= 64
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if valid {
+        Ok(())
+    } else {
+        Err(AgentError::InvalidWorkspace(format!(
+            "Invalid workspace id '{}': use letters, digits, '_' or '-'",
+            id
+        )))
+    }
+}
+
+fn validate_spec(
+    workspaces: &BTreeMap<WorkspaceId, Workspace>,
+    id: &str,
+    spec: &WorkspaceSpec,
+) -> Result<(), AgentError> {
+    if spec.name.trim().is_empty() {
+        return Err(AgentError::InvalidWorkspace(
+            "The workspace name must not be empty".to_string(),
+        ));
+    }
+    let geometry = [spec.x, spec.y, spec.width, spec.height];
+    if geometry.iter().any(|value| !value.is_finite()) || spec.width <= 0.0 || spec.height <= 0.0 {
+        return Err(AgentError::InvalidWorkspace(
+            "The workspace needs a finite position and a positive size".to_string(),
+        ));
+    }
+    for other in spec
+        .input_connections
+        .iter()
+        .chain(&spec.output_connections)
+    {
+        if other == id {
+            return Err(AgentError::InvalidWorkspace(
+                "A workspace can't be connected to itself".to_string(),
+            ));
+        }
+        if !workspaces.contains_key(other) {
+            return Err(AgentError::InvalidWorkspace(format!(
+                "Unknown connected workspace '{}'",
+                other
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Stores `spec` as workspace `id`'s settings and brings the other end of each
+/// connection in line with it. Returns every workspace that changed, `id` first.
+fn apply(
+    workspaces: &mut BTreeMap<WorkspaceId, Workspace>,
+    id: &str,
+    mut spec: WorkspaceSpec,
+    now: u64,
+) -> Result<Vec<Workspace>, AgentError> {
+    validate_spec(workspaces, id, &spec)?;
+    dedup(&mut spec.input_connections);
+    dedup(&mut spec.output_connections);
+
+    let mut changed = Vec::new();
+    for (other_id, other) in workspaces.iter_mut() {
+        if other_id == id {
+            continue;
+        }
+        let feeds_us = spec.input_connections.contains(other_id);
+        let fed_by_us = spec.output_connections.contains(other_id);
+        let outputs = set_member(&mut other.spec.output_connections, id, feeds_us);
+        let inputs = set_member(&mut other.spec.input_connections, id, fed_by_us);
+        if outputs || inputs {
+            other.updated_at = now;
+            changed.push(other.clone());
+        }
+    }
+
+    let workspace = workspaces.get_mut(id).ok_or(AgentError::NotFound)?;
+    workspace.spec = spec;
+    workspace.updated_at = now;
+    changed.insert(0, workspace.clone());
+    Ok(changed)
+}
+
+fn dedup(ids: &mut Vec<WorkspaceId>) {
+    let mut seen = std::collections::HashSet::new();
+    ids.retain(|id| seen.insert(id.clone()));
+}
+
+/// Adds or removes `id` from `ids`; returns whether anything changed.
+fn set_member(ids: &mut Vec<WorkspaceId>, id: &str, present: bool) -> bool {
+    let contains = ids.iter().any(|other| other == id);
+    if present && !contains {
+        ids.push(id.to_string());
+        true
+    } else if !present && contains {
+        ids.retain(|other| other != id);
+        true
+    } else {
+        false
+    }
+}
+
+fn announce<F: Fn(AgentEvent)>(changed: Vec<Workspace>, emit_event: &F) {
+    for workspace in changed {
+        emit_event(AgentEvent::WorkspaceChanged {
+            workspace: Box::new(workspace),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            let path = std::env::temp_dir().join(format!("giga-workspaces-{}", Uuid::new_v4()));
+            std::fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+
+        fn store(&self) -> WorkspaceStore {
+            WorkspaceStore::open(self.0.join("workspaces.json")).unwrap()
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn spec(name: &str) -> WorkspaceSpec {
+        WorkspaceSpec {
+            name: name.to_string(),
+            width: 400.0,
+            height: 300.0,
+            ..WorkspaceSpec::default()
+        }
+    }
+
+    fn new(id: &str, spec: WorkspaceSpec) -> NewWorkspace {
+        NewWorkspace {
+            id: Some(id.to_string()),
+            spec,
+        }
+    }
+
+    /// Records the ids of the workspaces announced as changed or deleted.
+    #[derive(Default)]
+    struct Announced(RefCell<Vec<String>>);
+
+    impl Announced {
+        fn emit(&self) -> impl Fn(AgentEvent) + '_ {
+            |event| {
+                let entry = match event {
+                    AgentEvent::WorkspaceChanged { workspace } => workspace.id,
+                    AgentEvent::WorkspaceDeleted { workspace_id } => format!("-{}", workspace_id),
+                    other => panic!("unexpected event {:?}", other),
+                };
+                self.0.borrow_mut().push(entry);
+            }
+        }
+
+        fn take(&self) -> Vec<String> {
+            self.0.take()
+        }
+    }
+
+    fn connections(store: &WorkspaceStore, id: &str) -> (Vec<String>, Vec<String>) {
+        let spec = store.get(id).unwrap().spec;
+        (spec.input_connections, spec.output_connections)
+    }
+
+    #[test]
+    fn create_update_and_delete_persist() {
+        let dir = TempDir::new();
+        let store = dir.store();
+        let announced = Announced::default();
+
+        let created = store
+            .create(new("a", spec("Planner")), announced.emit())
+            .unwrap();
+        assert_eq!(created.spec.name, "Planner");
+        assert_eq!(announced.take(), ["a"]);
+        let generated = store
+            .create(
+                NewWorkspace {
+                    id: None,
+                    spec: spec("Coder"),
+                },
+                announced.emit(),
+            )
+            .unwrap();
+        assert!(Uuid::parse_str(&generated.id).is_ok());
+        announced.take();
+
+        let mut renamed = spec("Architect");
+        renamed.model = Some("opus".to_string());
+        let updated = store
+            .update("a", renamed.clone(), announced.emit())
+            .unwrap();
+        assert_eq!(updated.spec, renamed);
+        assert_eq!(updated.created_at, created.created_at);
+        assert!(updated.updated_at >= created.updated_at);
+        assert!(matches!(
+            store.update("missing", spec("x"), announced.emit()),
+            Err(AgentError::NotFound)
+        ));
+
+        let reopened = dir.store();
+        assert_eq!(reopened.list(), store.list());
+        assert_eq!(reopened.get("a").unwrap().spec, renamed);
+
+        store.delete(&generated.id, announced.emit()).unwrap();
+        assert!(matches!(
+            store.get(&generated.id),
+            Err(AgentError::NotFound)
+        ));
+        assert!(matches!(
+            store.delete(&generated.id, announced.emit()),
+            Err(AgentError::NotFound)
+        ));
+        let ids: Vec<String> = dir.store().list().into_iter().map(|w| w.id).collect();
+        assert_eq!(ids, ["a"]);
+    }
+
+    #[test]
+    fn connections_are_mirrored_to_both_ends() {
+        let dir = TempDir::new();
+        let store = dir.store();
+        let announced = Announced::default();
+        store.create(new("a", spec("A")), announced.emit()).unwrap();
+        store.create(new("b", spec("B")), announced.emit()).unwrap();
+        announced.take();
+
+        let mut c = spec("C");
+        c.input_connections = vec!["a".to_string(), "a".to_string()];
+        c.output_connections = vec!["b".to_string()];
+        store.create(new("c", c), announced.emit()).unwrap();
+        assert_eq!(announced.take(), ["c", "a", "b"]);
+        assert_eq!(
+            connections(&store, "c"),
+            (vec!["a".into()], vec!["b".into()])
+        );
+        assert_eq!(connections(&store, "a"), (vec![], vec!["c".into()]));
+        assert_eq!(connections(&store, "b"), (vec!["c".into()], vec![]));
+
+        // Dropping `a` from c's inputs drops c from a's outputs, and only a changes.
+        let mut c = store.get("c").unwrap().spec;
+        c.input_connections.clear();
+        store.update("c", c, announced.emit()).unwrap();
+        assert_eq!(announced.take(), ["c", "a"]);
+        assert_eq!(connections(&store, "a"), (vec![], vec![]));
+
+        store.delete("c", announced.emit()).unwrap();
+        assert_eq!(announced.take(), ["-c", "b"]);
+        assert_eq!(connections(&store, "b"), (vec![], vec![]));
+    }
+
+    #[test]
+    fn rejects_invalid_ids_and_specs() {
+        let dir = TempDir::new();
+        let store = dir.store();
+        let announced = Announced::default();
+        store.create(new("a", spec("A")), announced.emit()).unwrap();
+
+        let invalid = |result: Result<Workspace, AgentError>, expected: &str| match result {
+            Err(AgentError::InvalidWorkspace(message)) => {
+                assert!(message.contains(expected), "{}", message)
+            }
+            other => panic!("expected InvalidWorkspace, got {:?}", other),
+        };
+        for id in ["", "../etc", "has space", &"x".repeat(65)] {
+            invalid(
+                store.create(new(id, spec("X")), announced.emit()),
+                "Invalid workspace id",
+            );
+        }
+        invalid(
+            store.create(new("a", spec("X")), announced.emit()),
+            "already exists",
+        );
+        invalid(store.create(new("b", spec("  ")), announced.emit()), "name");
+        let mut flat = spec("Flat");
+        flat.height = 0.0;
+        invalid(
+            store.create(new("b", flat), announced.emit()),
+            "positive size",
+        );
+
+        let mut dangling = spec("B");
+        dangling.output_connections = vec!["ghost".to_string()];
+        invalid(
+            store.create(new("b", dangling), announced.emit()),
+            "Unknown connected workspace 'ghost'",
+        );
+        let mut looped = spec("A");
+        looped.input_connections = vec!["a".to_string()];
+        invalid(store.update("a", looped, announced.emit()), "itself");
+
+        // Nothing was stored or announced past the first workspace.
+        assert_eq!(announced.take(), ["a"]);
+        assert_eq!(dir.store().list().len(), 1);
+        assert_eq!(connections(&store, "a"), (vec![], vec![]));
+    }
+}
@@ -0,0 +1,246 @@
+//! Git worktrees that isolate runs sharing a repository, so parallel agents
+//! don't overwrite each other's edits. Each run gets its own branch; what it
+//! changed is committed there when it ends, ready to merge back or discard.
+
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+
+use crate::types::*;
+
+/// Committer used when the repository has no `user.email` configured.
+const FALLBACK_IDENTITY: [&str; 4] = [
+    "-c",
+    "user.name=Giga Command Center",
+    "-c",
+    "user.email=agents@giga-command-center.local",
+];
+
+/// Where a run's CLI works.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Isolation {
+    /// Directly in `working_directory`.
+    #[default]
+    None,
+    /// In a fresh git worktree and branch of `working_directory`'s repository.
+    Worktree,
+}
+
+/// What happens to a run's worktree once it ends. Its changes are committed to
+/// the run's branch either way.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WorktreeCleanup {
+    /// Leave it checked out for inspection.
+    #[default]
+    Keep,
+    /// Remove the checkout; the branch stays for merging. A run that changed
+    /// nothing has its branch deleted too.
+    Remove,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WorktreeStatus {
+    /// A run is working in it.
+    Active,
+    /// The run ended and the worktree is still checked out.
+    Kept,
+    /// The checkout was removed; the branch is left.
+    Removed,
+    /// The branch was merged into the repository's current branch and deleted.
+    Merged,
+    /// The worktree and branch were deleted without merging.
+    Discarded,
+}
+
+/// A run's worktree, as reported in `Started` and `WorktreeChanged`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorktreeInfo {
+    /// Root of the repository the worktree belongs to.
+    pub repo: PathBuf,
+    /// The worktree's checkout.
+    pub path: PathBuf,
+    pub branch: String,
+    /// Commit the branch started from.
+    pub base: String,
+    /// `working_directory` relative to the repository root, mirrored inside the worktree.
+    #[serde(default)]
+    pub subdir: Option<String>,
+    pub status: WorktreeStatus,
+    /// Commits on the branch since `base`.
+    #[serde(default)]
+    pub commits: u32,
+}
+
+impl WorktreeInfo {
+    /// The directory the CLI runs in.
+    pub fn working_directory(&self) -> PathBuf {
+        match &self.subdir {
+            Some(subdir) => self.path.join(subdir),
+            None => self.path.clone(),
+        }
+    }
+
+    /// Whether the branch still exists to merge, discard or continue from.
+    pub fn is_open(&self) -> bool {
+        matches!(
+            self.status,
+            WorktreeStatus::Active | WorktreeStatus::Kept | WorktreeStatus::Removed
+        )
+    }
+}
+
+/// Runs git in `dir`, returning its trimmed stdout.
+async fn git(dir: &Path, args: &[&str]) -> Result<String, AgentError> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .map_err(|e| AgentError::Git(format!("Could not run git: {}", e)))?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        Err(AgentError::Git(format!(
+            "git {} failed: {}",
+            args.iter()
+                .find(|arg| !arg.starts_with('-') && !arg.contains('='))
+                .copied()
+                .unwrap_or_default(),
+            stderr.trim()
+        )))
+    }
+}
+
+/// Where worktrees are checked out: outside the repository, so they don't
+/// show up in its status.
+fn worktrees_dir() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("giga-command-center")
+        .join("worktrees")
+}
+
+/// Creates a worktree on a new branch from `dir`'s repository `HEAD`.
+pub(crate) async fn create(agent_id: &AgentId, dir: &Path) -> Result<WorktreeInfo, AgentError> {
+    let repo = PathBuf::from(git(dir, &["rev-parse", "--show-toplevel"]).await?);
+    let prefix = git(dir, &["rev-parse", "--show-prefix"]).await?;
+    let base = git(&repo, &["rev-parse", "HEAD"])
+        .await
+        .map_err(|_| AgentError::Git("The repository has no commits to branch from".to_string()))?;
+    let info = WorktreeInfo {
+        path: worktrees_dir().join(agent_id),
+        // The whole id: a shortened one could collide with another run's branch.
+        branch: format!("agent/{}", agent_id),
+        subdir: Some(prefix.trim_end_matches('/').to_string()).filter(|p| !p.is_empty()),
+        repo,
+        base,
+        status: WorktreeStatus::Active,
+        commits: 0,
+    };
+    let path = info.path.to_string_lossy().into_owned();
+    git(
+        &info.repo,
+        &["worktree", "add", "-b", &info.branch, &path, &info.base],
+    )
+    .await?;
+    Ok(info)
+}
+
+/// Checks `info`'s branch out again where it was, for a run continuing an
+/// earlier one whose worktree was removed.
+pub(crate) async fn restore(info: &WorktreeInfo) -> Result<WorktreeInfo, AgentError> {
+    let path = info.path.to_string_lossy().into_owned();
+    git(&info.repo, &["worktree", "prune"]).await?;
+    git(&info.repo, &["worktree", "add", &path, &info.branch]).await?;
+    Ok(WorktreeInfo {
+        status: WorktreeStatus::Active,
+        ..info.clone()
+    })
+}
+
+/// Commits whatever the run left uncommitted to its branch, counts the branch's
+/// commits and applies `cleanup`.
+pub(crate) async fn finish(
+    info: &mut WorktreeInfo,
+    message: &str,
+    cleanup: WorktreeCleanup,
+) -> Result<(), AgentError> {
+    if info.path.exists() {
+        git(&info.path, &["add", "-A"]).await?;
+        let staged = git(&info.path, &["diff", "--cached", "--name-only"]).await?;
+        if !staged.is_empty() {
+            let mut args: Vec<&str> = identity(&info.path).await;
+            args.extend(["commit", "--no-verify", "-q", "-m", message]);
+            git(&info.path, &args).await?;
+        }
+    }
+    let range = format!("{}..{}", info.base, info.branch);
+    info.commits = git(&info.repo, &["rev-list", "--count", &range])
+        .await?
+        .parse()
+        .unwrap_or(0);
+
+    info.status = WorktreeStatus::Kept;
+    if cleanup == WorktreeCleanup::Remove {
+        remove_checkout(info).await?;
+        info.status = WorktreeStatus::Removed;
+        if info.commits == 0 {
+            git(&info.repo, &["branch", "-D", &info.branch]).await?;
+            info.status = WorktreeStatus::Discarded;
+        }
+    }
+    Ok(())
+}
+
+/// Merges the branch into whatever the repository has checked out, then deletes
+/// the worktree and branch. A conflicting merge is aborted and reported.
+pub(crate) async fn merge(info: &mut WorktreeInfo) -> Result<(), AgentError> {
+    let message = format!("Merge {}", info.branch);
+    let mut args: Vec<&str> = identity(&info.repo).await;
+    args.extend(["merge", "--no-ff", "-m", &message, &info.branch]);
+    if let Err(e) = git(&info.repo, &args).await {
+        let _ = git(&info.repo, &["merge", "--abort"]).await;
+        return Err(e);
+    }
+    remove_checkout(info).await?;
+    git(&info.repo, &["branch", "-D", &info.branch]).await?;
+    info.status = WorktreeStatus::Merged;
+    Ok(())
+}
+
+/// Deletes the worktree and its branch, dropping the run's changes.
+pub(crate) async fn discard(info: &mut WorktreeInfo) -> Result<(), AgentError> {
+    remove_checkout(info).await?;
+    git(&info.repo, &["branch", "-D", &info.branch]).await?;
+    info.status = WorktreeStatus::Discarded;
+    Ok(())
+}
+
+/// `-c` options supplying a committer when the repository has none configured.
+async fn identity(dir: &Path) -> Vec<&'static str> {
+    let configured = git(dir, &["config", "user.email"])
+        .await
+        .is_ok_and(|email| !email.is_empty());
+    if configured {
+        Vec::new()
+    } else {
+        FALLBACK_IDENTITY.to_vec()
+    }
+}
+
+async fn remove_checkout(info: &WorktreeInfo) -> Result<(), AgentError> {
+    if info.path.exists() {
+        let path = info.path.to_string_lossy().into_owned();
+        git(&info.repo, &["worktree", "remove", "--force", &path]).await?;
+    }
+    git(&info.repo, &["worktree", "prune"]).await.map(|_| ())
+}
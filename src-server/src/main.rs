@@ -4,13 +4,13 @@ mod websocket;
 use std::sync::Arc;
 use std::path::PathBuf;
 use axum::{
-    extract::Extension,
+    extract::{DefaultBodyLimit, Extension},
     routing::{get, post},
     Router,
 };
 use tower_http::cors::CorsLayer;
 use tower_http::services::ServeDir;
-use giga_command_center_core::AgentManager;
+use giga_command_center_core::{AgentManager, HistoryStore, SecretVault, SkillsRepository, WorkspaceStore, MAX_BUNDLE_SIZE};
 use tokio::sync::broadcast;
 
 #[tokio::main]
@@ -19,12 +19,87 @@ async fn main() {
     tracing_subscriber::fmt::init();
 
     // Create shared state
-    let manager = Arc::new(AgentManager::new());
+    let manager = match HistoryStore::open_default() {
+        Ok(history) => {
+            tracing::info!("Recording run history in: {}", history.dir().display());
+            AgentManager::with_history(history)
+        }
+        Err(e) => {
+            tracing::warn!("Run history disabled: {}", e);
+            AgentManager::new()
+        }
+    };
+    let manager = match SecretVault::open_default() {
+        Ok(vault) => {
+            tracing::info!("Secrets vault: {}", vault.path().display());
+            manager.with_vault(vault)
+        }
+        Err(e) => {
+            tracing::warn!("Secrets vault disabled: {}", e);
+            manager
+        }
+    };
+    let manager = match WorkspaceStore::open_default() {
+        Ok(store) => {
+            tracing::info!("Workspaces stored in: {}", store.path().display());
+            manager.with_workspaces(store)
+        }
+        Err(e) => {
+            tracing::warn!("Workspace storage disabled: {}", e);
+            manager
+        }
+    };
+    let manager = match SkillsRepository::open_default() {
+        Ok(skills) => {
+            tracing::info!("Skills directory: {}", skills.root().display());
+            manager.with_skills(skills)
+        }
+        Err(e) => {
+            tracing::warn!("Skills disabled: {}", e);
+            manager
+        }
+    };
+    let max_concurrent = std::env::var("MAX_CONCURRENT_AGENTS")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(giga_command_center_core::DEFAULT_MAX_CONCURRENT);
+    tracing::info!("Running at most {} agents at once", max_concurrent);
+    let stop_grace = std::env::var("AGENT_STOP_GRACE_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .map(std::time::Duration::from_secs)
+        .unwrap_or(giga_command_center_core::DEFAULT_STOP_GRACE_PERIOD);
+    let manager = Arc::new(
+        manager
+            .with_max_concurrent(max_concurrent)
+            .with_stop_grace_period(stop_grace),
+    );
+
+    // AGENT_CLI_PATH_<ID> points a backend at a specific binary
+    manager.registry().load_env_overrides();
+    for cli in manager.registry().detect(false).await {
+        match (cli.available, &cli.path) {
+            (true, Some(path)) => tracing::info!(
+                "{} CLI {} at {}",
+                cli.name,
+                cli.version.as_deref().unwrap_or("(unknown version)"),
+                path.display()
+            ),
+            _ => tracing::info!("{} CLI unavailable: {}", cli.name, cli.error.unwrap_or_default()),
+        }
+    }
     
     // Create broadcast channel for WebSocket events
     let (event_tx, _) = broadcast::channel::<String>(1000);
     let event_tx = Arc::new(event_tx);
 
+    // Skill edits made outside the app reach clients as SkillsChanged
+    if let Some(skills) = manager.skills() {
+        if let Err(e) = skills.watch(routes::event_emitter(event_tx.clone())).await {
+            tracing::warn!("Not watching skills for changes: {}", e);
+        }
+    }
+
     // Determine static file directory
     // Try environment variable first, then relative paths
     let static_dir = std::env::var("STATIC_DIR")
@@ -56,9 +131,39 @@ async fn main() {
         .route("/api/agents/:id", axum::routing::delete(routes::stop_agent))
         .route("/api/agents", get(routes::list_agents))
         .route("/api/agents/all", axum::routing::delete(routes::stop_all_agents))
-        .route("/api/cli/check/:cli", get(routes::check_cli_available))
-        .route("/api/skills", get(routes::list_skills))
-        .route("/api/skills/:name", get(routes::get_skill))
+        .route("/api/agents/:id/messages", post(routes::send_message))
+        .route("/api/agents/:id/input", post(routes::send_input).delete(routes::close_input))
+        .route("/api/agents/:id/worktree", get(routes::get_worktree).delete(routes::discard_worktree))
+        .route("/api/agents/:id/worktree/merge", post(routes::merge_worktree))
+        .route("/api/agents/:id/permissions/:request_id", post(routes::respond_permission))
+        .route("/api/permissions", get(routes::list_permissions))
+        .route("/api/workflows", post(routes::start_workflow).get(routes::list_workflows))
+        .route("/api/workflows/:id", get(routes::get_workflow).delete(routes::cancel_workflow))
+        .route("/api/queue", get(routes::list_queue))
+        .route("/api/queue/:id", axum::routing::delete(routes::cancel_queued))
+        .route("/api/cli", get(routes::list_clis))
+        .route("/api/history", get(routes::list_runs))
+        .route("/api/history/:id", get(routes::get_run).delete(routes::delete_run))
+        .route("/api/usage", get(routes::usage_report))
+        .route("/api/workspaces", get(routes::list_workspaces).post(routes::create_workspace))
+        .route(
+            "/api/workspaces/:id",
+            get(routes::get_workspace).put(routes::update_workspace).delete(routes::delete_workspace),
+        )
+        .route("/api/secrets", get(routes::list_secrets))
+        .route("/api/secrets/:name", axum::routing::put(routes::set_secret).delete(routes::delete_secret))
+        .route("/api/skills", get(routes::list_skills).post(routes::create_skill))
+        .route(
+            "/api/skills/:name",
+            get(routes::get_skill).put(routes::update_skill).delete(routes::delete_skill),
+        )
+        .route("/api/skills/:name/rename", post(routes::rename_skill))
+        .route("/api/skills/:name/export", get(routes::export_skill))
+        .route(
+            "/api/skill-bundles",
+            post(routes::import_skill).layer(DefaultBodyLimit::max(MAX_BUNDLE_SIZE as usize)),
+        )
+        .route("/api/skill-bundles/git", post(routes::import_skill_from_git))
         // WebSocket route
         .route("/ws", get(websocket::websocket_handler))
         // Static file serving (for frontend)
@@ -1,38 +1,216 @@
 use axum::{
-    extract::{Extension, Path},
-    http::StatusCode,
-    response::Json,
+    body::Bytes,
+    extract::{Extension, Path, Query},
+    http::{header, StatusCode},
+    response::{IntoResponse, Json, Response},
 };
 use std::sync::Arc;
 use tokio::sync::broadcast;
-use giga_command_center_core::{AgentManager, AgentConfig, AgentId, SkillInfo, SkillDetail, AgentEvent};
-use tokio::process::Command;
-use tokio::fs;
-use std::path::PathBuf;
+use giga_command_center_core::{AgentManager, AgentConfig, AgentId, CliInfo, SecretEntry, SecretVault, SkillInfo, WorktreeInfo, SkillDetail, SkillLocation, NewSkill, SkillUpdate, SkillsRepository, BundleFormat, GitSkillSource, ImportOptions, SkillSource, AgentEvent, AgentError, HistoryStore, PendingPermission, PermissionDecision, QueueEntry, RunRecord, RunSummary, UsageReport, WorkflowDefinition, WorkflowId, WorkflowInfo, NewWorkspace, Workspace, WorkspaceSpec, WorkspaceStore};
+use serde::Deserialize;
+
+/// Event emitter that forwards every event to the WebSocket broadcast channel.
+pub fn event_emitter(
+    event_tx: Arc<broadcast::Sender<String>>,
+) -> impl Fn(AgentEvent) + Send + Sync + Clone + 'static {
+    move |event: AgentEvent| {
+        if let Ok(json) = serde_json::to_string(&event) {
+            let _ = event_tx.send(json);
+        }
+    }
+}
+
+fn start_error_status(e: AgentError) -> StatusCode {
+    tracing::error!("Failed to start agent: {}", e);
+    match e {
+        AgentError::CliNotAvailable => StatusCode::SERVICE_UNAVAILABLE,
+        AgentError::AlreadyRunning => StatusCode::CONFLICT,
+        AgentError::NotFound => StatusCode::NOT_FOUND,
+        AgentError::NotSupported(_) => StatusCode::BAD_REQUEST,
+        AgentError::SecretNotFound(_) => StatusCode::BAD_REQUEST,
+        AgentError::Git(_) => StatusCode::BAD_REQUEST,
+        AgentError::InvalidWorkflow(_) => StatusCode::BAD_REQUEST,
+        AgentError::InvalidSkill(_) | AgentError::SkillNotFound(_) => StatusCode::BAD_REQUEST,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
 
 pub async fn start_agent(
     Extension(manager): Extension<Arc<AgentManager>>,
     Extension(event_tx): Extension<Arc<broadcast::Sender<String>>>,
     Json(config): Json<AgentConfig>,
 ) -> Result<Json<AgentId>, StatusCode> {
-    // Create event emitter that sends to WebSocket channel
-    let emit_event = move |event: AgentEvent| {
-        if let Ok(json) = serde_json::to_string(&event) {
-            let _ = event_tx.send(json);
+    manager.start_agent(config, event_emitter(event_tx))
+        .await
+        .map(Json)
+        .map_err(start_error_status)
+}
+
+/// Starts a workflow of connected runs; returns its id.
+pub async fn start_workflow(
+    Extension(manager): Extension<Arc<AgentManager>>,
+    Extension(event_tx): Extension<Arc<broadcast::Sender<String>>>,
+    Json(definition): Json<WorkflowDefinition>,
+) -> Result<Json<WorkflowId>, StatusCode> {
+    manager.start_workflow(definition, event_emitter(event_tx))
+        .await
+        .map(Json)
+        .map_err(start_error_status)
+}
+
+pub async fn list_workflows(
+    Extension(manager): Extension<Arc<AgentManager>>,
+) -> Json<Vec<WorkflowInfo>> {
+    Json(manager.list_workflows().await)
+}
+
+pub async fn get_workflow(
+    Extension(manager): Extension<Arc<AgentManager>>,
+    Path(workflow_id): Path<WorkflowId>,
+) -> Result<Json<WorkflowInfo>, StatusCode> {
+    manager.workflow(&workflow_id)
+        .await
+        .map(Json)
+        .map_err(|_| StatusCode::NOT_FOUND)
+}
+
+/// Cancels a workflow: stops its running nodes and skips the rest.
+pub async fn cancel_workflow(
+    Extension(manager): Extension<Arc<AgentManager>>,
+    Path(workflow_id): Path<WorkflowId>,
+) -> StatusCode {
+    match manager.cancel_workflow(&workflow_id).await {
+        Ok(()) => StatusCode::NO_CONTENT,
+        Err(_) => StatusCode::NOT_FOUND,
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SendMessageRequest {
+    pub prompt: String,
+}
+
+/// Continues the conversation of agent (or session) `:id` with a follow-up prompt.
+/// Returns the id of the new run.
+pub async fn send_message(
+    Extension(manager): Extension<Arc<AgentManager>>,
+    Extension(event_tx): Extension<Arc<broadcast::Sender<String>>>,
+    Path(id): Path<String>,
+    Json(request): Json<SendMessageRequest>,
+) -> Result<Json<AgentId>, StatusCode> {
+    manager.continue_session(&id, request.prompt, event_emitter(event_tx))
+        .await
+        .map(Json)
+        .map_err(start_error_status)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SendInputRequest {
+    pub text: String,
+}
+
+fn input_error_status(e: AgentError) -> StatusCode {
+    match e {
+        AgentError::NotFound => StatusCode::NOT_FOUND,
+        AgentError::NotSupported(_) => StatusCode::CONFLICT,
+        _ => {
+            tracing::error!("Failed to send agent input: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
         }
-    };
-    
-    manager.start_agent(config, emit_event)
+    }
+}
+
+/// Writes a user message to a running interactive agent's stdin.
+pub async fn send_input(
+    Extension(manager): Extension<Arc<AgentManager>>,
+    Path(agent_id): Path<AgentId>,
+    Json(request): Json<SendInputRequest>,
+) -> Result<StatusCode, StatusCode> {
+    manager.send_input(&agent_id, request.text)
+        .await
+        .map(|_| StatusCode::NO_CONTENT)
+        .map_err(input_error_status)
+}
+
+/// Closes a running interactive agent's stdin so it can finish.
+pub async fn close_input(
+    Extension(manager): Extension<Arc<AgentManager>>,
+    Path(agent_id): Path<AgentId>,
+) -> Result<StatusCode, StatusCode> {
+    manager.close_input(&agent_id)
+        .await
+        .map(|_| StatusCode::NO_CONTENT)
+        .map_err(input_error_status)
+}
+
+/// Answers a permission request from a run in a gated permission mode.
+pub async fn respond_permission(
+    Extension(manager): Extension<Arc<AgentManager>>,
+    Path((agent_id, request_id)): Path<(AgentId, String)>,
+    Json(decision): Json<PermissionDecision>,
+) -> StatusCode {
+    match manager.respond_permission(&agent_id, &request_id, decision) {
+        Ok(()) => StatusCode::NO_CONTENT,
+        Err(_) => StatusCode::NOT_FOUND,
+    }
+}
+
+/// Permission requests still waiting for an answer.
+pub async fn list_permissions(
+    Extension(manager): Extension<Arc<AgentManager>>,
+) -> Json<Vec<PendingPermission>> {
+    Json(manager.pending_permissions())
+}
+
+fn worktree_status(e: AgentError) -> StatusCode {
+    match e {
+        AgentError::NotFound => StatusCode::NOT_FOUND,
+        AgentError::AlreadyRunning => StatusCode::CONFLICT,
+        AgentError::NotSupported(_) => StatusCode::CONFLICT,
+        AgentError::Git(_) => {
+            tracing::warn!("Worktree operation failed: {}", e);
+            StatusCode::CONFLICT
+        }
+        _ => {
+            tracing::error!("Worktree error: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+/// The git worktree of a run started with `isolation: "worktree"`.
+pub async fn get_worktree(
+    Extension(manager): Extension<Arc<AgentManager>>,
+    Path(agent_id): Path<AgentId>,
+) -> Result<Json<WorktreeInfo>, StatusCode> {
+    manager.worktree(&agent_id)
         .await
         .map(Json)
-        .map_err(|e| {
-            tracing::error!("Failed to start agent: {}", e);
-            match e {
-                giga_command_center_core::AgentError::CliNotAvailable => StatusCode::SERVICE_UNAVAILABLE,
-                giga_command_center_core::AgentError::AlreadyRunning => StatusCode::CONFLICT,
-                _ => StatusCode::INTERNAL_SERVER_ERROR,
-            }
-        })
+        .map_err(worktree_status)
+}
+
+/// Merges a finished run's branch into its repository's current branch.
+pub async fn merge_worktree(
+    Extension(manager): Extension<Arc<AgentManager>>,
+    Extension(event_tx): Extension<Arc<broadcast::Sender<String>>>,
+    Path(agent_id): Path<AgentId>,
+) -> Result<Json<WorktreeInfo>, StatusCode> {
+    manager.merge_worktree(&agent_id, event_emitter(event_tx))
+        .await
+        .map(Json)
+        .map_err(worktree_status)
+}
+
+/// Deletes a finished run's worktree and branch without merging.
+pub async fn discard_worktree(
+    Extension(manager): Extension<Arc<AgentManager>>,
+    Extension(event_tx): Extension<Arc<broadcast::Sender<String>>>,
+    Path(agent_id): Path<AgentId>,
+) -> Result<Json<WorktreeInfo>, StatusCode> {
+    manager.discard_worktree(&agent_id, event_emitter(event_tx))
+        .await
+        .map(Json)
+        .map_err(worktree_status)
 }
 
 pub async fn stop_agent(
@@ -45,7 +223,7 @@ pub async fn stop_agent(
         .map_err(|e| {
             tracing::debug!("Failed to stop agent {}: {}", agent_id, e);
             match e {
-                giga_command_center_core::AgentError::NotFound => StatusCode::NOT_FOUND,
+                AgentError::NotFound => StatusCode::NOT_FOUND,
                 _ => {
                     tracing::error!("Unexpected error stopping agent: {}", e);
                     StatusCode::INTERNAL_SERVER_ERROR
@@ -67,146 +245,418 @@ pub async fn list_agents(
     Json(manager.list_agents().await)
 }
 
-pub async fn check_cli_available(
-    Path(cli): Path<String>,
-) -> Json<bool> {
-    let binary = match cli.as_str() {
-        "claude" => "claude",
-        "cursor" => "agent",
-        "kilo" => "kilo",
-        "gemini" => "gemini",
-        "grok" => "grok",
-        "deepseek" => "deepseek",
-        _ => return Json(false),
-    };
-    
-    let result = Command::new(binary).arg("--version").output().await;
-    Json(result.map(|o| o.status.success()).unwrap_or(false))
+pub async fn list_queue(
+    Extension(manager): Extension<Arc<AgentManager>>,
+) -> Json<Vec<QueueEntry>> {
+    Json(manager.list_queue().await)
 }
 
-pub async fn list_skills() -> Result<Json<Vec<SkillInfo>>, StatusCode> {
-    let home = dirs::home_dir().ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
-    let skills_dir = home.join(".claude").join("skills");
-
-    if !skills_dir.exists() {
-        return Ok(Json(vec![]));
+pub async fn cancel_queued(
+    Extension(manager): Extension<Arc<AgentManager>>,
+    Path(agent_id): Path<AgentId>,
+) -> StatusCode {
+    match manager.cancel_queued(&agent_id).await {
+        Ok(()) => StatusCode::NO_CONTENT,
+        Err(_) => StatusCode::NOT_FOUND,
     }
+}
 
-    let mut skills = Vec::new();
-    let mut entries = fs::read_dir(&skills_dir).await.map_err(|e| {
-        tracing::error!("Failed to read skills directory: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
-
-    while let Some(entry) = entries.next_entry().await.map_err(|e| {
-        tracing::error!("Failed to read directory entry: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })? {
-        let path = entry.path();
-        let metadata = entry.metadata().await.map_err(|e| {
-            tracing::error!("Failed to read entry metadata: {}", e);
+fn history_store(manager: &AgentManager) -> Result<&HistoryStore, StatusCode> {
+    manager.history().ok_or_else(|| {
+        tracing::warn!("Run history requested but history is disabled");
+        StatusCode::SERVICE_UNAVAILABLE
+    })
+}
+
+fn history_status(e: AgentError) -> StatusCode {
+    match e {
+        AgentError::NotFound => StatusCode::NOT_FOUND,
+        AgentError::AlreadyRunning => StatusCode::CONFLICT,
+        _ => {
+            tracing::error!("History error: {}", e);
             StatusCode::INTERNAL_SERVER_ERROR
-        })?;
-        
-        if metadata.is_dir() {
-            let skill_md = path.join("SKILL.md");
-            if skill_md.exists() {
-                if let Ok(content) = fs::read_to_string(&skill_md).await {
-                    if let Some(info) = parse_skill_frontmatter(&content, &path) {
-                        skills.push(info);
-                    }
-                }
-            }
         }
     }
-
-    Ok(Json(skills))
 }
 
-pub async fn get_skill(
-    Path(skill_name): Path<String>,
-) -> Result<Json<SkillDetail>, StatusCode> {
-    let home = dirs::home_dir().ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
-    let skill_path = home.join(".claude").join("skills").join(&skill_name);
-    let skill_md = skill_path.join("SKILL.md");
+pub async fn list_runs(
+    Extension(manager): Extension<Arc<AgentManager>>,
+) -> Result<Json<Vec<RunSummary>>, StatusCode> {
+    history_store(&manager)?
+        .list()
+        .await
+        .map(Json)
+        .map_err(history_status)
+}
 
-    if !skill_md.exists() {
-        return Err(StatusCode::NOT_FOUND);
-    }
+pub async fn get_run(
+    Extension(manager): Extension<Arc<AgentManager>>,
+    Path(agent_id): Path<AgentId>,
+) -> Result<Json<RunRecord>, StatusCode> {
+    history_store(&manager)?
+        .get(&agent_id)
+        .await
+        .map(Json)
+        .map_err(history_status)
+}
 
-    let content = fs::read_to_string(&skill_md).await.map_err(|e| {
-        tracing::error!("Failed to read skill file: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
-    let info = parse_skill_frontmatter(&content, &skill_path)
-        .ok_or_else(|| {
-            tracing::error!("Failed to parse skill frontmatter for: {}", skill_name);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+pub async fn delete_run(
+    Extension(manager): Extension<Arc<AgentManager>>,
+    Path(agent_id): Path<AgentId>,
+) -> Result<StatusCode, StatusCode> {
+    history_store(&manager)?;
+    manager
+        .delete_run(&agent_id)
+        .await
+        .map(|_| StatusCode::NO_CONTENT)
+        .map_err(history_status)
+}
 
-    let markdown = extract_markdown_content(&content);
+pub async fn usage_report(
+    Extension(manager): Extension<Arc<AgentManager>>,
+) -> Result<Json<UsageReport>, StatusCode> {
+    history_store(&manager)?
+        .usage_report()
+        .await
+        .map(Json)
+        .map_err(history_status)
+}
 
-    Ok(Json(SkillDetail {
-        info,
-        markdown,
-        path: skill_path.to_string_lossy().to_string(),
-    }))
+#[derive(Debug, Default, Deserialize)]
+pub struct ListClisQuery {
+    /// Probe every CLI again instead of returning cached results.
+    #[serde(default)]
+    pub refresh: bool,
 }
 
-fn parse_skill_frontmatter(content: &str, path: &PathBuf) -> Option<SkillInfo> {
-    let lines: Vec<&str> = content.lines().collect();
+/// Every registered CLI backend with its path, version and capabilities.
+pub async fn list_clis(
+    Extension(manager): Extension<Arc<AgentManager>>,
+    Query(query): Query<ListClisQuery>,
+) -> Json<Vec<CliInfo>> {
+    Json(manager.registry().detect(query.refresh).await)
+}
 
-    if lines.first()? != &"---" {
-        let name = path.file_name()?.to_string_lossy().to_string();
-        return Some(SkillInfo {
-            name: name.clone(),
-            description: format!("Custom skill: {}", name),
-        });
-    }
+fn secret_vault(manager: &AgentManager) -> Result<Arc<SecretVault>, StatusCode> {
+    manager.vault().ok_or_else(|| {
+        tracing::warn!("Secrets requested but the vault is disabled");
+        StatusCode::SERVICE_UNAVAILABLE
+    })
+}
 
-    let mut end_index = None;
-    for (i, line) in lines.iter().enumerate().skip(1) {
-        if *line == "---" {
-            end_index = Some(i);
-            break;
+fn secret_status(e: AgentError) -> StatusCode {
+    match e {
+        AgentError::SecretNotFound(_) => StatusCode::NOT_FOUND,
+        AgentError::NotSupported(_) => StatusCode::BAD_REQUEST,
+        _ => {
+            tracing::error!("Vault error: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
         }
     }
+}
 
-    let end_index = end_index?;
-    let frontmatter: Vec<&str> = lines[1..end_index].to_vec();
+/// Names and timestamps of every secret in the vault; values are never returned.
+pub async fn list_secrets(
+    Extension(manager): Extension<Arc<AgentManager>>,
+) -> Result<Json<Vec<SecretEntry>>, StatusCode> {
+    Ok(Json(secret_vault(&manager)?.list()))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetSecretRequest {
+    pub value: String,
+}
 
-    let mut name = path.file_name()?.to_string_lossy().to_string();
-    let mut description = String::new();
+/// Adds or replaces a secret, for `AgentConfig::env` to reference as `${secret:NAME}`.
+pub async fn set_secret(
+    Extension(manager): Extension<Arc<AgentManager>>,
+    Path(name): Path<String>,
+    Json(request): Json<SetSecretRequest>,
+) -> Result<Json<SecretEntry>, StatusCode> {
+    secret_vault(&manager)?
+        .set(&name, &request.value)
+        .map(Json)
+        .map_err(secret_status)
+}
 
-    for line in frontmatter {
-        if let Some(value) = line.strip_prefix("name:") {
-            name = value.trim().to_string();
-        } else if let Some(value) = line.strip_prefix("description:") {
-            description = value.trim().to_string();
+pub async fn delete_secret(
+    Extension(manager): Extension<Arc<AgentManager>>,
+    Path(name): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    secret_vault(&manager)?
+        .remove(&name)
+        .map(|_| StatusCode::NO_CONTENT)
+        .map_err(secret_status)
+}
+
+fn workspace_store(manager: &AgentManager) -> Result<Arc<WorkspaceStore>, StatusCode> {
+    manager.workspaces().ok_or_else(|| {
+        tracing::warn!("Workspaces requested but workspace storage is disabled");
+        StatusCode::SERVICE_UNAVAILABLE
+    })
+}
+
+fn workspace_status(e: AgentError) -> StatusCode {
+    match e {
+        AgentError::NotFound => StatusCode::NOT_FOUND,
+        AgentError::InvalidWorkspace(_) => StatusCode::BAD_REQUEST,
+        _ => {
+            tracing::error!("Workspace storage error: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
         }
     }
+}
 
-    Some(SkillInfo { name, description })
+/// Every stored workspace, oldest first.
+pub async fn list_workspaces(
+    Extension(manager): Extension<Arc<AgentManager>>,
+) -> Result<Json<Vec<Workspace>>, StatusCode> {
+    Ok(Json(workspace_store(&manager)?.list()))
 }
 
-fn extract_markdown_content(content: &str) -> String {
-    let lines: Vec<&str> = content.lines().collect();
+pub async fn get_workspace(
+    Extension(manager): Extension<Arc<AgentManager>>,
+    Path(workspace_id): Path<String>,
+) -> Result<Json<Workspace>, StatusCode> {
+    workspace_store(&manager)?
+        .get(&workspace_id)
+        .map(Json)
+        .map_err(workspace_status)
+}
 
-    if lines.first().map(|l| *l) != Some("---") {
-        return content.to_string();
-    }
+/// Stores a new workspace; every client hears about it as `WorkspaceChanged`.
+pub async fn create_workspace(
+    Extension(manager): Extension<Arc<AgentManager>>,
+    Extension(event_tx): Extension<Arc<broadcast::Sender<String>>>,
+    Json(workspace): Json<NewWorkspace>,
+) -> Result<Json<Workspace>, StatusCode> {
+    workspace_store(&manager)?
+        .create(workspace, event_emitter(event_tx))
+        .map(Json)
+        .map_err(workspace_status)
+}
+
+/// Replaces a workspace's settings; connections are mirrored on the other end.
+pub async fn update_workspace(
+    Extension(manager): Extension<Arc<AgentManager>>,
+    Extension(event_tx): Extension<Arc<broadcast::Sender<String>>>,
+    Path(workspace_id): Path<String>,
+    Json(spec): Json<WorkspaceSpec>,
+) -> Result<Json<Workspace>, StatusCode> {
+    workspace_store(&manager)?
+        .update(&workspace_id, spec, event_emitter(event_tx))
+        .map(Json)
+        .map_err(workspace_status)
+}
 
-    let mut end_index = None;
-    for (i, line) in lines.iter().enumerate().skip(1) {
-        if *line == "---" {
-            end_index = Some(i);
-            break;
+pub async fn delete_workspace(
+    Extension(manager): Extension<Arc<AgentManager>>,
+    Extension(event_tx): Extension<Arc<broadcast::Sender<String>>>,
+    Path(workspace_id): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    workspace_store(&manager)?
+        .delete(&workspace_id, event_emitter(event_tx))
+        .map(|_| StatusCode::NO_CONTENT)
+        .map_err(workspace_status)
+}
+
+fn skills_repository(manager: &AgentManager) -> Result<Arc<SkillsRepository>, StatusCode> {
+    manager.skills().ok_or_else(|| {
+        tracing::warn!("Skills requested but no skills directory is configured");
+        StatusCode::SERVICE_UNAVAILABLE
+    })
+}
+
+fn skill_status(e: AgentError) -> StatusCode {
+    match e {
+        AgentError::NotFound | AgentError::SkillNotFound(_) => StatusCode::NOT_FOUND,
+        AgentError::InvalidSkill(_) | AgentError::Git(_) => StatusCode::BAD_REQUEST,
+        AgentError::SkillExists(_) => StatusCode::CONFLICT,
+        _ => {
+            tracing::error!("Skills error: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
         }
     }
+}
 
-    if let Some(end_index) = end_index {
-        lines[end_index + 1..].join("\n").trim().to_string()
-    } else {
-        content.to_string()
-    }
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListSkillsQuery {
+    /// Also list the project skills under `<working_directory>/.claude/skills`.
+    #[serde(default)]
+    pub working_directory: Option<String>,
+}
+
+/// User, plugin and (with `workingDirectory`) project skills.
+pub async fn list_skills(
+    Extension(manager): Extension<Arc<AgentManager>>,
+    Query(query): Query<ListSkillsQuery>,
+) -> Result<Json<Vec<SkillInfo>>, StatusCode> {
+    skills_repository(&manager)?
+        .list(query.working_directory.as_deref().map(std::path::Path::new))
+        .await
+        .map(Json)
+        .map_err(skill_status)
+}
+
+/// A user skill, or with `source` (and `plugin` or `workingDirectory`) a plugin or
+/// project skill.
+pub async fn get_skill(
+    Extension(manager): Extension<Arc<AgentManager>>,
+    Path(skill_name): Path<String>,
+    Query(location): Query<SkillLocation>,
+) -> Result<Json<SkillDetail>, StatusCode> {
+    skills_repository(&manager)?
+        .get_at(&skill_name, &location)
+        .await
+        .map(Json)
+        .map_err(skill_status)
+}
+
+/// Writes a new skill directory with its `SKILL.md`; 409 if the name is taken.
+pub async fn create_skill(
+    Extension(manager): Extension<Arc<AgentManager>>,
+    Json(skill): Json<NewSkill>,
+) -> Result<Json<SkillDetail>, StatusCode> {
+    skills_repository(&manager)?
+        .create(skill)
+        .await
+        .map(Json)
+        .map_err(skill_status)
+}
+
+/// Replaces a skill's description and instructions.
+pub async fn update_skill(
+    Extension(manager): Extension<Arc<AgentManager>>,
+    Path(skill_name): Path<String>,
+    Json(update): Json<SkillUpdate>,
+) -> Result<Json<SkillDetail>, StatusCode> {
+    skills_repository(&manager)?
+        .update(&skill_name, update)
+        .await
+        .map(Json)
+        .map_err(skill_status)
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RenameSkillRequest {
+    pub new_name: String,
+}
+
+pub async fn rename_skill(
+    Extension(manager): Extension<Arc<AgentManager>>,
+    Path(skill_name): Path<String>,
+    Json(request): Json<RenameSkillRequest>,
+) -> Result<Json<SkillDetail>, StatusCode> {
+    skills_repository(&manager)?
+        .rename(&skill_name, &request.new_name)
+        .await
+        .map(Json)
+        .map_err(skill_status)
+}
+
+pub async fn delete_skill(
+    Extension(manager): Extension<Arc<AgentManager>>,
+    Path(skill_name): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    skills_repository(&manager)?
+        .delete(&skill_name)
+        .await
+        .map(|_| StatusCode::NO_CONTENT)
+        .map_err(skill_status)
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportSkillQuery {
+    #[serde(default)]
+    pub format: BundleFormat,
+    #[serde(default)]
+    pub source: SkillSource,
+    #[serde(default)]
+    pub plugin: Option<String>,
+    #[serde(default)]
+    pub working_directory: Option<String>,
+}
+
+/// The skill's directory as a zip or gzipped tar bundle, located like `get_skill`.
+pub async fn export_skill(
+    Extension(manager): Extension<Arc<AgentManager>>,
+    Path(skill_name): Path<String>,
+    Query(query): Query<ExportSkillQuery>,
+) -> Result<Response, StatusCode> {
+    let location = SkillLocation {
+        source: query.source,
+        plugin: query.plugin,
+        working_directory: query.working_directory,
+    };
+    let bundle = skills_repository(&manager)?
+        .export(&skill_name, &location, query.format)
+        .await
+        .map_err(skill_status)?;
+    let file_stem: String = skill_name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' { c } else { '_' })
+        .collect();
+    let disposition = format!(
+        "attachment; filename=\"{}.{}\"",
+        file_stem,
+        query.format.extension()
+    );
+    Ok((
+        [
+            (header::CONTENT_TYPE, query.format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        bundle,
+    )
+        .into_response())
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportSkillQuery {
+    #[serde(default)]
+    pub format: BundleFormat,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub overwrite: bool,
+}
+
+/// Installs the bundle in the request body as a user skill; 409 if the name is
+/// taken and `overwrite` isn't set.
+pub async fn import_skill(
+    Extension(manager): Extension<Arc<AgentManager>>,
+    Query(query): Query<ImportSkillQuery>,
+    body: Bytes,
+) -> Result<Json<SkillDetail>, StatusCode> {
+    let options = ImportOptions { name: query.name, overwrite: query.overwrite };
+    skills_repository(&manager)?
+        .import(body.to_vec(), query.format, options)
+        .await
+        .map(Json)
+        .map_err(skill_status)
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportGitSkillRequest {
+    #[serde(flatten)]
+    pub source: GitSkillSource,
+    #[serde(flatten)]
+    pub options: ImportOptions,
+}
+
+/// Installs a skill committed in a local git repository.
+pub async fn import_skill_from_git(
+    Extension(manager): Extension<Arc<AgentManager>>,
+    Json(request): Json<ImportGitSkillRequest>,
+) -> Result<Json<SkillDetail>, StatusCode> {
+    skills_repository(&manager)?
+        .import_git(request.source, request.options)
+        .await
+        .map(Json)
+        .map_err(skill_status)
 }
@@ -3,20 +3,40 @@ use axum::{
     response::Response,
 };
 use futures_util::{SinkExt, StreamExt};
+use serde::Deserialize;
 use std::sync::Arc;
 use tokio::sync::broadcast;
-use giga_command_center_core::AgentManager;
+use giga_command_center_core::{AgentEvent, AgentId, AgentManager, PermissionBehavior, PermissionDecision};
+
+/// Messages a client may send over the WebSocket.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type")]
+enum ClientMessage {
+    /// Write a user message to a running interactive agent's stdin.
+    SendInput { agent_id: AgentId, text: String },
+    /// Close a running interactive agent's stdin.
+    CloseInput { agent_id: AgentId },
+    /// Answer a run's permission request.
+    RespondPermission {
+        agent_id: AgentId,
+        request_id: String,
+        behavior: PermissionBehavior,
+        #[serde(default)]
+        message: Option<String>,
+    },
+}
 
 pub async fn websocket_handler(
     ws: WebSocketUpgrade,
-    Extension(_manager): Extension<Arc<AgentManager>>,
+    Extension(manager): Extension<Arc<AgentManager>>,
     Extension(event_tx): Extension<Arc<broadcast::Sender<String>>>,
 ) -> Response {
-    ws.on_upgrade(|socket| handle_socket(socket, event_tx))
+    ws.on_upgrade(|socket| handle_socket(socket, manager, event_tx))
 }
 
 async fn handle_socket(
     socket: axum::extract::ws::WebSocket,
+    manager: Arc<AgentManager>,
     event_tx: Arc<broadcast::Sender<String>>,
 ) {
     let (mut sender, mut receiver) = socket.split();
@@ -34,8 +54,12 @@ async fn handle_socket(
     // Spawn task to receive messages from WebSocket
     let recv_task = tokio::spawn(async move {
         while let Some(Ok(msg)) = receiver.next().await {
-            if let axum::extract::ws::Message::Close(_) = msg {
-                break;
+            match msg {
+                axum::extract::ws::Message::Text(text) => {
+                    handle_client_message(&manager, &event_tx, &text).await;
+                }
+                axum::extract::ws::Message::Close(_) => break,
+                _ => {}
             }
         }
     });
@@ -52,3 +76,45 @@ async fn handle_socket(
         },
     };
 }
+
+async fn handle_client_message(
+    manager: &AgentManager,
+    event_tx: &broadcast::Sender<String>,
+    text: &str,
+) {
+    let message = match serde_json::from_str::<ClientMessage>(text) {
+        Ok(message) => message,
+        Err(e) => {
+            tracing::warn!("Ignoring invalid WebSocket message: {}", e);
+            return;
+        }
+    };
+
+    let (agent_id, result) = match message {
+        ClientMessage::SendInput { agent_id, text } => {
+            let result = manager.send_input(&agent_id, text).await;
+            (agent_id, result)
+        }
+        ClientMessage::CloseInput { agent_id } => {
+            let result = manager.close_input(&agent_id).await;
+            (agent_id, result)
+        }
+        ClientMessage::RespondPermission { agent_id, request_id, behavior, message } => {
+            let decision = PermissionDecision { behavior, message };
+            let result = manager.respond_permission(&agent_id, &request_id, decision);
+            (agent_id, result)
+        }
+    };
+
+    // Report failures on the agent's own event stream so the UI shows them in its log.
+    if let Err(e) = result {
+        tracing::warn!("WebSocket input for agent {} failed: {}", agent_id, e);
+        let event = AgentEvent::Error {
+            agent_id,
+            message: e.to_string(),
+        };
+        if let Ok(json) = serde_json::to_string(&event) {
+            let _ = event_tx.send(json);
+        }
+    }
+}
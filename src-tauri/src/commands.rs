@@ -1,10 +1,17 @@
-use std::path::PathBuf;
 use std::sync::Arc;
-use tokio::fs;
-use tokio::process::Command;
 use tauri::{AppHandle, Emitter, State};
 
-use giga_command_center_core::{AgentManager, AgentConfig, AgentEvent, AgentId, StopReason, SkillInfo, SkillDetail};
+use giga_command_center_core::{AgentManager, AgentConfig, AgentEvent, AgentId, CliInfo, SecretEntry, SecretVault, SkillInfo, WorktreeInfo, SkillDetail, SkillLocation, NewSkill, SkillUpdate, SkillsRepository, BundleFormat, GitSkillSource, ImportOptions, HistoryStore, PendingPermission, PermissionDecision, QueueEntry, RunRecord, RunSummary, UsageReport, WorkflowDefinition, WorkflowId, WorkflowInfo, NewWorkspace, Workspace, WorkspaceSpec, WorkspaceStore};
+
+/// Event emitter that forwards every event to the frontend as `agent-event`.
+pub fn event_emitter(app: AppHandle) -> impl Fn(AgentEvent) + Send + Sync + Clone + 'static {
+    move |event: AgentEvent| {
+        println!("[CCC] Emitting event: {:?}", event);
+        if let Err(e) = app.emit("agent-event", &event) {
+            eprintln!("[CCC] Failed to emit event: {}", e);
+        }
+    }
+}
 
 #[tauri::command]
 pub async fn start_agent(
@@ -14,15 +21,7 @@ pub async fn start_agent(
 ) -> Result<AgentId, String> {
     println!("[CCC] Starting agent with prompt: {}", config.prompt);
 
-    let app_clone = app.clone();
-    let emit_event = move |event: AgentEvent| {
-        println!("[CCC] Emitting event: {:?}", event);
-        if let Err(e) = app_clone.emit("agent-event", &event) {
-            eprintln!("[CCC] Failed to emit event: {}", e);
-        }
-    };
-
-    match manager.start_agent(config, emit_event).await {
+    match manager.start_agent(config, event_emitter(app)).await {
         Ok(agent_id) => {
             println!("[CCC] Agent started successfully: {}", agent_id);
             Ok(agent_id)
@@ -34,26 +33,161 @@ pub async fn start_agent(
     }
 }
 
+/// Continues the conversation of agent (or session) `id` with a follow-up prompt.
+/// Returns the id of the new run.
 #[tauri::command]
-pub async fn stop_agent(
+pub async fn continue_session(
     app: AppHandle,
+    manager: State<'_, Arc<AgentManager>>,
+    id: String,
+    prompt: String,
+) -> Result<AgentId, String> {
+    manager
+        .continue_session(&id, prompt, event_emitter(app))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Writes a user message to a running interactive agent's stdin.
+#[tauri::command]
+pub async fn send_input(
     manager: State<'_, Arc<AgentManager>>,
     agent_id: AgentId,
+    text: String,
 ) -> Result<(), String> {
     manager
-        .stop_agent(&agent_id)
+        .send_input(&agent_id, text)
         .await
-        .map_err(|e| e.to_string())?;
+        .map_err(|e| e.to_string())
+}
 
-    let _ = app.emit(
-        "agent-event",
-        &AgentEvent::Stopped {
-            agent_id,
-            reason: StopReason::Cancelled,
-        },
-    );
+/// Closes a running interactive agent's stdin so it can finish.
+#[tauri::command]
+pub async fn close_input(
+    manager: State<'_, Arc<AgentManager>>,
+    agent_id: AgentId,
+) -> Result<(), String> {
+    manager
+        .close_input(&agent_id)
+        .await
+        .map_err(|e| e.to_string())
+}
 
-    Ok(())
+/// Starts a workflow of connected runs; node and run events go out as `agent-event`.
+#[tauri::command]
+pub async fn start_workflow(
+    app: AppHandle,
+    manager: State<'_, Arc<AgentManager>>,
+    definition: WorkflowDefinition,
+) -> Result<WorkflowId, String> {
+    manager
+        .start_workflow(definition, event_emitter(app))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn list_workflows(
+    manager: State<'_, Arc<AgentManager>>,
+) -> Result<Vec<WorkflowInfo>, String> {
+    Ok(manager.list_workflows().await)
+}
+
+#[tauri::command]
+pub async fn get_workflow(
+    manager: State<'_, Arc<AgentManager>>,
+    workflow_id: WorkflowId,
+) -> Result<WorkflowInfo, String> {
+    manager
+        .workflow(&workflow_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Cancels a workflow: stops its running nodes and skips the rest.
+#[tauri::command]
+pub async fn cancel_workflow(
+    manager: State<'_, Arc<AgentManager>>,
+    workflow_id: WorkflowId,
+) -> Result<(), String> {
+    manager
+        .cancel_workflow(&workflow_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Answers a permission request from a run in a gated permission mode.
+#[tauri::command]
+pub async fn respond_permission(
+    manager: State<'_, Arc<AgentManager>>,
+    agent_id: AgentId,
+    request_id: String,
+    decision: PermissionDecision,
+) -> Result<(), String> {
+    manager
+        .respond_permission(&agent_id, &request_id, decision)
+        .map_err(|e| e.to_string())
+}
+
+/// Permission requests still waiting for an answer.
+#[tauri::command]
+pub async fn list_permission_requests(
+    manager: State<'_, Arc<AgentManager>>,
+) -> Result<Vec<PendingPermission>, String> {
+    Ok(manager.pending_permissions())
+}
+
+/// The git worktree of a run started with `isolation: "worktree"`.
+#[tauri::command]
+pub async fn get_worktree(
+    manager: State<'_, Arc<AgentManager>>,
+    agent_id: AgentId,
+) -> Result<WorktreeInfo, String> {
+    manager
+        .worktree(&agent_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Merges a finished run's branch into its repository's current branch.
+#[tauri::command]
+pub async fn merge_worktree(
+    app: AppHandle,
+    manager: State<'_, Arc<AgentManager>>,
+    agent_id: AgentId,
+) -> Result<WorktreeInfo, String> {
+    manager
+        .merge_worktree(&agent_id, event_emitter(app))
+        .await
+        .map_err(|e| {
+            eprintln!("[CCC] Failed to merge worktree of {}: {}", agent_id, e);
+            e.to_string()
+        })
+}
+
+/// Deletes a finished run's worktree and branch without merging.
+#[tauri::command]
+pub async fn discard_worktree(
+    app: AppHandle,
+    manager: State<'_, Arc<AgentManager>>,
+    agent_id: AgentId,
+) -> Result<WorktreeInfo, String> {
+    manager
+        .discard_worktree(&agent_id, event_emitter(app))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn stop_agent(
+    manager: State<'_, Arc<AgentManager>>,
+    agent_id: AgentId,
+) -> Result<(), String> {
+    // The manager emits `Stopped { reason: cancelled }` through the agent's own emitter.
+    manager
+        .stop_agent(&agent_id)
+        .await
+        .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
@@ -68,192 +202,279 @@ pub async fn list_agents(manager: State<'_, Arc<AgentManager>>) -> Result<Vec<Ag
 }
 
 #[tauri::command]
-pub async fn check_cli_available() -> Result<bool, String> {
-    match Command::new("claude").arg("--version").output().await {
-        Ok(output) => Ok(output.status.success()),
-        Err(_) => Ok(false),
-    }
+pub async fn list_queue(manager: State<'_, Arc<AgentManager>>) -> Result<Vec<QueueEntry>, String> {
+    Ok(manager.list_queue().await)
 }
 
-/// Check if the Cursor Agent CLI (`agent`) is available.
-/// Install: curl https://cursor.com/install -fsS | bash
 #[tauri::command]
-pub async fn check_cursor_cli_available() -> Result<bool, String> {
-    // agent --version or agent -h; --version is more likely to exit 0 when present
-    match Command::new("agent").arg("--version").output().await {
-        Ok(output) => Ok(output.status.success()),
-        Err(_) => Ok(false),
-    }
+pub async fn cancel_queued(
+    manager: State<'_, Arc<AgentManager>>,
+    agent_id: AgentId,
+) -> Result<(), String> {
+    manager
+        .cancel_queued(&agent_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+fn history_store(manager: &AgentManager) -> Result<&HistoryStore, String> {
+    manager
+        .history()
+        .ok_or_else(|| "Run history is disabled".to_string())
 }
 
-/// Check if the Kilo CLI (`kilo` or `kilocode`) is available.
-/// Install: npm install -g @kilocode/cli
 #[tauri::command]
-pub async fn check_kilo_cli_available() -> Result<bool, String> {
-    // Try `kilo` first, then `kilocode` as fallback
-    let kilo_check = Command::new("kilo").arg("--version").output().await;
-    if let Ok(output) = kilo_check {
-        if output.status.success() {
-            return Ok(true);
-        }
-    }
-    
-    // Fallback to kilocode
-    match Command::new("kilocode").arg("--version").output().await {
-        Ok(output) => Ok(output.status.success()),
-        Err(_) => Ok(false),
-    }
+pub async fn list_runs(manager: State<'_, Arc<AgentManager>>) -> Result<Vec<RunSummary>, String> {
+    history_store(&manager)?
+        .list()
+        .await
+        .map_err(|e| e.to_string())
 }
 
-/// Check if the Gemini CLI (`gemini`) is available.
-/// Install: npm install -g @google/gemini-cli
 #[tauri::command]
-pub async fn check_gemini_cli_available() -> Result<bool, String> {
-    match Command::new("gemini").arg("--version").output().await {
-        Ok(output) => Ok(output.status.success()),
-        Err(_) => Ok(false),
-    }
+pub async fn get_run(
+    manager: State<'_, Arc<AgentManager>>,
+    agent_id: AgentId,
+) -> Result<RunRecord, String> {
+    history_store(&manager)?
+        .get(&agent_id)
+        .await
+        .map_err(|e| e.to_string())
 }
 
-/// Check if the Grok CLI (`grok`) is available.
-/// Install: bun add -g @vibe-kit/grok-cli or npm install -g @vibe-kit/grok-cli
 #[tauri::command]
-pub async fn check_grok_cli_available() -> Result<bool, String> {
-    match Command::new("grok").arg("--version").output().await {
-        Ok(output) => Ok(output.status.success()),
-        Err(_) => Ok(false),
-    }
+pub async fn delete_run(
+    manager: State<'_, Arc<AgentManager>>,
+    agent_id: AgentId,
+) -> Result<(), String> {
+    manager
+        .delete_run(&agent_id)
+        .await
+        .map_err(|e| e.to_string())
 }
 
-/// Check if the DeepSeek CLI (`deepseek`) is available.
-/// Install: pip install deepseek-cli
 #[tauri::command]
-pub async fn check_deepseek_cli_available() -> Result<bool, String> {
-    match Command::new("deepseek").arg("--version").output().await {
-        Ok(output) => Ok(output.status.success()),
-        Err(_) => Ok(false),
-    }
+pub async fn usage_report(manager: State<'_, Arc<AgentManager>>) -> Result<UsageReport, String> {
+    history_store(&manager)?
+        .usage_report()
+        .await
+        .map_err(|e| e.to_string())
 }
 
+fn workspace_store(manager: &AgentManager) -> Result<Arc<WorkspaceStore>, String> {
+    manager
+        .workspaces()
+        .ok_or_else(|| "Workspace storage is disabled".to_string())
+}
+
+/// Every stored workspace, oldest first.
 #[tauri::command]
-pub async fn list_skills() -> Result<Vec<SkillInfo>, String> {
-    let home = dirs::home_dir().ok_or("Could not find home directory")?;
-    let skills_dir = home.join(".claude").join("skills");
+pub async fn list_workspaces(manager: State<'_, Arc<AgentManager>>) -> Result<Vec<Workspace>, String> {
+    Ok(workspace_store(&manager)?.list())
+}
 
-    if !skills_dir.exists() {
-        return Ok(vec![]);
-    }
+#[tauri::command]
+pub async fn get_workspace(
+    manager: State<'_, Arc<AgentManager>>,
+    workspace_id: String,
+) -> Result<Workspace, String> {
+    workspace_store(&manager)?
+        .get(&workspace_id)
+        .map_err(|e| e.to_string())
+}
 
-    let mut skills = Vec::new();
-    let mut entries = fs::read_dir(&skills_dir).await.map_err(|e| {
-        format!("Failed to read skills directory: {}", e)
-    })?;
-
-    while let Some(entry) = entries.next_entry().await.map_err(|e| {
-        format!("Failed to read directory entry: {}", e)
-    })? {
-        let path = entry.path();
-        let metadata = entry.metadata().await.map_err(|e| {
-            format!("Failed to read entry metadata: {}", e)
-        })?;
-        
-        if metadata.is_dir() {
-            let skill_md = path.join("SKILL.md");
-            if skill_md.exists() {
-                if let Ok(content) = fs::read_to_string(&skill_md).await {
-                    if let Some(info) = parse_skill_frontmatter(&content, &path) {
-                        skills.push(info);
-                    }
-                }
-            }
-        }
-    }
+/// Stores a new workspace; the frontend hears about it as `WorkspaceChanged`.
+#[tauri::command]
+pub async fn create_workspace(
+    app: AppHandle,
+    manager: State<'_, Arc<AgentManager>>,
+    workspace: NewWorkspace,
+) -> Result<Workspace, String> {
+    workspace_store(&manager)?
+        .create(workspace, event_emitter(app))
+        .map_err(|e| e.to_string())
+}
 
-    Ok(skills)
+/// Replaces a workspace's settings; connections are mirrored on the other end.
+#[tauri::command]
+pub async fn update_workspace(
+    app: AppHandle,
+    manager: State<'_, Arc<AgentManager>>,
+    workspace_id: String,
+    spec: WorkspaceSpec,
+) -> Result<Workspace, String> {
+    workspace_store(&manager)?
+        .update(&workspace_id, spec, event_emitter(app))
+        .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-pub async fn get_skill(skill_name: String) -> Result<SkillDetail, String> {
-    let home = dirs::home_dir().ok_or("Could not find home directory")?;
-    let skill_path = home.join(".claude").join("skills").join(&skill_name);
-    let skill_md = skill_path.join("SKILL.md");
+pub async fn delete_workspace(
+    app: AppHandle,
+    manager: State<'_, Arc<AgentManager>>,
+    workspace_id: String,
+) -> Result<(), String> {
+    workspace_store(&manager)?
+        .delete(&workspace_id, event_emitter(app))
+        .map_err(|e| e.to_string())
+}
 
-    if !skill_md.exists() {
-        return Err(format!("Skill '{}' not found", skill_name));
-    }
+fn secret_vault(manager: &AgentManager) -> Result<Arc<SecretVault>, String> {
+    manager
+        .vault()
+        .ok_or_else(|| "Secrets vault is disabled".to_string())
+}
 
-    let content = fs::read_to_string(&skill_md).await.map_err(|e| {
-        format!("Failed to read skill file '{}': {}", skill_name, e)
-    })?;
-    let info = parse_skill_frontmatter(&content, &skill_path)
-        .ok_or_else(|| format!("Failed to parse skill frontmatter for '{}'", skill_name))?;
-
-    // Extract content after frontmatter
-    let markdown = extract_markdown_content(&content);
-
-    Ok(SkillDetail {
-        info,
-        markdown,
-        path: skill_path.to_string_lossy().to_string(),
-    })
-}
-
-fn parse_skill_frontmatter(content: &str, path: &PathBuf) -> Option<SkillInfo> {
-    let lines: Vec<&str> = content.lines().collect();
-
-    // Look for YAML frontmatter
-    if lines.first()? != &"---" {
-        // No frontmatter, try to extract from directory name
-        let name = path.file_name()?.to_string_lossy().to_string();
-        return Some(SkillInfo {
-            name: name.clone(),
-            description: format!("Custom skill: {}", name),
-        });
-    }
+/// Names and timestamps of every secret in the vault; values are never returned.
+#[tauri::command]
+pub async fn list_secrets(manager: State<'_, Arc<AgentManager>>) -> Result<Vec<SecretEntry>, String> {
+    Ok(secret_vault(&manager)?.list())
+}
 
-    let mut end_index = None;
-    for (i, line) in lines.iter().enumerate().skip(1) {
-        if *line == "---" {
-            end_index = Some(i);
-            break;
-        }
-    }
+/// Adds or replaces a secret, for `AgentConfig::env` to reference as `${secret:NAME}`.
+#[tauri::command]
+pub async fn set_secret(
+    manager: State<'_, Arc<AgentManager>>,
+    name: String,
+    value: String,
+) -> Result<SecretEntry, String> {
+    secret_vault(&manager)?
+        .set(&name, &value)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn delete_secret(manager: State<'_, Arc<AgentManager>>, name: String) -> Result<(), String> {
+    secret_vault(&manager)?
+        .remove(&name)
+        .map_err(|e| e.to_string())
+}
 
-    let end_index = end_index?;
-    let frontmatter: Vec<&str> = lines[1..end_index].to_vec();
+/// Every registered CLI backend with its path, version and capabilities.
+/// Results are cached; pass `refresh` to probe the CLIs again.
+#[tauri::command]
+pub async fn detect_clis(
+    manager: State<'_, Arc<AgentManager>>,
+    refresh: Option<bool>,
+) -> Result<Vec<CliInfo>, String> {
+    Ok(manager.registry().detect(refresh.unwrap_or(false)).await)
+}
 
-    let mut name = path.file_name()?.to_string_lossy().to_string();
-    let mut description = String::new();
+fn skills_repository(manager: &AgentManager) -> Result<Arc<SkillsRepository>, String> {
+    manager
+        .skills()
+        .ok_or_else(|| "No skills directory is configured".to_string())
+}
 
-    for line in frontmatter {
-        if let Some(value) = line.strip_prefix("name:") {
-            name = value.trim().to_string();
-        } else if let Some(value) = line.strip_prefix("description:") {
-            description = value.trim().to_string();
-        }
-    }
+#[tauri::command]
+pub async fn list_skills(
+    manager: State<'_, Arc<AgentManager>>,
+    working_directory: Option<String>,
+) -> Result<Vec<SkillInfo>, String> {
+    skills_repository(&manager)?
+        .list(working_directory.as_deref().map(std::path::Path::new))
+        .await
+        .map_err(|e| e.to_string())
+}
 
-    Some(SkillInfo { name, description })
+#[tauri::command]
+pub async fn get_skill(
+    manager: State<'_, Arc<AgentManager>>,
+    skill_name: String,
+    location: Option<SkillLocation>,
+) -> Result<SkillDetail, String> {
+    skills_repository(&manager)?
+        .get_at(&skill_name, &location.unwrap_or_default())
+        .await
+        .map_err(|e| e.to_string())
 }
 
-fn extract_markdown_content(content: &str) -> String {
-    let lines: Vec<&str> = content.lines().collect();
+/// Writes a new skill directory with its `SKILL.md`; fails if the name is taken.
+#[tauri::command]
+pub async fn create_skill(
+    manager: State<'_, Arc<AgentManager>>,
+    skill: NewSkill,
+) -> Result<SkillDetail, String> {
+    skills_repository(&manager)?
+        .create(skill)
+        .await
+        .map_err(|e| e.to_string())
+}
 
-    if lines.first().map(|l| *l) != Some("---") {
-        return content.to_string();
-    }
+/// Replaces a skill's description and instructions.
+#[tauri::command]
+pub async fn update_skill(
+    manager: State<'_, Arc<AgentManager>>,
+    skill_name: String,
+    update: SkillUpdate,
+) -> Result<SkillDetail, String> {
+    skills_repository(&manager)?
+        .update(&skill_name, update)
+        .await
+        .map_err(|e| e.to_string())
+}
 
-    let mut end_index = None;
-    for (i, line) in lines.iter().enumerate().skip(1) {
-        if *line == "---" {
-            end_index = Some(i);
-            break;
-        }
-    }
+#[tauri::command]
+pub async fn rename_skill(
+    manager: State<'_, Arc<AgentManager>>,
+    skill_name: String,
+    new_name: String,
+) -> Result<SkillDetail, String> {
+    skills_repository(&manager)?
+        .rename(&skill_name, &new_name)
+        .await
+        .map_err(|e| e.to_string())
+}
 
-    if let Some(end_index) = end_index {
-        lines[end_index + 1..].join("\n").trim().to_string()
-    } else {
-        content.to_string()
-    }
+#[tauri::command]
+pub async fn delete_skill(
+    manager: State<'_, Arc<AgentManager>>,
+    skill_name: String,
+) -> Result<(), String> {
+    skills_repository(&manager)?
+        .delete(&skill_name)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// The skill's directory as a zip or gzipped tar bundle, located like `get_skill`.
+#[tauri::command]
+pub async fn export_skill(
+    manager: State<'_, Arc<AgentManager>>,
+    skill_name: String,
+    format: BundleFormat,
+    location: Option<SkillLocation>,
+) -> Result<Vec<u8>, String> {
+    skills_repository(&manager)?
+        .export(&skill_name, &location.unwrap_or_default(), format)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Installs a bundle as a user skill; fails if the name is taken unless
+/// `options.overwrite` is set.
+#[tauri::command]
+pub async fn import_skill(
+    manager: State<'_, Arc<AgentManager>>,
+    bundle: Vec<u8>,
+    format: BundleFormat,
+    options: Option<ImportOptions>,
+) -> Result<SkillDetail, String> {
+    skills_repository(&manager)?
+        .import(bundle, format, options.unwrap_or_default())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Installs a skill committed in a local git repository.
+#[tauri::command]
+pub async fn import_skill_from_git(
+    manager: State<'_, Arc<AgentManager>>,
+    source: GitSkillSource,
+    options: Option<ImportOptions>,
+) -> Result<SkillDetail, String> {
+    skills_repository(&manager)?
+        .import_git(source, options.unwrap_or_default())
+        .await
+        .map_err(|e| e.to_string())
 }
@@ -2,29 +2,102 @@ mod commands;
 
 use std::sync::Arc;
 
-use giga_command_center_core::AgentManager;
+use giga_command_center_core::{AgentManager, HistoryStore, SecretVault, SkillsRepository, WorkspaceStore};
 use commands::*;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    let manager = Arc::new(AgentManager::new());
+    let manager = match HistoryStore::open_default() {
+        Ok(history) => AgentManager::with_history(history),
+        Err(e) => {
+            eprintln!("[CCC] Run history disabled: {}", e);
+            AgentManager::new()
+        }
+    };
+    let manager = match SecretVault::open_default() {
+        Ok(vault) => manager.with_vault(vault),
+        Err(e) => {
+            eprintln!("[CCC] Secrets vault disabled: {}", e);
+            manager
+        }
+    };
+    let manager = match WorkspaceStore::open_default() {
+        Ok(store) => manager.with_workspaces(store),
+        Err(e) => {
+            eprintln!("[CCC] Workspace storage disabled: {}", e);
+            manager
+        }
+    };
+    let manager = match SkillsRepository::open_default() {
+        Ok(skills) => manager.with_skills(skills),
+        Err(e) => {
+            eprintln!("[CCC] Skills disabled: {}", e);
+            manager
+        }
+    };
+    let manager = Arc::new(manager);
+    // AGENT_CLI_PATH_<ID> points a backend at a specific binary
+    manager.registry().load_env_overrides();
 
     tauri::Builder::default()
         .plugin(tauri_plugin_shell::init())
         .manage(manager.clone())
+        .setup({
+            let manager = manager.clone();
+            move |app| {
+                // Skill edits made outside the app reach the UI as SkillsChanged
+                if let Some(skills) = manager.skills() {
+                    let emit_event = event_emitter(app.handle().clone());
+                    tauri::async_runtime::spawn(async move {
+                        if let Err(e) = skills.watch(emit_event).await {
+                            eprintln!("[CCC] Not watching skills for changes: {}", e);
+                        }
+                    });
+                }
+                Ok(())
+            }
+        })
         .invoke_handler(tauri::generate_handler![
             start_agent,
+            continue_session,
+            send_input,
+            close_input,
+            start_workflow,
+            list_workflows,
+            get_workflow,
+            cancel_workflow,
+            respond_permission,
+            list_permission_requests,
+            get_worktree,
+            merge_worktree,
+            discard_worktree,
             stop_agent,
             stop_all_agents,
             list_agents,
-            check_cli_available,
-            check_cursor_cli_available,
-            check_kilo_cli_available,
-            check_gemini_cli_available,
-            check_grok_cli_available,
-            check_deepseek_cli_available,
+            list_queue,
+            cancel_queued,
+            list_runs,
+            get_run,
+            delete_run,
+            usage_report,
+            list_workspaces,
+            get_workspace,
+            create_workspace,
+            update_workspace,
+            delete_workspace,
+            list_secrets,
+            set_secret,
+            delete_secret,
+            detect_clis,
             list_skills,
             get_skill,
+            create_skill,
+            update_skill,
+            rename_skill,
+            delete_skill,
+            export_skill,
+            import_skill,
+            import_skill_from_git,
         ])
         .on_window_event({
             let manager = manager.clone();
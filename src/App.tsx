@@ -6,51 +6,42 @@ import { OutputModal } from './components/OutputModal';
 import { useAgentEvents } from './hooks/useAgentEvents';
 import { useAgentCommands } from './hooks/useAgentCommands';
 import { useUIStore } from './stores/ui';
+import { startWorkspaceSync } from './utils/workspaceSync';
 
 function App() {
-  const { setCliAvailable, setCursorCliAvailable, setKiloCliAvailable, setGeminiCliAvailable, setGrokCliAvailable, setDeepseekCliAvailable, setStatusMessage } = useUIStore();
-  const { checkCliAvailable, checkCursorCliAvailable, checkKiloCliAvailable, checkGeminiCliAvailable, checkGrokCliAvailable, checkDeepseekCliAvailable } = useAgentCommands();
+  const { setClis, setStatusMessage } = useUIStore();
+  const { detectClis } = useAgentCommands();
 
   // Set up event listeners
   useAgentEvents();
 
-  // Check if Claude, Cursor, Kilo, Gemini, Grok, and DeepSeek CLIs are available on mount
+  // Load the stored workspaces and keep them in sync with the backend
+  useEffect(() => {
+    let stop: (() => void) | null = null;
+    let cancelled = false;
+    startWorkspaceSync().then((stopFn) => {
+      if (cancelled) {
+        stopFn();
+      } else {
+        stop = stopFn;
+      }
+    });
+    return () => {
+      cancelled = true;
+      stop?.();
+    };
+  }, []);
+
+  // Detect which CLIs (Claude, Cursor, Kilo, Gemini, Grok, DeepSeek) are installed on mount
   useEffect(() => {
     const check = async () => {
       setStatusMessage('Checking CLIs...');
-      const [claude, cursor, kilo, gemini, grok, deepseek] = await Promise.all([
-        checkCliAvailable(),
-        checkCursorCliAvailable(),
-        checkKiloCliAvailable(),
-        checkGeminiCliAvailable(),
-        checkGrokCliAvailable(),
-        checkDeepseekCliAvailable(),
-      ]);
-      setCliAvailable(claude);
-      setCursorCliAvailable(cursor);
-      setKiloCliAvailable(kilo);
-      setGeminiCliAvailable(gemini);
-      setGrokCliAvailable(grok);
-      setDeepseekCliAvailable(deepseek);
-      const which = [claude && 'Claude', cursor && 'Cursor', kilo && 'Kilo', gemini && 'Gemini', grok && 'Grok', deepseek && 'DeepSeek'].filter(Boolean).join(', ') || 'none';
-      setStatusMessage(which !== 'none' ? 'Ready' : 'No CLI found (Claude, Cursor, Kilo, Gemini, Grok, or DeepSeek)');
+      const clis = await detectClis();
+      setClis(clis);
+      setStatusMessage(clis.some((cli) => cli.available) ? 'Ready' : 'No CLI found (Claude, Cursor, Kilo, Gemini, Grok, or DeepSeek)');
     };
     check();
-  }, [
-    checkCliAvailable,
-    checkCursorCliAvailable,
-    checkKiloCliAvailable,
-    checkGeminiCliAvailable,
-    checkGrokCliAvailable,
-    checkDeepseekCliAvailable,
-    setCliAvailable,
-    setCursorCliAvailable,
-    setKiloCliAvailable,
-    setGeminiCliAvailable,
-    setGrokCliAvailable,
-    setDeepseekCliAvailable,
-    setStatusMessage,
-  ]);
+  }, [detectClis, setClis, setStatusMessage]);
 
   return (
     <div className="h-screen w-screen flex flex-col bg-canvas-bg text-gray-100">
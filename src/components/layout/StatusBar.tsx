@@ -5,7 +5,8 @@ import { useUIStore } from '../../stores/ui';
 export function StatusBar() {
   const agents = useAgentsStore((s) => s.agents);
   const workspaces = useWorkspacesStore((s) => s.workspaces);
-  const { statusMessage, cliAvailable } = useUIStore();
+  const { statusMessage, clis } = useUIStore();
+  const cliAvailable = clis === null ? null : Object.values(clis).some((cli) => cli.available);
 
   const workspaceCount = Object.keys(workspaces).length;
   const agentCount = Object.keys(agents).length;
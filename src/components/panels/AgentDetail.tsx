@@ -7,6 +7,8 @@ import { Badge } from '../common/Badge';
 import { ProgressBar } from '../common/ProgressBar';
 import { AGENT_STATE_COLORS } from '../../types/agent';
 import { AGENT_EMOJIS } from '../../utils/emoji';
+import { api } from '../../utils/api';
+import type { PermissionBehavior } from '../../types/agent';
 
 export function AgentDetail() {
   const { selectedAgentId, selectWorkspace } = useUIStore();
@@ -39,6 +41,15 @@ export function AgentDetail() {
     await stopTask(agent.id);
   };
 
+  const handlePermission = async (requestId: string, behavior: PermissionBehavior) => {
+    try {
+      await api.respondPermission(agent.id, requestId, behavior);
+    } catch (error) {
+      // Already answered, timed out or the run ended; the events say which.
+      console.warn('[Permission]', error);
+    }
+  };
+
   return (
     <div className="p-4 overflow-y-auto h-full">
       {/* Header */}
@@ -95,6 +106,31 @@ export function AgentDetail() {
         </div>
       </div>
 
+      {/* Permission requests */}
+      {agent.permissionRequests.map((request) => (
+        <div key={request.requestId} className="mb-4 p-3 bg-yellow-900/30 border border-yellow-700 rounded">
+          <label className="block text-xs font-medium text-yellow-400 mb-1">
+            Permission requested: {request.toolName}
+          </label>
+          <pre className="text-xs text-yellow-200 whitespace-pre-wrap break-all max-h-32 overflow-y-auto mb-2">
+            {JSON.stringify(request.input, null, 2)}
+          </pre>
+          <div className="flex gap-2">
+            <Button size="sm" onClick={() => handlePermission(request.requestId, 'allow')} className="flex-1">
+              Allow
+            </Button>
+            <Button
+              size="sm"
+              variant="danger"
+              onClick={() => handlePermission(request.requestId, 'deny')}
+              className="flex-1"
+            >
+              Deny
+            </Button>
+          </div>
+        </div>
+      ))}
+
       {/* Error display */}
       {agent.error && (
         <div className="mb-4 p-3 bg-red-900/30 border border-red-700 rounded">
@@ -1,18 +1,308 @@
-import { useEffect } from 'react';
+import { useEffect, useState } from 'react';
 import { useSkillsStore } from '../../stores/skills';
 import { Button } from '../common/Button';
+import { api } from '../../utils/api';
+import type { BundleFormat, SkillDetail, SkillInfo } from '../../types/skill';
+
+interface SkillDraft {
+  /** The skill being edited; null when creating one. */
+  original: string | null;
+  name: string;
+  description: string;
+  markdown: string;
+}
+
+function sourceLabel(info: SkillInfo) {
+  return info.source === 'plugin' && info.plugin ? `plugin: ${info.plugin}` : info.source;
+}
+
+function SourceBadge({ info }: { info: SkillInfo }) {
+  return (
+    <span className="ml-2 px-1.5 py-0.5 rounded bg-gray-700 text-xs text-gray-300 font-normal">
+      {sourceLabel(info)}
+    </span>
+  );
+}
+
+// Project skills are read from <directory>/.claude/skills
+function ProjectDirectoryInput() {
+  const { workingDirectory, setWorkingDirectory } = useSkillsStore();
+  const [value, setValue] = useState(workingDirectory ?? '');
+
+  return (
+    <input
+      type="text"
+      value={value}
+      onChange={(e) => setValue(e.target.value)}
+      onBlur={() => value.trim() !== (workingDirectory ?? '') && setWorkingDirectory(value.trim())}
+      onKeyDown={(e) => e.key === 'Enter' && setWorkingDirectory(value.trim())}
+      placeholder="Project directory (for project skills)"
+      className="w-full mt-2 bg-gray-800 border border-gray-600 rounded-lg px-3 py-1.5 text-sm text-white focus:outline-none focus:border-blue-500"
+    />
+  );
+}
+
+// The skill's directory name; plugin skill ids are prefixed with their plugin
+function skillDirName(info: SkillInfo) {
+  return info.source === 'plugin' ? info.id.slice(info.id.indexOf(':') + 1) : info.id;
+}
+
+function bundleFormatOf(fileName: string): BundleFormat {
+  return /\.(tar|tar\.gz|tgz)$/i.test(fileName) ? 'tar' : 'zip';
+}
+
+// Installs a skill from a zip/tar bundle or a local git repository
+function ImportSkillForm({ onClose }: { onClose: () => void }) {
+  const { importSkill, importSkillFromGit } = useSkillsStore();
+  const [file, setFile] = useState<File | null>(null);
+  const [gitPath, setGitPath] = useState('');
+  const [subdir, setSubdir] = useState('');
+  const [name, setName] = useState('');
+  const [overwrite, setOverwrite] = useState(false);
+  const [importing, setImporting] = useState(false);
+  const [importError, setImportError] = useState<string | null>(null);
+
+  const handleImport = async () => {
+    setImporting(true);
+    setImportError(null);
+    const options = { name: name.trim() || null, overwrite };
+    try {
+      if (file) {
+        await importSkill(file, bundleFormatOf(file.name), options);
+      } else {
+        await importSkillFromGit({ path: gitPath.trim(), subdir: subdir.trim() || null }, options);
+      }
+      onClose();
+    } catch (err) {
+      setImportError(String(err));
+    } finally {
+      setImporting(false);
+    }
+  };
+
+  const inputClass =
+    'w-full bg-gray-800 border border-gray-600 rounded-lg px-3 py-2 text-sm text-white focus:outline-none focus:border-blue-500';
+
+  return (
+    <div className="h-full flex flex-col">
+      <div className="p-4 border-b border-gray-700 flex items-center justify-between">
+        <h3 className="text-base font-semibold text-gray-200">Import Skill</h3>
+        <Button size="sm" variant="ghost" onClick={onClose} disabled={importing}>
+          Cancel
+        </Button>
+      </div>
+
+      <div className="flex-1 overflow-y-auto p-4 space-y-4">
+        <div>
+          <label className="block text-sm font-medium text-gray-400 mb-2">Bundle (.zip, .tar, .tar.gz)</label>
+          <input
+            type="file"
+            accept=".zip,.tar,.tar.gz,.tgz"
+            onChange={(e) => setFile(e.target.files?.[0] ?? null)}
+            className="w-full text-sm text-gray-300"
+          />
+        </div>
+        {!file && (
+          <div>
+            <label className="block text-sm font-medium text-gray-400 mb-2">Or a local git repository</label>
+            <input
+              type="text"
+              value={gitPath}
+              onChange={(e) => setGitPath(e.target.value)}
+              placeholder="/path/to/repo"
+              className={inputClass}
+            />
+            <input
+              type="text"
+              value={subdir}
+              onChange={(e) => setSubdir(e.target.value)}
+              placeholder="Skill directory in the repository (optional)"
+              className={`${inputClass} mt-2`}
+            />
+          </div>
+        )}
+        <div>
+          <label className="block text-sm font-medium text-gray-400 mb-2">Name (optional)</label>
+          <input
+            type="text"
+            value={name}
+            onChange={(e) => setName(e.target.value)}
+            placeholder="Taken from the bundle"
+            className={inputClass}
+          />
+        </div>
+        <label className="flex items-center text-sm text-gray-400">
+          <input
+            type="checkbox"
+            checked={overwrite}
+            onChange={(e) => setOverwrite(e.target.checked)}
+            className="mr-2"
+          />
+          Replace an existing skill with the same name
+        </label>
+        {importError && <p className="text-red-400 text-sm">{importError}</p>}
+      </div>
+
+      <div className="p-3 border-t border-gray-700">
+        <Button
+          size="sm"
+          onClick={handleImport}
+          disabled={importing || (!file && !gitPath.trim())}
+          className="w-full"
+        >
+          {importing ? 'Importing...' : 'Import Skill'}
+        </Button>
+      </div>
+    </div>
+  );
+}
 
 interface SkillsPanelProps {
   onSelectSkill?: (skillName: string) => void;
 }
 
 export function SkillsPanel({ onSelectSkill }: SkillsPanelProps) {
-  const { skills, selectedSkill, loading, error, loadSkills, selectSkill, clearSelection } = useSkillsStore();
+  const {
+    skills,
+    selectedSkill,
+    loading,
+    error,
+    loadSkills,
+    selectSkill,
+    clearSelection,
+    createSkill,
+    updateSkill,
+    renameSkill,
+    deleteSkill,
+  } = useSkillsStore();
+  const [draft, setDraft] = useState<SkillDraft | null>(null);
+  const [saving, setSaving] = useState(false);
+  const [saveError, setSaveError] = useState<string | null>(null);
+  const [showImport, setShowImport] = useState(false);
 
   useEffect(() => {
     loadSkills();
   }, [loadSkills]);
 
+  const startDraft = (next: SkillDraft) => {
+    setSaveError(null);
+    setDraft(next);
+  };
+
+  const handleSave = async () => {
+    if (!draft) return;
+    setSaving(true);
+    setSaveError(null);
+    try {
+      const name = draft.name.trim();
+      if (draft.original === null) {
+        await createSkill({ name, description: draft.description, markdown: draft.markdown });
+      } else {
+        await updateSkill(draft.original, { description: draft.description, markdown: draft.markdown });
+        if (name !== draft.original) {
+          await renameSkill(draft.original, name);
+        }
+      }
+      setDraft(null);
+    } catch (err) {
+      setSaveError(String(err));
+    } finally {
+      setSaving(false);
+    }
+  };
+
+  const handleDelete = async (detail: SkillDetail) => {
+    if (!confirm(`Delete skill "${detail.info.name}" and its directory?`)) return;
+    try {
+      await deleteSkill(detail.info.id);
+    } catch (err) {
+      setSaveError(String(err));
+    }
+  };
+
+  const handleExport = async (detail: SkillDetail, format: BundleFormat) => {
+    setSaveError(null);
+    try {
+      const dirName = skillDirName(detail.info);
+      const bundle = await api.exportSkill(dirName, format, {
+        source: detail.info.source,
+        plugin: detail.info.plugin,
+        workingDirectory: useSkillsStore.getState().workingDirectory,
+      });
+      const url = URL.createObjectURL(bundle);
+      const link = document.createElement('a');
+      link.href = url;
+      link.download = `${dirName}.${format === 'zip' ? 'zip' : 'tar.gz'}`;
+      link.click();
+      URL.revokeObjectURL(url);
+    } catch (err) {
+      setSaveError(String(err));
+    }
+  };
+
+  if (showImport) {
+    return <ImportSkillForm onClose={() => setShowImport(false)} />;
+  }
+
+  // Create / edit form
+  if (draft) {
+    return (
+      <div className="h-full flex flex-col">
+        <div className="p-4 border-b border-gray-700 flex items-center justify-between">
+          <h3 className="text-base font-semibold text-gray-200">
+            {draft.original === null ? 'New Skill' : `Edit ${draft.original}`}
+          </h3>
+          <Button size="sm" variant="ghost" onClick={() => setDraft(null)} disabled={saving}>
+            Cancel
+          </Button>
+        </div>
+
+        <div className="flex-1 overflow-y-auto p-4 space-y-4">
+          <div>
+            <label className="block text-sm font-medium text-gray-400 mb-2">Name</label>
+            <input
+              type="text"
+              value={draft.name}
+              onChange={(e) => setDraft({ ...draft, name: e.target.value })}
+              placeholder="my-skill"
+              className="w-full bg-gray-800 border border-gray-600 rounded-lg px-3 py-2 text-sm text-white focus:outline-none focus:border-blue-500"
+            />
+            <p className="text-xs text-gray-500 mt-1">Lowercase letters, digits and hyphens</p>
+          </div>
+          <div>
+            <label className="block text-sm font-medium text-gray-400 mb-2">Description</label>
+            <textarea
+              value={draft.description}
+              onChange={(e) => setDraft({ ...draft, description: e.target.value })}
+              placeholder="What the skill does and when to use it"
+              className="w-full h-20 bg-gray-800 border border-gray-600 rounded-lg p-3 text-sm text-white resize-none focus:outline-none focus:border-blue-500"
+            />
+          </div>
+          <div>
+            <label className="block text-sm font-medium text-gray-400 mb-2">Instructions (Markdown)</label>
+            <textarea
+              value={draft.markdown}
+              onChange={(e) => setDraft({ ...draft, markdown: e.target.value })}
+              className="w-full h-64 bg-gray-800 border border-gray-600 rounded-lg p-3 text-sm text-white font-mono resize-y focus:outline-none focus:border-blue-500"
+            />
+          </div>
+          {saveError && <p className="text-red-400 text-sm">{saveError}</p>}
+        </div>
+
+        <div className="p-3 border-t border-gray-700">
+          <Button
+            size="sm"
+            onClick={handleSave}
+            disabled={saving || !draft.name.trim() || !draft.description.trim()}
+            className="w-full"
+          >
+            {saving ? 'Saving...' : 'Save Skill'}
+          </Button>
+        </div>
+      </div>
+    );
+  }
+
   if (loading) {
     return (
       <div className="p-5 text-center text-gray-500">
@@ -40,6 +330,17 @@ export function SkillsPanel({ onSelectSkill }: SkillsPanelProps) {
         <p className="text-sm mt-2">
           Skills are stored in ~/.claude/skills/
         </p>
+        <ProjectDirectoryInput />
+        <Button
+          size="sm"
+          className="mt-4"
+          onClick={() => startDraft({ original: null, name: '', description: '', markdown: '' })}
+        >
+          + New Skill
+        </Button>
+        <Button size="sm" variant="ghost" className="mt-4 ml-2" onClick={() => setShowImport(true)}>
+          Import
+        </Button>
       </div>
     );
   }
@@ -54,20 +355,62 @@ export function SkillsPanel({ onSelectSkill }: SkillsPanelProps) {
             <Button size="sm" variant="ghost" onClick={clearSelection}>
               ← Back
             </Button>
-            {onSelectSkill && (
-              <Button
-                size="sm"
-                onClick={() => onSelectSkill(selectedSkill.info.name)}
-              >
-                Use Skill
+            <div className="flex space-x-2">
+              {/* Only the user's skills are written; project and plugin skills are read-only */}
+              {selectedSkill.info.source === 'user' && (
+                <>
+                  <Button
+                    size="sm"
+                    variant="ghost"
+                    onClick={() =>
+                      startDraft({
+                        original: selectedSkill.info.id,
+                        name: selectedSkill.info.id,
+                        description: selectedSkill.info.description,
+                        markdown: selectedSkill.markdown,
+                      })
+                    }
+                  >
+                    Edit
+                  </Button>
+                  <Button size="sm" variant="danger" onClick={() => handleDelete(selectedSkill)}>
+                    Delete
+                  </Button>
+                </>
+              )}
+              <Button size="sm" variant="ghost" onClick={() => handleExport(selectedSkill, 'zip')}>
+                Export .zip
+              </Button>
+              <Button size="sm" variant="ghost" onClick={() => handleExport(selectedSkill, 'tar')}>
+                Export .tar.gz
               </Button>
-            )}
+              {onSelectSkill && (
+                <Button
+                  size="sm"
+                  onClick={() => onSelectSkill(selectedSkill.info.name)}
+                >
+                  Use Skill
+                </Button>
+              )}
+            </div>
           </div>
+          {saveError && <p className="text-red-400 text-sm mb-2">{saveError}</p>}
           <h3 className="text-lg font-bold text-white flex items-center">
             <span className="mr-2 text-xl">🔧</span>
             {selectedSkill.info.name}
+            <SourceBadge info={selectedSkill.info} />
           </h3>
-          <p className="text-sm text-gray-400 mt-1">{selectedSkill.info.description}</p>
+          <p className="text-sm text-gray-400 mt-1 whitespace-pre-wrap">{selectedSkill.info.description}</p>
+          {selectedSkill.info.allowedTools && (
+            <p className="text-xs text-gray-500 mt-2">
+              Allowed tools: {selectedSkill.info.allowedTools.join(', ') || 'none'}
+            </p>
+          )}
+          {Object.entries(selectedSkill.info.metadata).map(([key, value]) => (
+            <p key={key} className="text-xs text-gray-500 mt-1">
+              {key}: {typeof value === 'string' ? value : JSON.stringify(value)}
+            </p>
+          ))}
         </div>
 
         {/* Markdown content */}
@@ -93,24 +436,42 @@ export function SkillsPanel({ onSelectSkill }: SkillsPanelProps) {
   return (
     <div className="overflow-y-auto h-full">
       <div className="p-4 border-b border-gray-700">
-        <h3 className="text-base font-semibold text-gray-200">
-          Available Skills ({skills.length})
-        </h3>
+        <div className="flex items-center justify-between">
+          <h3 className="text-base font-semibold text-gray-200">
+            Available Skills ({skills.length})
+          </h3>
+          <div className="flex space-x-2">
+            <Button size="sm" variant="ghost" onClick={() => setShowImport(true)}>
+              Import
+            </Button>
+            <Button
+              size="sm"
+              variant="ghost"
+              onClick={() => startDraft({ original: null, name: '', description: '', markdown: '' })}
+            >
+              + New
+            </Button>
+          </div>
+        </div>
         <p className="text-sm text-gray-500 mt-1">
           Click a skill to view details or use it
         </p>
+        <ProjectDirectoryInput />
       </div>
 
       {skills.map((skill) => (
         <button
-          key={skill.name}
-          onClick={() => selectSkill(skill.name)}
+          key={`${skill.source}:${skill.id}`}
+          onClick={() => selectSkill(skill)}
           className="w-full p-4 text-left border-b border-gray-700 hover:bg-gray-800 transition-colors"
         >
           <div className="flex items-start">
             <span className="text-2xl mr-3">🔧</span>
             <div className="flex-1 min-w-0">
-              <h4 className="text-base font-medium text-white">{skill.name}</h4>
+              <h4 className="text-base font-medium text-white">
+                {skill.name}
+                <SourceBadge info={skill} />
+              </h4>
               <p className="text-sm text-gray-400 mt-1 line-clamp-2">
                 {skill.description}
               </p>
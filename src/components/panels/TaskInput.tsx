@@ -12,7 +12,7 @@ export function TaskInput() {
   const [selectedSkill, setSelectedSkill] = useState<string | null>(null);
   const textareaRef = useRef<HTMLTextAreaElement>(null);
 
-  const { selectedWorkspaceId, cliAvailable, cursorCliAvailable, kiloCliAvailable, geminiCliAvailable, grokCliAvailable, deepseekCliAvailable } = useUIStore();
+  const { selectedWorkspaceId, clis } = useUIStore();
   const workspaces = useWorkspacesStore((s) => s.workspaces);
   const agents = useAgentsStore((s) => s.agents);
   const { skills, loadSkills } = useSkillsStore();
@@ -21,6 +21,7 @@ export function TaskInput() {
   const workspace = selectedWorkspaceId ? workspaces[selectedWorkspaceId] : null;
   const agent = workspace?.agentId ? agents[workspace.agentId] : null;
   const isAgentBusy = agent && !['idle', 'success', 'error'].includes(agent.state);
+  const cliInfo = clis?.[workspace?.cli ?? 'claude'];
 
   // Load skills on mount
   useEffect(() => {
@@ -74,14 +75,13 @@ export function TaskInput() {
         const useGemini = cliType === 'gemini';
         const useGrok = cliType === 'grok';
         const useDeepseek = cliType === 'deepseek';
-        const ok = useCursor ? cursorCliAvailable : useKilo ? kiloCliAvailable : useGemini ? geminiCliAvailable : useGrok ? grokCliAvailable : useDeepseek ? deepseekCliAvailable : cliAvailable;
-        if (ok !== false || (cliAvailable === null && cursorCliAvailable === null && kiloCliAvailable === null && geminiCliAvailable === null && grokCliAvailable === null && deepseekCliAvailable === null)) return null;
+        if (clis === null || cliInfo?.available) return null;
         return (
           <div className="mb-3 p-3 bg-red-900/30 border border-red-700 rounded text-sm text-red-300">
             {useCursor
               ? 'Cursor CLI (agent) not found. Install: curl https://cursor.com/install -fsS | bash'
               : useKilo
-              ? 'Kilo CLI (kilo) not found. Install: npm install -g @kilocode/cli'
+              ? 'Kilo CLI (kilo or kilocode) not found. Install: npm install -g @kilocode/cli'
               : useGemini
               ? 'Gemini CLI (gemini) not found. Install: npm install -g @google/gemini-cli'
               : useGrok
@@ -89,6 +89,7 @@ export function TaskInput() {
               : useDeepseek
               ? 'DeepSeek CLI (deepseek) not found. Install: pip install deepseek-cli'
               : 'Claude CLI not found. Install it first.'}
+            {cliInfo?.error && <div className="mt-1 text-xs text-red-400">{cliInfo.error}</div>}
           </div>
         );
       })()}
@@ -166,17 +167,7 @@ export function TaskInput() {
               disabled={
                 !prompt.trim() ||
                 isSubmitting ||
-                (workspace?.cli === 'cursor'
-                  ? !cursorCliAvailable
-                  : workspace?.cli === 'kilo'
-                  ? !kiloCliAvailable
-                  : workspace?.cli === 'gemini'
-                  ? !geminiCliAvailable
-                  : workspace?.cli === 'grok'
-                  ? !grokCliAvailable
-                  : workspace?.cli === 'deepseek'
-                  ? !deepseekCliAvailable
-                  : !cliAvailable)
+                !cliInfo?.available
               }
               size="sm"
             >
@@ -1,5 +1,6 @@
-import { useState } from 'react';
+import { useEffect, useState } from 'react';
 import { useWorkspacesStore } from '../../stores/workspaces';
+import { useSkillsStore } from '../../stores/skills';
 import { useAgentsStore } from '../../stores/agents';
 import { useUIStore } from '../../stores/ui';
 import { useAgentCommands } from '../../hooks/useAgentCommands';
@@ -10,9 +11,63 @@ import { ProgressBar } from '../common/ProgressBar';
 import { WORKSPACE_EMOJIS } from '../../utils/emoji';
 import { AGENT_EMOJIS } from '../../utils/emoji';
 import { AVAILABLE_MODELS, ModelId, CliType } from '../../types/workspace';
+import type { NetworkAccess, PermissionMode } from '../../types/agent';
 
 const WORKFLOW_SECTION_STYLE = "mb-5 p-4 bg-gray-800/50 rounded-lg border border-gray-700";
 
+// The skills every run of a workspace starts with, picked from the Skills panel's list
+function WorkspaceSkills({ workspaceId, selected }: { workspaceId: string; selected: string[] }) {
+  const setSkills = useWorkspacesStore((s) => s.setSkills);
+  const { skills, loadSkills } = useSkillsStore();
+
+  useEffect(() => {
+    if (skills.length === 0) {
+      loadSkills();
+    }
+  }, [skills.length, loadSkills]);
+
+  const label = (id: string) => skills.find((skill) => skill.id === id)?.name ?? id;
+
+  return (
+    <div className="mb-5">
+      <label className="block text-sm font-medium text-gray-400 mb-2">
+        Skills
+      </label>
+      {selected.length > 0 && (
+        <div className="flex flex-wrap gap-2 mb-2">
+          {selected.map((id) => (
+            <span key={id} className="flex items-center px-2 py-1 rounded bg-gray-700 text-sm text-gray-200">
+              {label(id)}
+              <button
+                onClick={() => setSkills(workspaceId, selected.filter((s) => s !== id))}
+                className="ml-2 text-gray-400 hover:text-white"
+                title="Remove skill"
+              >
+                ×
+              </button>
+            </span>
+          ))}
+        </div>
+      )}
+      <select
+        value=""
+        onChange={(e) => e.target.value && setSkills(workspaceId, [...selected, e.target.value])}
+        className="w-full bg-gray-800 border border-gray-700 rounded px-4 py-3 text-base text-white focus:border-blue-500 focus:outline-none"
+      >
+        <option value="">Add a skill...</option>
+        {skills
+          .filter((skill) => !selected.includes(skill.id))
+          .map((skill) => (
+            <option key={`${skill.source}:${skill.id}`} value={skill.id}>
+              {skill.name} ({skill.plugin ?? skill.source})
+            </option>
+          ))}
+      </select>
+      <p className="text-xs text-gray-500 mt-1">Claude loads these itself; other CLIs get their instructions in the system prompt.</p>
+    </div>
+  );
+}
+
 export function WorkspacePanel() {
   const { selectedWorkspaceId, selectAgent, showOutputModal } = useUIStore();
   const workspaces = useWorkspacesStore((s) => s.workspaces);
@@ -22,13 +77,15 @@ export function WorkspacePanel() {
     setModel,
     setCli,
     setMode,
+    setSandbox,
+    setPermissionMode,
     setTaskTemplate,
     setAutoRun,
     connectWorkspaces,
     disconnectWorkspaces,
   } = useWorkspacesStore();
   const agents = useAgentsStore((s) => s.agents);
-  const { deleteWorkspace } = useAgentCommands();
+  const { deleteWorkspace, runWorkflow, cancelWorkflow } = useAgentCommands();
 
   const [isEditing, setIsEditing] = useState(false);
   const [editName, setEditName] = useState('');
@@ -152,6 +209,51 @@ export function WorkspacePanel() {
         </div>
       )}
 
+      <div className="mb-5">
+        <label className="block text-sm font-medium text-gray-400 mb-2">
+          Sandbox (Linux)
+        </label>
+        <select
+          value={workspace.sandbox ? workspace.sandbox.network ?? 'full' : 'off'}
+          onChange={(e) =>
+            setSandbox(
+              workspace.id,
+              e.target.value === 'off'
+                ? null
+                : { ...workspace.sandbox, network: e.target.value as NetworkAccess }
+            )
+          }
+          className="w-full bg-gray-800 border border-gray-700 rounded px-4 py-3 text-base text-white focus:border-blue-500 focus:outline-none"
+        >
+          <option value="off">Off</option>
+          <option value="full">Working directory only</option>
+          <option value="modelApi">Working directory, model API only</option>
+          <option value="none">Working directory, no network</option>
+        </select>
+        <p className="text-xs text-gray-500 mt-1">Runs the CLI under bubblewrap with everything but the working directory read-only or hidden.</p>
+      </div>
+
+      {(workspace.cli ?? 'claude') === 'claude' && (
+        <div className="mb-5">
+          <label className="block text-sm font-medium text-gray-400 mb-2">
+            Permissions
+          </label>
+          <select
+            value={workspace.permissionMode ?? 'bypass'}
+            onChange={(e) => setPermissionMode(workspace.id, e.target.value as PermissionMode)}
+            className="w-full bg-gray-800 border border-gray-700 rounded px-4 py-3 text-base text-white focus:border-blue-500 focus:outline-none"
+          >
+            <option value="bypass">Bypass (default)</option>
+            <option value="acceptEdits">Accept edits</option>
+            <option value="default">Ask</option>
+            <option value="plan">Plan</option>
+          </select>
+          <p className="text-xs text-gray-500 mt-1">Tool uses the CLI asks about wait for Allow or Deny on the agent's panel.</p>
+        </div>
+      )}
+
+      <WorkspaceSkills workspaceId={workspace.id} selected={workspace.skills ?? []} />
+
       <div className="mb-5">
         <label className="block text-sm font-medium text-gray-400 mb-2">
           Model
@@ -276,6 +378,20 @@ export function WorkspacePanel() {
           </div>
         )}
 
+        {(workspace.inputConnections?.length > 0 || workspace.outputConnections?.length > 0) && (
+          <div className="flex space-x-2 mt-3">
+            {agent?.workflowId && agent.state !== 'idle' && agent.state !== 'success' && agent.state !== 'error' ? (
+              <Button size="sm" variant="danger" onClick={() => cancelWorkflow(agent.workflowId!)}>
+                Cancel Workflow
+              </Button>
+            ) : (
+              <Button size="sm" variant="secondary" onClick={() => runWorkflow(workspace.id)}>
+                ▶ Run Workflow
+              </Button>
+            )}
+          </div>
+        )}
+
         {showWorkflow && (
           <div className={WORKFLOW_SECTION_STYLE}>
             {/* Task Template */}
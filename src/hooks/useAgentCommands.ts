@@ -3,10 +3,12 @@ import { api } from '../utils/api';
 import { useAgentsStore } from '../stores/agents';
 import { useWorkspacesStore } from '../stores/workspaces';
 import { useUIStore } from '../stores/ui';
-import type { AgentConfig } from '../types/agent';
+import type { AgentConfig, CliInfo } from '../types/agent';
+import type { Workspace } from '../types/workspace';
+import type { WorkflowDefinition, WorkflowId } from '../types/workflow';
 
 export function useAgentCommands() {
-  const { removeAgent, getAgentByWorkspace } = useAgentsStore();
+  const { removeAgent, trackAgent, getAgentByWorkspace } = useAgentsStore();
   const { setWorkspaceAgent, removeWorkspace, updateWorkspaceState, getInputsForWorkspace, workspaces } =
     useWorkspacesStore();
   const { setStatusMessage, selectWorkspace, selectAgent } = useUIStore();
@@ -76,35 +78,16 @@ export function useAgentCommands() {
           allowedTools: options?.allowedTools,
           systemPrompt: workspace?.systemPrompt || undefined,
           model: workspace?.model || undefined,
+          sandbox: workspace?.sandbox,
+          permissionMode: workspace?.permissionMode,
+          skills: workspace?.skills,
         };
 
         // The backend returns the agent ID it creates
         const backendAgentId = await api.startAgent(config);
 
         // Create the agent in our store with the backend's ID
-        const { agents } = useAgentsStore.getState();
-        if (!agents[backendAgentId]) {
-          // Manually add agent with the backend's ID
-          useAgentsStore.setState((state) => ({
-            agents: {
-              ...state.agents,
-              [backendAgentId]: {
-                id: backendAgentId,
-                workspaceId,
-                state: 'thinking',
-                task: prompt,
-                progress: 0,
-                logs: [],
-                sessionId: null,
-                model: null,
-                startedAt: Date.now(),
-                completedAt: null,
-                error: null,
-              },
-            },
-          }));
-        }
-
+        trackAgent(backendAgentId, workspaceId, prompt);
         setWorkspaceAgent(workspaceId, backendAgentId);
 
         return backendAgentId;
@@ -113,7 +96,7 @@ export function useAgentCommands() {
         throw error;
       }
     },
-    [getAgentByWorkspace, removeAgent, setWorkspaceAgent, updateWorkspaceState, setStatusMessage, buildWorkflowPrompt]
+    [getAgentByWorkspace, removeAgent, trackAgent, setWorkspaceAgent, updateWorkspaceState, setStatusMessage, buildWorkflowPrompt]
   );
 
   // Start a workflow task (triggered by auto-run)
@@ -143,6 +126,77 @@ export function useAgentCommands() {
     };
   }, [startWorkflowTask]);
 
+  // Run every workspace connected to this one as a backend-driven workflow
+  const runWorkflow = useCallback(
+    async (workspaceId: string): Promise<WorkflowId | undefined> => {
+      const { workspaces } = useWorkspacesStore.getState();
+
+      // Collect the connected workspaces, following connections both ways
+      const members: Workspace[] = [];
+      const seen = new Set<string>([workspaceId]);
+      const queue = [workspaceId];
+      while (queue.length > 0) {
+        const ws = workspaces[queue.shift()!];
+        if (!ws) continue;
+        members.push(ws);
+        for (const id of [...(ws.inputConnections ?? []), ...(ws.outputConnections ?? [])]) {
+          if (!seen.has(id)) {
+            seen.add(id);
+            queue.push(id);
+          }
+        }
+      }
+
+      const missing = members.find((ws) => !ws.taskTemplate);
+      if (missing) {
+        setStatusMessage(`Add a task template to "${missing.name}" before running the workflow`);
+        return undefined;
+      }
+
+      const definition: WorkflowDefinition = {
+        name: workspaces[workspaceId]?.name,
+        nodes: members.map((ws) => ({
+          id: ws.id,
+          name: ws.name,
+          config: {
+            workspaceId: ws.id,
+            prompt: ws.taskTemplate!,
+            cli: ws.cli,
+            mode: ws.mode,
+            systemPrompt: ws.systemPrompt || undefined,
+            model: ws.model || undefined,
+            sandbox: ws.sandbox,
+            permissionMode: ws.permissionMode,
+            skills: ws.skills,
+          },
+          inputs: (ws.inputConnections ?? []).filter((id) => seen.has(id)),
+        })),
+      };
+
+      try {
+        setStatusMessage('Starting workflow...');
+        return await api.startWorkflow(definition);
+      } catch (error) {
+        setStatusMessage(`Failed to start workflow: ${error}`);
+        throw error;
+      }
+    },
+    [setStatusMessage]
+  );
+
+  const cancelWorkflow = useCallback(
+    async (workflowId: WorkflowId) => {
+      try {
+        setStatusMessage('Cancelling workflow...');
+        await api.cancelWorkflow(workflowId);
+      } catch (error) {
+        setStatusMessage(`Failed to cancel workflow: ${error}`);
+        throw error;
+      }
+    },
+    [setStatusMessage]
+  );
+
   const stopTask = useCallback(
     async (agentId: string) => {
       try {
@@ -177,64 +231,21 @@ export function useAgentCommands() {
     [getAgentByWorkspace, removeAgent, removeWorkspace, selectWorkspace, selectAgent, setStatusMessage]
   );
 
-  const checkCliAvailable = useCallback(async (): Promise<boolean> => {
-    try {
-      return await api.checkCliAvailable('claude');
-    } catch {
-      return false;
-    }
-  }, []);
-
-  const checkCursorCliAvailable = useCallback(async (): Promise<boolean> => {
-    try {
-      return await api.checkCliAvailable('cursor');
-    } catch {
-      return false;
-    }
-  }, []);
-
-  const checkKiloCliAvailable = useCallback(async (): Promise<boolean> => {
-    try {
-      return await api.checkCliAvailable('kilo');
-    } catch {
-      return false;
-    }
-  }, []);
-
-  const checkGeminiCliAvailable = useCallback(async (): Promise<boolean> => {
-    try {
-      return await api.checkCliAvailable('gemini');
-    } catch {
-      return false;
-    }
-  }, []);
-
-  const checkGrokCliAvailable = useCallback(async (): Promise<boolean> => {
-    try {
-      return await api.checkCliAvailable('grok');
-    } catch {
-      return false;
-    }
-  }, []);
-
-  const checkDeepseekCliAvailable = useCallback(async (): Promise<boolean> => {
+  const detectClis = useCallback(async (refresh = false): Promise<CliInfo[]> => {
     try {
-      return await api.checkCliAvailable('deepseek');
+      return await api.detectClis(refresh);
     } catch {
-      return false;
+      return [];
     }
   }, []);
 
   return {
     startTask,
     startWorkflowTask,
+    runWorkflow,
+    cancelWorkflow,
     stopTask,
     deleteWorkspace,
-    checkCliAvailable,
-    checkCursorCliAvailable,
-    checkKiloCliAvailable,
-    checkGeminiCliAvailable,
-    checkGrokCliAvailable,
-    checkDeepseekCliAvailable,
+    detectClis,
   };
 }
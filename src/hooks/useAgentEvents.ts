@@ -3,7 +3,9 @@ import { api } from '../utils/api';
 import { useAgentsStore } from '../stores/agents';
 import { useWorkspacesStore } from '../stores/workspaces';
 import { useUIStore } from '../stores/ui';
+import { useSkillsStore } from '../stores/skills';
 import type { AgentEvent } from '../types/events';
+import { applyRemoteDelete, applyRemoteWorkspace } from '../utils/workspaceSync';
 
 /**
  * Hook to listen to agent events from either Tauri or WebSocket.
@@ -22,6 +24,10 @@ export function useAgentEvents() {
         appendLog,
         setAgentError,
         completeAgent,
+        addPermissionRequest,
+        removePermissionRequest,
+        trackAgent,
+        removeAgent,
         agents,
       } = useAgentsStore.getState();
 
@@ -31,20 +37,39 @@ export function useAgentEvents() {
         incrementMessiness,
         setLastOutput,
         getDownstreamWorkspaces,
+        setWorkspaceAgent,
       } = useWorkspacesStore.getState();
       const { setStatusMessage } = useUIStore.getState();
 
-      console.log('[Event]', data.type, 'agent_id:', data.agent_id, 'known agents:', Object.keys(agents));
+      const agentId = 'agent_id' in data ? data.agent_id : null;
+      console.log('[Event]', data.type, 'agent_id:', agentId, 'known agents:', Object.keys(agents));
 
       switch (data.type) {
         case 'Started':
           setStatusMessage(`Agent started for workspace`);
           appendLog(data.agent_id, {
             type: 'info',
-            content: 'Agent started',
+            content: data.worktree
+              ? `Agent started on branch ${data.worktree.branch}`
+              : 'Agent started',
           });
           break;
 
+        case 'WorktreeChanged':
+          appendLog(data.agent_id, {
+            type: 'info',
+            content: `Worktree ${data.worktree.branch} ${data.worktree.status} (${data.worktree.commits} commit${data.worktree.commits === 1 ? '' : 's'})`,
+          });
+          break;
+
+        case 'FallingBack':
+          appendLog(data.agent_id, {
+            type: 'info',
+            content: `${data.from} failed (${data.reason}${data.message ? `: ${data.message}` : ''}); falling back to ${data.to}${data.model ? ` (${data.model})` : ''}`,
+          });
+          setStatusMessage(`Falling back to ${data.to}`);
+          break;
+
         case 'Init':
           setAgentSession(data.agent_id, data.session_id, data.model);
           appendLog(data.agent_id, {
@@ -98,6 +123,36 @@ export function useAgentEvents() {
           updateAgentState(data.agent_id, 'thinking');
           break;
 
+        case 'PermissionRequest': {
+          const requestedAt = Date.now();
+          addPermissionRequest({
+            agentId: data.agent_id,
+            requestId: data.request_id,
+            toolName: data.tool_name,
+            input: data.input,
+            requestedAt,
+            expiresAt: requestedAt + data.timeout_secs * 1000,
+          });
+          appendLog(data.agent_id, {
+            type: 'tool',
+            content: `Waiting for permission to use ${data.tool_name}`,
+            toolName: data.tool_name,
+            toolInput: data.input,
+          });
+          setStatusMessage(`Agent is asking to use ${data.tool_name}`);
+          break;
+        }
+
+        case 'PermissionResolved':
+          removePermissionRequest(data.agent_id, data.request_id);
+          appendLog(data.agent_id, {
+            type: 'info',
+            content: data.timed_out
+              ? `Permission request timed out: ${data.behavior}`
+              : `Permission ${data.behavior === 'allow' ? 'granted' : 'denied'}`,
+          });
+          break;
+
         case 'Result': {
           console.log('[Event] Result - completing agent', data.agent_id, 'success:', data.success);
           completeAgent(data.agent_id, data.success);
@@ -120,8 +175,11 @@ export function useAgentEvents() {
                 setLastOutput(resultAgent.workspaceId, fullOutput);
               }
 
-              // Trigger auto-run on downstream workspaces
-              const downstreamIds = getDownstreamWorkspaces(resultAgent.workspaceId);
+              // Trigger auto-run on downstream workspaces; a backend workflow
+              // starts its own downstream nodes
+              const downstreamIds = resultAgent.workflowId
+                ? []
+                : getDownstreamWorkspaces(resultAgent.workspaceId);
               downstreamIds.forEach((downstreamId) => {
                 const downstream = workspaces[downstreamId];
                 if (downstream?.autoRun && downstream.taskTemplate) {
@@ -152,6 +210,56 @@ export function useAgentEvents() {
           break;
         }
 
+        case 'Retrying': {
+          const delay = Math.round(data.delay_ms / 1000);
+          appendLog(data.agent_id, {
+            type: 'info',
+            content: `Attempt failed (${data.reason}); retrying in ${delay}s (attempt ${data.attempt} of ${data.max_attempts})`,
+          });
+          updateAgentState(data.agent_id, 'thinking');
+          setStatusMessage(`Retrying in ${delay}s (${data.reason})`);
+          break;
+        }
+
+        case 'Log':
+          appendLog(data.agent_id, {
+            type: 'info',
+            content: data.message,
+          });
+          break;
+
+        case 'AuthFailed':
+          setAgentError(data.agent_id, data.message);
+          appendLog(data.agent_id, {
+            type: 'error',
+            content: `${data.message} (log in to the CLI or check its API key)`,
+          });
+          setStatusMessage('Authentication failed: log in to the CLI and retry');
+          break;
+
+        case 'RateLimited': {
+          const wait = data.retry_after_secs !== null ? `in ${data.retry_after_secs}s` : 'later';
+          appendLog(data.agent_id, {
+            type: 'error',
+            content: `${data.message} (retry ${wait})`,
+          });
+          setStatusMessage(`Rate limited: retry ${wait}`);
+          break;
+        }
+
+        case 'QuotaExhausted': {
+          const reset = data.resets_at !== null
+            ? `wait until ${new Date(data.resets_at * 1000).toLocaleString()}`
+            : 'wait for the limit to reset or check billing';
+          setAgentError(data.agent_id, data.message);
+          appendLog(data.agent_id, {
+            type: 'error',
+            content: `${data.message} (${reset})`,
+          });
+          setStatusMessage(`Quota exhausted: ${reset}`);
+          break;
+        }
+
         case 'Error':
           setAgentError(data.agent_id, data.message);
           appendLog(data.agent_id, {
@@ -162,6 +270,7 @@ export function useAgentEvents() {
           break;
 
         case 'Stopped': {
+          removePermissionRequest(data.agent_id);
           const stoppedAgent = agents[data.agent_id];
           if (stoppedAgent) {
             if (data.reason === 'cancelled') {
@@ -175,6 +284,56 @@ export function useAgentEvents() {
           });
           break;
         }
+
+        case 'NodeStarted': {
+          // Node ids are workspace ids; show the run in its workspace
+          const workspace = workspaces[data.node_id];
+          if (workspace) {
+            const previous = workspace.agentId ? agents[workspace.agentId] : undefined;
+            if (previous && previous.id !== data.agent_id) {
+              removeAgent(previous.id);
+            }
+            trackAgent(data.agent_id, data.node_id, workspace.taskTemplate, data.workflow_id);
+            setWorkspaceAgent(data.node_id, data.agent_id);
+            updateWorkspaceState(data.node_id, 'working');
+          }
+          setStatusMessage(`Workflow step "${workspace?.name ?? data.node_id}" started`);
+          break;
+        }
+
+        case 'NodeFinished': {
+          const name = workspaces[data.node_id]?.name ?? data.node_id;
+          if (data.error && data.agent_id) {
+            appendLog(data.agent_id, {
+              type: 'error',
+              content: data.error,
+            });
+          }
+          setStatusMessage(
+            data.error
+              ? `Workflow step "${name}" ${data.status}: ${data.error}`
+              : `Workflow step "${name}" ${data.status}`
+          );
+          break;
+        }
+
+        case 'WorkflowFinished':
+          setStatusMessage(`Workflow ${data.status}`);
+          break;
+
+        case 'WorkspaceChanged':
+          applyRemoteWorkspace(data.workspace);
+          break;
+
+        case 'WorkspaceDeleted':
+          applyRemoteDelete(data.workspace_id);
+          break;
+
+        case 'SkillsChanged':
+          useSkillsStore
+            .getState()
+            .applySkillsChanged(data.working_directory, data.modified, data.removed);
+          break;
       }
     }).then((cleanupFn) => {
       cleanup = cleanupFn;
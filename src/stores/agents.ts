@@ -1,7 +1,7 @@
 import { create } from 'zustand';
 import { immer } from 'zustand/middleware/immer';
 import { nanoid } from 'nanoid';
-import type { Agent, AgentState, LogEntry } from '../types/agent';
+import type { Agent, AgentState, LogEntry, PendingPermission } from '../types/agent';
 
 const MAX_LOG_ENTRIES = 500;
 
@@ -9,6 +9,7 @@ interface AgentsState {
   agents: Record<string, Agent>;
 
   addAgent: (workspaceId: string) => string;
+  trackAgent: (agentId: string, workspaceId: string, task: string | null, workflowId?: string) => void;
   removeAgent: (agentId: string) => void;
   updateAgentState: (agentId: string, state: AgentState) => void;
   setAgentTask: (agentId: string, task: string) => void;
@@ -17,6 +18,8 @@ interface AgentsState {
   updateProgress: (agentId: string, progress: number) => void;
   setAgentError: (agentId: string, error: string) => void;
   completeAgent: (agentId: string, success: boolean) => void;
+  addPermissionRequest: (request: PendingPermission) => void;
+  removePermissionRequest: (agentId: string, requestId?: string) => void;
   getAgentByWorkspace: (workspaceId: string) => Agent | undefined;
 }
 
@@ -39,11 +42,36 @@ export const useAgentsStore = create<AgentsState>()(
           startedAt: null,
           completedAt: null,
           error: null,
+          permissionRequests: [],
         };
       });
       return id;
     },
 
+    // Register a run the backend already started, under the backend's ID
+    trackAgent: (agentId: string, workspaceId: string, task: string | null, workflowId?: string) => {
+      set((state) => {
+        if (state.agents[agentId]) {
+          return;
+        }
+        state.agents[agentId] = {
+          id: agentId,
+          workspaceId,
+          state: 'thinking',
+          task,
+          progress: 0,
+          logs: [],
+          sessionId: null,
+          model: null,
+          startedAt: Date.now(),
+          completedAt: null,
+          error: null,
+          permissionRequests: [],
+          workflowId,
+        };
+      });
+    },
+
     removeAgent: (agentId: string) => {
       set((state) => {
         delete state.agents[agentId];
@@ -124,6 +152,25 @@ export const useAgentsStore = create<AgentsState>()(
       });
     },
 
+    addPermissionRequest: (request: PendingPermission) => {
+      set((state) => {
+        if (state.agents[request.agentId]) {
+          state.agents[request.agentId].permissionRequests.push(request);
+        }
+      });
+    },
+
+    /** Drops one answered request, or every request when `requestId` is omitted. */
+    removePermissionRequest: (agentId: string, requestId?: string) => {
+      set((state) => {
+        if (state.agents[agentId]) {
+          state.agents[agentId].permissionRequests = requestId
+            ? state.agents[agentId].permissionRequests.filter((r) => r.requestId !== requestId)
+            : [];
+        }
+      });
+    },
+
     getAgentByWorkspace: (workspaceId: string) => {
       const state = get();
       return Object.values(state.agents).find((a) => a.workspaceId === workspaceId);
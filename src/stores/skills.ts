@@ -1,43 +1,74 @@
 import { create } from 'zustand';
 import { api } from '../utils/api';
-import type { SkillInfo, SkillDetail } from '../types/skill';
+import type {
+  SkillInfo,
+  SkillDetail,
+  SkillLocation,
+  NewSkill,
+  SkillUpdate,
+  BundleFormat,
+  ImportOptions,
+  GitSkillSource,
+} from '../types/skill';
 
 interface SkillsState {
   skills: SkillInfo[];
   selectedSkill: SkillDetail | null;
+  /** Project whose .claude/skills are listed besides the user and plugin skills. */
+  workingDirectory: string | null;
   loading: boolean;
   error: string | null;
 
   // Actions
   loadSkills: () => Promise<void>;
-  selectSkill: (skillName: string | null) => Promise<void>;
+  setWorkingDirectory: (workingDirectory: string | null) => Promise<void>;
+  selectSkill: (skill: SkillInfo | null) => Promise<void>;
   clearSelection: () => void;
+  createSkill: (skill: NewSkill) => Promise<void>;
+  updateSkill: (skillName: string, update: SkillUpdate) => Promise<void>;
+  renameSkill: (skillName: string, newName: string) => Promise<void>;
+  deleteSkill: (skillName: string) => Promise<void>;
+  importSkill: (bundle: Blob, format: BundleFormat, options: ImportOptions) => Promise<void>;
+  importSkillFromGit: (source: GitSkillSource, options: ImportOptions) => Promise<void>;
+  /** Follows a `SkillsChanged` event: relists, and rereads or drops the selected skill. */
+  applySkillsChanged: (workingDirectory: string | null, modified: SkillInfo[], removed: SkillInfo[]) => Promise<void>;
 }
 
-export const useSkillsStore = create<SkillsState>((set) => ({
+export const useSkillsStore = create<SkillsState>((set, get) => ({
   skills: [],
   selectedSkill: null,
+  workingDirectory: null,
   loading: false,
   error: null,
 
   loadSkills: async () => {
     set({ loading: true, error: null });
     try {
-      const skills = await api.listSkills();
+      const skills = await api.listSkills(get().workingDirectory);
       set({ skills, loading: false });
     } catch (error) {
       set({ error: String(error), loading: false });
     }
   },
 
-  selectSkill: async (skillName: string | null) => {
-    if (!skillName) {
+  setWorkingDirectory: async (workingDirectory: string | null) => {
+    set({ workingDirectory: workingDirectory || null });
+    await get().loadSkills();
+  },
+
+  selectSkill: async (skill: SkillInfo | null) => {
+    if (!skill) {
       set({ selectedSkill: null });
       return;
     }
 
+    const location: SkillLocation = {
+      source: skill.source,
+      plugin: skill.plugin,
+      workingDirectory: get().workingDirectory,
+    };
     try {
-      const detail = await api.getSkill(skillName);
+      const detail = await api.getSkill(skill.name, location);
       set({ selectedSkill: detail });
     } catch (error) {
       set({ error: String(error) });
@@ -47,4 +78,63 @@ export const useSkillsStore = create<SkillsState>((set) => ({
   clearSelection: () => {
     set({ selectedSkill: null });
   },
+
+  // Writes go to the user's skills and throw so the editor can keep the user's input on failure
+  createSkill: async (skill: NewSkill) => {
+    const detail = await api.createSkill(skill);
+    set({ selectedSkill: detail });
+    await get().loadSkills();
+  },
+
+  updateSkill: async (skillName: string, update: SkillUpdate) => {
+    const detail = await api.updateSkill(skillName, update);
+    set({ selectedSkill: detail });
+    await get().loadSkills();
+  },
+
+  renameSkill: async (skillName: string, newName: string) => {
+    const detail = await api.renameSkill(skillName, newName);
+    set({ selectedSkill: detail });
+    await get().loadSkills();
+  },
+
+  deleteSkill: async (skillName: string) => {
+    await api.deleteSkill(skillName);
+    set({ selectedSkill: null });
+    await get().loadSkills();
+  },
+
+  importSkill: async (bundle: Blob, format: BundleFormat, options: ImportOptions) => {
+    const detail = await api.importSkill(bundle, format, options);
+    set({ selectedSkill: detail });
+    await get().loadSkills();
+  },
+
+  importSkillFromGit: async (source: GitSkillSource, options: ImportOptions) => {
+    const detail = await api.importSkillFromGit(source, options);
+    set({ selectedSkill: detail });
+    await get().loadSkills();
+  },
+
+  applySkillsChanged: async (workingDirectory: string | null, modified: SkillInfo[], removed: SkillInfo[]) => {
+    // Another project's skills aren't shown
+    if (workingDirectory && workingDirectory !== get().workingDirectory) return;
+
+    const selected = get().selectedSkill?.info;
+    const sameSkill = (skill: SkillInfo) => selected && skill.source === selected.source && skill.id === selected.id;
+    if (removed.some(sameSkill)) {
+      set({ selectedSkill: null });
+    } else {
+      const changed = modified.find(sameSkill);
+      if (changed) await get().selectSkill(changed);
+    }
+
+    // Refresh quietly: keep the list on screen rather than flashing the loading state
+    try {
+      const skills = await api.listSkills(get().workingDirectory);
+      set({ skills });
+    } catch (error) {
+      set({ error: String(error) });
+    }
+  },
 }));
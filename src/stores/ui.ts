@@ -1,5 +1,6 @@
 import { create } from 'zustand';
 import { immer } from 'zustand/middleware/immer';
+import type { CliInfo } from '../types/agent';
 
 export type ActivePanel = 'list' | 'workspace' | 'agent' | 'skills' | 'logs';
 
@@ -18,12 +19,8 @@ interface UIState {
   activePanel: ActivePanel;
   sidebarCollapsed: boolean;
   statusMessage: string;
-  cliAvailable: boolean | null;
-  cursorCliAvailable: boolean | null;
-  kiloCliAvailable: boolean | null;
-  geminiCliAvailable: boolean | null;
-  grokCliAvailable: boolean | null;
-  deepseekCliAvailable: boolean | null;
+  /** Detected CLI backends by id; null until detection has finished. */
+  clis: Record<string, CliInfo> | null;
   outputModalAgentId: string | null;
   editingWorkspaceId: string | null; // For inline name editing
   wiring: WiringState;
@@ -33,12 +30,7 @@ interface UIState {
   setActivePanel: (panel: ActivePanel) => void;
   toggleSidebar: () => void;
   setStatusMessage: (message: string) => void;
-  setCliAvailable: (available: boolean) => void;
-  setCursorCliAvailable: (available: boolean) => void;
-  setKiloCliAvailable: (available: boolean) => void;
-  setGeminiCliAvailable: (available: boolean) => void;
-  setGrokCliAvailable: (available: boolean) => void;
-  setDeepseekCliAvailable: (available: boolean) => void;
+  setClis: (clis: CliInfo[]) => void;
   showOutputModal: (agentId: string | null) => void;
   setEditingWorkspace: (workspaceId: string | null) => void;
   startWiring: (workspaceId: string, type: 'input' | 'output', x: number, y: number) => void;
@@ -53,12 +45,7 @@ export const useUIStore = create<UIState>()(
     activePanel: 'list',
     sidebarCollapsed: false,
     statusMessage: 'Ready',
-    cliAvailable: null,
-    cursorCliAvailable: null,
-    kiloCliAvailable: null,
-    geminiCliAvailable: null,
-    grokCliAvailable: null,
-    deepseekCliAvailable: null,
+    clis: null,
     outputModalAgentId: null,
     editingWorkspaceId: null,
     wiring: {
@@ -105,39 +92,9 @@ export const useUIStore = create<UIState>()(
       });
     },
 
-    setCliAvailable: (available: boolean) => {
+    setClis: (clis: CliInfo[]) => {
       set((state) => {
-        state.cliAvailable = available;
-      });
-    },
-
-    setCursorCliAvailable: (available: boolean) => {
-      set((state) => {
-        state.cursorCliAvailable = available;
-      });
-    },
-
-    setKiloCliAvailable: (available: boolean) => {
-      set((state) => {
-        state.kiloCliAvailable = available;
-      });
-    },
-
-    setGeminiCliAvailable: (available: boolean) => {
-      set((state) => {
-        state.geminiCliAvailable = available;
-      });
-    },
-
-    setGrokCliAvailable: (available: boolean) => {
-      set((state) => {
-        state.grokCliAvailable = available;
-      });
-    },
-
-    setDeepseekCliAvailable: (available: boolean) => {
-      set((state) => {
-        state.deepseekCliAvailable = available;
+        state.clis = Object.fromEntries(clis.map((cli) => [cli.id, cli]));
       });
     },
 
@@ -1,7 +1,8 @@
 import { create } from 'zustand';
 import { immer } from 'zustand/middleware/immer';
 import { nanoid } from 'nanoid';
-import type { Workspace, WorkspaceState, DrawingState, ModelId, CliType } from '../types/workspace';
+import type { Workspace, WorkspaceState, DrawingState, ModelId, CliType, StoredWorkspace } from '../types/workspace';
+import type { PermissionMode, SandboxProfile } from '../types/agent';
 import { MIN_WORKSPACE_SIZE } from '../types/workspace';
 
 interface WorkspacesState {
@@ -10,6 +11,7 @@ interface WorkspacesState {
 
   addWorkspace: (workspace: Omit<Workspace, 'id' | 'createdAt' | 'taskTemplate' | 'lastOutput' | 'inputConnections' | 'outputConnections' | 'autoRun' | 'cli' | 'mode'>) => string;
   removeWorkspace: (workspaceId: string) => void;
+  applyStoredWorkspace: (stored: StoredWorkspace) => void;
   updateWorkspaceState: (workspaceId: string, state: WorkspaceState) => void;
   setWorkspaceAgent: (workspaceId: string, agentId: string | null) => void;
   updateMessiness: (workspaceId: string, messiness: number) => void;
@@ -19,6 +21,9 @@ interface WorkspacesState {
   setModel: (workspaceId: string, model: ModelId) => void;
   setCli: (workspaceId: string, cli: CliType) => void;
   setMode: (workspaceId: string, mode: string | null) => void;
+  setSandbox: (workspaceId: string, sandbox: SandboxProfile | null) => void;
+  setPermissionMode: (workspaceId: string, mode: PermissionMode) => void;
+  setSkills: (workspaceId: string, skills: string[]) => void;
 
   // Workflow methods
   setTaskTemplate: (workspaceId: string, template: string | null) => void;
@@ -70,6 +75,40 @@ export const useWorkspacesStore = create<WorkspacesState>()(
       return id;
     },
 
+    // Take a workspace's settings from the backend, keeping its run state
+    applyStoredWorkspace: (stored: StoredWorkspace) => {
+      set((state) => {
+        const existing = state.workspaces[stored.id];
+        if (!existing) {
+          workspaceCounter++;
+        }
+        state.workspaces[stored.id] = {
+          state: existing?.state ?? 'empty',
+          agentId: existing?.agentId ?? null,
+          messiness: existing?.messiness ?? 0,
+          lastOutput: existing?.lastOutput ?? null,
+          id: stored.id,
+          name: stored.name,
+          x: stored.x,
+          y: stored.y,
+          width: stored.width,
+          height: stored.height,
+          createdAt: stored.createdAt,
+          systemPrompt: stored.systemPrompt,
+          model: (stored.model ?? 'claude-sonnet-4-20250514') as ModelId,
+          cli: stored.cli ?? undefined,
+          mode: stored.mode ?? undefined,
+          sandbox: stored.sandbox ?? undefined,
+          permissionMode: stored.permissionMode ?? undefined,
+          skills: stored.skills ?? [],
+          taskTemplate: stored.taskTemplate,
+          inputConnections: stored.inputConnections,
+          outputConnections: stored.outputConnections,
+          autoRun: stored.autoRun,
+        };
+      });
+    },
+
     removeWorkspace: (workspaceId: string) => {
       set((state) => {
         // Remove this workspace from all connections
@@ -155,6 +194,30 @@ export const useWorkspacesStore = create<WorkspacesState>()(
       });
     },
 
+    setSandbox: (workspaceId: string, sandbox: SandboxProfile | null) => {
+      set((state) => {
+        if (state.workspaces[workspaceId]) {
+          state.workspaces[workspaceId].sandbox = sandbox ?? undefined;
+        }
+      });
+    },
+
+    setPermissionMode: (workspaceId: string, mode: PermissionMode) => {
+      set((state) => {
+        if (state.workspaces[workspaceId]) {
+          state.workspaces[workspaceId].permissionMode = mode;
+        }
+      });
+    },
+
+    setSkills: (workspaceId: string, skills: string[]) => {
+      set((state) => {
+        if (state.workspaces[workspaceId]) {
+          state.workspaces[workspaceId].skills = skills;
+        }
+      });
+    },
+
     // Workflow methods
     setTaskTemplate: (workspaceId: string, template: string | null) => {
       set((state) => {
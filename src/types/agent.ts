@@ -20,6 +20,10 @@ export interface Agent {
   startedAt: number | null;
   completedAt: number | null;
   error: string | null;
+  /** Permission requests waiting for an answer, oldest first. */
+  permissionRequests: PendingPermission[];
+  /** Set when the run is a node of a workflow the backend drives. */
+  workflowId?: string;
 }
 
 export interface LogEntry {
@@ -45,6 +49,149 @@ export interface AgentConfig {
   workingDirectory?: string;
   systemPrompt?: string;
   model?: string;
+  /** CLI session to resume instead of starting a new conversation (Claude and Cursor only). */
+  sessionId?: string;
+  /** The earlier run this one continues. */
+  parentAgentId?: string;
+  /** Keep stdin open so messages can be sent while the agent runs (Claude only). */
+  interactive?: boolean;
+  /** Kill the run after this many seconds of wall-clock time. */
+  timeoutSecs?: number;
+  /** Cap on agentic turns (Claude only). */
+  maxTurns?: number;
+  /** Kill the run if the CLI prints nothing for this many seconds. */
+  idleTimeoutSecs?: number;
+  /** Retry the run when it fails in one of `retryOn`'s failure classes. */
+  retry?: RetryPolicy;
+  /** Backends to try, in order, when `cli` can't be started or fails in a `fallbackOn` class. */
+  fallbacks?: BackendChoice[];
+  /** Defaults to auth, quota and rate_limit. */
+  fallbackOn?: FailureClass[];
+  /** Extra environment variables for the CLI; values may reference vault secrets as `${secret:NAME}`. */
+  env?: Record<string, string>;
+  /** Start the CLI without the server's environment (PATH, HOME, locale and temp dirs are kept). */
+  envClear?: boolean;
+  /** Server variables kept under `envClear`; a trailing `*` matches a prefix. */
+  envAllow?: string[];
+  /** `worktree` runs the CLI in a fresh git worktree and branch of `workingDirectory`'s repository. */
+  isolation?: 'none' | 'worktree';
+  /** What happens to the worktree after the run; its changes are committed to the branch either way. */
+  worktreeCleanup?: 'keep' | 'remove';
+  /** Run the CLI in a Linux sandbox, usually the workspace's profile. */
+  sandbox?: SandboxProfile;
+  /** Which tool uses the CLI asks about first (default `bypass`; gated modes are Claude only). */
+  permissionMode?: PermissionMode;
+  /** How long a permission request waits for an answer (default 300). */
+  approvalTimeoutSecs?: number;
+  /** The answer a permission request gets when nobody answers in time (default `deny`). */
+  approvalDefault?: PermissionBehavior;
+  /** Skill ids (`SkillInfo.id`); CLIs other than Claude get their instructions in the system prompt. */
+  skills?: string[];
+}
+
+export type PermissionMode = 'default' | 'acceptEdits' | 'plan' | 'bypass';
+
+export type PermissionBehavior = 'allow' | 'deny';
+
+/** A run's question about a tool use, waiting for an answer. */
+export interface PendingPermission {
+  agentId: string;
+  requestId: string;
+  toolName: string;
+  input: Record<string, unknown>;
+  requestedAt: number;
+  /** When the run's default answer applies. */
+  expiresAt: number;
+}
+
+export type NetworkAccess = 'full' | 'none' | 'modelApi';
+
+/** A Linux sandbox for a run. `Started` reports the effective profile, with every path and host it allowed. */
+export interface SandboxProfile {
+  /** `bwrap` (default) restricts the filesystem; `unshare` only isolates processes and the network. */
+  runner?: 'bwrap' | 'unshare';
+  /** `modelApi` allows only the backend's API hosts (and `allowHosts`), through a proxy. */
+  network?: NetworkAccess;
+  writable?: string[];
+  readOnly?: string[];
+  allowHosts?: string[];
+}
+
+export type WorktreeStatus = 'active' | 'kept' | 'removed' | 'merged' | 'discarded';
+
+/** An isolated run's git worktree. */
+export interface WorktreeInfo {
+  repo: string;
+  path: string;
+  branch: string;
+  /** Commit the branch started from. */
+  base: string;
+  subdir: string | null;
+  status: WorktreeStatus;
+  /** Commits on the branch since `base`. */
+  commits: number;
+}
+
+/** A vault secret's metadata; values are never sent to the frontend. */
+export interface SecretEntry {
+  name: string;
+  createdAt: number;
+  updatedAt: number;
+}
+
+export interface BackendChoice {
+  cli: CliType;
+  /** Model for this backend; the CLI's default when unset. */
+  model?: string;
+}
+
+export type FailureClass =
+  | 'rate_limit'
+  | 'network'
+  | 'timeout'
+  | 'stalled'
+  | 'auth'
+  | 'quota'
+  | 'unavailable'
+  | 'error';
+
+/** Every field is optional; defaults are 3 attempts, 2s doubling up to 60s, on rate_limit/network/stalled. */
+export interface RetryPolicy {
+  /** Attempts in total, including the first. */
+  maxAttempts?: number;
+  initialDelayMs?: number;
+  multiplier?: number;
+  maxDelayMs?: number;
+  retryOn?: FailureClass[];
+  /** Continue the failed attempt's CLI session when the CLI can resume (default true). */
+  resume?: boolean;
+}
+
+/** What a backend's CLI supports beyond a single prompt-in, output-out run. */
+export interface CliCapabilities {
+  resume: boolean;
+  liveInput: boolean;
+  maxTurns: boolean;
+  systemPrompt: boolean;
+  allowedTools: boolean;
+  modes: boolean;
+  /** Can ask before using tools, for gated permission modes. */
+  permissionPrompts: boolean;
+}
+
+/** One CLI backend as detected on the host. */
+export interface CliInfo {
+  id: CliType;
+  name: string;
+  available: boolean;
+  /** Where the binary was found, or the configured override. */
+  path: string | null;
+  version: string | null;
+  /** The path came from an `AGENT_CLI_PATH_<ID>` override. */
+  overridden: boolean;
+  capabilities: CliCapabilities;
+  /** Why the CLI is unavailable. */
+  error: string | null;
 }
 
 export const AGENT_STATE_EMOJI: Record<AgentState, string> = {
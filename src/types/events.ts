@@ -1,17 +1,89 @@
+import type {
+  CliType,
+  FailureClass,
+  PermissionBehavior,
+  SandboxProfile,
+  WorktreeInfo,
+} from './agent';
+import type { NodeStatus, WorkflowStatus } from './workflow';
+import type { StoredWorkspace } from './workspace';
+import type { SkillInfo } from './skill';
+
 export type AgentEventType =
+  | 'Queued'
+  | 'Dequeued'
   | 'Started'
+  | 'WorktreeChanged'
+  | 'FallingBack'
   | 'Init'
   | 'Message'
+  | 'Input'
   | 'ToolUse'
   | 'ToolResult'
+  | 'PermissionRequest'
+  | 'PermissionResolved'
+  | 'Usage'
   | 'Result'
+  | 'Retrying'
+  | 'Log'
+  | 'AuthFailed'
+  | 'RateLimited'
+  | 'QuotaExhausted'
   | 'Error'
-  | 'Stopped';
+  | 'Stopped'
+  | 'NodeStarted'
+  | 'NodeFinished'
+  | 'WorkflowFinished'
+  | 'WorkspaceChanged'
+  | 'WorkspaceDeleted'
+  | 'SkillsChanged';
+
+export interface AgentEventQueued {
+  type: 'Queued';
+  agent_id: string;
+  workspace_id: string;
+  /** 1-based; re-announced as earlier runs leave the queue. */
+  position: number;
+}
+
+export interface AgentEventDequeued {
+  type: 'Dequeued';
+  agent_id: string;
+}
 
 export interface AgentEventStarted {
   type: 'Started';
   agent_id: string;
   workspace_id: string;
+  parent_agent_id: string | null;
+  /** The backend the run spawned on (differs from the config's `cli` after a fallback). */
+  cli: CliType | null;
+  model: string | null;
+  /** Set when the run works in its own git worktree. */
+  worktree?: WorktreeInfo | null;
+  /** The sandbox the CLI was spawned in. */
+  sandbox?: SandboxProfile | null;
+  /** The skills the config's `skills` resolved to. */
+  skills?: SkillInfo[];
+}
+
+/** A run's worktree was finished, merged back or discarded. */
+export interface AgentEventWorktreeChanged {
+  type: 'WorktreeChanged';
+  agent_id: string;
+  worktree: WorktreeInfo;
+}
+
+/** The run moved on to the next backend in its config's `fallbacks`. */
+export interface AgentEventFallingBack {
+  type: 'FallingBack';
+  agent_id: string;
+  from: CliType;
+  to: CliType;
+  model: string | null;
+  reason: FailureClass;
+  /** Why `from` couldn't be started, when reason is 'unavailable'. */
+  message: string | null;
 }
 
 export interface AgentEventInit {
@@ -27,6 +99,12 @@ export interface AgentEventMessage {
   content: string;
 }
 
+export interface AgentEventInput {
+  type: 'Input';
+  agent_id: string;
+  content: string;
+}
+
 export interface AgentEventToolUse {
   type: 'ToolUse';
   agent_id: string;
@@ -41,11 +119,97 @@ export interface AgentEventToolResult {
   success: boolean;
 }
 
+/** The CLI is waiting to hear whether it may use a tool. */
+export interface AgentEventPermissionRequest {
+  type: 'PermissionRequest';
+  agent_id: string;
+  request_id: string;
+  tool_name: string;
+  input: Record<string, unknown>;
+  /** After this long the run's default answer applies. */
+  timeout_secs: number;
+}
+
+/** A permission request was answered, or got the run's default after timing out. */
+export interface AgentEventPermissionResolved {
+  type: 'PermissionResolved';
+  agent_id: string;
+  request_id: string;
+  behavior: PermissionBehavior;
+  timed_out: boolean;
+}
+
+export interface TokenUsage {
+  input_tokens: number;
+  output_tokens: number;
+  cache_creation_input_tokens: number;
+  cache_read_input_tokens: number;
+}
+
+export interface AgentEventUsage {
+  type: 'Usage';
+  agent_id: string;
+  /** Tokens since the previous Usage event. */
+  usage: TokenUsage;
+  /** Running total for the run. */
+  total: TokenUsage;
+  /** null if no cost was reported and the model has no known price. */
+  total_cost_usd: number | null;
+  /** True while the cost is estimated from list prices rather than reported by the CLI. */
+  estimated: boolean;
+}
+
 export interface AgentEventResult {
   type: 'Result';
   agent_id: string;
   success: boolean;
+  /** The CLI's own duration when it reported one, otherwise wall_clock_ms. */
   duration_ms: number;
+  /** Time from spawn to exit, measured by the backend. */
+  wall_clock_ms: number;
+  /** Time spent waiting on the model API, if the CLI reported it. */
+  api_duration_ms: number | null;
+}
+
+/** An attempt failed; the same agent_id tries again after delay_ms. */
+export interface AgentEventRetrying {
+  type: 'Retrying';
+  agent_id: string;
+  /** The attempt about to start (2 for the first retry). */
+  attempt: number;
+  max_attempts: number;
+  delay_ms: number;
+  reason: FailureClass;
+}
+
+/** CLI stderr that isn't a failure: progress, debug output and warnings. */
+export interface AgentEventLog {
+  type: 'Log';
+  agent_id: string;
+  level: 'info' | 'warning';
+  message: string;
+}
+
+/** The CLI rejected its credentials; the fix is to log in again. */
+export interface AgentEventAuthFailed {
+  type: 'AuthFailed';
+  agent_id: string;
+  message: string;
+}
+
+export interface AgentEventRateLimited {
+  type: 'RateLimited';
+  agent_id: string;
+  message: string;
+  retry_after_secs: number | null;
+}
+
+export interface AgentEventQuotaExhausted {
+  type: 'QuotaExhausted';
+  agent_id: string;
+  message: string;
+  /** Unix seconds when the limit resets, if the CLI said. */
+  resets_at: number | null;
 }
 
 export interface AgentEventError {
@@ -57,18 +221,91 @@ export interface AgentEventError {
 export interface AgentEventStopped {
   type: 'Stopped';
   agent_id: string;
-  reason: 'completed' | 'cancelled' | 'error';
+  reason: 'completed' | 'cancelled' | 'error' | 'timeout' | 'stalled';
+  /** How the CLI process ended; null when it never started. */
+  exit: ProcessExit | null;
+}
+
+export interface ProcessExit {
+  code: number | null;
+  signal: number | null;
+  /** True when the CLI ignored SIGINT/SIGTERM and its process group was killed. */
+  forced: boolean;
+}
+
+/** A workflow node's run started; its own events follow under agent_id. */
+export interface AgentEventNodeStarted {
+  type: 'NodeStarted';
+  workflow_id: string;
+  node_id: string;
+  agent_id: string;
+}
+
+export interface AgentEventNodeFinished {
+  type: 'NodeFinished';
+  workflow_id: string;
+  node_id: string;
+  /** null when the node never started (skipped, cancelled or failed to start). */
+  agent_id: string | null;
+  status: NodeStatus;
+  error: string | null;
+}
+
+export interface AgentEventWorkflowFinished {
+  type: 'WorkflowFinished';
+  workflow_id: string;
+  status: WorkflowStatus;
+}
+
+/** A stored workspace was created or changed, by this client or another one. */
+export interface AgentEventWorkspaceChanged {
+  type: 'WorkspaceChanged';
+  workspace: StoredWorkspace;
+}
+
+export interface AgentEventWorkspaceDeleted {
+  type: 'WorkspaceDeleted';
+  workspace_id: string;
+}
+
+/** Skills changed on disk (the app, an editor or an agent); one event per skills directory. */
+export interface AgentEventSkillsChanged {
+  type: 'SkillsChanged';
+  /** The project whose .claude/skills changed; null for user and plugin skills. */
+  working_directory: string | null;
+  added: SkillInfo[];
+  modified: SkillInfo[];
+  removed: SkillInfo[];
 }
 
 export type AgentEvent =
+  | AgentEventQueued
+  | AgentEventDequeued
   | AgentEventStarted
+  | AgentEventWorktreeChanged
+  | AgentEventFallingBack
   | AgentEventInit
   | AgentEventMessage
+  | AgentEventInput
   | AgentEventToolUse
   | AgentEventToolResult
+  | AgentEventPermissionRequest
+  | AgentEventPermissionResolved
+  | AgentEventUsage
   | AgentEventResult
+  | AgentEventRetrying
+  | AgentEventLog
+  | AgentEventAuthFailed
+  | AgentEventRateLimited
+  | AgentEventQuotaExhausted
   | AgentEventError
-  | AgentEventStopped;
+  | AgentEventStopped
+  | AgentEventNodeStarted
+  | AgentEventNodeFinished
+  | AgentEventWorkflowFinished
+  | AgentEventWorkspaceChanged
+  | AgentEventWorkspaceDeleted
+  | AgentEventSkillsChanged;
 
 export function isAgentEvent(event: unknown): event is AgentEvent {
   return (
@@ -1,6 +1,24 @@
+/** Where a skill was found: ~/.claude/skills, <project>/.claude/skills or an installed plugin. */
+export type SkillSource = 'user' | 'project' | 'plugin';
+
 export interface SkillInfo {
+  /** How a run's `skills` refer to it: its directory, prefixed `plugin:` for plugin skills. */
+  id: string;
   name: string;
   description: string;
+  source: SkillSource;
+  /** The plugin providing the skill, for plugin skills. */
+  plugin: string | null;
+  allowedTools: string[] | null;
+  /** Every other frontmatter key, as written. */
+  metadata: Record<string, unknown>;
+}
+
+/** Which skills directory to read a skill from; the user's when omitted. */
+export interface SkillLocation {
+  source: SkillSource;
+  plugin?: string | null;
+  workingDirectory?: string | null;
 }
 
 export interface SkillDetail {
@@ -8,3 +26,34 @@ export interface SkillDetail {
   markdown: string;
   path: string;
 }
+
+export interface NewSkill {
+  /** Lowercase letters, digits and '-', at most 64 characters. */
+  name: string;
+  description: string;
+  markdown: string;
+}
+
+export interface SkillUpdate {
+  description: string;
+  markdown: string;
+}
+
+/** Skill bundles are zip or tar archives; exported tars are gzipped. */
+export type BundleFormat = 'zip' | 'tar';
+
+export interface ImportOptions {
+  /** Defaults to the bundle's manifest, directory or frontmatter name. */
+  name?: string | null;
+  /** Replace a user skill that already has the name. */
+  overwrite?: boolean;
+}
+
+/** A skill committed in a local git repository. */
+export interface GitSkillSource {
+  path: string;
+  /** The skill's directory within the repository; its root when omitted. */
+  subdir?: string | null;
+  /** Commit, branch or tag; HEAD when omitted. */
+  rev?: string | null;
+}
@@ -0,0 +1,40 @@
+import type { AgentConfig } from './agent';
+
+export type WorkflowId = string;
+
+export type NodeStatus = 'pending' | 'running' | 'succeeded' | 'failed' | 'cancelled' | 'skipped';
+
+export type WorkflowStatus = 'running' | 'succeeded' | 'failed' | 'cancelled';
+
+export interface WorkflowNode {
+  id: string;
+  name?: string;
+  config: AgentConfig;
+  /**
+   * Nodes whose output this node receives. The prompt can place it with
+   * `{{input}}` (every input) or `{{input:<id>}}` (one input).
+   */
+  inputs: string[];
+}
+
+export interface WorkflowDefinition {
+  name?: string;
+  nodes: WorkflowNode[];
+}
+
+export interface NodeInfo {
+  id: string;
+  status: NodeStatus;
+  agentId: string | null;
+  output: string | null;
+  error: string | null;
+}
+
+export interface WorkflowInfo {
+  id: WorkflowId;
+  name: string | null;
+  status: WorkflowStatus;
+  nodes: NodeInfo[];
+  startedAt: number;
+  finishedAt: number | null;
+}
@@ -1,3 +1,5 @@
+import type { PermissionMode, SandboxProfile } from './agent';
+
 export type WorkspaceState = 'empty' | 'occupied' | 'working' | 'success' | 'error';
 
 export type ModelId = 'claude-sonnet-4-20250514' | 'claude-opus-4-20250514' | 'claude-3-5-haiku-20241022';
@@ -28,6 +30,12 @@ export interface Workspace {
   cli?: CliType;
   /** Cursor-only: `agent`, `plan`, or `ask`. Ignored for Claude and Kilo. */
   mode?: string;
+  /** Linux sandbox every run in this workspace starts in. */
+  sandbox?: SandboxProfile;
+  /** Which tool uses runs in this workspace ask about first (Claude only). */
+  permissionMode?: PermissionMode;
+  /** Skill ids every run in this workspace starts with. */
+  skills?: string[];
 
   // Workflow features
   taskTemplate: string | null;      // Pre-defined task prompt for this workspace
@@ -37,6 +45,33 @@ export interface Workspace {
   autoRun: boolean;                 // Auto-run when all inputs complete
 }
 
+/** The settings of a workspace the backend stores; run state stays in the client. */
+export interface WorkspaceSpec {
+  name: string;
+  x: number;
+  y: number;
+  width: number;
+  height: number;
+  systemPrompt: string | null;
+  model: string | null;
+  cli: CliType | null;
+  mode: string | null;
+  sandbox: SandboxProfile | null;
+  permissionMode: PermissionMode | null;
+  skills: string[];
+  taskTemplate: string | null;
+  inputConnections: string[];
+  outputConnections: string[];
+  autoRun: boolean;
+}
+
+/** A workspace as stored by the backend (`/api/workspaces`). */
+export interface StoredWorkspace extends WorkspaceSpec {
+  id: string;
+  createdAt: number;
+  updatedAt: number;
+}
+
 export const AVAILABLE_MODELS: { id: ModelId; name: string; description: string }[] = [
   { id: 'claude-sonnet-4-20250514', name: 'Claude Sonnet 4', description: 'Fast & capable (default)' },
   { id: 'claude-opus-4-20250514', name: 'Claude Opus 4', description: 'Most powerful' },
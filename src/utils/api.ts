@@ -1,6 +1,24 @@
 import { isTauri, getApiBaseUrl } from './env';
-import type { AgentConfig, AgentEvent, AgentId } from '../types/agent';
-import type { SkillInfo, SkillDetail } from '../types/skill';
+import type {
+  AgentConfig,
+  AgentEvent,
+  AgentId,
+  CliInfo,
+  PermissionBehavior,
+  WorktreeInfo,
+} from '../types/agent';
+import type {
+  SkillInfo,
+  SkillDetail,
+  SkillLocation,
+  NewSkill,
+  SkillUpdate,
+  BundleFormat,
+  ImportOptions,
+  GitSkillSource,
+} from '../types/skill';
+import type { WorkflowDefinition, WorkflowId } from '../types/workflow';
+import type { StoredWorkspace, WorkspaceSpec } from '../types/workspace';
 import { isAgentEvent } from '../types/events';
 
 // Tauri imports (only used when in Tauri mode)
@@ -256,42 +274,242 @@ export const api = {
   },
 
   /**
-   * Check if a CLI is available
+   * Answer a run's permission request. Fails with 404 once the request was
+   * answered, timed out or its run ended.
    */
-  async checkCliAvailable(cli: 'claude' | 'cursor' | 'kilo' | 'gemini' | 'grok' | 'deepseek'): Promise<boolean> {
+  async respondPermission(
+    agentId: AgentId,
+    requestId: string,
+    behavior: PermissionBehavior,
+    message?: string,
+  ): Promise<void> {
+    const decision = { behavior, message };
     if (isTauri) {
       if (!tauriInvoke) {
         await new Promise(resolve => setTimeout(resolve, 100));
       }
-      const commandMap: Record<string, string> = {
-        claude: 'check_cli_available',
-        cursor: 'check_cursor_cli_available',
-        kilo: 'check_kilo_cli_available',
-        gemini: 'check_gemini_cli_available',
-        grok: 'check_grok_cli_available',
-        deepseek: 'check_deepseek_cli_available',
-      };
-      return await tauriInvoke<boolean>(commandMap[cli]);
+      await tauriInvoke('respond_permission', { agentId, requestId, decision });
+    } else {
+      const response = await fetch(
+        `${getApiBaseUrl()}/api/agents/${agentId}/permissions/${encodeURIComponent(requestId)}`,
+        {
+          method: 'POST',
+          headers: { 'Content-Type': 'application/json' },
+          body: JSON.stringify(decision),
+        },
+      );
+      if (!response.ok) {
+        const errorText = await response.text().catch(() => response.statusText);
+        throw new Error(`Failed to answer permission request: ${response.status} ${errorText}`);
+      }
+    }
+  },
+
+  /**
+   * Start a workflow: the backend runs each node once all its inputs have
+   * succeeded. Node runs report through the usual agent events.
+   */
+  async startWorkflow(definition: WorkflowDefinition): Promise<WorkflowId> {
+    if (isTauri) {
+      if (!tauriInvoke) {
+        await new Promise(resolve => setTimeout(resolve, 100));
+      }
+      return await tauriInvoke<WorkflowId>('start_workflow', { definition });
+    } else {
+      const response = await fetch(`${getApiBaseUrl()}/api/workflows`, {
+        method: 'POST',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify(definition),
+      });
+      if (!response.ok) {
+        const errorText = await response.text().catch(() => response.statusText);
+        throw new Error(`Failed to start workflow: ${response.status} ${errorText}`);
+      }
+      return await response.json();
+    }
+  },
+
+  /**
+   * Cancel a workflow: running nodes are stopped and pending ones never start.
+   */
+  async cancelWorkflow(workflowId: WorkflowId): Promise<void> {
+    if (isTauri) {
+      if (!tauriInvoke) {
+        await new Promise(resolve => setTimeout(resolve, 100));
+      }
+      await tauriInvoke('cancel_workflow', { workflowId });
+    } else {
+      const response = await fetch(`${getApiBaseUrl()}/api/workflows/${workflowId}`, {
+        method: 'DELETE',
+      });
+      if (!response.ok) {
+        const errorText = await response.text().catch(() => response.statusText);
+        throw new Error(`Failed to cancel workflow: ${response.status} ${errorText}`);
+      }
+    }
+  },
+
+  /**
+   * List the workspaces stored on the backend
+   */
+  async listWorkspaces(): Promise<StoredWorkspace[]> {
+    if (isTauri) {
+      if (!tauriInvoke) {
+        await new Promise(resolve => setTimeout(resolve, 100));
+      }
+      return await tauriInvoke<StoredWorkspace[]>('list_workspaces');
+    } else {
+      const response = await fetch(`${getApiBaseUrl()}/api/workspaces`);
+      if (!response.ok) {
+        const errorText = await response.text().catch(() => response.statusText);
+        throw new Error(`Failed to list workspaces: ${response.status} ${errorText}`);
+      }
+      return await response.json();
+    }
+  },
+
+  /**
+   * Store a new workspace under the id the client gave it
+   */
+  async createWorkspace(id: string, spec: WorkspaceSpec): Promise<StoredWorkspace> {
+    const workspace = { id, ...spec };
+    if (isTauri) {
+      if (!tauriInvoke) {
+        await new Promise(resolve => setTimeout(resolve, 100));
+      }
+      return await tauriInvoke<StoredWorkspace>('create_workspace', { workspace });
+    } else {
+      const response = await fetch(`${getApiBaseUrl()}/api/workspaces`, {
+        method: 'POST',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify(workspace),
+      });
+      if (!response.ok) {
+        const errorText = await response.text().catch(() => response.statusText);
+        throw new Error(`Failed to create workspace: ${response.status} ${errorText}`);
+      }
+      return await response.json();
+    }
+  },
+
+  /**
+   * Replace a stored workspace's settings
+   */
+  async updateWorkspace(workspaceId: string, spec: WorkspaceSpec): Promise<StoredWorkspace> {
+    if (isTauri) {
+      if (!tauriInvoke) {
+        await new Promise(resolve => setTimeout(resolve, 100));
+      }
+      return await tauriInvoke<StoredWorkspace>('update_workspace', { workspaceId, spec });
+    } else {
+      const response = await fetch(`${getApiBaseUrl()}/api/workspaces/${encodeURIComponent(workspaceId)}`, {
+        method: 'PUT',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify(spec),
+      });
+      if (!response.ok) {
+        const errorText = await response.text().catch(() => response.statusText);
+        throw new Error(`Failed to update workspace: ${response.status} ${errorText}`);
+      }
+      return await response.json();
+    }
+  },
+
+  /**
+   * Delete a stored workspace and every connection to it
+   */
+  async deleteWorkspace(workspaceId: string): Promise<void> {
+    if (isTauri) {
+      if (!tauriInvoke) {
+        await new Promise(resolve => setTimeout(resolve, 100));
+      }
+      await tauriInvoke('delete_workspace', { workspaceId });
+    } else {
+      const response = await fetch(`${getApiBaseUrl()}/api/workspaces/${encodeURIComponent(workspaceId)}`, {
+        method: 'DELETE',
+      });
+      if (!response.ok) {
+        const errorText = await response.text().catch(() => response.statusText);
+        throw new Error(`Failed to delete workspace: ${response.status} ${errorText}`);
+      }
+    }
+  },
+
+  /**
+   * Merge an isolated run's branch into its repository's current branch and
+   * delete the worktree. Fails with 409 on conflicts, leaving the branch as it was.
+   */
+  async mergeWorktree(agentId: AgentId): Promise<WorktreeInfo> {
+    if (isTauri) {
+      if (!tauriInvoke) {
+        await new Promise(resolve => setTimeout(resolve, 100));
+      }
+      return await tauriInvoke<WorktreeInfo>('merge_worktree', { agentId });
+    } else {
+      const response = await fetch(`${getApiBaseUrl()}/api/agents/${agentId}/worktree/merge`, {
+        method: 'POST',
+      });
+      if (!response.ok) {
+        const errorText = await response.text().catch(() => response.statusText);
+        throw new Error(`Failed to merge worktree: ${response.status} ${errorText}`);
+      }
+      return await response.json();
+    }
+  },
+
+  /**
+   * Delete an isolated run's worktree and branch without merging.
+   */
+  async discardWorktree(agentId: AgentId): Promise<WorktreeInfo> {
+    if (isTauri) {
+      if (!tauriInvoke) {
+        await new Promise(resolve => setTimeout(resolve, 100));
+      }
+      return await tauriInvoke<WorktreeInfo>('discard_worktree', { agentId });
+    } else {
+      const response = await fetch(`${getApiBaseUrl()}/api/agents/${agentId}/worktree`, {
+        method: 'DELETE',
+      });
+      if (!response.ok) {
+        const errorText = await response.text().catch(() => response.statusText);
+        throw new Error(`Failed to discard worktree: ${response.status} ${errorText}`);
+      }
+      return await response.json();
+    }
+  },
+
+  /**
+   * Detect every CLI backend: availability, path, version and capabilities.
+   * Results are cached by the backend; pass `refresh` to probe again.
+   */
+  async detectClis(refresh = false): Promise<CliInfo[]> {
+    if (isTauri) {
+      if (!tauriInvoke) {
+        await new Promise(resolve => setTimeout(resolve, 100));
+      }
+      return await tauriInvoke<CliInfo[]>('detect_clis', { refresh });
     } else {
-      const response = await fetch(`${getApiBaseUrl()}/api/cli/check/${cli}`);
+      const response = await fetch(`${getApiBaseUrl()}/api/cli${refresh ? '?refresh=true' : ''}`);
       if (!response.ok) {
-        return false;
+        const errorText = await response.text().catch(() => response.statusText);
+        throw new Error(`Failed to detect CLIs: ${response.status} ${errorText}`);
       }
       return await response.json();
     }
   },
 
   /**
-   * List skills
+   * List user and plugin skills, plus the project skills of workingDirectory if given
    */
-  async listSkills(): Promise<SkillInfo[]> {
+  async listSkills(workingDirectory?: string | null): Promise<SkillInfo[]> {
     if (isTauri) {
       if (!tauriInvoke) {
         await new Promise(resolve => setTimeout(resolve, 100));
       }
-      return await tauriInvoke<SkillInfo[]>('list_skills');
+      return await tauriInvoke<SkillInfo[]>('list_skills', { workingDirectory: workingDirectory ?? null });
     } else {
-      const response = await fetch(`${getApiBaseUrl()}/api/skills`);
+      const query = workingDirectory ? `?workingDirectory=${encodeURIComponent(workingDirectory)}` : '';
+      const response = await fetch(`${getApiBaseUrl()}/api/skills${query}`);
       if (!response.ok) {
         const errorText = await response.text().catch(() => response.statusText);
         throw new Error(`Failed to list skills: ${response.status} ${errorText}`);
@@ -301,16 +519,23 @@ export const api = {
   },
 
   /**
-   * Get skill detail
+   * Get skill detail, from the user's skills unless a location is given
    */
-  async getSkill(skillName: string): Promise<SkillDetail> {
+  async getSkill(skillName: string, location?: SkillLocation): Promise<SkillDetail> {
     if (isTauri) {
       if (!tauriInvoke) {
         await new Promise(resolve => setTimeout(resolve, 100));
       }
-      return await tauriInvoke<SkillDetail>('get_skill', { skillName });
+      return await tauriInvoke<SkillDetail>('get_skill', { skillName, location: location ?? null });
     } else {
-      const response = await fetch(`${getApiBaseUrl()}/api/skills/${encodeURIComponent(skillName)}`);
+      const params = new URLSearchParams();
+      if (location) {
+        params.set('source', location.source);
+        if (location.plugin) params.set('plugin', location.plugin);
+        if (location.workingDirectory) params.set('workingDirectory', location.workingDirectory);
+      }
+      const query = params.toString() ? `?${params}` : '';
+      const response = await fetch(`${getApiBaseUrl()}/api/skills/${encodeURIComponent(skillName)}${query}`);
       if (!response.ok) {
         const errorText = await response.text().catch(() => response.statusText);
         if (response.status === 404) {
@@ -322,6 +547,184 @@ export const api = {
     }
   },
 
+  /**
+   * Create a skill (a new directory with its SKILL.md)
+   */
+  async createSkill(skill: NewSkill): Promise<SkillDetail> {
+    if (isTauri) {
+      if (!tauriInvoke) {
+        await new Promise(resolve => setTimeout(resolve, 100));
+      }
+      return await tauriInvoke<SkillDetail>('create_skill', { skill });
+    } else {
+      const response = await fetch(`${getApiBaseUrl()}/api/skills`, {
+        method: 'POST',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify(skill),
+      });
+      if (!response.ok) {
+        const errorText = await response.text().catch(() => response.statusText);
+        if (response.status === 409) {
+          throw new Error(`Skill '${skill.name}' already exists`);
+        }
+        throw new Error(`Failed to create skill: ${response.status} ${errorText}`);
+      }
+      return await response.json();
+    }
+  },
+
+  /**
+   * Replace a skill's description and instructions
+   */
+  async updateSkill(skillName: string, update: SkillUpdate): Promise<SkillDetail> {
+    if (isTauri) {
+      if (!tauriInvoke) {
+        await new Promise(resolve => setTimeout(resolve, 100));
+      }
+      return await tauriInvoke<SkillDetail>('update_skill', { skillName, update });
+    } else {
+      const response = await fetch(`${getApiBaseUrl()}/api/skills/${encodeURIComponent(skillName)}`, {
+        method: 'PUT',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify(update),
+      });
+      if (!response.ok) {
+        const errorText = await response.text().catch(() => response.statusText);
+        throw new Error(`Failed to update skill: ${response.status} ${errorText}`);
+      }
+      return await response.json();
+    }
+  },
+
+  /**
+   * Rename a skill's directory and the name in its frontmatter
+   */
+  async renameSkill(skillName: string, newName: string): Promise<SkillDetail> {
+    if (isTauri) {
+      if (!tauriInvoke) {
+        await new Promise(resolve => setTimeout(resolve, 100));
+      }
+      return await tauriInvoke<SkillDetail>('rename_skill', { skillName, newName });
+    } else {
+      const response = await fetch(`${getApiBaseUrl()}/api/skills/${encodeURIComponent(skillName)}/rename`, {
+        method: 'POST',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify({ newName }),
+      });
+      if (!response.ok) {
+        const errorText = await response.text().catch(() => response.statusText);
+        if (response.status === 409) {
+          throw new Error(`Skill '${newName}' already exists`);
+        }
+        throw new Error(`Failed to rename skill: ${response.status} ${errorText}`);
+      }
+      return await response.json();
+    }
+  },
+
+  /**
+   * Delete a skill's directory
+   */
+  async deleteSkill(skillName: string): Promise<void> {
+    if (isTauri) {
+      if (!tauriInvoke) {
+        await new Promise(resolve => setTimeout(resolve, 100));
+      }
+      await tauriInvoke('delete_skill', { skillName });
+    } else {
+      const response = await fetch(`${getApiBaseUrl()}/api/skills/${encodeURIComponent(skillName)}`, {
+        method: 'DELETE',
+      });
+      if (!response.ok) {
+        const errorText = await response.text().catch(() => response.statusText);
+        throw new Error(`Failed to delete skill: ${response.status} ${errorText}`);
+      }
+    }
+  },
+
+  /**
+   * Export a skill's directory as a zip or gzipped tar bundle
+   */
+  async exportSkill(skillName: string, format: BundleFormat, location?: SkillLocation): Promise<Blob> {
+    const type = format === 'zip' ? 'application/zip' : 'application/gzip';
+    if (isTauri) {
+      if (!tauriInvoke) {
+        await new Promise(resolve => setTimeout(resolve, 100));
+      }
+      const bytes = await tauriInvoke<number[]>('export_skill', { skillName, format, location: location ?? null });
+      return new Blob([new Uint8Array(bytes)], { type });
+    } else {
+      const params = new URLSearchParams({ format });
+      if (location) {
+        params.set('source', location.source);
+        if (location.plugin) params.set('plugin', location.plugin);
+        if (location.workingDirectory) params.set('workingDirectory', location.workingDirectory);
+      }
+      const response = await fetch(`${getApiBaseUrl()}/api/skills/${encodeURIComponent(skillName)}/export?${params}`);
+      if (!response.ok) {
+        const errorText = await response.text().catch(() => response.statusText);
+        throw new Error(`Failed to export skill: ${response.status} ${errorText}`);
+      }
+      return await response.blob();
+    }
+  },
+
+  /**
+   * Install a skill bundle (zip or tar) as a user skill
+   */
+  async importSkill(bundle: Blob, format: BundleFormat, options: ImportOptions = {}): Promise<SkillDetail> {
+    if (isTauri) {
+      if (!tauriInvoke) {
+        await new Promise(resolve => setTimeout(resolve, 100));
+      }
+      const bytes = Array.from(new Uint8Array(await bundle.arrayBuffer()));
+      return await tauriInvoke<SkillDetail>('import_skill', { bundle: bytes, format, options });
+    } else {
+      const params = new URLSearchParams({ format });
+      if (options.name) params.set('name', options.name);
+      if (options.overwrite) params.set('overwrite', 'true');
+      const response = await fetch(`${getApiBaseUrl()}/api/skill-bundles?${params}`, {
+        method: 'POST',
+        headers: { 'Content-Type': 'application/octet-stream' },
+        body: bundle,
+      });
+      if (!response.ok) {
+        const errorText = await response.text().catch(() => response.statusText);
+        if (response.status === 409) {
+          throw new Error('A skill with that name already exists');
+        }
+        throw new Error(`Failed to import skill: ${response.status} ${errorText}`);
+      }
+      return await response.json();
+    }
+  },
+
+  /**
+   * Install a skill committed in a local git repository
+   */
+  async importSkillFromGit(source: GitSkillSource, options: ImportOptions = {}): Promise<SkillDetail> {
+    if (isTauri) {
+      if (!tauriInvoke) {
+        await new Promise(resolve => setTimeout(resolve, 100));
+      }
+      return await tauriInvoke<SkillDetail>('import_skill_from_git', { source, options });
+    } else {
+      const response = await fetch(`${getApiBaseUrl()}/api/skill-bundles/git`, {
+        method: 'POST',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify({ ...source, ...options }),
+      });
+      if (!response.ok) {
+        const errorText = await response.text().catch(() => response.statusText);
+        if (response.status === 409) {
+          throw new Error('A skill with that name already exists');
+        }
+        throw new Error(`Failed to import skill: ${response.status} ${errorText}`);
+      }
+      return await response.json();
+    }
+  },
+
   /**
    * Listen to agent events
    */
@@ -0,0 +1,142 @@
+import { api } from './api';
+import { useWorkspacesStore } from '../stores/workspaces';
+import type { StoredWorkspace, Workspace, WorkspaceSpec } from '../types/workspace';
+
+// Edits within this window (e.g. while dragging) are saved as one update
+const SAVE_DELAY_MS = 300;
+
+// The spec each workspace last had on the backend, serialized; only real edits are sent
+const synced = new Map<string, string>();
+const timers = new Map<string, ReturnType<typeof setTimeout>>();
+let seen: Record<string, Workspace> = {};
+let enabled = false;
+
+function toSpec(ws: Workspace | StoredWorkspace): WorkspaceSpec {
+  return {
+    name: ws.name,
+    x: ws.x,
+    y: ws.y,
+    width: ws.width,
+    height: ws.height,
+    systemPrompt: ws.systemPrompt ?? null,
+    model: ws.model ?? null,
+    cli: ws.cli ?? null,
+    mode: ws.mode ?? null,
+    sandbox: ws.sandbox ?? null,
+    permissionMode: ws.permissionMode ?? null,
+    skills: ws.skills ?? [],
+    taskTemplate: ws.taskTemplate ?? null,
+    inputConnections: ws.inputConnections ?? [],
+    outputConnections: ws.outputConnections ?? [],
+    autoRun: ws.autoRun ?? false,
+  };
+}
+
+async function save(workspaceId: string) {
+  timers.delete(workspaceId);
+  const workspace = useWorkspacesStore.getState().workspaces[workspaceId];
+  if (!workspace) return;
+
+  const spec = toSpec(workspace);
+  const key = JSON.stringify(spec);
+  const previous = synced.get(workspaceId);
+  if (previous === key) return;
+
+  synced.set(workspaceId, key);
+  try {
+    if (previous === undefined) {
+      await api.createWorkspace(workspaceId, spec);
+    } else {
+      await api.updateWorkspace(workspaceId, spec);
+    }
+  } catch (error) {
+    console.warn('[Workspaces] Failed to save workspace', workspaceId, error);
+    if (previous === undefined) {
+      synced.delete(workspaceId);
+    } else {
+      synced.set(workspaceId, previous);
+    }
+  }
+}
+
+async function remove(workspaceId: string) {
+  clearTimeout(timers.get(workspaceId));
+  timers.delete(workspaceId);
+  if (!synced.delete(workspaceId)) return;
+  try {
+    await api.deleteWorkspace(workspaceId);
+  } catch (error) {
+    console.warn('[Workspaces] Failed to delete workspace', workspaceId, error);
+  }
+}
+
+function handleChange(workspaces: Record<string, Workspace>) {
+  for (const [id, workspace] of Object.entries(workspaces)) {
+    if (seen[id] === workspace) continue;
+    // Run state changes and updates from the backend need no saving
+    if (synced.get(id) === JSON.stringify(toSpec(workspace))) continue;
+    if (!synced.has(id)) {
+      // Create right away so connections to it can be saved
+      save(id);
+    } else if (!timers.has(id)) {
+      timers.set(id, setTimeout(() => save(id), SAVE_DELAY_MS));
+    }
+  }
+  for (const id of Object.keys(seen)) {
+    if (!workspaces[id]) {
+      remove(id);
+    }
+  }
+  seen = workspaces;
+}
+
+/**
+ * Load the backend's workspaces into the store, then save every local edit back.
+ * Does nothing (workspaces stay local) if the backend doesn't store workspaces.
+ * Returns a function that stops syncing.
+ */
+export async function startWorkspaceSync(): Promise<() => void> {
+  let stored: StoredWorkspace[];
+  try {
+    stored = await api.listWorkspaces();
+  } catch (error) {
+    console.warn('[Workspaces] Not stored on the backend:', error);
+    return () => {};
+  }
+
+  const { applyStoredWorkspace } = useWorkspacesStore.getState();
+  for (const workspace of stored) {
+    synced.set(workspace.id, JSON.stringify(toSpec(workspace)));
+    applyStoredWorkspace(workspace);
+  }
+  // Workspaces drawn before the list arrived are created now
+  seen = {};
+  handleChange(useWorkspacesStore.getState().workspaces);
+
+  enabled = true;
+  const unsubscribe = useWorkspacesStore.subscribe((state) => handleChange(state.workspaces));
+  return () => {
+    enabled = false;
+    unsubscribe();
+  };
+}
+
+/** Apply a `WorkspaceChanged` event from the backend. */
+export function applyRemoteWorkspace(workspace: StoredWorkspace) {
+  if (!enabled) return;
+  synced.set(workspace.id, JSON.stringify(toSpec(workspace)));
+  // A local edit waiting to be saved wins; it is compared against this version
+  if (timers.has(workspace.id)) return;
+  useWorkspacesStore.getState().applyStoredWorkspace(workspace);
+}
+
+/** Apply a `WorkspaceDeleted` event from the backend. */
+export function applyRemoteDelete(workspaceId: string) {
+  if (!enabled) return;
+  clearTimeout(timers.get(workspaceId));
+  timers.delete(workspaceId);
+  synced.delete(workspaceId);
+  if (useWorkspacesStore.getState().workspaces[workspaceId]) {
+    useWorkspacesStore.getState().removeWorkspace(workspaceId);
+  }
+}